@@ -35,19 +35,27 @@ impl Closer {
             }
         };
 
-        // Flush table.
-        let opts = TableFlushOptions::default();
+        // Flush table, unless the memtables miss some data in the wal, which is
+        // recovered by replaying the wal when the table is opened again.
         let mut serial_exec = table_data.serial_exec.lock().await;
-        let flush_scheduler = serial_exec.flush_scheduler();
+        if table_data.needs_recovery() {
+            warn!(
+                "Skip flushing the table needs recovery when close, table:{}, table_id:{}",
+                table_data.name, table_data.id
+            );
+        } else {
+            let opts = TableFlushOptions::default();
+            let flush_scheduler = serial_exec.flush_scheduler();
 
-        self.flusher
-            .do_flush(flush_scheduler, &table_data, opts)
-            .await
-            .context(FlushTable {
-                space_id: self.space.id,
-                table: &table_data.name,
-                table_id: table_data.id,
-            })?;
+            self.flusher
+                .do_flush(flush_scheduler, &table_data, opts)
+                .await
+                .context(FlushTable {
+                    space_id: self.space.id,
+                    table: &table_data.name,
+                    table_id: table_data.id,
+                })?;
+        }
 
         // Force manifest to do snapshot.
         let snapshot_request = SnapshotRequest {
@@ -24,7 +24,7 @@ use futures::{
     stream, SinkExt, TryStreamExt,
 };
 use log::{debug, error, info};
-use snafu::{Backtrace, ResultExt, Snafu};
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
 use table_engine::predicate::Predicate;
 use tokio::{sync::oneshot, time::Instant};
 use wal::manager::WalLocation;
@@ -252,6 +252,17 @@ impl FlushTask {
     /// Each table can only have one running flush task at the same time, which
     /// should be ensured by the caller.
     async fn run(&self) -> Result<()> {
+        // The wal must not be purged past the data missing from the memtables.
+        ensure!(
+            !self.table_data.needs_recovery(),
+            Other {
+                msg: format!(
+                    "table needs recovery from the wal before flush, table:{}, table_id:{}",
+                    self.table_data.name, self.table_data.id
+                ),
+            }
+        );
+
         let instant = Instant::now();
         let flush_req = self.preprocess_flush(&self.table_data).await?;
 
@@ -18,7 +18,7 @@ pub(crate) mod serial_executor;
 pub mod wal_replayer;
 pub(crate) mod write;
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use common_types::table::TableId;
 use common_util::{
@@ -44,7 +44,10 @@ use crate::{
         file::FilePurgerRef,
         meta_data::cache::MetaCacheRef,
     },
-    table::data::{TableDataRef, TableShardInfo},
+    table::{
+        data::{TableDataRef, TableShardInfo},
+        write_dedup::WriteDedupOptions,
+    },
     RecoverMode, TableOptions,
 };
 
@@ -155,8 +158,22 @@ pub struct Instance {
     pub(crate) write_sst_max_buffer_size: usize,
     /// Max retry limit to flush memtables
     pub(crate) max_retry_flush_limit: usize,
+    /// Max time to wait for the flush before rejecting the write
+    pub(crate) write_stall_timeout: Option<Duration>,
+    /// Max time to wait for the lock of another table to flush it
+    pub(crate) other_table_flush_lock_wait: Duration,
     /// Max bytes per write batch
     pub(crate) max_bytes_per_write_batch: Option<usize>,
+    /// Max rows per write batch
+    pub(crate) max_rows_per_write_batch: Option<usize>,
+    /// Pipeline the writes of the splitted batches
+    pub(crate) enable_pipelined_write_batches: bool,
+    /// Options to deduplicate the write requests
+    pub(crate) write_dedup_opts: WriteDedupOptions,
+    /// Group the rows by the time range of memtables before inserting
+    pub(crate) enable_presort_write_rows: bool,
+    /// Max number of the pooled buffers to write memtables of a table
+    pub(crate) write_buffer_pool_size: usize,
     /// Options for scanning sst
     pub(crate) scan_options: ScanOptions,
     pub(crate) iter_options: Option<IterOptions>,
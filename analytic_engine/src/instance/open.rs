@@ -32,7 +32,7 @@ use crate::{
         factory::{FactoryRef as SstFactoryRef, ObjectStorePickerRef, ScanOptions},
         file::FilePurger,
     },
-    table::data::TableDataRef,
+    table::{data::TableDataRef, write_dedup::WriteDedupOptions},
     table_meta_set_impl::TableMetaSetImpl,
     RecoverMode,
 };
@@ -121,10 +121,20 @@ impl Instance {
             replay_batch_size: ctx.config.replay_batch_size,
             write_sst_max_buffer_size: ctx.config.write_sst_max_buffer_size.as_byte() as usize,
             max_retry_flush_limit: ctx.config.max_retry_flush_limit,
+            write_stall_timeout: ctx.config.write_stall_timeout.map(|v| v.0),
+            other_table_flush_lock_wait: ctx.config.other_table_flush_lock_wait.0,
             max_bytes_per_write_batch: ctx
                 .config
                 .max_bytes_per_write_batch
                 .map(|v| v.as_byte() as usize),
+            max_rows_per_write_batch: ctx.config.max_rows_per_write_batch,
+            enable_pipelined_write_batches: ctx.config.enable_pipelined_write_batches,
+            write_dedup_opts: WriteDedupOptions {
+                capacity: ctx.config.write_dedup_capacity,
+                ttl: ctx.config.write_dedup_ttl.0,
+            },
+            enable_presort_write_rows: ctx.config.enable_presort_write_rows,
+            write_buffer_pool_size: ctx.config.write_buffer_pool_size,
             iter_options,
             scan_options,
             recover_mode: ctx.config.recover_mode,
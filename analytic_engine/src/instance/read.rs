@@ -18,7 +18,7 @@ use common_types::{
 use common_util::{define_result, error::BoxError};
 use futures::stream::Stream;
 use log::debug;
-use snafu::{ResultExt, Snafu};
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
 use table_engine::{
     stream::{
         self, ErrWithSource, PartitionedStreams, RecordBatchStream, SendableRecordBatchStream,
@@ -64,6 +64,13 @@ pub enum Error {
         table: String,
         source: crate::row_iter::chain::Error,
     },
+
+    #[snafu(display(
+        "Table needs recovery from the wal before read, table:{}.\nBacktrace:\n{}",
+        table,
+        backtrace
+    ))]
+    TableNeedsRecovery { table: String, backtrace: Backtrace },
 }
 
 define_result!(Error);
@@ -96,6 +103,14 @@ impl Instance {
         );
 
         let table_data = space_table.table_data();
+        // The memtables may miss some data in the wal, so the result is incomplete.
+        ensure!(
+            !table_data.needs_recovery(),
+            TableNeedsRecovery {
+                table: &table_data.name,
+            }
+        );
+
         let table_options = table_data.table_options();
 
         // Collect metrics.
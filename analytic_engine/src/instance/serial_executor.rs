@@ -1,10 +1,7 @@
 // Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
 
 use std::{
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc, Mutex,
-    },
+    sync::{Arc, Mutex},
     time::Instant,
 };
 
@@ -38,26 +35,6 @@ type ScheduleSyncRef = Arc<ScheduleSync>;
 struct ScheduleSync {
     state: Mutex<FlushState>,
     notifier: Sender<()>,
-    continuous_flush_failure_count: AtomicUsize,
-}
-
-impl ScheduleSync {
-    #[inline]
-    pub fn should_retry_flush(&self, max_retry_limit: usize) -> bool {
-        self.continuous_flush_failure_count.load(Ordering::Relaxed) < max_retry_limit
-    }
-
-    #[inline]
-    pub fn reset_flush_failure_count(&self) {
-        self.continuous_flush_failure_count
-            .store(0, Ordering::Relaxed);
-    }
-
-    #[inline]
-    pub fn inc_flush_failure_count(&self) {
-        self.continuous_flush_failure_count
-            .fetch_add(1, Ordering::Relaxed);
-    }
 }
 
 pub struct TableFlushScheduler {
@@ -71,7 +48,6 @@ impl Default for TableFlushScheduler {
         let schedule_sync = ScheduleSync {
             state: Mutex::new(FlushState::Ready),
             notifier: tx,
-            continuous_flush_failure_count: AtomicUsize::new(0),
         };
         Self {
             schedule_sync: Arc::new(schedule_sync),
@@ -160,10 +136,7 @@ impl TableFlushScheduler {
                         }
                     }
                     FlushState::Failed { err_msg } => {
-                        if self
-                            .schedule_sync
-                            .should_retry_flush(opts.max_retry_flush_limit)
-                        {
+                        if table_data.flush_failure_count() < opts.max_retry_flush_limit {
                             warn!("Re-flush memory tables after background flush failed:{err_msg}");
                             // Mark the worker is flushing.
                             *flush_state = FlushState::Flushing;
@@ -204,7 +177,7 @@ impl TableFlushScheduler {
         let schedule_sync = self.schedule_sync.clone();
         let task = async move {
             let flush_res = flush_job.await;
-            on_flush_finished(schedule_sync, &flush_res);
+            on_flush_finished(schedule_sync, &table_data, &flush_res);
             send_flush_result(opts.res_sender, flush_res);
         };
 
@@ -218,18 +191,18 @@ impl TableFlushScheduler {
     }
 }
 
-fn on_flush_finished(schedule_sync: ScheduleSyncRef, res: &Result<()>) {
+fn on_flush_finished(schedule_sync: ScheduleSyncRef, table_data: &TableData, res: &Result<()>) {
     {
         let mut flush_state = schedule_sync.state.lock().unwrap();
         match res {
             Ok(()) => {
-                schedule_sync.reset_flush_failure_count();
+                table_data.reset_flush_failure_count();
                 *flush_state = FlushState::Ready;
             }
             Err(e) => {
                 error!("Failed to run flush task, err:{e}");
 
-                schedule_sync.inc_flush_failure_count();
+                table_data.inc_flush_failure_count();
                 let err_msg = e.to_string();
                 *flush_state = FlushState::Failed { err_msg };
             }
@@ -20,7 +20,8 @@ use tokio::sync::MutexGuard;
 use wal::{
     log_batch::LogEntry,
     manager::{
-        ReadBoundary, ReadContext, ReadRequest, RegionId, ScanContext, ScanRequest, WalManagerRef,
+        ReadBoundary, ReadContext, ReadRequest, RegionId, ScanContext, ScanRequest, SequenceNumber,
+        WalManagerRef,
     },
 };
 
@@ -183,53 +184,90 @@ impl TableBasedReplay {
         table_data: &TableDataRef,
         read_ctx: &ReadContext,
     ) -> Result<()> {
-        let table_location = table_data.table_location();
-        let wal_location =
-            instance::create_wal_location(table_location.id, table_location.shard_info);
-        let read_req = ReadRequest {
-            location: wal_location,
-            start: ReadBoundary::Excluded(table_data.current_version().flushed_sequence()),
-            end: ReadBoundary::Max,
-        };
+        let mut serial_exec = table_data.serial_exec.lock().await;
+        let flushed_sequence = table_data.current_version().flushed_sequence();
+        replay_table_logs_after(
+            context,
+            read_ctx,
+            &mut serial_exec,
+            table_data,
+            flushed_sequence,
+        )
+        .await
+    }
+}
 
-        // Read all wal of current table.
-        let mut log_iter = context
-            .wal_manager
-            .read_batch(read_ctx, &read_req)
+/// Replay the logs of the table after its last sequence into the memtables,
+/// which brings back the rows written to the wal but missing from the
+/// memtables because the memtable write failed.
+///
+/// The rows of the failed write that are already in the memtables are
+/// ignored, because they are put with the same key sequences again.
+pub(crate) async fn replay_table_logs_after_last_sequence(
+    context: &ReplayContext,
+    serial_exec: &mut TableOpSerialExecutor,
+    table_data: &TableDataRef,
+) -> Result<()> {
+    let read_ctx = ReadContext {
+        batch_size: context.wal_replay_batch_size,
+        ..Default::default()
+    };
+    let last_sequence = table_data.last_sequence();
+    replay_table_logs_after(context, &read_ctx, serial_exec, table_data, last_sequence).await
+}
+
+/// Replay the logs of the table whose sequences are greater than `sequence`.
+async fn replay_table_logs_after(
+    context: &ReplayContext,
+    read_ctx: &ReadContext,
+    serial_exec: &mut TableOpSerialExecutor,
+    table_data: &TableDataRef,
+    sequence: SequenceNumber,
+) -> Result<()> {
+    let table_location = table_data.table_location();
+    let wal_location = instance::create_wal_location(table_location.id, table_location.shard_info);
+    let read_req = ReadRequest {
+        location: wal_location,
+        start: ReadBoundary::Excluded(sequence),
+        end: ReadBoundary::Max,
+    };
+
+    // Read all wal of current table.
+    let mut log_iter = context
+        .wal_manager
+        .read_batch(read_ctx, &read_req)
+        .await
+        .box_err()
+        .context(ReplayWalWithCause { msg: None })?;
+
+    let mut log_entry_buf = VecDeque::with_capacity(context.wal_replay_batch_size);
+    loop {
+        // fetch entries to log_entry_buf
+        let _timer = PULL_LOGS_DURATION_HISTOGRAM.start_timer();
+        let decoder = WalDecoder::default();
+        log_entry_buf = log_iter
+            .next_log_entries(decoder, log_entry_buf)
             .await
             .box_err()
             .context(ReplayWalWithCause { msg: None })?;
 
-        let mut serial_exec = table_data.serial_exec.lock().await;
-        let mut log_entry_buf = VecDeque::with_capacity(context.wal_replay_batch_size);
-        loop {
-            // fetch entries to log_entry_buf
-            let _timer = PULL_LOGS_DURATION_HISTOGRAM.start_timer();
-            let decoder = WalDecoder::default();
-            log_entry_buf = log_iter
-                .next_log_entries(decoder, log_entry_buf)
-                .await
-                .box_err()
-                .context(ReplayWalWithCause { msg: None })?;
-
-            if log_entry_buf.is_empty() {
-                break;
-            }
-
-            // Replay all log entries of current table
-            let _timer = APPLY_LOGS_DURATION_HISTOGRAM.start_timer();
-            replay_table_log_entries(
-                &context.flusher,
-                context.max_retry_flush_limit,
-                &mut serial_exec,
-                table_data,
-                log_entry_buf.iter(),
-            )
-            .await?;
+        if log_entry_buf.is_empty() {
+            break;
         }
 
-        Ok(())
+        // Replay all log entries of current table
+        let _timer = APPLY_LOGS_DURATION_HISTOGRAM.start_timer();
+        replay_table_log_entries(
+            &context.flusher,
+            context.max_retry_flush_limit,
+            serial_exec,
+            table_data,
+            log_entry_buf.iter(),
+        )
+        .await?;
     }
+
+    Ok(())
 }
 
 /// Region based wal replay
@@ -476,8 +514,8 @@ async fn replay_table_log_entries(
 
                 let index_in_writer =
                     IndexInWriterSchema::for_same_schema(row_group.schema().num_columns());
-                let memtable_writer = MemTableWriter::new(table_data.clone(), serial_exec);
-                memtable_writer
+                let mut memtable_writer = MemTableWriter::new(table_data.clone(), serial_exec);
+                let stats = memtable_writer
                     .write(sequence, &row_group.into(), index_in_writer)
                     .box_err()
                     .context(ReplayWalWithCause {
@@ -488,7 +526,7 @@ async fn replay_table_log_entries(
                     })?;
 
                 // Flush the table if necessary.
-                if table_data.should_flush_table(serial_exec) {
+                if stats.should_flush {
                     let opts = TableFlushOptions {
                         res_sender: None,
                         max_retry_flush_limit,
@@ -2,31 +2,49 @@
 
 //! Write logic of instance
 
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Once},
+    time::{Duration, Instant},
+};
+
 use ceresdbproto::{schema as schema_pb, table_requests};
 use common_types::{
     bytes::ByteVec,
-    row::{RowGroup, RowGroupSlicer},
-    schema::{IndexInWriterSchema, Schema},
+    column_schema,
+    datum::DatumKind,
+    row::{Row, RowGroup, RowGroupBuilder, RowGroupSlicer},
+    schema::{self, IndexInWriterSchema, Schema, Version},
+    time::Timestamp,
 };
 use common_util::{codec::row, define_result};
+use futures::{Stream, StreamExt};
 use log::{debug, error, info, trace, warn};
 use smallvec::SmallVec;
-use snafu::{ensure, Backtrace, ResultExt, Snafu};
-use table_engine::table::WriteRequest;
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+use table_engine::table::{Durability, TableId, WriteMode, WriteRequest};
 use wal::{
     kv_encoder::LogBatchEncoder,
-    manager::{SequenceNumber, WalLocation, WriteContext},
+    manager::{SequenceNumber, WalLocation, WriteContext, WriteDurability},
 };
 
 use crate::{
     instance,
     instance::{
-        flush_compaction::TableFlushOptions, serial_executor::TableOpSerialExecutor, InstanceRef,
+        flush_compaction::TableFlushOptions,
+        serial_executor::TableOpSerialExecutor,
+        wal_replayer::{self, ReplayContext},
+        Instance, InstanceRef,
     },
-    memtable::{key::KeySequence, PutContext},
-    payload::WritePayload,
+    memtable::key::KeySequence,
+    payload::{WritePayload, WAL_WRITE_REQUEST_VERSION},
     space::{SpaceAndTable, SpaceRef},
-    table::{data::TableDataRef, version::MemTableForWrite},
+    table::{
+        data::{TableData, TableDataRef},
+        version::MemTableForWrite,
+        write_dedup::{WriteDedupOptions, WrittenRequest},
+    },
 };
 
 #[derive(Debug, Snafu)]
@@ -90,11 +108,65 @@ pub enum Error {
     ))]
     BackgroundFlushFailed { msg: String, backtrace: Backtrace },
 
+    #[snafu(display(
+        "Write is stalled by the flush for too long, table:{}, flush_table:{}, timeout:{:?}.\nBacktrace:\n{}",
+        table,
+        flush_table,
+        timeout,
+        backtrace
+    ))]
+    WriteStalled {
+        table: String,
+        flush_table: String,
+        timeout: Duration,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Write is rate limited, table:{}, rows:{}, max_write_rows_per_sec:{}.\nBacktrace:\n{}",
+        table,
+        rows,
+        max_write_rows_per_sec,
+        backtrace
+    ))]
+    RateLimited {
+        table: String,
+        rows: usize,
+        max_write_rows_per_sec: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Timestamp is missing from the row, table:{}, row_idx:{}.\nBacktrace:\n{}",
+        table,
+        row_idx,
+        backtrace
+    ))]
+    MissingTimestamp {
+        table: String,
+        row_idx: usize,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Schema of request is incompatible with table, err:{}", source))]
     IncompatSchema {
         source: common_types::schema::CompatError,
     },
 
+    #[snafu(display(
+        "Data type of column to write is incompatible with table, table:{}, column:{}, expect:{:?}, given:{:?}",
+        table,
+        column,
+        expect,
+        given
+    ))]
+    IncompatDataType {
+        table: String,
+        column: String,
+        expect: DatumKind,
+        given: DatumKind,
+    },
+
     #[snafu(display("Failed to encode row group, err:{}", source))]
     EncodeRowGroup {
         source: common_util::codec::row::Error,
@@ -102,17 +174,157 @@ pub enum Error {
 
     #[snafu(display("Failed to update sequence of memtable, err:{}", source))]
     UpdateMemTableSequence { source: crate::memtable::Error },
+
+    #[snafu(display(
+        "Write request is partially applied, table:{}, rows_written:{}, \
+         batches_committed:{}, batches_in_wal:{}, err:{}",
+        table,
+        result.rows_written,
+        result.batches_committed,
+        result.batches_in_wal,
+        source
+    ))]
+    PartialWrite {
+        table: String,
+        result: WriteResult,
+        source: Box<Error>,
+    },
+
+    #[snafu(display(
+        "Batches of a write request must have increasing sequences, table:{}, table_id:{}, prev_sequence:{}, sequence:{}.\nBacktrace:\n{}",
+        table,
+        table_id,
+        prev_sequence,
+        sequence,
+        backtrace
+    ))]
+    NonIncreasingSequence {
+        table: String,
+        table_id: TableId,
+        prev_sequence: SequenceNumber,
+        sequence: SequenceNumber,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Table to write is not found, table_id:{}.\nBacktrace:\n{}",
+        table_id,
+        backtrace
+    ))]
+    TableNotFound {
+        table_id: TableId,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Table needs recovery from the wal before write, table:{}.\nBacktrace:\n{}",
+        table,
+        backtrace
+    ))]
+    TableNeedsRecovery { table: String, backtrace: Backtrace },
 }
 
 define_result!(Error);
 
+impl Error {
+    /// Whether the failed memtable write may succeed if it is retried.
+    ///
+    /// Only the creation of the memtable may fail temporarily, and the other
+    /// errors (e.g. the rows can't be encoded) fail the same way every time.
+    fn is_retryable_memtable_write(&self) -> bool {
+        matches!(
+            self,
+            Error::FindMutableMemTable {
+                source: crate::table::data::Error::CreateMemTable { .. },
+                ..
+            }
+        )
+    }
+}
+
 /// Max rows in a write request, must less than [u32::MAX]
 const MAX_ROWS_TO_WRITE: usize = 10_000_000;
 
+/// Max retries of writing the memtable after the rows are written to the wal.
+const MAX_MEMTABLE_WRITE_RETRIES: usize = 2;
+
+/// A row failed to be written in the [WriteMode::BestEffort] mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedRow {
+    /// Index of the row in the write request.
+    pub row_idx: usize,
+    /// Message of the error.
+    pub msg: String,
+}
+
+/// Result of a write request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteResult {
+    /// Number of rows of the committed batches, including the expired ones
+    /// skipped by the memtable.
+    pub rows_written: usize,
+    /// Number of the encoded bytes of the committed batches.
+    pub bytes_written: usize,
+    /// Number of batches committed, the write request may be splitted into
+    /// multiple batches.
+    pub batches_committed: usize,
+    /// Number of batches written to the wal, which is greater than the
+    /// `batches_committed` if the memtable write of a batch fails after its
+    /// wal write (or the wal write of the next batch in the pipelined way),
+    /// and these batches are applied by the replay of the wal.
+    pub batches_in_wal: usize,
+    /// Number of rows skipped because they are expired.
+    pub rows_skipped_expired: usize,
+    /// Rows failed to be encoded, only set in the [WriteMode::BestEffort]
+    /// mode.
+    pub failed_rows: Vec<FailedRow>,
+    /// Sequence of the last committed batch.
+    pub sequence: SequenceNumber,
+    /// Whether the request is a duplicate of a written one with the same
+    /// request id, and nothing is written if set.
+    pub deduplicated: bool,
+}
+
+/// Result of a streaming write, see [Writer::write_stream].
+#[derive(Debug, Default)]
+pub struct WriteStreamResult {
+    /// Number of rows written of all the row groups, including the ones of
+    /// the committed batches of the partially written row groups.
+    pub rows_written: usize,
+    /// Number of the row groups written successfully.
+    pub row_groups_written: usize,
+    /// Sequence of the last committed batch.
+    pub sequence: SequenceNumber,
+    /// Errors of the row groups failed to be written, along with the index of
+    /// the row group in the stream.
+    pub errors: Vec<(usize, Error)>,
+}
+
+/// Stats of writing rows into the memtable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteStats {
+    /// Number of rows written into the memtable.
+    pub written: usize,
+    /// Number of rows skipped because they are expired.
+    pub skipped_expired: usize,
+    /// Whether the table should be flushed after the write.
+    pub should_flush: bool,
+}
+
+/// The [IndexInWriterSchema] of a writer schema, which can be reused by the
+/// row groups of the same schema until the table schema is altered.
+struct CachedIndexInWriter {
+    writer_schema: Schema,
+    table_schema_version: Version,
+    index_in_writer: IndexInWriterSchema,
+}
+
 pub(crate) struct EncodeContext {
     pub row_group: RowGroup,
     pub index_in_writer: IndexInWriterSchema,
     pub encoded_rows: Vec<ByteVec>,
+    /// Buffers checked out from the pool to encode the rows into.
+    row_bufs: Vec<ByteVec>,
 }
 
 impl EncodeContext {
@@ -121,32 +333,118 @@ impl EncodeContext {
             row_group,
             index_in_writer: IndexInWriterSchema::default(),
             encoded_rows: Vec::new(),
+            row_bufs: Vec::new(),
         }
     }
 
-    pub fn encode_rows(&mut self, table_schema: &Schema) -> Result<()> {
-        row::encode_row_group_for_wal(
-            &self.row_group,
-            table_schema,
-            &self.index_in_writer,
-            &mut self.encoded_rows,
-        )
-        .context(EncodeRowGroup)?;
+    /// Encode the rows into the `row_bufs` instead of the newly allocated
+    /// buffers, and the buffers are allocated if the `row_bufs` run out.
+    pub fn with_row_bufs(mut self, row_bufs: Vec<ByteVec>) -> Self {
+        self.row_bufs = row_bufs;
+        self
+    }
+
+    /// Encode all the rows, and [Error::MissingTimestamp] is returned if any
+    /// row has no timestamp.
+    ///
+    /// The rows without timestamp must be rejected before they are written to
+    /// the wal, because they can't be inserted into the memtable, neither by
+    /// the write nor by the replay of the wal.
+    pub fn encode_rows(&mut self, table: &str, table_schema: &Schema) -> Result<()> {
+        for (row_idx, row) in self.row_group.iter().enumerate() {
+            ensure!(
+                row.timestamp(table_schema).is_some(),
+                MissingTimestamp { table, row_idx }
+            );
+        }
+
+        if self.row_bufs.is_empty() {
+            row::encode_row_group_for_wal(
+                &self.row_group,
+                table_schema,
+                &self.index_in_writer,
+                &mut self.encoded_rows,
+            )
+            .context(EncodeRowGroup)?;
+        } else {
+            self.encoded_rows.reserve(self.row_group.num_rows());
+            for row in self.row_group.iter() {
+                let mut buf = self.row_bufs.pop().unwrap_or_default();
+                row::encode_row_for_wal(row, table_schema, &self.index_in_writer, &mut buf)
+                    .context(EncodeRowGroup)?;
+                self.encoded_rows.push(buf);
+            }
+        }
 
         assert_eq!(self.row_group.num_rows(), self.encoded_rows.len());
 
         Ok(())
     }
+
+    /// Encode the rows one by one, and the rows failed to be encoded are
+    /// removed from the `row_group` and returned, including the ones without
+    /// timestamp.
+    pub fn encode_rows_best_effort(&mut self, table_schema: &Schema) -> Vec<FailedRow> {
+        let mut failed_rows = Vec::new();
+        self.encoded_rows.reserve(self.row_group.num_rows());
+        for (row_idx, row) in self.row_group.iter().enumerate() {
+            if row.timestamp(table_schema).is_none() {
+                failed_rows.push(FailedRow {
+                    row_idx,
+                    msg: "Timestamp is missing from the row".to_string(),
+                });
+                continue;
+            }
+
+            let mut buf = self.row_bufs.pop().unwrap_or_default();
+            match row::encode_row_for_wal(row, table_schema, &self.index_in_writer, &mut buf) {
+                Ok(()) => self.encoded_rows.push(buf),
+                Err(e) => {
+                    buf.clear();
+                    self.row_bufs.push(buf);
+                    failed_rows.push(FailedRow {
+                        row_idx,
+                        msg: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        if !failed_rows.is_empty() {
+            // Remove the failed rows to keep the row group matched with the encoded rows.
+            let schema = self.row_group.schema().clone();
+            let rows = self.row_group.take_rows();
+            let mut builder = RowGroupBuilder::with_capacity(schema, self.encoded_rows.len());
+            let mut failed_row_idxs = failed_rows.iter().map(|v| v.row_idx).peekable();
+            for (row_idx, row) in rows.into_iter().enumerate() {
+                if failed_row_idxs.peek() == Some(&row_idx) {
+                    failed_row_idxs.next();
+                    continue;
+                }
+                builder.push_checked_row(row);
+            }
+            self.row_group = builder.build();
+        }
+
+        assert_eq!(self.row_group.num_rows(), self.encoded_rows.len());
+
+        failed_rows
+    }
 }
 
 /// Split the write request into multiple batches whose size is determined by
-/// the `max_bytes_per_batch`.
+/// the `max_bytes_per_batch` and the `max_rows_per_batch`.
 struct WriteRowGroupSplitter {
     /// Max bytes per batch. Actually, the size of a batch is not exactly
     /// ensured less than this `max_bytes_per_batch`, but it is guaranteed that
     /// the batch contains at most one more row when its size exceeds this
     /// `max_bytes_per_batch`.
+    ///
+    /// Zero is considered as a misconfiguration and no limit is applied on the
+    /// bytes, rather than making every row a batch.
     max_bytes_per_batch: usize,
+    /// Max rows per batch, no limit on the rows if not set.
+    max_rows_per_batch: Option<usize>,
 }
 
 enum SplitResult<'a> {
@@ -160,10 +458,50 @@ enum SplitResult<'a> {
     },
 }
 
+impl<'a> SplitResult<'a> {
+    /// Skip the leading `n` batches, None if all the batches are skipped.
+    fn skip_batches(self, n: usize) -> Option<Self> {
+        if n == 0 {
+            return Some(self);
+        }
+
+        match self {
+            SplitResult::Integrate { .. } => None,
+            SplitResult::Splitted {
+                mut encoded_batches,
+                mut row_group_batches,
+            } => {
+                if n >= encoded_batches.len() {
+                    return None;
+                }
+
+                encoded_batches.drain(..n);
+                row_group_batches.drain(..n);
+                Some(SplitResult::Splitted {
+                    encoded_batches,
+                    row_group_batches,
+                })
+            }
+        }
+    }
+}
+
 impl WriteRowGroupSplitter {
-    pub fn new(max_bytes_per_batch: usize) -> Self {
+    pub fn new(max_bytes_per_batch: usize, max_rows_per_batch: Option<usize>) -> Self {
+        static ZERO_MAX_BYTES_WARNING: Once = Once::new();
+
+        let max_bytes_per_batch = if max_bytes_per_batch == 0 {
+            ZERO_MAX_BYTES_WARNING.call_once(|| {
+                warn!("Max bytes per write batch is zero, and the write request won't be split by the bytes");
+            });
+            usize::MAX
+        } else {
+            max_bytes_per_batch
+        };
+
         Self {
             max_bytes_per_batch,
+            max_rows_per_batch,
         }
     }
 
@@ -217,22 +555,30 @@ impl WriteRowGroupSplitter {
     /// batch.
     fn compute_batches(&self, encoded_rows: &[ByteVec]) -> Vec<usize> {
         let mut current_batch_size = 0;
+        let mut current_batch_rows = 0;
         let mut end_row_indexes = Vec::new();
         for (row_idx, encoded_row) in encoded_rows.iter().enumerate() {
             let row_size = encoded_row.len();
             current_batch_size += row_size;
+            current_batch_rows += 1;
 
-            // If the current batch size exceeds the `max_bytes_per_batch`, freeze this
-            // batch by recording its end row index.
+            // If the current batch size exceeds the `max_bytes_per_batch` or the rows of
+            // it reaches the `max_rows_per_batch`, freeze this batch by recording its end
+            // row index.
             // Note that such check may cause the batch size exceeds the
             // `max_bytes_per_batch`.
-            if current_batch_size >= self.max_bytes_per_batch {
+            let reach_max_rows = self
+                .max_rows_per_batch
+                .map(|max_rows| current_batch_rows >= max_rows)
+                .unwrap_or(false);
+            if current_batch_size >= self.max_bytes_per_batch || reach_max_rows {
                 current_batch_size = 0;
+                current_batch_rows = 0;
                 end_row_indexes.push(row_idx + 1)
             }
         }
 
-        if current_batch_size > 0 {
+        if current_batch_rows > 0 {
             end_row_indexes.push(encoded_rows.len());
         }
 
@@ -240,6 +586,148 @@ impl WriteRowGroupSplitter {
     }
 }
 
+/// Wait for the `flush` to finish, and [Error::WriteStalled] is returned if
+/// it takes longer than the `timeout`.
+async fn with_write_stall_timeout<F>(
+    timeout: Option<Duration>,
+    table: &str,
+    flush_table: &str,
+    flush: F,
+) -> Result<()>
+where
+    F: Future<Output = Result<()>>,
+{
+    let timeout = match timeout {
+        Some(v) => v,
+        None => return flush.await,
+    };
+
+    match tokio::time::timeout(timeout, flush).await {
+        Ok(res) => res,
+        Err(_) => {
+            warn!("Write is stalled by the flush, table:{table}, flush_table:{flush_table}, timeout:{timeout:?}");
+            WriteStalled {
+                table,
+                flush_table,
+                timeout,
+            }
+            .fail()
+        }
+    }
+}
+
+/// Resolve the max bytes per write batch, and the table level one takes
+/// precedence over the instance level one.
+#[inline]
+fn resolve_max_bytes_per_write_batch(
+    table_level: Option<usize>,
+    instance_level: Option<usize>,
+) -> Option<usize> {
+    table_level.or(instance_level)
+}
+
+impl Instance {
+    /// Write the requests of multiple tables in one call.
+    ///
+    /// The requests are grouped by the table, and the tables are written
+    /// concurrently. The requests of the same table are written in order by one
+    /// [Writer], so the table is looked up and its serial executor is acquired
+    /// only once.
+    ///
+    /// Returns the result of every request in the order of the `requests`.
+    pub async fn write_many(
+        self: &Arc<Self>,
+        requests: Vec<(TableId, WriteRequest)>,
+    ) -> Vec<(TableId, Result<WriteResult>)> {
+        let num_requests = requests.len();
+        let mut table_indexes = HashMap::new();
+        let mut table_requests: Vec<(TableId, Vec<(usize, WriteRequest)>)> = Vec::new();
+        for (idx, (table_id, request)) in requests.into_iter().enumerate() {
+            let table_idx = *table_indexes.entry(table_id).or_insert_with(|| {
+                table_requests.push((table_id, Vec::new()));
+                table_requests.len() - 1
+            });
+            table_requests[table_idx].1.push((idx, request));
+        }
+
+        let table_writes = table_requests
+            .into_iter()
+            .map(|(table_id, requests)| self.write_table_requests(table_id, requests));
+        let mut results: Vec<_> = futures::future::join_all(table_writes)
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        results.sort_unstable_by_key(|(idx, _)| *idx);
+        debug_assert_eq!(num_requests, results.len());
+
+        results.into_iter().map(|(_, res)| res).collect()
+    }
+
+    /// Write the `requests` of the table in order, and the results are returned
+    /// along with the index of the request.
+    async fn write_table_requests(
+        self: &Arc<Self>,
+        table_id: TableId,
+        requests: Vec<(usize, WriteRequest)>,
+    ) -> Vec<(usize, (TableId, Result<WriteResult>))> {
+        let space_table = self
+            .space_store
+            .spaces
+            .read()
+            .unwrap()
+            .find_table_by_id(table_id);
+        let space_table = match space_table {
+            Some(v) => v,
+            None => {
+                return requests
+                    .into_iter()
+                    .map(|(idx, _)| (idx, (table_id, TableNotFound { table_id }.fail())))
+                    .collect();
+            }
+        };
+
+        let table_data = space_table.table_data().clone();
+        let _timer = table_data.metrics.start_table_total_timer();
+        let mut serial_exec = table_data.serial_exec.lock().await;
+        let mut writer = Writer::new(self.clone(), space_table, &mut serial_exec);
+        let mut results = Vec::with_capacity(requests.len());
+        for (idx, request) in requests {
+            let res = writer.write(request).await;
+            results.push((idx, (table_id, res)));
+        }
+
+        results
+    }
+
+    /// Write the row groups pushed by the `row_groups` over time into the
+    /// table, see [Writer::write_stream].
+    pub async fn write_stream<S>(
+        self: &Arc<Self>,
+        table_id: TableId,
+        row_groups: S,
+        durability: Durability,
+    ) -> Result<WriteStreamResult>
+    where
+        S: Stream<Item = RowGroup> + Unpin,
+    {
+        let space_table = self
+            .space_store
+            .spaces
+            .read()
+            .unwrap()
+            .find_table_by_id(table_id)
+            .context(TableNotFound { table_id })?;
+
+        let table_data = space_table.table_data().clone();
+        let _timer = table_data.metrics.start_table_total_timer();
+        let mut serial_exec = table_data.serial_exec.lock().await;
+        let mut writer = Writer::new(self.clone(), space_table, &mut serial_exec);
+
+        Ok(writer.write_stream(row_groups, durability).await)
+    }
+}
+
 pub struct Writer<'a> {
     instance: InstanceRef,
     space: SpaceRef,
@@ -264,48 +752,98 @@ impl<'a> Writer<'a> {
     }
 }
 
+type WroteMemTables = SmallVec<[MemTableForWrite; 4]>;
+
+/// Push the memtable into `wrote_memtables` if it is not in it yet.
+///
+/// The same memtable may be found again when the rows are out of order, and
+/// it is enough to update its last sequence only once.
+fn push_wrote_memtable(wrote_memtables: &mut WroteMemTables, memtable: MemTableForWrite) {
+    if wrote_memtables.iter().all(|v| v.id() != memtable.id()) {
+        wrote_memtables.push(memtable);
+    }
+}
+
+/// Interval of rows to check whether the table should be flushed when
+/// inserting into the memtable.
+const ROWS_PER_FLUSH_CHECK: usize = 1024;
+
 pub(crate) struct MemTableWriter<'a> {
     table_data: TableDataRef,
-    _serial_exec: &'a mut TableOpSerialExecutor,
+    serial_exec: &'a mut TableOpSerialExecutor,
+    presort_rows: bool,
+    buffer_pool_size: usize,
 }
 
 impl<'a> MemTableWriter<'a> {
     pub fn new(table_data: TableDataRef, serial_exec: &'a mut TableOpSerialExecutor) -> Self {
         Self {
             table_data,
-            _serial_exec: serial_exec,
+            serial_exec,
+            presort_rows: false,
+            buffer_pool_size: 0,
         }
     }
 
-    // TODO(yingwen): How to trigger flush if we found memtables are full during
-    // inserting memtable? RocksDB checks memtable size in MemTableInserter
-    /// Write data into memtable.
+    /// Group the rows by the time range of their memtables before inserting.
+    pub fn presort_rows(mut self, presort_rows: bool) -> Self {
+        self.presort_rows = presort_rows;
+        self
+    }
+
+    /// Reuse the buffers of the table to put rows, and at most
+    /// `buffer_pool_size` buffers are kept after the write.
+    pub fn buffer_pool_size(mut self, buffer_pool_size: usize) -> Self {
+        self.buffer_pool_size = buffer_pool_size;
+        self
+    }
+
+    /// Write data into memtable, and the expired rows are skipped.
+    ///
+    /// Like the MemTableInserter of RocksDB, whether the table should be
+    /// flushed is checked after switching the memtable and every
+    /// [ROWS_PER_FLUSH_CHECK] rows, and the result is returned in
+    /// [WriteStats::should_flush]. The insertion is never blocked by the
+    /// check and it is up to the caller to schedule the flush.
+    ///
+    /// The key sequence of a row is `(sequence, row_idx)`, where `row_idx` is
+    /// the index in the `row_group` rather than in the original request, so
+    /// every batch of a splitted request must be written with its own
+    /// sequence to keep the key sequences unique.
     ///
     /// index_in_writer must match the schema in table_data.
     pub fn write(
-        &self,
+        &mut self,
         sequence: SequenceNumber,
         row_group: &RowGroupSlicer,
         index_in_writer: IndexInWriterSchema,
-    ) -> Result<()> {
+    ) -> Result<WriteStats> {
         let _timer = self.table_data.metrics.start_table_write_memtable_timer();
+        let mut stats = WriteStats::default();
         if row_group.is_empty() {
-            return Ok(());
+            return Ok(stats);
         }
 
         let schema = &self.table_data.schema();
         // Store all memtables we wrote and update their last sequence later.
-        let mut wrote_memtables: SmallVec<[_; 4]> = SmallVec::new();
+        let mut wrote_memtables = WroteMemTables::new();
         let mut last_mutable_mem: Option<MemTableForWrite> = None;
-
-        let mut ctx = PutContext::new(index_in_writer);
-        for (row_idx, row) in row_group.iter().enumerate() {
-            // TODO(yingwen): Add RowWithSchema and take RowWithSchema as input, then remove
-            // this unwrap()
-            let timestamp = row.timestamp(schema).unwrap();
+        let mut rows_since_flush_check = 0;
+
+        let rows_order = self.rows_order(row_group, schema)?;
+        // The context is dropped instead of being returned to the pool if the write
+        // fails.
+        let mut ctx = self.table_data.write_buffer_pool.get(index_in_writer);
+        for i in 0..row_group.num_rows() {
+            // The index in the row group rather than the insertion order is used in the key
+            // sequence, so the rows are mapped in the same way when replaying wal.
+            let row_idx = rows_order.as_ref().map_or(i, |order| order[i]);
+            let row = row_group.get_row(row_idx).unwrap();
+            let timestamp = self.row_timestamp(row, row_idx, schema)?;
             // skip expired row
             if self.table_data.is_expired(timestamp) {
                 trace!("Skip expired row when write to memtable, row:{:?}", row);
+                stats.skipped_expired += 1;
                 continue;
             }
             if last_mutable_mem.is_none()
@@ -321,8 +859,10 @@ impl<'a> MemTableWriter<'a> {
                     .context(FindMutableMemTable {
                         table: &self.table_data.name,
                     })?;
-                wrote_memtables.push(mutable_mem.clone());
+                push_wrote_memtable(&mut wrote_memtables, mutable_mem.clone());
                 last_mutable_mem = Some(mutable_mem);
+                // Check as soon as the row is put into the switched memtable.
+                rows_since_flush_check = ROWS_PER_FLUSH_CHECK;
             }
 
             // We have check the row num is less than `MAX_ROWS_TO_WRITE`, it is safe to
@@ -336,8 +876,22 @@ impl<'a> MemTableWriter<'a> {
                 .context(WriteMemTable {
                     table: &self.table_data.name,
                 })?;
+            stats.written += 1;
+
+            rows_since_flush_check += 1;
+            if !stats.should_flush && rows_since_flush_check >= ROWS_PER_FLUSH_CHECK {
+                stats.should_flush = self.table_data.should_flush_table(self.serial_exec);
+                rows_since_flush_check = 0;
+            }
         }
 
+        if !stats.should_flush && rows_since_flush_check > 0 {
+            stats.should_flush = self.table_data.should_flush_table(self.serial_exec);
+        }
+        self.table_data
+            .write_buffer_pool
+            .put(ctx, self.buffer_pool_size);
+
         // Update last sequence of memtable.
         for mem_wrote in wrote_memtables {
             mem_wrote
@@ -345,97 +899,588 @@ impl<'a> MemTableWriter<'a> {
                 .context(UpdateMemTableSequence)?;
         }
 
-        Ok(())
+        Ok(stats)
+    }
+
+    /// Returns the order to insert the rows, or None if the rows should be
+    /// inserted as they are.
+    ///
+    /// The rows are stably sorted by the start of the segment they belong to,
+    /// so the rows of the same memtable are inserted together while keeping
+    /// their relative order. Nothing needs to be sorted if the segment
+    /// duration is not determined yet, because all the rows are inserted into
+    /// the sampling memtable.
+    fn rows_order(
+        &self,
+        row_group: &RowGroupSlicer,
+        schema: &Schema,
+    ) -> Result<Option<Vec<usize>>> {
+        if !self.presort_rows {
+            return Ok(None);
+        }
+
+        let segment_duration_ms = match self
+            .table_data
+            .table_options()
+            .segment_duration()
+            .and_then(|v| i64::try_from(v.as_millis()).ok())
+        {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let mut segment_starts = Vec::with_capacity(row_group.num_rows());
+        for (row_idx, row) in row_group.iter().enumerate() {
+            let timestamp = self.row_timestamp(row, row_idx, schema)?;
+            // The overflowed timestamp will be rejected when finding its memtable.
+            let segment_start = timestamp
+                .checked_floor_by_i64(segment_duration_ms)
+                .unwrap_or(timestamp);
+            segment_starts.push((segment_start, row_idx));
+        }
+
+        // Most requests only write one segment, and no need to sort them.
+        if segment_starts.windows(2).all(|pair| pair[0].0 == pair[1].0) {
+            return Ok(None);
+        }
+        segment_starts.sort_by_key(|(segment_start, _)| *segment_start);
+
+        Ok(Some(
+            segment_starts
+                .into_iter()
+                .map(|(_, row_idx)| row_idx)
+                .collect(),
+        ))
+    }
+
+    /// Returns the timestamp of the row, or [Error::MissingTimestamp] if the
+    /// row has no valid timestamp at the timestamp column of the table.
+    ///
+    /// Such rows are already rejected by [EncodeContext] before the wal is
+    /// written, so this is only a guard against a panic.
+    fn row_timestamp(&self, row: &Row, row_idx: usize, schema: &Schema) -> Result<Timestamp> {
+        row.timestamp(schema).with_context(|| MissingTimestamp {
+            table: &self.table_data.name,
+            row_idx,
+        })
     }
 }
 
 impl<'a> Writer<'a> {
-    pub(crate) async fn write(&mut self, request: WriteRequest) -> Result<usize> {
+    /// Write the request into the wal and memtable.
+    ///
+    /// If the request is in dry run, only [Writer::validate_write] is done and
+    /// nothing is written.
+    ///
+    /// If the request contains no rows, nothing is written to the wal and no
+    /// sequence is consumed.
+    ///
+    /// If the request is splitted into multiple batches and a latter batch
+    /// fails, [Error::PartialWrite] carrying the already committed batches
+    /// will be returned.
+    ///
+    /// An access log with the totals of the request is emitted under the
+    /// [WRITE_ACCESS_LOG_TARGET] once the write is done.
+    pub(crate) async fn write(&mut self, request: WriteRequest) -> Result<WriteResult> {
+        let begin = Instant::now();
+        let res = self.do_write(request, &mut None).await;
+        log_write_access(&self.table_data, &res, begin.elapsed());
+
+        res
+    }
+
+    /// Write the row groups pushed by the `row_groups` over time, which is
+    /// useful for the very large ingests.
+    ///
+    /// Every row group is written as a standalone [WriteRequest] (preprocessed,
+    /// encoded, splitted and written into the wal and memtable) once it is
+    /// received, and the [IndexInWriterSchema] is reused across the row groups
+    /// as long as the schema is unchanged.
+    ///
+    /// A failed row group doesn't stop the following ones, and its error is
+    /// collected in the returned [WriteStreamResult].
+    pub(crate) async fn write_stream<S>(
+        &mut self,
+        mut row_groups: S,
+        durability: Durability,
+    ) -> WriteStreamResult
+    where
+        S: Stream<Item = RowGroup> + Unpin,
+    {
+        let mut cached_index = None;
+        let mut stream_result = WriteStreamResult::default();
+        let mut row_group_idx = 0;
+        while let Some(row_group) = row_groups.next().await {
+            let request = WriteRequest {
+                durability,
+                ..WriteRequest::new(row_group)
+            };
+
+            let begin = Instant::now();
+            let res = self.do_write(request, &mut cached_index).await;
+            log_write_access(&self.table_data, &res, begin.elapsed());
+
+            match res {
+                Ok(write_result) => {
+                    stream_result.rows_written += write_result.rows_written;
+                    stream_result.row_groups_written += 1;
+                    stream_result.sequence = stream_result.sequence.max(write_result.sequence);
+                }
+                Err(e) => {
+                    if let Error::PartialWrite { result, .. } = &e {
+                        stream_result.rows_written += result.rows_written;
+                        stream_result.sequence = stream_result.sequence.max(result.sequence);
+                    }
+                    stream_result.errors.push((row_group_idx, e));
+                }
+            }
+            row_group_idx += 1;
+        }
+
+        stream_result
+    }
+
+    /// Write the request, and the [IndexInWriterSchema] in the `cached_index`
+    /// is reused if the schemas are unchanged.
+    async fn do_write(
+        &mut self,
+        request: WriteRequest,
+        cached_index: &mut Option<CachedIndexInWriter>,
+    ) -> Result<WriteResult> {
         let _timer = self.table_data.metrics.start_table_write_execute_timer();
         self.table_data.metrics.on_write_request_begin();
 
+        if request.dry_run {
+            self.validate_write(&request)?;
+            return Ok(WriteResult::default());
+        }
+
+        self.maybe_recover_from_wal().await;
         self.validate_before_write(&request)?;
-        let mut encode_ctx = EncodeContext::new(request.row_group);
+        if request.row_group.is_empty() {
+            debug!(
+                "Skip writing empty request, table:{}, table_id:{}",
+                self.table_data.name, self.table_data.id
+            );
+            return Ok(WriteResult::default());
+        }
 
-        self.preprocess_write(&mut encode_ctx).await?;
+        let dedup_opts = self.instance.write_dedup_opts;
+        let written = self.find_written_request(&request, &dedup_opts);
+        if let Some(written) = written.filter(|written| written.partial_batches.is_none()) {
+            return Ok(WriteResult {
+                rows_written: written.rows_written,
+                sequence: written.sequence,
+                deduplicated: true,
+                ..Default::default()
+            });
+        }
 
-        {
+        self.acquire_write_rate(request.row_group.num_rows())?;
+
+        let WriteRequest {
+            row_group,
+            mode,
+            durability,
+            request_id,
+            ..
+        } = request;
+        let write_ctx = build_wal_write_context(durability, self.table_data.wal_sync());
+        let mut encode_ctx = EncodeContext::new(row_group)
+            .with_row_bufs(self.table_data.write_buffer_pool.get_row_bufs());
+
+        self.preprocess_write(&mut encode_ctx, cached_index).await?;
+
+        let failed_rows = {
             let _timer = self.table_data.metrics.start_table_write_encode_timer();
             let schema = self.table_data.schema();
-            encode_ctx.encode_rows(&schema)?;
-        }
+            match mode {
+                WriteMode::AllOrNothing => {
+                    encode_ctx.encode_rows(&self.table_data.name, &schema)?;
+                    Vec::new()
+                }
+                WriteMode::BestEffort => encode_ctx.encode_rows_best_effort(&schema),
+            }
+        };
 
         let EncodeContext {
             row_group,
             index_in_writer,
             encoded_rows,
+            ..
         } = encode_ctx;
 
         let table_data = self.table_data.clone();
-        let split_res = self.maybe_split_write_request(encoded_rows, &row_group);
+        let mut write_result = WriteResult {
+            failed_rows,
+            ..Default::default()
+        };
+        // The retry of a partially written request resumes after the batches in the
+        // wal, which are applied by the replay of the wal if their memtable write
+        // failed.
+        let resumed_batches = match written {
+            Some(WrittenRequest {
+                sequence,
+                rows_written,
+                partial_batches: Some(partial_batches),
+            }) => {
+                write_result.rows_written = rows_written;
+                write_result.sequence = sequence;
+                write_result.batches_committed = partial_batches;
+                write_result.batches_in_wal = partial_batches;
+                partial_batches
+            }
+            _ => 0,
+        };
+        let split_res = self
+            .maybe_split_write_request(encoded_rows, &row_group)
+            .skip_batches(resumed_batches);
         match split_res {
-            SplitResult::Integrate {
+            None => {
+                // All the batches are written by the partially written request.
+                write_result.deduplicated = true;
+            }
+            Some(SplitResult::Integrate {
                 encoded_rows,
                 row_group,
-            } => {
-                self.write_table_row_group(&table_data, row_group, index_in_writer, encoded_rows)
-                    .await?;
+            }) => {
+                self.write_table_row_group(
+                    &table_data,
+                    &write_ctx,
+                    row_group,
+                    index_in_writer,
+                    encoded_rows,
+                    &mut write_result,
+                )
+                .await?;
             }
-            SplitResult::Splitted {
+            Some(SplitResult::Splitted {
                 encoded_batches,
                 row_group_batches,
-            } => {
-                for (encoded_rows, row_group) in encoded_batches.into_iter().zip(row_group_batches)
-                {
-                    self.write_table_row_group(
+            }) => {
+                let res = if self.instance.enable_pipelined_write_batches {
+                    self.write_batches_pipelined(
                         &table_data,
-                        row_group,
-                        index_in_writer.clone(),
-                        encoded_rows,
+                        &write_ctx,
+                        encoded_batches,
+                        row_group_batches,
+                        index_in_writer,
+                        &mut write_result,
                     )
-                    .await?;
+                    .await
+                } else {
+                    self.write_batches_sequentially(
+                        &table_data,
+                        &write_ctx,
+                        encoded_batches,
+                        row_group_batches,
+                        index_in_writer,
+                        &mut write_result,
+                    )
+                    .await
+                };
+
+                if let Err(e) = res {
+                    if write_result.batches_in_wal == 0 {
+                        return Err(e);
+                    }
+
+                    self.remember_written_request(request_id, &write_result, true, &dedup_opts);
+                    return Err(Error::PartialWrite {
+                        table: table_data.name.clone(),
+                        result: write_result,
+                        source: Box::new(e),
+                    });
                 }
             }
         }
 
-        Ok(row_group.num_rows())
+        self.remember_written_request(request_id, &write_result, false, &dedup_opts);
+
+        Ok(write_result)
     }
 
-    fn maybe_split_write_request<'b>(
-        &'a self,
-        encoded_rows: Vec<ByteVec>,
-        row_group: &'b RowGroup,
-    ) -> SplitResult<'b> {
-        if self.instance.max_bytes_per_write_batch.is_none() {
-            return SplitResult::Integrate {
-                encoded_rows,
-                row_group: RowGroupSlicer::from(row_group),
+    /// Remember the outcome of the request with the request id, so its retry is
+    /// deduplicated, or resumes after the batches in the wal if it is
+    /// partially written.
+    fn remember_written_request(
+        &self,
+        request_id: Option<String>,
+        write_result: &WriteResult,
+        partial: bool,
+        dedup_opts: &WriteDedupOptions,
+    ) {
+        let request_id = match request_id {
+            Some(v) if dedup_opts.is_enabled() => v,
+            _ => return,
+        };
+
+        let written = WrittenRequest {
+            sequence: write_result.sequence,
+            rows_written: write_result.rows_written,
+            partial_batches: partial.then_some(write_result.batches_in_wal),
+        };
+        self.table_data
+            .write_dedup
+            .put(request_id, written, dedup_opts);
+    }
+
+    /// Take the rows of the request from the rate limiter of the table, and
+    /// [Error::RateLimited] is returned if the rate is exhausted.
+    fn acquire_write_rate(&self, rows: usize) -> Result<()> {
+        let max_write_rows_per_sec = match self.table_data.max_write_rows_per_sec() {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        if !self
+            .table_data
+            .write_rate_limiter
+            .try_acquire(rows, max_write_rows_per_sec)
+        {
+            self.table_data.metrics.on_write_rate_limited();
+            return RateLimited {
+                table: &self.table_data.name,
+                rows,
+                max_write_rows_per_sec,
+            }
+            .fail();
+        }
+
+        Ok(())
+    }
+
+    /// Find the written request with the same request id within the window of
+    /// the deduplication, which may be partially written.
+    fn find_written_request(
+        &self,
+        request: &WriteRequest,
+        dedup_opts: &WriteDedupOptions,
+    ) -> Option<WrittenRequest> {
+        if !dedup_opts.is_enabled() {
+            return None;
+        }
+
+        let request_id = request.request_id.as_ref()?;
+        let written = self.table_data.write_dedup.get(request_id, dedup_opts)?;
+        match written.partial_batches {
+            Some(partial_batches) => info!(
+                "Resume partially written request, table:{}, table_id:{}, request_id:{}, \
+                 sequence:{}, rows_written:{}, partial_batches:{}",
+                self.table_data.name,
+                self.table_data.id,
+                request_id,
+                written.sequence,
+                written.rows_written,
+                partial_batches
+            ),
+            None => info!(
+                "Skip duplicate write request, table:{}, table_id:{}, request_id:{}, \
+                 sequence:{}, rows_written:{}",
+                self.table_data.name,
+                self.table_data.id,
+                request_id,
+                written.sequence,
+                written.rows_written
+            ),
+        }
+
+        Some(written)
+    }
+
+    fn maybe_split_write_request<'b>(
+        &'a self,
+        encoded_rows: Vec<ByteVec>,
+        row_group: &'b RowGroup,
+    ) -> SplitResult<'b> {
+        let max_bytes_per_write_batch = resolve_max_bytes_per_write_batch(
+            self.table_data.max_bytes_per_write_batch(),
+            self.instance.max_bytes_per_write_batch,
+        );
+        let max_rows_per_write_batch = self.instance.max_rows_per_write_batch;
+        match (max_bytes_per_write_batch, max_rows_per_write_batch) {
+            (None, None) => SplitResult::Integrate {
+                encoded_rows,
+                row_group: RowGroupSlicer::from(row_group),
+            },
+            (max_bytes_per_batch, max_rows_per_batch) => {
+                let splitter = WriteRowGroupSplitter::new(
+                    max_bytes_per_batch.unwrap_or(usize::MAX),
+                    max_rows_per_batch,
+                );
+                splitter.split(encoded_rows, row_group)
+            }
+        }
+    }
+
+    async fn write_batches_sequentially(
+        &mut self,
+        table_data: &TableDataRef,
+        write_ctx: &WriteContext,
+        encoded_batches: Vec<Vec<ByteVec>>,
+        row_group_batches: Vec<RowGroupSlicer<'_>>,
+        index_in_writer: IndexInWriterSchema,
+        write_result: &mut WriteResult,
+    ) -> Result<()> {
+        for (encoded_rows, row_group) in encoded_batches.into_iter().zip(row_group_batches) {
+            self.write_table_row_group(
+                table_data,
+                write_ctx,
+                row_group,
+                index_in_writer.clone(),
+                encoded_rows,
+                write_result,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the batches in a pipelined way: the wal write of the batch N+1 is
+    /// issued while the batch N is being inserted into the memtable.
+    ///
+    /// The wal write of the batch N+1 is issued only after the one of the batch
+    /// N is done, and the batch N is inserted into the memtable before the
+    /// batch N+1, so the sequences are still monotonic.
+    ///
+    /// If the memtable write of the batch N fails, the batch N+1 may be in the
+    /// wal already, which is reported by [WriteResult::batches_in_wal].
+    async fn write_batches_pipelined(
+        &mut self,
+        table_data: &TableDataRef,
+        write_ctx: &WriteContext,
+        encoded_batches: Vec<Vec<ByteVec>>,
+        row_group_batches: Vec<RowGroupSlicer<'_>>,
+        index_in_writer: IndexInWriterSchema,
+        write_result: &mut WriteResult,
+    ) -> Result<()> {
+        let instance = self.instance.clone();
+        let mut batches = encoded_batches.into_iter().zip(row_group_batches);
+        let (encoded_rows, row_group) = match batches.next() {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        // Nothing can be overlapped with the wal write of the first batch.
+        let mut pending_bytes = encoded_size(&encoded_rows);
+        let mut pending_sequence =
+            write_to_wal(&instance, table_data, write_ctx, encoded_rows).await?;
+        write_result.batches_in_wal += 1;
+        let mut pending_row_group = row_group;
+        for (encoded_rows, row_group) in batches {
+            let encoded_bytes = encoded_size(&encoded_rows);
+            let wal_write = write_to_wal(&instance, table_data, write_ctx, encoded_rows);
+            let memtable_write = async {
+                self.write_to_memtable(
+                    table_data,
+                    pending_sequence,
+                    &pending_row_group,
+                    pending_bytes,
+                    index_in_writer.clone(),
+                    write_result,
+                )
             };
+            // The wal write is polled first so its io can be in flight while the memtable
+            // write is running.
+            let (wal_res, memtable_res) = futures::join!(wal_write, memtable_write);
+            // Unlike the sequential way, the next batch may be written to the wal even if
+            // the memtable write fails, so it must be counted before the failure is
+            // returned.
+            if wal_res.is_ok() {
+                write_result.batches_in_wal += 1;
+            }
+            let stats = memtable_res?;
+            pending_sequence = wal_res?;
+            pending_row_group = row_group;
+            pending_bytes = encoded_bytes;
+            self.maybe_flush_after_write(table_data, &stats).await?;
         }
 
-        let splitter = WriteRowGroupSplitter::new(self.instance.max_bytes_per_write_batch.unwrap());
-        splitter.split(encoded_rows, row_group)
+        let stats = self.write_to_memtable(
+            table_data,
+            pending_sequence,
+            &pending_row_group,
+            pending_bytes,
+            index_in_writer,
+            write_result,
+        )?;
+        self.maybe_flush_after_write(table_data, &stats).await
     }
 
     async fn write_table_row_group(
         &mut self,
         table_data: &TableDataRef,
+        write_ctx: &WriteContext,
         row_group: RowGroupSlicer<'_>,
         index_in_writer: IndexInWriterSchema,
         encoded_rows: Vec<ByteVec>,
+        write_result: &mut WriteResult,
     ) -> Result<()> {
-        let sequence = self.write_to_wal(encoded_rows).await?;
-        let memtable_writer = MemTableWriter::new(table_data.clone(), self.serial_exec);
+        let encoded_bytes = encoded_size(&encoded_rows);
+        let sequence = write_to_wal(&self.instance, table_data, write_ctx, encoded_rows).await?;
+        write_result.batches_in_wal += 1;
 
-        memtable_writer
-            .write(sequence, &row_group, index_in_writer)
-            .map_err(|e| {
-                error!(
-                    "Failed to write to memtable, table:{}, table_id:{}, err:{}",
-                    table_data.name, table_data.id, e
-                );
-                e
-            })?;
+        let stats = self.write_to_memtable(
+            table_data,
+            sequence,
+            &row_group,
+            encoded_bytes,
+            index_in_writer,
+            write_result,
+        )?;
+        self.maybe_flush_after_write(table_data, &stats).await
+    }
+
+    /// Schedule a background flush if the memtables are found full during the
+    /// insertion.
+    ///
+    /// The `table_data` is the one this writer writes to, so the flush is
+    /// scheduled with the held `serial_exec` without acquiring any lock, and
+    /// it returns immediately if a flush of the table is running.
+    async fn maybe_flush_after_write(
+        &mut self,
+        table_data: &TableDataRef,
+        stats: &WriteStats,
+    ) -> Result<()> {
+        if !stats.should_flush {
+            return Ok(());
+        }
+
+        debug!(
+            "Memtables are full after write, try to flush table:{}, table_id:{}",
+            table_data.name, table_data.id
+        );
+        self.handle_memtable_flush(table_data).await
+    }
+
+    fn write_to_memtable(
+        &mut self,
+        table_data: &TableDataRef,
+        sequence: SequenceNumber,
+        row_group: &RowGroupSlicer<'_>,
+        encoded_bytes: usize,
+        index_in_writer: IndexInWriterSchema,
+        write_result: &mut WriteResult,
+    ) -> Result<WriteStats> {
+        // The row index in the key sequence restarts from zero in every batch, so the
+        // rows of different batches would collide if the batches shared a sequence.
+        ensure!(
+            write_result.batches_committed == 0 || sequence > write_result.sequence,
+            NonIncreasingSequence {
+                table: &table_data.name,
+                table_id: table_data.id,
+                prev_sequence: write_result.sequence,
+                sequence,
+            }
+        );
+
+        let mut memtable_writer = MemTableWriter::new(table_data.clone(), self.serial_exec)
+            .presort_rows(self.instance.enable_presort_write_rows)
+            .buffer_pool_size(self.instance.write_buffer_pool_size);
+
+        let stats = write_memtable_with_retry(table_data, || {
+            memtable_writer.write(sequence, row_group, index_in_writer.clone())
+        })?;
 
         // Failure of writing memtable may cause inconsecutive sequence.
         if table_data.last_sequence() + 1 != sequence {
@@ -453,35 +1498,111 @@ impl<'a> Writer<'a> {
         );
 
         table_data.set_last_sequence(sequence);
+        write_result.sequence = sequence;
+        write_result.rows_written += row_group.num_rows();
+        write_result.bytes_written += encoded_bytes;
+        write_result.batches_committed += 1;
+        write_result.rows_skipped_expired += stats.skipped_expired;
+
+        // Too many expired rows may be caused by the backfill of the historical data.
+        if stats.skipped_expired * 2 > row_group.num_rows() {
+            warn!(
+                "Most rows are skipped for being expired, table:{}, table_id:{}, rows:{}, expired_rows:{}",
+                table_data.name,
+                table_data.id,
+                row_group.num_rows(),
+                stats.skipped_expired
+            );
+        }
 
         // Collect metrics.
         table_data
             .metrics
-            .on_write_request_done(row_group.num_rows());
+            .on_write_request_done(row_group.num_rows(), stats.skipped_expired);
 
-        Ok(())
+        Ok(stats)
+    }
+
+    /// Validate the write request without writing the wal and memtable, and
+    /// no flush will be triggered.
+    ///
+    /// Returns the index mapping of the table schema in the writer schema.
+    pub(crate) fn validate_write(&self, request: &WriteRequest) -> Result<IndexInWriterSchema> {
+        validate_write_request(&self.table_data, request)
     }
 
     /// Return Ok if the request is valid, this is done before entering the
     /// write thread.
+    ///
+    /// The write is rejected if the table still needs recovery, otherwise the
+    /// rows would be written to the wal while the table can't be read or
+    /// flushed.
     fn validate_before_write(&self, request: &WriteRequest) -> Result<()> {
         ensure!(
-            request.row_group.num_rows() < MAX_ROWS_TO_WRITE,
-            TooManyRows {
+            !self.table_data.needs_recovery(),
+            TableNeedsRecovery {
                 table: &self.table_data.name,
-                rows: request.row_group.num_rows(),
             }
         );
 
-        Ok(())
+        validate_rows_num(&self.table_data, request)
+    }
+
+    /// Replay the wal into the memtables if the table needs recovery, and the
+    /// flag is cleared once the replay succeeds.
+    ///
+    /// The failure of the replay is only logged, and the write will be
+    /// rejected by [Writer::validate_before_write].
+    async fn maybe_recover_from_wal(&mut self) {
+        if !self.table_data.needs_recovery() {
+            return;
+        }
+
+        let table_data = self.table_data.clone();
+        let context = ReplayContext {
+            shard_id: table_data.shard_info.shard_id,
+            wal_manager: self.instance.space_store.wal_manager.clone(),
+            wal_replay_batch_size: self.instance.replay_batch_size,
+            flusher: self.instance.make_flusher(),
+            max_retry_flush_limit: self.instance.max_retry_flush_limit(),
+        };
+        match wal_replayer::replay_table_logs_after_last_sequence(
+            &context,
+            self.serial_exec,
+            &table_data,
+        )
+        .await
+        {
+            Ok(()) => {
+                info!(
+                    "Table is recovered from the wal, table:{}, table_id:{}, last_sequence:{}",
+                    table_data.name,
+                    table_data.id,
+                    table_data.last_sequence()
+                );
+                table_data.clear_needs_recovery();
+            }
+            Err(e) => {
+                error!(
+                    "Failed to recover table from the wal, table:{}, table_id:{}, err:{}",
+                    table_data.name, table_data.id, e
+                );
+            }
+        }
     }
 
     /// Preprocess before write, check:
     ///  - whether table is dropped
     ///  - memtable capacity and maybe trigger flush
     ///
-    /// Fills [common_types::schema::IndexInWriterSchema] in [EncodeContext]
-    async fn preprocess_write(&mut self, encode_ctx: &mut EncodeContext) -> Result<()> {
+    /// Fills [common_types::schema::IndexInWriterSchema] in [EncodeContext],
+    /// which is taken from the `cached_index` if the schemas are unchanged and
+    /// cached otherwise.
+    async fn preprocess_write(
+        &mut self,
+        encode_ctx: &mut EncodeContext,
+        cached_index: &mut Option<CachedIndexInWriter>,
+    ) -> Result<()> {
         let _total_timer = self.table_data.metrics.start_table_write_preprocess_timer();
         ensure!(
             !self.table_data.is_dropped(),
@@ -489,15 +1610,32 @@ impl<'a> Writer<'a> {
                 table: &self.table_data.name,
             }
         );
+        check_background_flush(&self.table_data, self.instance.max_retry_flush_limit())?;
 
         // Checks schema compatibility.
-        self.table_data
-            .schema()
-            .compatible_for_write(
-                encode_ctx.row_group.schema(),
-                &mut encode_ctx.index_in_writer,
-            )
-            .context(IncompatSchema)?;
+        let table_schema = self.table_data.schema();
+        let writer_schema = encode_ctx.row_group.schema();
+        match cached_index {
+            Some(cached)
+                if cached.table_schema_version == table_schema.version()
+                    && &cached.writer_schema == writer_schema =>
+            {
+                encode_ctx.index_in_writer = cached.index_in_writer.clone();
+            }
+            _ => {
+                check_schema_compatible(
+                    &self.table_data.name,
+                    &table_schema,
+                    writer_schema,
+                    &mut encode_ctx.index_in_writer,
+                )?;
+                *cached_index = Some(CachedIndexInWriter {
+                    writer_schema: writer_schema.clone(),
+                    table_schema_version: table_schema.version(),
+                    index_in_writer: encode_ctx.index_in_writer.clone(),
+                });
+            }
+        }
 
         if self.instance.should_flush_instance() {
             if let Some(space) = self.instance.space_store.find_maximum_memory_usage_space() {
@@ -512,7 +1650,7 @@ impl<'a> Writer<'a> {
                         .table_data
                         .metrics
                         .start_table_write_instance_flush_wait_timer();
-                    self.handle_memtable_flush(&table).await?;
+                    self.wait_memtable_flush(&table).await?;
                 }
             }
         }
@@ -529,56 +1667,31 @@ impl<'a> Writer<'a> {
                     .table_data
                     .metrics
                     .start_table_write_space_flush_wait_timer();
-                self.handle_memtable_flush(&table).await?;
+                self.wait_memtable_flush(&table).await?;
             }
         }
 
         if self.table_data.should_flush_table(self.serial_exec) {
             let table_data = self.table_data.clone();
             let _timer = table_data.metrics.start_table_write_flush_wait_timer();
-            self.handle_memtable_flush(&table_data).await?;
+            self.wait_memtable_flush(&table_data).await?;
         }
 
         Ok(())
     }
 
-    /// Write log_batch into wal, return the sequence number of log_batch.
-    async fn write_to_wal(&self, encoded_rows: Vec<ByteVec>) -> Result<SequenceNumber> {
-        let _timer = self.table_data.metrics.start_table_write_wal_timer();
-        // Convert into pb
-        let write_req_pb = table_requests::WriteRequest {
-            // FIXME: Shall we avoid the magic number here?
-            version: 0,
-            // Use the table schema instead of the schema in request to avoid schema
-            // mismatch during replaying
-            schema: Some(schema_pb::TableSchema::from(&self.table_data.schema())),
-            rows: encoded_rows,
-        };
-
-        // Encode payload
-        let payload = WritePayload::Write(&write_req_pb);
-        let table_location = self.table_data.table_location();
-        let wal_location =
-            instance::create_wal_location(table_location.id, table_location.shard_info);
-        let log_batch_encoder = LogBatchEncoder::create(wal_location);
-        let log_batch = log_batch_encoder.encode(&payload).context(EncodePayloads {
-            table: &self.table_data.name,
-            wal_location,
-        })?;
-
-        // Write to wal manager
-        let write_ctx = WriteContext::default();
-        let sequence = self
-            .instance
-            .space_store
-            .wal_manager
-            .write(&write_ctx, &log_batch)
-            .await
-            .context(WriteLogBatch {
-                table: &self.table_data.name,
-            })?;
-
-        Ok(sequence)
+    /// Same as [Writer::handle_memtable_flush], but [Error::WriteStalled] is
+    /// returned if it isn't done within the `write_stall_timeout`.
+    async fn wait_memtable_flush(&mut self, table_data: &TableDataRef) -> Result<()> {
+        let table = self.table_data.name.clone();
+        let timeout = self.instance.write_stall_timeout;
+        with_write_stall_timeout(
+            timeout,
+            &table,
+            &table_data.name,
+            self.handle_memtable_flush(table_data),
+        )
+        .await
     }
 
     /// Flush memtables of table in background.
@@ -608,8 +1721,9 @@ impl<'a> Writer<'a> {
             "Try to trigger flush of other table:{} from the write procedure of table:{}",
             table_data.name, self.table_data.name
         );
-        match table_data.serial_exec.try_lock() {
-            Ok(mut serial_exec) => {
+        let wait = self.instance.other_table_flush_lock_wait;
+        match lock_table_for_flush(table_data, wait).await {
+            Some(mut serial_exec) => {
                 let flush_scheduler = serial_exec.flush_scheduler();
                 // Set `block_on_write_thread` to false and let flush do in background.
                 flusher
@@ -619,19 +1733,277 @@ impl<'a> Writer<'a> {
                         table: &table_data.name,
                     })
             }
-            Err(_) => {
+            None => {
                 warn!(
-                    "Failed to acquire write lock for flush table:{}",
-                    table_data.name,
+                    "Failed to acquire write lock for flush table:{}, wait:{:?}",
+                    table_data.name, wait,
                 );
+                table_data.metrics.on_flush_skipped();
                 Ok(())
             }
         }
     }
 }
 
+/// Acquire the lock of the table to schedule its flush from the write of
+/// another table, and `None` is returned if the lock isn't acquired within
+/// `wait`.
+///
+/// The wait must be bounded because the writer already holds the lock of its
+/// own table, and the writers of two tables may try to flush each other.
+async fn lock_table_for_flush(
+    table_data: &TableData,
+    wait: Duration,
+) -> Option<tokio::sync::MutexGuard<'_, TableOpSerialExecutor>> {
+    if wait.is_zero() {
+        return table_data.serial_exec.try_lock().ok();
+    }
+
+    tokio::time::timeout(wait, table_data.serial_exec.lock())
+        .await
+        .ok()
+}
+
+fn validate_rows_num(table_data: &TableData, request: &WriteRequest) -> Result<()> {
+    ensure!(
+        request.row_group.num_rows() < MAX_ROWS_TO_WRITE,
+        TooManyRows {
+            table: &table_data.name,
+            rows: request.row_group.num_rows(),
+        }
+    );
+
+    Ok(())
+}
+
+/// Check the rows num and the schema compatibility of the write request, and
+/// return the index mapping of the table schema in the writer schema.
+fn validate_write_request(
+    table_data: &TableData,
+    request: &WriteRequest,
+) -> Result<IndexInWriterSchema> {
+    validate_rows_num(table_data, request)?;
+
+    let mut index_in_writer = IndexInWriterSchema::default();
+    check_schema_compatible(
+        &table_data.name,
+        &table_data.schema(),
+        request.row_group.schema(),
+        &mut index_in_writer,
+    )?;
+
+    Ok(index_in_writer)
+}
+
+/// Check whether the rows with `writer_schema` can be written to the table.
+///
+/// The mismatched data type of a column is reported with the column and both
+/// data types, so the client knows which column to fix.
+fn check_schema_compatible(
+    table: &str,
+    table_schema: &Schema,
+    writer_schema: &Schema,
+    index_in_writer: &mut IndexInWriterSchema,
+) -> Result<()> {
+    table_schema
+        .compatible_for_write(writer_schema, index_in_writer)
+        .map_err(|e| match e {
+            schema::CompatError::IncompatWriteColumn {
+                source:
+                    column_schema::CompatError::IncompatDataType {
+                        name,
+                        expect,
+                        given,
+                        ..
+                    },
+            } => Error::IncompatDataType {
+                table: table.to_string(),
+                column: name,
+                expect,
+                given,
+            },
+            e => Error::IncompatSchema { source: e },
+        })
+}
+
+/// Write the memtable by `write` after the rows are written to the wal, and
+/// retry at most [MAX_MEMTABLE_WRITE_RETRIES] times on the failure that may
+/// go away, see [Error::is_retryable_memtable_write].
+///
+/// The rows already inserted by a failed attempt are put again with the same
+/// key sequences, which are ignored by the memtable and not counted in its
+/// metrics again. The table is marked as needing recovery if the write still
+/// fails, because the rows in the wal are missing from the memtables and only
+/// replaying the wal can bring them back.
+fn write_memtable_with_retry<F>(table_data: &TableData, mut write: F) -> Result<WriteStats>
+where
+    F: FnMut() -> Result<WriteStats>,
+{
+    let mut retries = 0;
+    loop {
+        match write() {
+            Ok(stats) => return Ok(stats),
+            Err(e) if e.is_retryable_memtable_write() && retries < MAX_MEMTABLE_WRITE_RETRIES => {
+                retries += 1;
+                warn!(
+                    "Failed to write to memtable and will retry, table:{}, table_id:{}, retries:{}, err:{}",
+                    table_data.name, table_data.id, retries, e
+                );
+            }
+            Err(e) => {
+                error!(
+                    "Failed to write to memtable and the table needs recovery, table:{}, table_id:{}, err:{}",
+                    table_data.name, table_data.id, e
+                );
+                table_data.set_needs_recovery();
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Reject the write if the background flush of the table keeps failing and the
+/// memtables have reached the high-water mark, the `write_buffer_size` of the
+/// table, otherwise the memory will grow unboundedly (e.g. the object store is
+/// down).
+///
+/// The background flush is considered failing once the consecutive failures
+/// reach the `max_retry_flush_limit`, after which no more flush is retried.
+fn check_background_flush(table_data: &TableData, max_retry_flush_limit: usize) -> Result<()> {
+    let flush_failure_count = table_data.flush_failure_count();
+    if flush_failure_count == 0 || flush_failure_count < max_retry_flush_limit {
+        return Ok(());
+    }
+
+    let memory_usage = table_data.memtable_memory_usage();
+    let high_water_mark = table_data.table_options().write_buffer_size as usize;
+    ensure!(
+        memory_usage < high_water_mark,
+        BackgroundFlushFailed {
+            msg: format!(
+                "table:{}, table_id:{}, flush_failure_count:{}, memory_usage:{}, high_water_mark:{}",
+                table_data.name, table_data.id, flush_failure_count, memory_usage, high_water_mark
+            ),
+        }
+    );
+
+    Ok(())
+}
+
+/// Target of the access logs of the write requests, so they can be routed to a
+/// separate file.
+pub const WRITE_ACCESS_LOG_TARGET: &str = "write_access";
+
+#[inline]
+fn encoded_size(encoded_rows: &[ByteVec]) -> usize {
+    encoded_rows.iter().map(|row| row.len()).sum()
+}
+
+/// Emit one access log for the write request with the totals of all its
+/// committed batches.
+fn log_write_access(table_data: &TableData, res: &Result<WriteResult>, cost: Duration) {
+    let nothing_written = WriteResult::default();
+    let (status, result) = match res {
+        Ok(result) => ("ok", result),
+        Err(Error::PartialWrite { result, .. }) => ("partial", result),
+        Err(_) => ("failed", &nothing_written),
+    };
+
+    info!(
+        target: WRITE_ACCESS_LOG_TARGET,
+        "table:{}, table_id:{}, status:{}, rows:{}, bytes:{}, batches:{}, sequence:{}, deduplicated:{}, cost:{:?}",
+        table_data.name,
+        table_data.id,
+        status,
+        result.rows_written,
+        result.bytes_written,
+        result.batches_committed,
+        result.sequence,
+        result.deduplicated,
+        cost
+    );
+}
+
+/// Build the [WriteContext] to write wal according to the durability of the
+/// write request and the `wal_sync` option of the table:
+/// - [Durability::Relaxed] => [WriteDurability::Relaxed], the wal is allowed to
+///   sync the logs to the disk in batch;
+/// - [Durability::Strict] => [WriteDurability::Sync], the logs are synced to
+///   the disk before the write returns;
+/// - [WriteDurability::Sync] is always used if `wal_sync` is set.
+///
+/// Only the [WriteContext::durability] is derived, which is honored by the
+/// wal on the local disk, and the [WriteContext::timeout] honored by the
+/// remote wal is kept as the default one.
+fn build_wal_write_context(durability: Durability, wal_sync: bool) -> WriteContext {
+    let durability = match durability {
+        Durability::Relaxed if wal_sync => WriteDurability::Sync,
+        Durability::Relaxed => WriteDurability::Relaxed,
+        Durability::Strict => WriteDurability::Sync,
+    };
+
+    WriteContext {
+        durability,
+        ..Default::default()
+    }
+}
+
+/// Write log_batch into wal, return the sequence number of log_batch.
+async fn write_to_wal(
+    instance: &InstanceRef,
+    table_data: &TableDataRef,
+    write_ctx: &WriteContext,
+    encoded_rows: Vec<ByteVec>,
+) -> Result<SequenceNumber> {
+    let _timer = table_data.metrics.start_table_write_wal_timer();
+    let log_batch = {
+        let _encode_timer = table_data.metrics.start_table_write_wal_encode_timer();
+        // Convert into pb
+        let write_req_pb = table_requests::WriteRequest {
+            version: WAL_WRITE_REQUEST_VERSION,
+            // Use the table schema instead of the schema in request to avoid schema
+            // mismatch during replaying
+            schema: Some(schema_pb::TableSchema::from(&table_data.schema())),
+            rows: encoded_rows,
+        };
+
+        // Encode payload
+        let payload = WritePayload::Write(&write_req_pb);
+        let table_location = table_data.table_location();
+        let wal_location =
+            instance::create_wal_location(table_location.id, table_location.shard_info);
+        let log_batch_encoder = LogBatchEncoder::create(wal_location);
+        let log_batch = log_batch_encoder.encode(&payload).context(EncodePayloads {
+            table: &table_data.name,
+            wal_location,
+        })?;
+
+        // The rows have been copied into the log batch, so their buffers can be reused.
+        table_data
+            .write_buffer_pool
+            .put_row_bufs(write_req_pb.rows, instance.write_buffer_pool_size);
+
+        log_batch
+    };
+
+    // Write to wal manager
+    let _io_timer = table_data.metrics.start_table_write_wal_io_timer();
+    let sequence = instance
+        .space_store
+        .wal_manager
+        .write(write_ctx, &log_batch)
+        .await
+        .context(WriteLogBatch {
+            table: &table_data.name,
+        })?;
+
+    Ok(sequence)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use common_types::{
         column_schema::Builder as ColumnSchemaBuilder,
         datum::{Datum, DatumKind},
@@ -639,8 +2011,22 @@ mod tests {
         schema::Builder as SchemaBuilder,
         time::Timestamp,
     };
+    use common_util::{
+        config::{ReadableDuration, ReadableSize},
+        runtime,
+    };
+    use table_engine::table::{ReadOptions, ReadOrder};
 
     use super::*;
+    use crate::{
+        setup::WalsOpener,
+        table::data::tests::TableDataMocker,
+        table_options::TableOptions,
+        tests::{
+            table,
+            util::{self, MemoryEngineBuildContext, TestContext, TestEnv},
+        },
+    };
 
     fn generate_rows_for_test(sizes: Vec<usize>) -> (Vec<ByteVec>, RowGroup) {
         let encoded_rows: Vec<_> = sizes.iter().map(|size| vec![0; *size]).collect();
@@ -665,51 +2051,126 @@ mod tests {
         (encoded_rows, row_group)
     }
 
+    #[test]
+    fn test_build_wal_write_context() {
+        let cases = [
+            (Durability::Relaxed, false, WriteDurability::Relaxed),
+            (Durability::Strict, false, WriteDurability::Sync),
+            (Durability::Relaxed, true, WriteDurability::Sync),
+            (Durability::Strict, true, WriteDurability::Sync),
+        ];
+        for (durability, wal_sync, expect) in cases {
+            let write_ctx = build_wal_write_context(durability, wal_sync);
+            assert_eq!(expect, write_ctx.durability);
+            assert_eq!(WriteContext::default().timeout, write_ctx.timeout);
+        }
+        assert_eq!(
+            WriteContext::default().durability,
+            build_wal_write_context(Durability::default(), false).durability
+        );
+    }
+
     #[test]
     fn test_write_split_compute_batches() {
         let cases = vec![
-            (2, vec![1, 2, 3, 4, 5], vec![2, 3, 4, 5]),
-            (100, vec![50, 50, 100, 10], vec![2, 3, 4]),
-            (1000, vec![50, 50, 100, 10], vec![4]),
-            (2, vec![10, 10, 0, 10], vec![1, 2, 4]),
-            (0, vec![10, 10, 0, 10], vec![1, 2, 3, 4]),
-            (0, vec![0, 0], vec![1, 2]),
-            (10, vec![], vec![]),
+            (2, None, vec![1, 2, 3, 4, 5], vec![2, 3, 4, 5]),
+            (100, None, vec![50, 50, 100, 10], vec![2, 3, 4]),
+            (1000, None, vec![50, 50, 100, 10], vec![4]),
+            (2, None, vec![10, 10, 0, 10], vec![1, 2, 4]),
+            // Zero bytes is considered as no limit.
+            (0, None, vec![10, 10, 0, 10], vec![4]),
+            (0, None, vec![0, 0], vec![2]),
+            (0, Some(2), vec![10, 10, 0, 10], vec![2, 4]),
+            (10, None, vec![], vec![]),
+            // Only the rows limit takes effect.
+            (usize::MAX, Some(2), vec![1, 2, 3, 4, 5], vec![2, 4, 5]),
+            (usize::MAX, Some(5), vec![1, 2, 3, 4, 5], vec![5]),
+            (usize::MAX, Some(10), vec![1, 2, 3, 4, 5], vec![5]),
+            (usize::MAX, Some(1), vec![0, 0, 0], vec![1, 2, 3]),
+            // The bytes limit is hit before the rows limit.
+            (3, Some(3), vec![1, 2, 3, 4, 5], vec![2, 3, 4, 5]),
+            // The rows limit is hit before the bytes limit.
+            (100, Some(2), vec![50, 10, 10, 10, 100], vec![2, 4, 5]),
+            // Both limits take effect in turn.
+            (100, Some(3), vec![90, 20, 1, 1, 1, 1], vec![2, 5, 6]),
+            (100, Some(2), vec![], vec![]),
         ];
-        for (batch_size, sizes, expected_batch_indexes) in cases {
+        for (batch_size, batch_rows, sizes, expected_batch_indexes) in cases {
             let (encoded_rows, _) = generate_rows_for_test(sizes);
-            let write_row_group_splitter = WriteRowGroupSplitter::new(batch_size);
+            let write_row_group_splitter = WriteRowGroupSplitter::new(batch_size, batch_rows);
             let batch_indexes = write_row_group_splitter.compute_batches(&encoded_rows);
             assert_eq!(batch_indexes, expected_batch_indexes);
         }
     }
 
+    #[test]
+    fn test_table_level_max_bytes_per_write_batch() {
+        let cases = vec![
+            (None, None, None),
+            (None, Some(100), Some(100)),
+            (Some(10), None, Some(10)),
+            (Some(10), Some(100), Some(10)),
+            (Some(0), Some(usize::MAX), Some(0)),
+        ];
+        for (table_level, instance_level, expected) in cases {
+            let resolved = resolve_max_bytes_per_write_batch(table_level, instance_level);
+            assert_eq!(resolved, expected);
+        }
+
+        // A table level override of 0 disables the split by the bytes even if the
+        // instance level one is small enough to split the rows.
+        let max_bytes_per_batch = resolve_max_bytes_per_write_batch(Some(0), Some(10)).unwrap();
+        let (encoded_rows, row_group) = generate_rows_for_test(vec![10, 20, 30]);
+        let write_row_group_splitter = WriteRowGroupSplitter::new(max_bytes_per_batch, None);
+        let split_res = write_row_group_splitter.split(encoded_rows, &row_group);
+        match split_res {
+            SplitResult::Integrate { row_group, .. } => assert_eq!(row_group.num_rows(), 3),
+            SplitResult::Splitted { .. } => panic!("Expect the write request not to be splitted"),
+        }
+    }
+
     #[test]
     fn test_write_split_row_group() {
         let cases = vec![
             (
                 2,
+                None,
                 vec![1, 2, 3, 4, 5],
                 vec![vec![1, 2], vec![3], vec![4], vec![5]],
             ),
             (
                 100,
+                None,
                 vec![50, 50, 100, 10],
                 vec![vec![50, 50], vec![100], vec![10]],
             ),
-            (1000, vec![50, 50, 100, 10], vec![vec![50, 50, 100, 10]]),
+            (
+                1000,
+                None,
+                vec![50, 50, 100, 10],
+                vec![vec![50, 50, 100, 10]],
+            ),
             (
                 2,
+                None,
                 vec![10, 10, 0, 10],
                 vec![vec![10], vec![10], vec![0, 10]],
             ),
+            (0, None, vec![10, 10, 0, 10], vec![vec![10, 10, 0, 10]]),
+            (0, None, vec![0, 0], vec![vec![0, 0]]),
+            (10, None, vec![], vec![]),
             (
-                0,
-                vec![10, 10, 0, 10],
-                vec![vec![10], vec![10], vec![0], vec![10]],
+                usize::MAX,
+                Some(2),
+                vec![1, 2, 3],
+                vec![vec![1, 2], vec![3]],
+            ),
+            (
+                100,
+                Some(2),
+                vec![50, 10, 10, 10, 100],
+                vec![vec![50, 10], vec![10, 10], vec![100]],
             ),
-            (0, vec![0, 0], vec![vec![0], vec![0]]),
-            (10, vec![], vec![]),
         ];
 
         let check_encoded_rows = |encoded_rows: &[ByteVec], expected_row_sizes: &[usize]| {
@@ -720,9 +2181,9 @@ mod tests {
                 assert_eq!(encoded_row.len(), *expected_row_size);
             }
         };
-        for (batch_size, sizes, expected_batches) in cases {
+        for (batch_size, batch_rows, sizes, expected_batches) in cases {
             let (encoded_rows, row_group) = generate_rows_for_test(sizes.clone());
-            let write_row_group_splitter = WriteRowGroupSplitter::new(batch_size);
+            let write_row_group_splitter = WriteRowGroupSplitter::new(batch_size, batch_rows);
             let split_res = write_row_group_splitter.split(encoded_rows, &row_group);
             if expected_batches.is_empty() {
                 assert!(matches!(split_res, SplitResult::Integrate { .. }));
@@ -760,4 +2221,1021 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_memtable_write_skip_expired_rows() {
+        let table_data = Arc::new(TableDataMocker::default().build());
+        let mut serial_exec = TableOpSerialExecutor::new(table_data.id);
+        let schema = table_data.schema();
+
+        // The ttl is enabled by default, so the rows of the timestamp 0 are expired.
+        let now = Timestamp::now().as_i64();
+        let timestamps = [now, 0, now + 1, 0, 0, now + 2];
+        let rows: Vec<_> = timestamps
+            .iter()
+            .map(|ts| {
+                Row::from_datums(vec![
+                    Datum::Timestamp(Timestamp::new(*ts)),
+                    Datum::Double(1.0),
+                ])
+            })
+            .collect();
+        let row_group = RowGroupBuilder::with_rows(schema.clone(), rows)
+            .unwrap()
+            .build();
+
+        let mut memtable_writer = MemTableWriter::new(table_data.clone(), &mut serial_exec);
+        let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        let stats = memtable_writer
+            .write(1, &RowGroupSlicer::from(&row_group), index_in_writer)
+            .unwrap();
+        assert_eq!(
+            WriteStats {
+                written: 3,
+                skipped_expired: 3,
+                should_flush: false,
+            },
+            stats
+        );
+    }
+
+    #[test]
+    fn test_memtable_write_missing_timestamp() {
+        let table_data = Arc::new(TableDataMocker::default().build());
+        let mut serial_exec = TableOpSerialExecutor::new(table_data.id);
+        let schema = table_data.schema();
+
+        // The first column of the table is the timestamp, but it is a string in the
+        // rows.
+        let row_schema = table::create_schema_builder(
+            &[("key", DatumKind::String), ("ts", DatumKind::Timestamp)],
+            &[("value", DatumKind::Double)],
+        )
+        .build()
+        .unwrap();
+        let rows = vec![Row::from_datums(vec![
+            Datum::String("key1".into()),
+            Datum::Timestamp(Timestamp::now()),
+            Datum::Double(1.0),
+        ])];
+        let row_group = RowGroupBuilder::with_rows(row_schema, rows)
+            .unwrap()
+            .build();
+
+        for presort_rows in [false, true] {
+            let mut memtable_writer = MemTableWriter::new(table_data.clone(), &mut serial_exec)
+                .presort_rows(presort_rows);
+            let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+            let res = memtable_writer.write(1, &RowGroupSlicer::from(&row_group), index_in_writer);
+            assert!(
+                matches!(res, Err(Error::MissingTimestamp { row_idx: 0, .. })),
+                "{res:?}"
+            );
+        }
+
+        // The row is rejected before it is encoded into the wal.
+        let mut encode_ctx = EncodeContext::new(row_group.clone());
+        encode_ctx.index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        let res = encode_ctx.encode_rows(&table_data.name, &schema);
+        assert!(
+            matches!(res, Err(Error::MissingTimestamp { row_idx: 0, .. })),
+            "{res:?}"
+        );
+        assert!(encode_ctx.encoded_rows.is_empty());
+
+        let mut encode_ctx = EncodeContext::new(row_group);
+        encode_ctx.index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        let failed_rows = encode_ctx.encode_rows_best_effort(&schema);
+        assert_eq!(
+            vec![0],
+            failed_rows.iter().map(|v| v.row_idx).collect::<Vec<_>>()
+        );
+        assert!(encode_ctx.row_group.is_empty());
+    }
+
+    #[test]
+    fn test_memtable_write_not_retried_on_invalid_rows() {
+        let table_data = Arc::new(TableDataMocker::default().build());
+        table_data.set_table_options(TableOptions {
+            segment_duration: Some(ReadableDuration::hours(2)),
+            ..Default::default()
+        });
+        let mut serial_exec = TableOpSerialExecutor::new(table_data.id);
+        let schema = table_data.schema();
+
+        // The time range of the memtable for the last row overflows.
+        let segment_ms = ReadableDuration::hours(2).as_millis() as i64;
+        let start_ms = Timestamp::now().as_i64() / segment_ms * segment_ms;
+        let rows: Vec<_> = [start_ms, start_ms + 1, i64::MAX]
+            .iter()
+            .map(|ts| {
+                Row::from_datums(vec![
+                    Datum::Timestamp(Timestamp::new(*ts)),
+                    Datum::Double(1.0),
+                ])
+            })
+            .collect();
+        let row_group = RowGroupBuilder::with_rows(schema.clone(), rows)
+            .unwrap()
+            .build();
+        let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+
+        // The failure won't go away, so the write isn't retried.
+        let mut memtable_writer = MemTableWriter::new(table_data.clone(), &mut serial_exec);
+        let mut attempts = 0;
+        let res = write_memtable_with_retry(&table_data, || {
+            attempts += 1;
+            memtable_writer.write(
+                1,
+                &RowGroupSlicer::from(&row_group),
+                index_in_writer.clone(),
+            )
+        });
+        assert!(
+            matches!(res, Err(Error::FindMutableMemTable { .. })),
+            "{res:?}"
+        );
+        assert!(!res.unwrap_err().is_retryable_memtable_write());
+        assert_eq!(1, attempts);
+        assert!(table_data.needs_recovery());
+
+        // The rows put by the failed write are ignored if they are put again.
+        let mutable_mem = table_data
+            .find_or_create_mutable(Timestamp::new(start_ms), &schema)
+            .unwrap();
+        assert_eq!(2, mutable_mem.as_normal().mem.metrics().row_count);
+        let stats = memtable_writer
+            .write(1, &RowGroupSlicer::new(0..2, &row_group), index_in_writer)
+            .unwrap();
+        assert_eq!(2, stats.written);
+        assert_eq!(2, mutable_mem.as_normal().mem.metrics().row_count);
+    }
+
+    #[test]
+    fn test_memtable_write_check_flush() {
+        let table_data = Arc::new(TableDataMocker::default().build());
+        let mut serial_exec = TableOpSerialExecutor::new(table_data.id);
+        let schema = table_data.schema();
+        let now = Timestamp::now().as_i64();
+        let rows: Vec<_> = (0..ROWS_PER_FLUSH_CHECK as i64 * 2)
+            .map(|i| {
+                Row::from_datums(vec![
+                    Datum::Timestamp(Timestamp::new(now + i)),
+                    Datum::Double(1.0),
+                ])
+            })
+            .collect();
+        let row_group = RowGroupBuilder::with_rows(schema.clone(), rows)
+            .unwrap()
+            .build();
+        let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+
+        let mut memtable_writer = MemTableWriter::new(table_data.clone(), &mut serial_exec);
+        let stats = memtable_writer
+            .write(
+                1,
+                &RowGroupSlicer::from(&row_group),
+                index_in_writer.clone(),
+            )
+            .unwrap();
+        assert_eq!(row_group.num_rows(), stats.written);
+        assert!(!stats.should_flush);
+
+        // The memtables are full once any row is inserted.
+        table_data.set_table_options(TableOptions {
+            write_buffer_size: 1,
+            ..Default::default()
+        });
+        let mut memtable_writer = MemTableWriter::new(table_data.clone(), &mut serial_exec);
+        let stats = memtable_writer
+            .write(2, &RowGroupSlicer::from(&row_group), index_in_writer)
+            .unwrap();
+        assert_eq!(row_group.num_rows(), stats.written);
+        assert!(stats.should_flush);
+    }
+
+    #[test]
+    fn test_reject_write_on_failing_flush() {
+        let runtime = Arc::new(runtime::Builder::default().build().unwrap());
+        let table_data = Arc::new(TableDataMocker::default().build());
+        table_data.set_table_options(TableOptions {
+            write_buffer_size: 1,
+            ..Default::default()
+        });
+        let mut serial_exec = TableOpSerialExecutor::new(table_data.id);
+        let schema = table_data.schema();
+        let row = Row::from_datums(vec![Datum::Timestamp(Timestamp::now()), Datum::Double(1.0)]);
+        let row_group = RowGroupBuilder::with_rows(schema.clone(), vec![row])
+            .unwrap()
+            .build();
+        let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        MemTableWriter::new(table_data.clone(), &mut serial_exec)
+            .write(1, &RowGroupSlicer::from(&row_group), index_in_writer)
+            .unwrap();
+
+        let max_retry_flush_limit = 2;
+        let run_failed_flush = |serial_exec: &mut TableOpSerialExecutor| {
+            let opts = TableFlushOptions {
+                res_sender: None,
+                max_retry_flush_limit,
+            };
+            let flush_job = async {
+                crate::instance::flush_compaction::Other {
+                    msg: "mock flush failure",
+                }
+                .fail()
+            };
+            runtime.block_on(serial_exec.flush_scheduler().flush_sequentially(
+                flush_job,
+                true,
+                opts,
+                &runtime,
+                table_data.clone(),
+            ))
+        };
+
+        for _ in 0..max_retry_flush_limit {
+            check_background_flush(&table_data, max_retry_flush_limit).unwrap();
+            run_failed_flush(&mut serial_exec).unwrap();
+        }
+        assert_eq!(max_retry_flush_limit, table_data.flush_failure_count());
+        let res = check_background_flush(&table_data, max_retry_flush_limit);
+        assert!(matches!(res, Err(Error::BackgroundFlushFailed { .. })));
+
+        // Writes are accepted if the memtables are below the high-water mark.
+        table_data.set_table_options(TableOptions::default());
+        check_background_flush(&table_data, max_retry_flush_limit).unwrap();
+
+        // Writes are accepted once the flush succeeds.
+        table_data.set_table_options(TableOptions {
+            write_buffer_size: 1,
+            ..Default::default()
+        });
+        table_data.reset_flush_failure_count();
+        check_background_flush(&table_data, max_retry_flush_limit).unwrap();
+    }
+
+    #[test]
+    fn test_write_stalled_by_slow_flush() {
+        let runtime = Arc::new(runtime::Builder::default().build().unwrap());
+        let table_data = Arc::new(TableDataMocker::default().build());
+        let mut serial_exec = TableOpSerialExecutor::new(table_data.id);
+        let new_opts = || TableFlushOptions {
+            res_sender: None,
+            max_retry_flush_limit: 0,
+        };
+
+        runtime.block_on(async {
+            // A slow flush is running in background.
+            let slow_flush = async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(())
+            };
+            serial_exec
+                .flush_scheduler()
+                .flush_sequentially(slow_flush, false, new_opts(), &runtime, table_data.clone())
+                .await
+                .unwrap();
+
+            // The next flush has to wait for the slow one.
+            let flush = serial_exec.flush_scheduler().flush_sequentially(
+                async { Ok(()) },
+                true,
+                new_opts(),
+                &runtime,
+                table_data.clone(),
+            );
+            let res = with_write_stall_timeout(
+                Some(Duration::from_millis(50)),
+                &table_data.name,
+                &table_data.name,
+                async {
+                    flush.await.context(FlushTable {
+                        table: &table_data.name,
+                    })
+                },
+            )
+            .await;
+            assert!(matches!(res, Err(Error::WriteStalled { .. })), "{res:?}");
+
+            // The flush is waited without limit if no timeout is set.
+            let res = with_write_stall_timeout(None, &table_data.name, &table_data.name, async {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Ok(())
+            })
+            .await;
+            assert!(res.is_ok());
+        });
+    }
+
+    #[test]
+    fn test_lock_table_for_flush() {
+        let runtime = Arc::new(runtime::Builder::default().build().unwrap());
+        let table_data = Arc::new(TableDataMocker::default().build());
+
+        runtime.block_on(async {
+            assert!(lock_table_for_flush(&table_data, Duration::ZERO)
+                .await
+                .is_some());
+
+            // The lock is held by the writer of the table.
+            let serial_exec = table_data.serial_exec.lock().await;
+            assert!(lock_table_for_flush(&table_data, Duration::ZERO)
+                .await
+                .is_none());
+            assert!(lock_table_for_flush(&table_data, Duration::from_millis(50))
+                .await
+                .is_none());
+
+            // The lock is acquired once it is released within the wait.
+            let release = async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                drop(serial_exec);
+            };
+            let (_, serial_exec) = tokio::join!(
+                release,
+                lock_table_for_flush(&table_data, Duration::from_secs(10))
+            );
+            assert!(serial_exec.is_some());
+        });
+    }
+
+    #[test]
+    fn test_validate_write_request() {
+        let table_data = TableDataMocker::default().build();
+
+        // The nullable column `value` is missing in the writer schema.
+        let writer_schema = table::create_schema_builder(&[("key", DatumKind::Timestamp)], &[])
+            .build()
+            .unwrap();
+        let request = WriteRequest::new(RowGroupBuilder::new(writer_schema).build());
+        let index_in_writer = validate_write_request(&table_data, &request).unwrap();
+        assert_eq!(Some(0), index_in_writer.column_index_in_writer(0));
+        assert_eq!(None, index_in_writer.column_index_in_writer(1));
+
+        // A string is written into the double column `value`.
+        let writer_schema = table::create_schema_builder(
+            &[("key", DatumKind::Timestamp)],
+            &[("value", DatumKind::String)],
+        )
+        .build()
+        .unwrap();
+        let request = WriteRequest::new(RowGroupBuilder::new(writer_schema).build());
+        let err = validate_write_request(&table_data, &request).unwrap_err();
+        match &err {
+            Error::IncompatDataType {
+                column,
+                expect,
+                given,
+                ..
+            } => {
+                assert_eq!("value", column);
+                assert_eq!(DatumKind::Double, *expect);
+                assert_eq!(DatumKind::String, *given);
+            }
+            e => panic!("unexpected error:{e:?}"),
+        }
+        let msg = err.to_string();
+        assert!(
+            msg.contains("column:value, expect:Double, given:String"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn test_encode_rows_best_effort() {
+        let table_data = TableDataMocker::default().build();
+        let schema = table_data.schema();
+        let rows: Vec<_> = (0..3)
+            .map(|ts| {
+                Row::from_datums(vec![
+                    Datum::Timestamp(Timestamp::new(ts)),
+                    Datum::Double(1.0),
+                ])
+            })
+            .collect();
+        let row_group = RowGroupBuilder::with_rows(schema.clone(), rows)
+            .unwrap()
+            .build();
+
+        let mut encode_ctx = EncodeContext::new(row_group.clone());
+        encode_ctx.index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        let failed_rows = encode_ctx.encode_rows_best_effort(&schema);
+        assert!(failed_rows.is_empty());
+        assert_eq!(3, encode_ctx.row_group.num_rows());
+
+        // The encoded rows should be the same as the ones in the all-or-nothing mode.
+        let mut expect_ctx = EncodeContext::new(row_group);
+        expect_ctx.index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        expect_ctx.encode_rows(&table_data.name, &schema).unwrap();
+        assert_eq!(expect_ctx.encoded_rows, encode_ctx.encoded_rows);
+
+        // The second and the fourth rows fail to be encoded as their timestamps are
+        // missing.
+        let new_row_group = |timestamps: &[i64]| {
+            let rows: Vec<_> = timestamps
+                .iter()
+                .map(|ts| {
+                    Row::from_datums(vec![
+                        Datum::Timestamp(Timestamp::new(*ts)),
+                        Datum::Double(*ts as f64),
+                    ])
+                })
+                .collect();
+            RowGroupBuilder::with_rows(schema.clone(), rows)
+                .unwrap()
+                .build()
+        };
+        let mut row_group = new_row_group(&[0, 1, 2, 3, 4]);
+        row_group.get_row_mut(1).unwrap()[0] = Datum::Null;
+        row_group.get_row_mut(3).unwrap()[0] = Datum::Null;
+
+        let mut encode_ctx = EncodeContext::new(row_group);
+        encode_ctx.index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        let failed_rows = encode_ctx.encode_rows_best_effort(&schema);
+        assert_eq!(
+            vec![1, 3],
+            failed_rows.iter().map(|v| v.row_idx).collect::<Vec<_>>()
+        );
+        let timestamps: Vec<_> = encode_ctx
+            .row_group
+            .iter()
+            .map(|row| row.timestamp(&schema).unwrap())
+            .collect();
+        assert_eq!(
+            vec![Timestamp::new(0), Timestamp::new(2), Timestamp::new(4)],
+            timestamps
+        );
+
+        // Only the good rows are encoded.
+        let mut expect_ctx = EncodeContext::new(new_row_group(&[0, 2, 4]));
+        expect_ctx.index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        expect_ctx.encode_rows(&table_data.name, &schema).unwrap();
+        assert_eq!(expect_ctx.encoded_rows, encode_ctx.encoded_rows);
+    }
+
+    #[test]
+    fn test_write_best_effort_with_failed_rows() {
+        let env = TestEnv::builder().build();
+        let mut test_ctx = env.new_context(MemoryEngineBuildContext::default());
+
+        env.block_on(async {
+            test_ctx.open().await;
+
+            let test_table = "test_table";
+            let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table).await;
+            let table_data = table_data_of(&test_ctx, test_table);
+            let sequence = test_ctx.wal_sequence_num(test_table).await;
+
+            let start_ms = test_ctx.start_ms();
+            let rows = [
+                ("key1", Timestamp::new(start_ms), "tag1", 1.0, 1.0, "field1"),
+                ("key2", Timestamp::new(start_ms), "tag2", 2.0, 2.0, "field2"),
+                ("key3", Timestamp::new(start_ms), "tag3", 3.0, 3.0, "field3"),
+            ];
+            // The timestamp of the second row is missing.
+            let mut row_group = fixed_schema_table.rows_to_row_group(&rows);
+            row_group.get_row_mut(1).unwrap()[1] = Datum::Null;
+            let mut request = WriteRequest::new(row_group);
+            request.mode = WriteMode::BestEffort;
+
+            let (_, res) = test_ctx
+                .instance()
+                .write_many(vec![(table_data.id, request)])
+                .await
+                .pop()
+                .unwrap();
+            let result = res.unwrap();
+            assert_eq!(
+                vec![1],
+                result
+                    .failed_rows
+                    .iter()
+                    .map(|v| v.row_idx)
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(2, result.rows_written);
+
+            // Only the good rows are in the memtable and the wal.
+            let good_rows = [rows[0], rows[2]];
+            assert_eq!(sequence + 1, test_ctx.wal_sequence_num(test_table).await);
+            util::check_read(
+                &test_ctx,
+                &fixed_schema_table,
+                "Test read after best effort write",
+                test_table,
+                &good_rows,
+            )
+            .await;
+
+            test_ctx.reopen_with_tables(&[test_table]).await;
+
+            util::check_read(
+                &test_ctx,
+                &fixed_schema_table,
+                "Test read after best effort write and reopen",
+                test_table,
+                &good_rows,
+            )
+            .await;
+        });
+    }
+
+    #[test]
+    fn test_memtable_write_interleaved_timestamps() {
+        let table_data = Arc::new(TableDataMocker::default().build());
+        table_data.set_table_options(TableOptions {
+            segment_duration: Some(ReadableDuration::hours(2)),
+            ..Default::default()
+        });
+        let mut serial_exec = TableOpSerialExecutor::new(table_data.id);
+        let schema = table_data.schema();
+
+        // The timestamps are interleaved between two segments.
+        let segment_ms = ReadableDuration::hours(2).as_millis() as i64;
+        let start_ms = Timestamp::now().as_i64() / segment_ms * segment_ms - segment_ms;
+        let timestamps = [
+            start_ms,
+            start_ms + segment_ms,
+            start_ms + 1,
+            start_ms + segment_ms + 1,
+            start_ms + 2,
+        ];
+
+        let mut wrote_memtables = WroteMemTables::new();
+        for ts in timestamps {
+            let mutable_mem = table_data
+                .find_or_create_mutable(Timestamp::new(ts), &schema)
+                .unwrap();
+            push_wrote_memtable(&mut wrote_memtables, mutable_mem);
+        }
+        assert_eq!(2, wrote_memtables.len());
+        assert_ne!(wrote_memtables[0].id(), wrote_memtables[1].id());
+
+        let rows: Vec<_> = timestamps
+            .iter()
+            .map(|ts| {
+                Row::from_datums(vec![
+                    Datum::Timestamp(Timestamp::new(*ts)),
+                    Datum::Double(1.0),
+                ])
+            })
+            .collect();
+        let row_group = RowGroupBuilder::with_rows(schema.clone(), rows)
+            .unwrap()
+            .build();
+        let mut memtable_writer = MemTableWriter::new(table_data.clone(), &mut serial_exec);
+        let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        let stats = memtable_writer
+            .write(10, &RowGroupSlicer::from(&row_group), index_in_writer)
+            .unwrap();
+        assert_eq!(5, stats.written);
+
+        for mem in wrote_memtables {
+            assert_eq!(10, mem.as_normal().last_sequence());
+        }
+    }
+
+    #[test]
+    fn test_memtable_write_presort_rows() {
+        let table_data = Arc::new(TableDataMocker::default().build());
+        table_data.set_table_options(TableOptions {
+            segment_duration: Some(ReadableDuration::hours(2)),
+            ..Default::default()
+        });
+        let mut serial_exec = TableOpSerialExecutor::new(table_data.id);
+        let schema = table_data.schema();
+
+        let segment_ms = ReadableDuration::hours(2).as_millis() as i64;
+        let start_ms = Timestamp::now().as_i64() / segment_ms * segment_ms - segment_ms;
+        let new_row_group = |timestamps: &[i64]| {
+            let rows: Vec<_> = timestamps
+                .iter()
+                .map(|ts| {
+                    Row::from_datums(vec![
+                        Datum::Timestamp(Timestamp::new(*ts)),
+                        Datum::Double(1.0),
+                    ])
+                })
+                .collect();
+            RowGroupBuilder::with_rows(schema.clone(), rows)
+                .unwrap()
+                .build()
+        };
+
+        // Rows of one segment are inserted as they are.
+        let row_group = new_row_group(&[start_ms + 2, start_ms, start_ms + 1]);
+        let memtable_writer = MemTableWriter::new(table_data.clone(), &mut serial_exec);
+        assert!(memtable_writer
+            .presort_rows(true)
+            .rows_order(&RowGroupSlicer::from(&row_group), &schema)
+            .unwrap()
+            .is_none());
+
+        // The timestamps are interleaved between two segments.
+        let row_group = new_row_group(&[
+            start_ms + segment_ms,
+            start_ms,
+            start_ms + segment_ms + 1,
+            start_ms + 1,
+            start_ms + 2,
+        ]);
+        let memtable_writer = MemTableWriter::new(table_data.clone(), &mut serial_exec);
+        assert!(memtable_writer
+            .rows_order(&RowGroupSlicer::from(&row_group), &schema)
+            .unwrap()
+            .is_none());
+        let memtable_writer =
+            MemTableWriter::new(table_data.clone(), &mut serial_exec).presort_rows(true);
+        assert_eq!(
+            Some(vec![1, 3, 4, 0, 2]),
+            memtable_writer
+                .rows_order(&RowGroupSlicer::from(&row_group), &schema)
+                .unwrap()
+        );
+
+        // The index in the slice is kept.
+        let slicer = RowGroupSlicer::new(1..4, &row_group);
+        assert_eq!(
+            Some(vec![0, 2, 1]),
+            memtable_writer.rows_order(&slicer, &schema).unwrap()
+        );
+
+        let mut memtable_writer = memtable_writer;
+        let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        let stats = memtable_writer
+            .write(10, &RowGroupSlicer::from(&row_group), index_in_writer)
+            .unwrap();
+        assert_eq!(5, stats.written);
+        for ts in [start_ms, start_ms + segment_ms] {
+            let mutable_mem = table_data
+                .find_or_create_mutable(Timestamp::new(ts), &schema)
+                .unwrap();
+            assert_eq!(10, mutable_mem.as_normal().last_sequence());
+        }
+    }
+
+    fn wal_write_sample_count(step: &str) -> u64 {
+        prometheus::gather()
+            .iter()
+            .filter(|family| family.get_name() == "table_write_wal_duration_seconds")
+            .flat_map(|family| family.get_metric())
+            .filter(|metric| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|label| label.get_name() == "step" && label.get_value() == step)
+            })
+            .map(|metric| metric.get_histogram().get_sample_count())
+            .sum()
+    }
+
+    #[test]
+    fn test_wal_write_timers() {
+        let env = TestEnv::builder().build();
+        let mut test_ctx = env.new_context(MemoryEngineBuildContext::default());
+
+        env.block_on(async {
+            test_ctx.open().await;
+
+            let test_table = "test_table";
+            let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table).await;
+            let encode_count = wal_write_sample_count("encode");
+            let write_count = wal_write_sample_count("write");
+
+            let start_ms = test_ctx.start_ms();
+            let row_group = fixed_schema_table.rows_to_row_group(&[(
+                "key1",
+                Timestamp::new(start_ms),
+                "tag1",
+                1.0,
+                1.0,
+                "field1",
+            )]);
+            test_ctx.write_to_table(test_table, row_group).await;
+
+            assert!(wal_write_sample_count("encode") > encode_count);
+            assert!(wal_write_sample_count("write") > write_count);
+        });
+    }
+
+    fn table_data_of<T: WalsOpener>(test_ctx: &TestContext<T>, table_name: &str) -> TableDataRef {
+        test_ctx
+            .instance()
+            .space_store
+            .spaces
+            .read()
+            .unwrap()
+            .find_table_by_id(test_ctx.table(table_name).id())
+            .unwrap()
+            .table_data()
+            .clone()
+    }
+
+    /// Write the rows to the wal of the table without inserting them into the
+    /// memtables, as if the memtable write failed.
+    async fn write_to_wal_only(instance: &InstanceRef, table_data: &TableDataRef, rows: RowGroup) {
+        let schema = table_data.schema();
+        let mut encode_ctx = EncodeContext::new(rows);
+        encode_ctx.index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        encode_ctx.encode_rows(&table_data.name, &schema).unwrap();
+        write_to_wal(
+            instance,
+            table_data,
+            &WriteContext::default(),
+            encode_ctx.encoded_rows,
+        )
+        .await
+        .unwrap();
+        table_data.set_needs_recovery();
+    }
+
+    #[test]
+    fn test_recover_from_wal_before_write() {
+        let env = TestEnv::builder().build();
+        let mut test_ctx = env.new_context(MemoryEngineBuildContext::default());
+
+        env.block_on(async {
+            test_ctx.open().await;
+
+            let test_table = "test_table";
+            let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table).await;
+            let instance = test_ctx.instance().clone();
+            let table_data = table_data_of(&test_ctx, test_table);
+
+            let start_ms = test_ctx.start_ms();
+            let rows = [
+                ("key1", Timestamp::new(start_ms), "tag1", 1.0, 1.0, "field1"),
+                ("key2", Timestamp::new(start_ms), "tag2", 2.0, 2.0, "field2"),
+            ];
+            write_to_wal_only(
+                &instance,
+                &table_data,
+                fixed_schema_table.rows_to_row_group(&rows[..1]),
+            )
+            .await;
+            let read_request =
+                fixed_schema_table.new_read_all_request(ReadOptions::default(), ReadOrder::None);
+            assert!(test_ctx.table(test_table).read(read_request).await.is_err());
+
+            // The next write replays the wal and the table can be read again.
+            test_ctx
+                .write_to_table(test_table, fixed_schema_table.rows_to_row_group(&rows[1..]))
+                .await;
+            assert!(!table_data.needs_recovery());
+            util::check_read(
+                &test_ctx,
+                &fixed_schema_table,
+                "Test read after recovering from wal",
+                test_table,
+                &rows,
+            )
+            .await;
+        });
+    }
+
+    #[test]
+    fn test_reject_write_on_failed_recovery() {
+        let env = TestEnv::builder().build();
+        let mut test_ctx = env.new_context(MemoryEngineBuildContext::default());
+
+        env.block_on(async {
+            test_ctx.open().await;
+
+            let test_table = "test_table";
+            let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table).await;
+            let table_id = test_ctx.table(test_table).id();
+            let instance = test_ctx.instance().clone();
+            let table_data = table_data_of(&test_ctx, test_table);
+            table_data.set_table_options(TableOptions {
+                segment_duration: Some(ReadableDuration::hours(2)),
+                ..Default::default()
+            });
+
+            // The time range of the memtable for the row in the wal overflows, so the
+            // replay always fails.
+            let row = ("key1", Timestamp::new(i64::MAX), "tag1", 1.0, 1.0, "field1");
+            write_to_wal_only(
+                &instance,
+                &table_data,
+                fixed_schema_table.rows_to_row_group(&[row]),
+            )
+            .await;
+            let sequence = test_ctx.wal_sequence_num(test_table).await;
+
+            let start_ms = test_ctx.start_ms();
+            let row = ("key2", Timestamp::new(start_ms), "tag2", 2.0, 2.0, "field2");
+            let request = WriteRequest::new(fixed_schema_table.rows_to_row_group(&[row]));
+            let (_, res) = instance
+                .write_many(vec![(table_id, request)])
+                .await
+                .pop()
+                .unwrap();
+            assert!(
+                matches!(res, Err(Error::TableNeedsRecovery { .. })),
+                "{res:?}"
+            );
+            assert!(table_data.needs_recovery());
+            assert_eq!(sequence, test_ctx.wal_sequence_num(test_table).await);
+        });
+    }
+
+    /// Write a request splitted into four batches whose third one fails to be
+    /// written into the memtable, and returns the result of the write along
+    /// with the number of the batches in the wal.
+    fn write_failed_split_request(pipelined: bool) -> (WriteResult, SequenceNumber) {
+        let env = TestEnv::builder().build();
+        let mut test_ctx = env.new_context(MemoryEngineBuildContext::default());
+        // Every row will be splitted into a single batch.
+        test_ctx.config_mut().max_bytes_per_write_batch = Some(ReadableSize(1));
+        test_ctx.config_mut().enable_pipelined_write_batches = pipelined;
+
+        env.block_on(async {
+            test_ctx.open().await;
+
+            let test_table = "test_table";
+            let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table).await;
+            let table_data = table_data_of(&test_ctx, test_table);
+            table_data.set_table_options(TableOptions {
+                segment_duration: Some(ReadableDuration::hours(2)),
+                ..Default::default()
+            });
+            let sequence = test_ctx.wal_sequence_num(test_table).await;
+
+            // The time range of the memtable for the third row overflows.
+            let start_ms = test_ctx.start_ms();
+            let rows = [
+                ("key1", Timestamp::new(start_ms), "tag1", 1.0, 1.0, "field1"),
+                ("key2", Timestamp::new(start_ms), "tag2", 2.0, 2.0, "field2"),
+                ("key3", Timestamp::new(i64::MAX), "tag3", 3.0, 3.0, "field3"),
+                ("key4", Timestamp::new(start_ms), "tag4", 4.0, 4.0, "field4"),
+            ];
+            let request = WriteRequest::new(fixed_schema_table.rows_to_row_group(&rows));
+            let (_, res) = test_ctx
+                .instance()
+                .write_many(vec![(table_data.id, request)])
+                .await
+                .pop()
+                .unwrap();
+            let result = match res {
+                Err(Error::PartialWrite { result, .. }) => result,
+                res => panic!("unexpected result:{res:?}"),
+            };
+
+            let batches_in_wal = test_ctx.wal_sequence_num(test_table).await - sequence;
+            (result, batches_in_wal)
+        })
+    }
+
+    #[test]
+    fn test_split_write_memtable_failure_wal_contents() {
+        let (sequential, sequential_wal) = write_failed_split_request(false);
+        let (pipelined, pipelined_wal) = write_failed_split_request(true);
+
+        // The first two batches are committed in both ways.
+        assert_eq!(2, sequential.batches_committed);
+        assert_eq!(2, pipelined.batches_committed);
+
+        // The failed batch is in the wal, and so is the next one in the pipelined way
+        // as it is written to the wal along with the failed memtable write.
+        assert_eq!(3, sequential_wal);
+        assert_eq!(4, pipelined_wal);
+        assert_eq!(sequential_wal, sequential.batches_in_wal as u64);
+        assert_eq!(pipelined_wal, pipelined.batches_in_wal as u64);
+    }
+
+    #[test]
+    fn test_retry_after_partial_write() {
+        let env = TestEnv::builder().build();
+        let mut test_ctx = env.new_context(MemoryEngineBuildContext::default());
+        test_ctx.config_mut().write_dedup_capacity = 16;
+        // Every row will be splitted into a single batch.
+        test_ctx.config_mut().max_bytes_per_write_batch = Some(ReadableSize(1));
+
+        env.block_on(async {
+            test_ctx.open().await;
+
+            let test_table = "test_table";
+            let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table).await;
+            let instance = test_ctx.instance().clone();
+            let table_data = table_data_of(&test_ctx, test_table);
+            table_data.set_table_options(TableOptions {
+                segment_duration: Some(ReadableDuration::hours(2)),
+                ..Default::default()
+            });
+            let sequence = test_ctx.wal_sequence_num(test_table).await;
+
+            // The time range of the memtable for the third row overflows.
+            let start_ms = test_ctx.start_ms();
+            let rows = [
+                ("key1", Timestamp::new(start_ms), "tag1", 1.0, 1.0, "field1"),
+                ("key2", Timestamp::new(start_ms), "tag2", 2.0, 2.0, "field2"),
+                (
+                    "key3",
+                    Timestamp::new(i64::MAX - 1),
+                    "tag3",
+                    3.0,
+                    3.0,
+                    "field3",
+                ),
+                ("key4", Timestamp::new(start_ms), "tag4", 4.0, 4.0, "field4"),
+            ];
+            let write_request = || {
+                let mut request = WriteRequest::new(fixed_schema_table.rows_to_row_group(&rows));
+                request.request_id = Some("req1".to_string());
+                (table_data.id, request)
+            };
+
+            let (_, res) = instance
+                .write_many(vec![write_request()])
+                .await
+                .pop()
+                .unwrap();
+            let result = match res {
+                Err(Error::PartialWrite { result, .. }) => result,
+                res => panic!("unexpected result:{res:?}"),
+            };
+            assert_eq!(2, result.batches_committed);
+            assert_eq!(3, result.batches_in_wal);
+
+            // Without the segment duration, the failed batch can be applied by the replay
+            // of the wal before the retry.
+            table_data.set_table_options(TableOptions::default());
+
+            // The retry only writes the batch not in the wal.
+            let (_, res) = instance
+                .write_many(vec![write_request()])
+                .await
+                .pop()
+                .unwrap();
+            let result = res.unwrap();
+            assert!(!result.deduplicated);
+            assert_eq!(4, result.batches_committed);
+            assert_eq!(4, result.batches_in_wal);
+            assert_eq!(sequence + 4, test_ctx.wal_sequence_num(test_table).await);
+            util::check_read(
+                &test_ctx,
+                &fixed_schema_table,
+                "Test read after retrying partial write",
+                test_table,
+                &rows,
+            )
+            .await;
+
+            // The request is fully written now.
+            let (_, res) = instance
+                .write_many(vec![write_request()])
+                .await
+                .pop()
+                .unwrap();
+            assert!(res.unwrap().deduplicated);
+            assert_eq!(sequence + 4, test_ctx.wal_sequence_num(test_table).await);
+        });
+    }
+
+    #[test]
+    fn test_encode_rows_with_row_bufs() {
+        let table_data = TableDataMocker::default().build();
+        let schema = table_data.schema();
+        let rows: Vec<_> = (0..3)
+            .map(|ts| {
+                Row::from_datums(vec![
+                    Datum::Timestamp(Timestamp::new(ts)),
+                    Datum::Double(1.0),
+                ])
+            })
+            .collect();
+        let row_group = RowGroupBuilder::with_rows(schema.clone(), rows)
+            .unwrap()
+            .build();
+        let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+
+        let mut expect_ctx = EncodeContext::new(row_group.clone());
+        expect_ctx.index_in_writer = index_in_writer.clone();
+        expect_ctx.encode_rows(&table_data.name, &schema).unwrap();
+
+        for write_mode in [WriteMode::AllOrNothing, WriteMode::BestEffort] {
+            // Only two buffers are provided, and the last row is encoded into a new one.
+            let row_bufs: Vec<_> = (0..2).map(|_| ByteVec::with_capacity(1024)).collect();
+            // The buffers are popped from the back.
+            let buf_ptrs: Vec<_> = row_bufs.iter().rev().map(|buf| buf.as_ptr()).collect();
+            let mut encode_ctx = EncodeContext::new(row_group.clone()).with_row_bufs(row_bufs);
+            encode_ctx.index_in_writer = index_in_writer.clone();
+            match write_mode {
+                WriteMode::AllOrNothing => {
+                    encode_ctx.encode_rows(&table_data.name, &schema).unwrap()
+                }
+                WriteMode::BestEffort => {
+                    assert!(encode_ctx.encode_rows_best_effort(&schema).is_empty())
+                }
+            }
+
+            assert_eq!(expect_ctx.encoded_rows, encode_ctx.encoded_rows);
+            let encoded_ptrs: Vec<_> = encode_ctx.encoded_rows[..2]
+                .iter()
+                .map(|buf| buf.as_ptr())
+                .collect();
+            assert_eq!(buf_ptrs, encoded_ptrs);
+        }
+    }
 }
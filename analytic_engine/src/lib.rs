@@ -84,10 +84,49 @@ pub struct Config {
     pub write_sst_max_buffer_size: ReadableSize,
     /// Max retry limit After flush failed
     pub max_retry_flush_limit: usize,
-    /// Max bytes per write batch.
+    /// Max time to wait for the flush triggered by the memory pressure before
+    /// rejecting the write, no limit on the wait if not set.
+    pub write_stall_timeout: Option<ReadableDuration>,
+    /// Max time to wait for the lock of another table to flush it when the
+    /// memory usage of the space or the instance is too high, and the flush is
+    /// skipped if the lock isn't acquired in time.
+    ///
+    /// Zero means not waiting for the lock.
+    pub other_table_flush_lock_wait: ReadableDuration,
+    /// Max bytes per write batch, and zero means no limit.
     ///
     /// If this is set, the atomicity of write request will be broken.
     pub max_bytes_per_write_batch: Option<ReadableSize>,
+    /// Max rows per write batch.
+    ///
+    /// If this is set, the atomicity of write request will be broken.
+    pub max_rows_per_write_batch: Option<usize>,
+    /// Whether to pipeline the writes of the splitted batches, that is to say,
+    /// the wal write of the next batch is issued while the current batch is
+    /// being inserted into the memtable.
+    ///
+    /// It only takes effect when the write request is splitted into multiple
+    /// batches according to `max_bytes_per_write_batch`.
+    pub enable_pipelined_write_batches: bool,
+    /// Max number of the write request ids remembered by a table to
+    /// deduplicate the retried write requests.
+    ///
+    /// Zero means disabling the deduplication.
+    pub write_dedup_capacity: usize,
+    /// How long a write request id is remembered by a table.
+    pub write_dedup_ttl: ReadableDuration,
+    /// Whether to group the rows of a write request by the time range of their
+    /// memtables before inserting, so every memtable is visited only once.
+    ///
+    /// It benefits the requests whose rows are interleaved across the time
+    /// ranges of multiple memtables.
+    pub enable_presort_write_rows: bool,
+    /// Max number of the buffers to encode the rows into the memtables, and the
+    /// groups of the buffers to encode the rows into the wal, pooled by a
+    /// table, which are reused by the writes to reduce the allocations.
+    ///
+    /// Zero means disabling the pool.
+    pub write_buffer_pool_size: usize,
 
     /// Wal storage config
     ///
@@ -136,7 +175,15 @@ impl Default for Config {
             scan_max_record_batches_in_flight: 1024,
             write_sst_max_buffer_size: ReadableSize::mb(10),
             max_retry_flush_limit: 0,
+            write_stall_timeout: None,
+            other_table_flush_lock_wait: ReadableDuration::millis(100),
             max_bytes_per_write_batch: None,
+            max_rows_per_write_batch: None,
+            enable_pipelined_write_batches: false,
+            write_dedup_capacity: 0,
+            write_dedup_ttl: ReadableDuration::minutes(10),
+            enable_presort_write_rows: false,
+            write_buffer_pool_size: 4,
             wal: WalStorageConfig::RocksDB(Box::default()),
             remote_engine_client: remote_engine_client::config::Config::default(),
             recover_mode: RecoverMode::TableBased,
@@ -689,6 +689,7 @@ impl MetaUpdateLogStore for WalBasedLogStore {
 
         let write_ctx = WriteContext {
             timeout: self.opts.store_timeout.0,
+            ..Default::default()
         };
 
         self.wal_manager
@@ -105,7 +105,11 @@ impl<A: Arena<Stats = BasicStats> + Clone + Sync + Send + 'static> MemTable
         let mut row_writer = ContiguousRowWriter::new(row_value, schema, &ctx.index_in_writer);
         row_writer.write_row(row).box_err().context(InvalidRow)?;
         let encoded_size = internal_key.len() + row_value.len();
-        self.skiplist.put(internal_key, row_value);
+        if !self.skiplist.put(internal_key, row_value) {
+            // The row has been put with the same key sequence, e.g. by a retried
+            // write, so it is ignored and not counted again.
+            return Ok(());
+        }
 
         // Update metrics
         self.metrics
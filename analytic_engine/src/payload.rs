@@ -13,7 +13,7 @@ use common_util::{
     define_result,
 };
 use prost::Message;
-use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
 use wal::log_batch::{Payload, PayloadDecoder};
 
 use crate::{table_options, TableOptions};
@@ -67,10 +67,26 @@ pub enum Error {
 
     #[snafu(display("Invalid table options, err:{}", source))]
     InvalidTableOptions { source: table_options::Error },
+
+    #[snafu(display(
+        "Unknown version of the write request, version:{}, expect:{}.\nBacktrace:\n{}",
+        version,
+        WAL_WRITE_REQUEST_VERSION,
+        backtrace
+    ))]
+    UnknownWriteRequestVersion { version: u32, backtrace: Backtrace },
 }
 
 define_result!(Error);
 
+/// Version of the [table_requests::WriteRequest] persisted in wal.
+///
+/// Version 0: each row in `rows` is encoded by
+/// [common_util::codec::row::encode_row_group_for_wal] according to the table
+/// schema carried in `schema`, and the columns missing in the writer are
+/// encoded as null.
+pub const WAL_WRITE_REQUEST_VERSION: u32 = 0;
+
 /// Wal entry header
 #[derive(Clone, Copy)]
 enum Header {
@@ -158,6 +174,12 @@ impl ReadPayload {
     fn decode_write_from_pb(buf: &[u8]) -> Result<Self> {
         let write_req_pb: table_requests::WriteRequest =
             Message::decode(buf).context(DecodeBody)?;
+        ensure!(
+            write_req_pb.version == WAL_WRITE_REQUEST_VERSION,
+            UnknownWriteRequestVersion {
+                version: write_req_pb.version,
+            }
+        );
 
         // Consume and convert schema in pb
         let schema: Schema = write_req_pb
@@ -242,3 +264,72 @@ impl PayloadDecoder for WalDecoder {
         Ok(payload)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ceresdbproto::schema as schema_pb;
+    use common_types::{datum::DatumKind, schema::IndexInWriterSchema, time::Timestamp};
+    use common_util::codec::row;
+
+    use super::*;
+    use crate::tests::{row_util, table::FixedSchemaTable};
+
+    fn build_write_request_pb(version: u32) -> table_requests::WriteRequest {
+        let schema = FixedSchemaTable::default_schema_builder().build().unwrap();
+        let rows = row_util::new_rows_6(&[
+            ("key1", Timestamp::new(1), "tag1", 1.0, 10.0, "tag2"),
+            ("key2", Timestamp::new(2), "tag1", 2.0, 20.0, "tag2"),
+        ]);
+        let row_group = RowGroupBuilder::with_rows(schema.clone(), rows)
+            .unwrap()
+            .build();
+
+        let mut encoded_rows = Vec::new();
+        let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        row::encode_row_group_for_wal(&row_group, &schema, &index_in_writer, &mut encoded_rows)
+            .unwrap();
+
+        table_requests::WriteRequest {
+            version,
+            schema: Some(schema_pb::TableSchema::from(&schema)),
+            rows: encoded_rows,
+        }
+    }
+
+    fn encode_payload(payload: &WritePayload) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(payload.encode_size());
+        payload.encode_to(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_write_payload_round_trip() {
+        let write_req_pb = build_write_request_pb(WAL_WRITE_REQUEST_VERSION);
+        let buf = encode_payload(&WritePayload::Write(&write_req_pb));
+
+        let decoded_pb: table_requests::WriteRequest =
+            Message::decode(&buf[HEADER_SIZE..]).unwrap();
+        assert_eq!(WAL_WRITE_REQUEST_VERSION, decoded_pb.version);
+
+        let payload = WalDecoder.decode(&mut buf.as_slice()).unwrap();
+        match payload {
+            ReadPayload::Write { row_group } => {
+                assert_eq!(2, row_group.num_rows());
+                assert_eq!(DatumKind::String, row_group.schema().column(0).data_type);
+            }
+            _ => panic!("Unexpected payload:{payload:?}"),
+        }
+    }
+
+    #[test]
+    fn test_decode_unknown_write_request_version() {
+        let write_req_pb = build_write_request_pb(WAL_WRITE_REQUEST_VERSION + 1);
+        let buf = encode_payload(&WritePayload::Write(&write_req_pb));
+
+        let res = WalDecoder.decode(&mut buf.as_slice());
+        assert!(matches!(
+            res,
+            Err(Error::UnknownWriteRequestVersion { version, .. }) if version == WAL_WRITE_REQUEST_VERSION + 1
+        ));
+    }
+}
@@ -111,6 +111,12 @@ pub struct EngineBuilder<'a> {
 
 impl<'a> EngineBuilder<'a> {
     pub async fn build(self) -> Result<TableEngineRef> {
+        let instance = self.build_instance().await?;
+        Ok(Arc::new(TableEngineImpl::new(instance)))
+    }
+
+    /// Build the [Instance] of the engine.
+    pub(crate) async fn build_instance(self) -> Result<InstanceRef> {
         let opened_storages =
             open_storage(self.config.storage.clone(), self.engine_runtimes.clone()).await?;
         let manifest_storages = ManifestStorages {
@@ -118,15 +124,14 @@ impl<'a> EngineBuilder<'a> {
             oss_storage: opened_storages.default_store().clone(),
         };
 
-        let instance = open_instance(
+        open_instance(
             self.config.clone(),
             self.engine_runtimes,
             self.opened_wals.data_wal,
             manifest_storages,
             Arc::new(opened_storages),
         )
-        .await?;
-        Ok(Arc::new(TableEngineImpl::new(instance)))
+        .await
     }
 }
 
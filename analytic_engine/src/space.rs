@@ -226,6 +226,15 @@ impl Spaces {
         self.id_to_space.get(&id)
     }
 
+    /// Find the table by its id in all spaces
+    pub fn find_table_by_id(&self, table_id: TableId) -> Option<SpaceAndTable> {
+        self.id_to_space.values().find_map(|space| {
+            space
+                .find_table_by_id(table_id)
+                .map(|table_data| SpaceAndTable::new(space.clone(), table_data))
+        })
+    }
+
     /// List all tables of all spaces
     pub fn list_all_tables(&self, tables: &mut Vec<TableDataRef>) {
         let total_tables = self.id_to_space.values().map(|s| s.table_num()).sum();
@@ -8,7 +8,7 @@ use std::{
     fmt,
     fmt::Formatter,
     sync::{
-        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
         Arc, Mutex,
     },
     time::Duration,
@@ -26,7 +26,7 @@ use common_types::{
 use common_util::{
     define_result,
     error::{GenericError, GenericResult},
-    id_allocator::IdAllocator,
+    id_allocator::{self, IdAllocator},
 };
 use log::{debug, info};
 use object_store::Path;
@@ -49,6 +49,9 @@ use crate::{
         metrics::Metrics,
         sst_util,
         version::{MemTableForWrite, MemTableState, SamplingMemTable, TableVersion},
+        write_buffer_pool::WriteBufferPool,
+        write_dedup::WriteDedupCache,
+        write_rate_limit::WriteRateLimiter,
     },
     TableOptions,
 };
@@ -87,6 +90,15 @@ pub type MemTableId = u64;
 
 pub const DEFAULT_ALLOC_STEP: u64 = 100;
 
+/// Name of the metrics of the allocators of the sst file ids.
+const FILE_ID_ALLOCATOR_METRICS_NAME: &str = "sst_file";
+
+/// New an allocator of the sst file ids starting from `max_file_id`.
+pub fn new_file_id_allocator(max_file_id: FileId) -> IdAllocator {
+    IdAllocator::new(max_file_id, max_file_id, DEFAULT_ALLOC_STEP)
+        .with_metrics(id_allocator::Metrics::new(FILE_ID_ALLOCATOR_METRICS_NAME))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TableShardInfo {
     pub shard_id: ShardId,
@@ -153,6 +165,32 @@ pub struct TableData {
     /// No write/alter is allowed if the table is dropped.
     dropped: AtomicBool,
 
+    /// Number of the consecutive failures of the background flush
+    ///
+    /// Not persist, reset once a flush succeeds.
+    flush_failure_count: AtomicUsize,
+
+    /// Flag denoting whether the memtables miss some data in the wal
+    ///
+    /// Not persist, no read/flush is allowed until the table is reopened and
+    /// the wal is replayed.
+    needs_recovery: AtomicBool,
+
+    /// Recently written requests with the request id
+    ///
+    /// Not persist, used to deduplicate the retried write requests.
+    pub write_dedup: WriteDedupCache,
+
+    /// Limiter of the rows written to the table
+    ///
+    /// Not persist, only takes effect if the rate is set in the table options.
+    pub write_rate_limiter: WriteRateLimiter,
+
+    /// Buffers reused by the writes of the memtables
+    ///
+    /// Not persist, the number of pooled buffers is bounded by the config.
+    pub write_buffer_pool: WriteBufferPool,
+
     /// Metrics of this table
     pub metrics: Metrics,
 
@@ -174,6 +212,14 @@ impl fmt::Debug for TableData {
             .field("last_sequence", &self.last_sequence)
             .field("last_memtable_id", &self.last_memtable_id)
             .field("dropped", &self.dropped.load(Ordering::Relaxed))
+            .field(
+                "flush_failure_count",
+                &self.flush_failure_count.load(Ordering::Relaxed),
+            )
+            .field(
+                "needs_recovery",
+                &self.needs_recovery.load(Ordering::Relaxed),
+            )
             .field("shard_info", &self.shard_info)
             .finish()
     }
@@ -239,9 +285,14 @@ impl TableData {
             current_version,
             last_sequence: AtomicU64::new(0),
             last_memtable_id: AtomicU64::new(0),
-            allocator: IdAllocator::new(0, 0, DEFAULT_ALLOC_STEP),
+            allocator: new_file_id_allocator(0),
             last_flush_time_ms: AtomicU64::new(0),
             dropped: AtomicBool::new(false),
+            flush_failure_count: AtomicUsize::new(0),
+            needs_recovery: AtomicBool::new(false),
+            write_dedup: WriteDedupCache::default(),
+            write_rate_limiter: WriteRateLimiter::default(),
+            write_buffer_pool: WriteBufferPool::default(),
             metrics,
             shard_info: TableShardInfo::new(shard_id),
             serial_exec: tokio::sync::Mutex::new(TableOpSerialExecutor::new(table_id)),
@@ -284,6 +335,11 @@ impl TableData {
             allocator,
             last_flush_time_ms: AtomicU64::new(0),
             dropped: AtomicBool::new(false),
+            flush_failure_count: AtomicUsize::new(0),
+            needs_recovery: AtomicBool::new(false),
+            write_dedup: WriteDedupCache::default(),
+            write_rate_limiter: WriteRateLimiter::default(),
+            write_buffer_pool: WriteBufferPool::default(),
             metrics,
             shard_info: TableShardInfo::new(shard_id),
             serial_exec: tokio::sync::Mutex::new(TableOpSerialExecutor::new(add_meta.table_id)),
@@ -368,6 +424,42 @@ impl TableData {
         self.current_version.total_memory_usage()
     }
 
+    /// Returns the number of the consecutive failures of the background flush.
+    #[inline]
+    pub fn flush_failure_count(&self) -> usize {
+        self.flush_failure_count.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn inc_flush_failure_count(&self) {
+        self.flush_failure_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn reset_flush_failure_count(&self) {
+        self.flush_failure_count.store(0, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn needs_recovery(&self) -> bool {
+        self.needs_recovery.load(Ordering::SeqCst)
+    }
+
+    /// Set the memtables miss some data in the wal and forbid any
+    /// reads/writes/flushes on this table, until the next write recovers the
+    /// memtables by replaying the wal.
+    #[inline]
+    pub fn set_needs_recovery(&self) {
+        self.needs_recovery.store(true, Ordering::SeqCst);
+    }
+
+    /// Clear the flag set by [TableData::set_needs_recovery] once the
+    /// memtables are recovered from the wal.
+    #[inline]
+    pub fn clear_needs_recovery(&self) {
+        self.needs_recovery.store(false, Ordering::SeqCst);
+    }
+
     /// Returns mutable memtable memory usage in bytes.
     #[inline]
     pub fn mutable_memory_usage(&self) -> usize {
@@ -553,6 +645,29 @@ impl TableData {
         self.table_options().is_expired(timestamp)
     }
 
+    /// Returns the table level max bytes per write batch, `None` means the
+    /// instance level one should be used.
+    pub fn max_bytes_per_write_batch(&self) -> Option<usize> {
+        self.table_options()
+            .max_bytes_per_write_batch
+            .map(|v| v.as_byte() as usize)
+    }
+
+    /// Returns the max rows written to the table per second, `None` means
+    /// the writes are not limited.
+    pub fn max_write_rows_per_sec(&self) -> Option<u64> {
+        self.table_options()
+            .max_write_rows_per_sec
+            .filter(|v| *v > 0)
+    }
+
+    /// Returns true if the logs of every write to the table should be synced
+    /// to the disk before the write returns.
+    #[inline]
+    pub fn wal_sync(&self) -> bool {
+        self.table_options().wal_sync
+    }
+
     pub fn table_location(&self) -> TableLocation {
         TableLocation {
             id: self.id.as_u64(),
@@ -7,7 +7,7 @@ use std::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use lazy_static::lazy_static;
@@ -36,6 +36,24 @@ lazy_static! {
     )
     .unwrap();
 
+    static ref TABLE_WRITE_EXPIRED_ROWS_COUNTER: IntCounter = register_int_counter!(
+        "table_write_expired_rows_total",
+        "Rows skipped for being expired when writing to table"
+    )
+    .unwrap();
+
+    static ref TABLE_WRITE_RATE_LIMITED_COUNTER: IntCounter = register_int_counter!(
+        "table_write_rate_limited_total",
+        "Write requests rejected for exceeding the write rate limit of table"
+    )
+    .unwrap();
+
+    static ref TABLE_FLUSH_SKIPPED_COUNTER: IntCounter = register_int_counter!(
+        "table_flush_skipped_total",
+        "Flushes of table skipped for failing to acquire the table lock in time"
+    )
+    .unwrap();
+
     static ref TABLE_READ_REQUEST_COUNTER: IntCounter = register_int_counter!(
         "table_read_request_counter",
         "Read request counter of table"
@@ -103,6 +121,22 @@ lazy_static! {
         exponential_buckets(0.01, 2.0, 13).unwrap()
     ).unwrap();
 
+    // Buckets: 0, 0.0001, .., 0.0001 * 2^17
+    static ref TABLE_WRITE_PHASE_DURATION_HISTOGRAM: HistogramVec = register_histogram_vec!(
+        "table_write_phase_duration_seconds",
+        "Histogram for duration of each phase of the table write in seconds",
+        &["phase"],
+        exponential_buckets(0.0001, 2.0, 18).unwrap()
+    ).unwrap();
+
+    // Buckets: 0, 0.0001, .., 0.0001 * 2^17
+    static ref TABLE_WRITE_WAL_DURATION_HISTOGRAM: HistogramVec = register_histogram_vec!(
+        "table_write_wal_duration_seconds",
+        "Histogram for duration of each step of writing the wal of the table in seconds",
+        &["step"],
+        exponential_buckets(0.0001, 2.0, 18).unwrap()
+    ).unwrap();
+
     // End of histograms.
 }
 
@@ -111,6 +145,8 @@ struct AtomicTableStats {
     num_write: AtomicU64,
     num_read: AtomicU64,
     num_flush: AtomicU64,
+    num_write_waiting_flush: AtomicU64,
+    write_flush_wait_ms: AtomicU64,
 }
 
 impl From<&AtomicTableStats> for TableStats {
@@ -119,10 +155,58 @@ impl From<&AtomicTableStats> for TableStats {
             num_write: stats.num_write.load(Ordering::Relaxed),
             num_read: stats.num_read.load(Ordering::Relaxed),
             num_flush: stats.num_flush.load(Ordering::Relaxed),
+            num_write_waiting_flush: stats.num_write_waiting_flush.load(Ordering::Relaxed),
+            write_flush_wait_ms: stats.write_flush_wait_ms.load(Ordering::Relaxed),
+            memtable_memory_usage: 0,
+            last_sequence: 0,
         }
     }
 }
 
+/// Timer of the write request waiting for the flush of the table.
+///
+/// The waiting state and the elapsed time are recorded into the stats of the
+/// table besides the histogram when it is dropped.
+pub struct FlushWaitTimer {
+    _timer: HistogramTimer,
+    stats: Arc<AtomicTableStats>,
+    start: Instant,
+}
+
+impl FlushWaitTimer {
+    fn new(timer: HistogramTimer, stats: Arc<AtomicTableStats>) -> Self {
+        stats
+            .num_write_waiting_flush
+            .fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            _timer: timer,
+            stats,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for FlushWaitTimer {
+    fn drop(&mut self) {
+        self.stats
+            .write_flush_wait_ms
+            .fetch_add(self.start.elapsed().as_millis() as u64, Ordering::Relaxed);
+        self.stats
+            .num_write_waiting_flush
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Timer of a phase of the table write.
+///
+/// The duration is observed by both the `table_write_duration` and the
+/// `table_write_phase_duration_seconds` histograms when it is dropped.
+pub struct WritePhaseTimer {
+    _timer: HistogramTimer,
+    _phase_timer: HistogramTimer,
+}
+
 /// Table metrics.
 ///
 /// Now the registered labels won't remove from the metrics vec to avoid panic
@@ -146,6 +230,14 @@ pub struct Metrics {
     table_write_flush_wait_duration: Histogram,
     table_write_execute_duration: Histogram,
     table_write_total_duration: Histogram,
+
+    table_write_encode_phase_duration: Histogram,
+    table_write_wal_phase_duration: Histogram,
+    table_write_memtable_phase_duration: Histogram,
+    table_write_preprocess_phase_duration: Histogram,
+
+    table_write_wal_encode_duration: Histogram,
+    table_write_wal_io_duration: Histogram,
 }
 
 impl Default for Metrics {
@@ -180,6 +272,20 @@ impl Default for Metrics {
                 .with_label_values(&["execute"]),
             table_write_total_duration: TABLE_WRITE_DURATION_HISTOGRAM
                 .with_label_values(&["total"]),
+
+            table_write_encode_phase_duration: TABLE_WRITE_PHASE_DURATION_HISTOGRAM
+                .with_label_values(&["encode"]),
+            table_write_wal_phase_duration: TABLE_WRITE_PHASE_DURATION_HISTOGRAM
+                .with_label_values(&["wal"]),
+            table_write_memtable_phase_duration: TABLE_WRITE_PHASE_DURATION_HISTOGRAM
+                .with_label_values(&["memtable"]),
+            table_write_preprocess_phase_duration: TABLE_WRITE_PHASE_DURATION_HISTOGRAM
+                .with_label_values(&["preprocess"]),
+
+            table_write_wal_encode_duration: TABLE_WRITE_WAL_DURATION_HISTOGRAM
+                .with_label_values(&["encode"]),
+            table_write_wal_io_duration: TABLE_WRITE_WAL_DURATION_HISTOGRAM
+                .with_label_values(&["write"]),
         }
     }
 }
@@ -196,8 +302,21 @@ impl Metrics {
     }
 
     #[inline]
-    pub fn on_write_request_done(&self, num_rows: usize) {
+    pub fn on_write_request_done(&self, num_rows: usize, num_expired_rows: usize) {
         TABLE_WRITE_BATCH_HISTOGRAM.observe(num_rows as f64);
+        if num_expired_rows > 0 {
+            TABLE_WRITE_EXPIRED_ROWS_COUNTER.inc_by(num_expired_rows as u64);
+        }
+    }
+
+    #[inline]
+    pub fn on_write_rate_limited(&self) {
+        TABLE_WRITE_RATE_LIMITED_COUNTER.inc();
+    }
+
+    #[inline]
+    pub fn on_flush_skipped(&self) {
+        TABLE_FLUSH_SKIPPED_COUNTER.inc();
     }
 
     #[inline]
@@ -223,23 +342,49 @@ impl Metrics {
     }
 
     #[inline]
-    pub fn start_table_write_encode_timer(&self) -> HistogramTimer {
-        self.table_write_encode_duration.start_timer()
+    pub fn start_table_write_encode_timer(&self) -> WritePhaseTimer {
+        WritePhaseTimer {
+            _timer: self.table_write_encode_duration.start_timer(),
+            _phase_timer: self.table_write_encode_phase_duration.start_timer(),
+        }
+    }
+
+    #[inline]
+    pub fn start_table_write_memtable_timer(&self) -> WritePhaseTimer {
+        WritePhaseTimer {
+            _timer: self.table_write_memtable_duration.start_timer(),
+            _phase_timer: self.table_write_memtable_phase_duration.start_timer(),
+        }
+    }
+
+    #[inline]
+    pub fn start_table_write_wal_timer(&self) -> WritePhaseTimer {
+        WritePhaseTimer {
+            _timer: self.table_write_wal_duration.start_timer(),
+            _phase_timer: self.table_write_wal_phase_duration.start_timer(),
+        }
     }
 
+    /// Timer of encoding the payload to write into the wal, which is a part of
+    /// the wal phase.
     #[inline]
-    pub fn start_table_write_memtable_timer(&self) -> HistogramTimer {
-        self.table_write_memtable_duration.start_timer()
+    pub fn start_table_write_wal_encode_timer(&self) -> HistogramTimer {
+        self.table_write_wal_encode_duration.start_timer()
     }
 
+    /// Timer of writing the encoded payload by the wal manager, which is a
+    /// part of the wal phase.
     #[inline]
-    pub fn start_table_write_wal_timer(&self) -> HistogramTimer {
-        self.table_write_wal_duration.start_timer()
+    pub fn start_table_write_wal_io_timer(&self) -> HistogramTimer {
+        self.table_write_wal_io_duration.start_timer()
     }
 
     #[inline]
-    pub fn start_table_write_preprocess_timer(&self) -> HistogramTimer {
-        self.table_write_preprocess_duration.start_timer()
+    pub fn start_table_write_preprocess_timer(&self) -> WritePhaseTimer {
+        WritePhaseTimer {
+            _timer: self.table_write_preprocess_duration.start_timer(),
+            _phase_timer: self.table_write_preprocess_phase_duration.start_timer(),
+        }
     }
 
     #[inline]
@@ -253,8 +398,11 @@ impl Metrics {
     }
 
     #[inline]
-    pub fn start_table_write_flush_wait_timer(&self) -> HistogramTimer {
-        self.table_write_flush_wait_duration.start_timer()
+    pub fn start_table_write_flush_wait_timer(&self) -> FlushWaitTimer {
+        FlushWaitTimer::new(
+            self.table_write_flush_wait_duration.start_timer(),
+            self.stats.clone(),
+        )
     }
 
     #[inline]
@@ -332,3 +480,35 @@ impl LocalFlushMetrics {
         self.flush_sst_size_histogram.observe(sst_size as f64 / KB);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_write_phase_duration_metrics() {
+        let metrics = Metrics::default();
+        drop(metrics.start_table_write_encode_timer());
+        drop(metrics.start_table_write_wal_timer());
+        drop(metrics.start_table_write_memtable_timer());
+        drop(metrics.start_table_write_preprocess_timer());
+
+        let metric_families = prometheus::gather();
+        let metric_family = metric_families
+            .iter()
+            .find(|family| family.get_name() == "table_write_phase_duration_seconds")
+            .unwrap();
+        let phases: HashSet<_> = metric_family
+            .get_metric()
+            .iter()
+            .flat_map(|metric| metric.get_label())
+            .filter(|label| label.get_name() == "phase")
+            .map(|label| label.get_value())
+            .collect();
+        for phase in ["encode", "wal", "memtable", "preprocess"] {
+            assert!(phases.contains(phase), "phase:{phase} is missing");
+        }
+    }
+}
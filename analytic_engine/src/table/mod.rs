@@ -7,7 +7,7 @@ use std::{collections::HashMap, fmt, sync::Mutex};
 use async_trait::async_trait;
 use common_types::{
     row::{Row, RowGroupBuilder},
-    schema::Schema,
+    schema::{IndexInWriterSchema, Schema},
     time::TimeRange,
 };
 use common_util::error::BoxError;
@@ -20,10 +20,10 @@ use table_engine::{
     predicate::PredicateBuilder,
     stream::{PartitionedStreams, SendableRecordBatchStream},
     table::{
-        AlterOptions, AlterSchema, AlterSchemaRequest, Compact, Flush, FlushRequest, Get,
-        GetInvalidPrimaryKey, GetNullPrimaryKey, GetRequest, MergeWrite, ReadOptions, ReadOrder,
-        ReadRequest, Result, Scan, Table, TableId, TableStats, TooManyPendingWrites,
-        WaitForPendingWrites, Write, WriteRequest,
+        AlterOptions, AlterSchema, AlterSchemaRequest, Compact, Durability, Flush, FlushRequest,
+        Get, GetInvalidPrimaryKey, GetNullPrimaryKey, GetRequest, MergeWrite, ReadOptions,
+        ReadOrder, ReadRequest, Result, Scan, Table, TableId, TableStats, TooManyPendingWrites,
+        WaitForPendingWrites, Write, WriteMode, WriteRequest,
     },
     ANALYTIC_ENGINE_TYPE,
 };
@@ -41,6 +41,9 @@ pub mod metrics;
 pub mod sst_util;
 pub mod version;
 pub mod version_edit;
+pub mod write_buffer_pool;
+pub mod write_dedup;
+pub mod write_rate_limit;
 
 const GET_METRICS_COLLECTOR_NAME: &str = "get";
 // Additional 1/10 of the pending writes capacity is reserved for new pending
@@ -81,6 +84,21 @@ impl TableImpl {
             pending_writes,
         }
     }
+
+    /// Validate the write request without writing the wal and memtable, see
+    /// [Writer::validate_write] for details.
+    pub async fn validate_write(&self, request: &WriteRequest) -> Result<IndexInWriterSchema> {
+        let mut serial_exec = self.table_data.serial_exec.lock().await;
+        let writer = Writer::new(
+            self.instance.clone(),
+            self.space_table.clone(),
+            &mut serial_exec,
+        );
+        writer
+            .validate_write(request)
+            .box_err()
+            .context(Write { table: self.name() })
+    }
 }
 
 impl fmt::Debug for TableImpl {
@@ -230,7 +248,7 @@ fn merge_pending_write_requests(
         row_group_builder.push_checked_row(row);
     }
     let row_group = row_group_builder.build();
-    WriteRequest { row_group }
+    WriteRequest::new(row_group)
 }
 
 impl TableImpl {
@@ -300,6 +318,7 @@ impl TableImpl {
         let write_res = writer
             .write(request)
             .await
+            .map(|res| res.rows_written)
             .box_err()
             .context(Write { table: self.name() });
 
@@ -338,8 +357,15 @@ impl TableImpl {
     }
 
     #[inline]
+    /// The requests in the [WriteMode::BestEffort] mode, in dry run,
+    /// requiring [Durability::Strict] or carrying a request id are never
+    /// queued because the merged request is written in the default way.
     fn should_queue_write_request(&self, request: &WriteRequest) -> bool {
-        request.row_group.num_rows() < self.instance.max_rows_in_write_queue
+        request.mode == WriteMode::AllOrNothing
+            && !request.dry_run
+            && request.durability == Durability::Relaxed
+            && request.request_id.is_none()
+            && request.row_group.num_rows() < self.instance.max_rows_in_write_queue
     }
 }
 
@@ -370,7 +396,11 @@ impl Table for TableImpl {
     }
 
     fn stats(&self) -> TableStats {
-        self.table_data.metrics.table_stats()
+        TableStats {
+            memtable_memory_usage: self.table_data.memtable_memory_usage() as u64,
+            last_sequence: self.table_data.last_sequence(),
+            ..self.table_data.metrics.table_stats()
+        }
     }
 
     async fn write(&self, request: WriteRequest) -> Result<usize> {
@@ -393,6 +423,7 @@ impl Table for TableImpl {
         writer
             .write(request)
             .await
+            .map(|res| res.rows_written)
             .box_err()
             .context(Write { table: self.name() })
     }
@@ -582,7 +613,7 @@ mod tests {
         }
         let rows = row_util::new_rows_6(&schema_rows);
         let row_group = RowGroupBuilder::with_rows(schema, rows).unwrap().build();
-        WriteRequest { row_group }
+        WriteRequest::new(row_group)
     }
 
     #[test]
@@ -145,6 +145,14 @@ pub enum MemTableForWrite {
 }
 
 impl MemTableForWrite {
+    #[inline]
+    pub fn id(&self) -> MemTableId {
+        match self {
+            MemTableForWrite::Sampling(v) => v.id,
+            MemTableForWrite::Normal(v) => v.id,
+        }
+    }
+
     #[inline]
     pub fn set_last_sequence(&self, seq: SequenceNumber) -> memtable::Result<()> {
         self.memtable().set_last_sequence(seq)
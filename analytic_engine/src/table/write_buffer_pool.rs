@@ -0,0 +1,124 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Pool of the buffers reused by the writes of a table.
+
+use std::sync::Mutex;
+
+use common_types::{bytes::ByteVec, schema::IndexInWriterSchema};
+
+use crate::memtable::PutContext;
+
+/// Pool of the [PutContext]s and the row buffers of a table.
+///
+/// The buffers to encode the rows into the wal and the memtables are checked
+/// out at the start of a write and returned at the end, so they are reused by
+/// the following writes instead of being allocated on every write.
+#[derive(Default)]
+pub struct WriteBufferPool {
+    contexts: Mutex<Vec<PutContext>>,
+    /// Buffers of the rows encoded for the wal, grouped by the write batches
+    /// they are returned from.
+    row_bufs: Mutex<Vec<Vec<ByteVec>>>,
+}
+
+impl WriteBufferPool {
+    /// Check out a context to write the rows with `index_in_writer`, and a new
+    /// context is created if the pool is empty.
+    pub fn get(&self, index_in_writer: IndexInWriterSchema) -> PutContext {
+        let ctx = self.contexts.lock().unwrap().pop();
+        match ctx {
+            Some(mut ctx) => {
+                ctx.index_in_writer = index_in_writer;
+                ctx
+            }
+            None => PutContext::new(index_in_writer),
+        }
+    }
+
+    /// Return the context to the pool, and it is dropped if there are already
+    /// `capacity` contexts in the pool.
+    pub fn put(&self, mut ctx: PutContext, capacity: usize) {
+        let mut contexts = self.contexts.lock().unwrap();
+        if contexts.len() < capacity {
+            ctx.key_buf.clear();
+            ctx.value_buf.clear();
+            contexts.push(ctx);
+        }
+    }
+
+    /// Check out the buffers to encode the rows for the wal, and an empty
+    /// vector is returned if the pool is empty.
+    pub fn get_row_bufs(&self) -> Vec<ByteVec> {
+        self.row_bufs.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    /// Return the buffers of the encoded rows to the pool, and they are dropped
+    /// if there are already `capacity` groups of buffers in the pool.
+    pub fn put_row_bufs(&self, mut bufs: Vec<ByteVec>, capacity: usize) {
+        if bufs.is_empty() {
+            return;
+        }
+
+        let mut row_bufs = self.row_bufs.lock().unwrap();
+        if row_bufs.len() < capacity {
+            bufs.iter_mut().for_each(|buf| buf.clear());
+            row_bufs.push(bufs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_buffer_pool_capacity() {
+        let pool = WriteBufferPool::default();
+        let mut ctx = pool.get(IndexInWriterSchema::for_same_schema(2));
+        ctx.key_buf.extend_from_slice(b"key");
+        let key_buf_ptr = ctx.key_buf.as_ptr();
+        pool.put(ctx, 1);
+
+        // The buffers are cleared and reused.
+        let ctx = pool.get(IndexInWriterSchema::for_same_schema(3));
+        assert!(ctx.key_buf.is_empty());
+        assert_eq!(key_buf_ptr, ctx.key_buf.as_ptr());
+        assert_eq!(Some(2), ctx.index_in_writer.column_index_in_writer(2));
+
+        // The contexts beyond the capacity are dropped.
+        let another_ctx = pool.get(IndexInWriterSchema::default());
+        pool.put(ctx, 1);
+        pool.put(another_ctx, 1);
+        assert_eq!(1, pool.contexts.lock().unwrap().len());
+
+        // Nothing is pooled with zero capacity.
+        let pool = WriteBufferPool::default();
+        pool.put(pool.get(IndexInWriterSchema::default()), 0);
+        assert!(pool.contexts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_write_buffer_pool_row_bufs() {
+        let pool = WriteBufferPool::default();
+        assert!(pool.get_row_bufs().is_empty());
+
+        let bufs = vec![b"row0".to_vec(), b"row1".to_vec()];
+        let buf_ptrs: Vec<_> = bufs.iter().map(|buf| buf.as_ptr()).collect();
+        pool.put_row_bufs(bufs, 1);
+        // The buffers beyond the capacity are dropped.
+        pool.put_row_bufs(vec![b"row2".to_vec()], 1);
+
+        // The buffers are cleared and reused.
+        let bufs = pool.get_row_bufs();
+        assert!(bufs.iter().all(|buf| buf.is_empty()));
+        assert_eq!(
+            buf_ptrs,
+            bufs.iter().map(|buf| buf.as_ptr()).collect::<Vec<_>>()
+        );
+        assert!(pool.get_row_bufs().is_empty());
+
+        // Nothing is pooled with zero capacity.
+        pool.put_row_bufs(bufs, 0);
+        assert!(pool.get_row_bufs().is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Deduplication of the write requests by the client request id.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use wal::manager::SequenceNumber;
+
+/// Options to deduplicate the write requests.
+#[derive(Debug, Clone, Copy)]
+pub struct WriteDedupOptions {
+    /// Max number of the request ids remembered by a table, zero means
+    /// disabling the deduplication.
+    pub capacity: usize,
+    /// How long a request id is remembered since it is written.
+    pub ttl: Duration,
+}
+
+impl WriteDedupOptions {
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+}
+
+/// Outcome of a written request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrittenRequest {
+    /// Sequence of the last batch of the request.
+    pub sequence: SequenceNumber,
+    /// Number of rows written by the request.
+    pub rows_written: usize,
+    /// Number of the leading batches of the request in the wal if the request
+    /// is partially written, and the retry of the request resumes after them.
+    /// None if the whole request is written.
+    pub partial_batches: Option<usize>,
+}
+
+struct Entry {
+    written: WrittenRequest,
+    written_at: Instant,
+}
+
+/// Recently written requests of a table, which are bounded by both the number
+/// of requests and the time since they are written.
+pub struct WriteDedupCache {
+    cache: Mutex<LruCache<String, Entry>>,
+}
+
+impl Default for WriteDedupCache {
+    fn default() -> Self {
+        Self {
+            cache: Mutex::new(LruCache::new(0)),
+        }
+    }
+}
+
+impl WriteDedupCache {
+    /// Get the outcome of the request if it is written within the window.
+    pub fn get(&self, request_id: &str, opts: &WriteDedupOptions) -> Option<WrittenRequest> {
+        let mut cache = self.cache.lock().unwrap();
+        let written_at = cache.get(request_id)?.written_at;
+        if written_at.elapsed() >= opts.ttl {
+            cache.pop(request_id);
+            return None;
+        }
+
+        cache.peek(request_id).map(|entry| entry.written)
+    }
+
+    /// Remember the outcome of the written request, the least recently used
+    /// one is evicted if the cache is full.
+    pub fn put(&self, request_id: String, written: WrittenRequest, opts: &WriteDedupOptions) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.cap() != opts.capacity {
+            cache.resize(opts.capacity);
+        }
+
+        cache.put(
+            request_id,
+            Entry {
+                written,
+                written_at: Instant::now(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_written(sequence: SequenceNumber) -> WrittenRequest {
+        WrittenRequest {
+            sequence,
+            rows_written: sequence as usize * 10,
+            partial_batches: None,
+        }
+    }
+
+    #[test]
+    fn test_dedup_within_window() {
+        let opts = WriteDedupOptions {
+            capacity: 2,
+            ttl: Duration::from_secs(3600),
+        };
+        let cache = WriteDedupCache::default();
+        assert_eq!(None, cache.get("req1", &opts));
+
+        cache.put("req1".to_string(), new_written(1), &opts);
+        assert_eq!(Some(new_written(1)), cache.get("req1", &opts));
+        assert_eq!(Some(new_written(1)), cache.get("req1", &opts));
+        assert_eq!(None, cache.get("req2", &opts));
+    }
+
+    #[test]
+    fn test_dedup_after_eviction() {
+        let opts = WriteDedupOptions {
+            capacity: 2,
+            ttl: Duration::from_secs(3600),
+        };
+        let cache = WriteDedupCache::default();
+        cache.put("req1".to_string(), new_written(1), &opts);
+        cache.put("req2".to_string(), new_written(2), &opts);
+        cache.put("req3".to_string(), new_written(3), &opts);
+        assert_eq!(None, cache.get("req1", &opts));
+        assert_eq!(Some(new_written(2)), cache.get("req2", &opts));
+        assert_eq!(Some(new_written(3)), cache.get("req3", &opts));
+
+        // The request is evicted once it is out of the time window.
+        let opts = WriteDedupOptions {
+            capacity: 2,
+            ttl: Duration::ZERO,
+        };
+        assert_eq!(None, cache.get("req2", &opts));
+        assert_eq!(None, cache.get("req3", &opts));
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Rate limiting of the rows written to a table.
+
+use std::{sync::Mutex, time::Instant};
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token bucket limiting the rows written to a table per second.
+///
+/// The capacity of the bucket is the rows of one second, and the rate is
+/// provided on every acquisition so that the altered rate takes effect
+/// immediately.
+#[derive(Default)]
+pub struct WriteRateLimiter {
+    bucket: Mutex<Option<Bucket>>,
+}
+
+impl WriteRateLimiter {
+    /// Try to take `rows` tokens from the bucket refilled at `rows_per_sec`,
+    /// returns false if the tokens are exhausted.
+    ///
+    /// A request with more rows than the capacity is admitted once the bucket
+    /// is full, and the following requests are rejected until the overdrawn
+    /// tokens are refilled.
+    pub fn try_acquire(&self, rows: usize, rows_per_sec: u64) -> bool {
+        self.try_acquire_at(rows, rows_per_sec, Instant::now())
+    }
+
+    fn try_acquire_at(&self, rows: usize, rows_per_sec: u64, now: Instant) -> bool {
+        let capacity = rows_per_sec as f64;
+        let mut bucket = self.bucket.lock().unwrap();
+        let bucket = bucket.get_or_insert(Bucket {
+            tokens: capacity,
+            refilled_at: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * capacity).min(capacity);
+        bucket.refilled_at = bucket.refilled_at.max(now);
+
+        let rows = rows as f64;
+        if rows <= bucket.tokens || bucket.tokens >= capacity {
+            bucket.tokens -= rows;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_exhausted() {
+        let limiter = WriteRateLimiter::default();
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(60, 100, now));
+        assert!(limiter.try_acquire_at(40, 100, now));
+        assert!(!limiter.try_acquire_at(1, 100, now));
+
+        // The bucket is refilled over time.
+        let now = now + Duration::from_millis(500);
+        assert!(!limiter.try_acquire_at(51, 100, now));
+        assert!(limiter.try_acquire_at(50, 100, now));
+        assert!(!limiter.try_acquire_at(1, 100, now));
+
+        // The refilled tokens are capped at the capacity.
+        let now = now + Duration::from_secs(10);
+        assert!(limiter.try_acquire_at(100, 100, now));
+        assert!(!limiter.try_acquire_at(1, 100, now));
+    }
+
+    #[test]
+    fn test_rate_limit_large_request() {
+        let limiter = WriteRateLimiter::default();
+        let now = Instant::now();
+        // The request larger than the capacity is admitted with a full bucket.
+        assert!(limiter.try_acquire_at(300, 100, now));
+
+        // The overdrawn tokens must be refilled first.
+        let now = now + Duration::from_secs(2);
+        assert!(!limiter.try_acquire_at(1, 100, now));
+        let now = now + Duration::from_millis(1500);
+        assert!(limiter.try_acquire_at(50, 100, now));
+    }
+}
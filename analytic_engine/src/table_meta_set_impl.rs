@@ -4,7 +4,7 @@
 
 use std::{fmt, sync::Arc};
 
-use common_util::{error::BoxError, id_allocator::IdAllocator};
+use common_util::error::BoxError;
 use log::debug;
 use snafu::{OptionExt, ResultExt};
 use table_engine::table::TableId;
@@ -24,7 +24,7 @@ use crate::{
     space::{Space, SpaceId, SpacesRef},
     sst::file::FilePurgerRef,
     table::{
-        data::{TableData, TableShardInfo, DEFAULT_ALLOC_STEP},
+        data::{new_file_id_allocator, TableData, TableShardInfo},
         version::{TableVersionMeta, TableVersionSnapshot},
         version_edit::VersionEdit,
     },
@@ -219,11 +219,8 @@ impl TableMetaSetImpl {
 
         // Apply max file id to the allocator
         let allocator = match version_meta.clone() {
-            Some(version_meta) => {
-                let max_file_id = version_meta.max_file_id_to_add();
-                IdAllocator::new(max_file_id, max_file_id, DEFAULT_ALLOC_STEP)
-            }
-            None => IdAllocator::new(0, 0, DEFAULT_ALLOC_STEP),
+            Some(version_meta) => new_file_id_allocator(version_meta.max_file_id_to_add()),
+            None => new_file_id_allocator(0),
         };
 
         let table_name = table_meta.table_name.clone();
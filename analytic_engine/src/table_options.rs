@@ -30,6 +30,9 @@ pub const NUM_ROWS_PER_ROW_GROUP: &str = "num_rows_per_row_group";
 pub const UPDATE_MODE: &str = "update_mode";
 pub const COMPRESSION: &str = "compression";
 pub const STORAGE_FORMAT: &str = "storage_format";
+pub const MAX_BYTES_PER_WRITE_BATCH: &str = "max_bytes_per_write_batch";
+pub const MAX_WRITE_ROWS_PER_SEC: &str = "max_write_rows_per_sec";
+pub const WAL_SYNC: &str = "wal_sync";
 
 const UPDATE_MODE_OVERWRITE: &str = "OVERWRITE";
 const UPDATE_MODE_APPEND: &str = "APPEND";
@@ -388,6 +391,12 @@ pub struct TableOptions {
     pub update_mode: UpdateMode,
     /// Hint for storage format.
     pub storage_format_hint: StorageFormatHint,
+    /// Max bytes per write batch, overrides the one of the instance if set, and
+    /// zero means no limit.
+    ///
+    /// NOTE: It is not persisted in the manifest yet, so it will fall back to
+    /// the instance level one after the table is reopened.
+    pub max_bytes_per_write_batch: Option<ReadableSize>,
 
     // The following options can be altered.
     /// Enable ttl
@@ -404,6 +413,18 @@ pub struct TableOptions {
     pub num_rows_per_row_group: usize,
     /// Table Compression
     pub compression: Compression,
+    /// Max rows written to the table per second, zero or `None` means no
+    /// limit.
+    ///
+    /// NOTE: It is not persisted in the manifest yet, so the writes won't be
+    /// limited after the table is reopened.
+    pub max_write_rows_per_sec: Option<u64>,
+    /// Whether the logs of every write are synced to the disk before the write
+    /// returns, no matter what durability the write request requires.
+    ///
+    /// NOTE: It is not persisted in the manifest yet, so it will fall back to
+    /// the durability of the write requests after the table is reopened.
+    pub wal_sync: bool,
 }
 
 impl TableOptions {
@@ -453,6 +474,18 @@ impl TableOptions {
         ]
         .into_iter()
         .collect();
+        if let Some(v) = self.max_bytes_per_write_batch {
+            m.insert(
+                MAX_BYTES_PER_WRITE_BATCH.to_string(),
+                v.as_byte().to_string(),
+            );
+        }
+        if let Some(v) = self.max_write_rows_per_sec {
+            m.insert(MAX_WRITE_ROWS_PER_SEC.to_string(), v.to_string());
+        }
+        if self.wal_sync {
+            m.insert(WAL_SYNC.to_string(), self.wal_sync.to_string());
+        }
         self.compaction_strategy.fill_raw_map(&mut m);
 
         m
@@ -661,6 +694,9 @@ impl TryFrom<manifest_pb::TableOptions> for TableOptions {
             write_buffer_size: opts.write_buffer_size,
             compression: Compression::from(compression),
             storage_format_hint: StorageFormatHint::try_from(storage_format_hint)?,
+            max_bytes_per_write_batch: None,
+            max_write_rows_per_sec: None,
+            wal_sync: false,
         };
 
         Ok(table_opts)
@@ -680,6 +716,9 @@ impl Default for TableOptions {
             write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
             compression: Compression::Zstd,
             storage_format_hint: StorageFormatHint::default(),
+            max_bytes_per_write_batch: None,
+            max_write_rows_per_sec: None,
+            wal_sync: false,
         }
     }
 }
@@ -712,6 +751,9 @@ fn merge_table_options(
         if let Some(v) = options.get(UPDATE_MODE) {
             table_opts.update_mode = UpdateMode::parse_from(v)?;
         }
+        if let Some(v) = options.get(MAX_BYTES_PER_WRITE_BATCH) {
+            table_opts.max_bytes_per_write_batch = Some(parse_size(v)?);
+        }
     }
 
     if let Some(v) = options.get(TTL) {
@@ -741,6 +783,12 @@ fn merge_table_options(
     if let Some(v) = options.get(STORAGE_FORMAT) {
         table_opts.storage_format_hint = v.as_str().try_into()?;
     }
+    if let Some(v) = options.get(MAX_WRITE_ROWS_PER_SEC) {
+        table_opts.max_write_rows_per_sec = Some(v.parse().context(ParseInt)?);
+    }
+    if let Some(v) = options.get(WAL_SYNC) {
+        table_opts.wal_sync = v.parse::<bool>().context(ParseBool)?;
+    }
     Ok(table_opts)
 }
 
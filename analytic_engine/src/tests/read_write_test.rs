@@ -4,14 +4,27 @@
 
 use std::{thread, time};
 
-use common_types::time::Timestamp;
+use common_types::{
+    datum::{Datum, DatumKind},
+    row::{Row, RowGroupBuilder},
+    time::Timestamp,
+};
+use common_util::config::ReadableSize;
 use log::info;
-use table_engine::table::ReadOrder;
+use table_engine::table::{Durability, ReadOrder, WriteRequest};
+use wal::manager::WriteDurability;
 
 use crate::{
+    instance::write::EncodeContext,
     setup::WalsOpener,
     table_options,
-    tests::util::{self, memory_ctxs, rocksdb_ctxs, EngineBuildContext, TestContext, TestEnv},
+    tests::{
+        table,
+        util::{
+            self, memory_ctxs, rocksdb_ctxs, EngineBuildContext, RecordingWalEngineBuildContext,
+            TestContext, TestEnv,
+        },
+    },
 };
 
 #[test]
@@ -253,6 +266,850 @@ fn test_table_write_read<T: EngineBuildContext>(engine_context: T) {
     });
 }
 
+#[test]
+fn test_table_write_read_pipelined_batches_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        test_table_write_read_pipelined_batches(ctx);
+    }
+}
+
+#[test]
+fn test_table_write_read_pipelined_batches_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        test_table_write_read_pipelined_batches(ctx);
+    }
+}
+
+fn test_table_write_read_pipelined_batches<T: EngineBuildContext>(engine_context: T) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+    // Every row will be splitted into a single batch.
+    test_ctx.config_mut().max_bytes_per_write_batch = Some(ReadableSize(1));
+    test_ctx.config_mut().enable_pipelined_write_batches = true;
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+
+        let start_ms = test_ctx.start_ms();
+        let mut rows: Vec<_> = (0..32)
+            .map(|i| {
+                (
+                    "key1",
+                    Timestamp::new(start_ms + i),
+                    "tag1",
+                    i as f64,
+                    110.0,
+                    "tag2",
+                )
+            })
+            .collect();
+        // The last row overrides the first one, and it should win only if the sequences
+        // of the batches are monotonic.
+        let mut write_rows = rows.clone();
+        write_rows.push((
+            "key1",
+            Timestamp::new(start_ms),
+            "tag1",
+            100.0,
+            110.0,
+            "tag2",
+        ));
+        rows[0].3 = 100.0;
+
+        let row_group = fixed_schema_table.rows_to_row_group(&write_rows);
+        test_ctx.write_to_table(test_table1, row_group).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read pipelined write table",
+            test_table1,
+            &rows,
+        )
+        .await;
+
+        // Reopen db.
+        test_ctx.reopen_with_tables(&[test_table1]).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read pipelined write table after reopen",
+            test_table1,
+            &rows,
+        )
+        .await;
+    });
+}
+
+#[test]
+fn test_write_split_batches_totals_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        for pipelined in [false, true] {
+            test_write_split_batches_totals(ctx.clone(), pipelined);
+        }
+    }
+}
+
+#[test]
+fn test_write_split_batches_totals_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        for pipelined in [false, true] {
+            test_write_split_batches_totals(ctx.clone(), pipelined);
+        }
+    }
+}
+
+fn test_write_split_batches_totals<T: EngineBuildContext>(engine_context: T, pipelined: bool) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+    // Every row will be splitted into a single batch.
+    test_ctx.config_mut().max_bytes_per_write_batch = Some(ReadableSize(1));
+    test_ctx.config_mut().enable_pipelined_write_batches = pipelined;
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+
+        let start_ms = test_ctx.start_ms();
+        let rows: Vec<_> = (0..5)
+            .map(|i| {
+                (
+                    "key1",
+                    Timestamp::new(start_ms + i),
+                    "tag1",
+                    i as f64,
+                    110.0,
+                    "tag2",
+                )
+            })
+            .collect();
+        let row_group = fixed_schema_table.rows_to_row_group(&rows);
+        let mut encode_ctx = EncodeContext::new(row_group.clone());
+        encode_ctx
+            .encode_rows(test_table1, &test_ctx.table(test_table1).schema())
+            .unwrap();
+        let expect_bytes: usize = encode_ctx.encoded_rows.iter().map(|v| v.len()).sum();
+
+        let table_id = test_ctx.table(test_table1).id();
+        let mut results = test_ctx
+            .instance()
+            .write_many(vec![(table_id, WriteRequest::new(row_group))])
+            .await;
+        let (_, res) = results.pop().unwrap();
+        let res = res.unwrap();
+        assert_eq!(rows.len(), res.batches_committed);
+        assert_eq!(rows.len(), res.rows_written);
+        assert_eq!(expect_bytes, res.bytes_written);
+        assert_eq!(
+            test_ctx.table(test_table1).stats().last_sequence,
+            res.sequence
+        );
+    });
+}
+
+#[test]
+fn test_table_write_read_split_batches_same_key_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        test_table_write_read_split_batches_same_key(ctx);
+    }
+}
+
+#[test]
+fn test_table_write_read_split_batches_same_key_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        test_table_write_read_split_batches_same_key(ctx);
+    }
+}
+
+fn test_table_write_read_split_batches_same_key<T: EngineBuildContext>(engine_context: T) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+    // Every row will be splitted into a single batch, so the rows of all batches
+    // have the same row index in their key sequences.
+    test_ctx.config_mut().max_bytes_per_write_batch = Some(ReadableSize(1));
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+
+        // All the rows share the same primary key, and only the last one should be
+        // read if every batch has its own sequence.
+        let start_ms = test_ctx.start_ms();
+        let write_rows: Vec<_> = (0..16)
+            .map(|i| {
+                (
+                    "key1",
+                    Timestamp::new(start_ms),
+                    "tag1",
+                    i as f64,
+                    110.0,
+                    "tag2",
+                )
+            })
+            .collect();
+        let rows = vec![*write_rows.last().unwrap()];
+
+        let row_group = fixed_schema_table.rows_to_row_group(&write_rows);
+        test_ctx.write_to_table(test_table1, row_group).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read split write table with same key",
+            test_table1,
+            &rows,
+        )
+        .await;
+
+        // Reopen db.
+        test_ctx.reopen_with_tables(&[test_table1]).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read split write table with same key after reopen",
+            test_table1,
+            &rows,
+        )
+        .await;
+    });
+}
+
+#[test]
+fn test_table_write_dry_run_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        test_table_write_dry_run(ctx);
+    }
+}
+
+#[test]
+fn test_table_write_dry_run_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        test_table_write_dry_run(ctx);
+    }
+}
+
+fn test_table_write_dry_run<T: EngineBuildContext>(engine_context: T) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+        let table_ref = test_ctx.table(test_table1);
+
+        // The compatible request passes the validation but nothing is written.
+        let start_ms = test_ctx.start_ms();
+        let rows = [(
+            "key1",
+            Timestamp::new(start_ms),
+            "tag1-1",
+            11.0,
+            110.0,
+            "tag2-1",
+        )];
+        let mut request = WriteRequest::new(fixed_schema_table.rows_to_row_group(&rows));
+        request.dry_run = true;
+        assert_eq!(0, table_ref.write(request).await.unwrap());
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after dry run",
+            test_table1,
+            &[],
+        )
+        .await;
+
+        // The request with unknown column is incompatible.
+        let schema = table::create_schema_builder(
+            &[("key", DatumKind::String), ("ts", DatumKind::Timestamp)],
+            &[("unknown_field", DatumKind::Double)],
+        )
+        .build()
+        .unwrap();
+        let mut request = WriteRequest::new(RowGroupBuilder::new(schema).build());
+        request.dry_run = true;
+        assert!(table_ref.write(request).await.is_err());
+    });
+}
+
+#[test]
+fn test_table_write_dedup_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        test_table_write_dedup(ctx);
+    }
+}
+
+#[test]
+fn test_table_write_dedup_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        test_table_write_dedup(ctx);
+    }
+}
+
+fn test_table_write_dedup<T: EngineBuildContext>(engine_context: T) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+    test_ctx.config_mut().write_dedup_capacity = 16;
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+        let table_ref = test_ctx.table(test_table1);
+
+        let start_ms = test_ctx.start_ms();
+        let rows = [
+            (
+                "key1",
+                Timestamp::new(start_ms),
+                "tag1-1",
+                11.0,
+                110.0,
+                "tag2-1",
+            ),
+            (
+                "key2",
+                Timestamp::new(start_ms),
+                "tag1-2",
+                12.0,
+                120.0,
+                "tag2-2",
+            ),
+        ];
+        let retried_rows = [(
+            "key3",
+            Timestamp::new(start_ms),
+            "tag1-3",
+            13.0,
+            130.0,
+            "tag2-3",
+        )];
+        let new_write_request = |rows: &[table::RowTuple], request_id: &str| {
+            let mut request = WriteRequest::new(fixed_schema_table.rows_to_row_group(rows));
+            request.request_id = Some(request_id.to_string());
+            request
+        };
+
+        // The first write is applied.
+        let written = table_ref
+            .write(new_write_request(&rows, "req1"))
+            .await
+            .unwrap();
+        assert_eq!(2, written);
+
+        // The retried write returns the outcome of the first write without being
+        // applied again.
+        let written = table_ref
+            .write(new_write_request(&retried_rows, "req1"))
+            .await
+            .unwrap();
+        assert_eq!(2, written);
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after duplicate write",
+            test_table1,
+            &rows,
+        )
+        .await;
+
+        // The write with another request id is applied.
+        let written = table_ref
+            .write(new_write_request(&retried_rows, "req2"))
+            .await
+            .unwrap();
+        assert_eq!(1, written);
+
+        let all_rows: Vec<_> = rows.iter().chain(retried_rows.iter()).copied().collect();
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after new write",
+            test_table1,
+            &all_rows,
+        )
+        .await;
+    });
+}
+
+#[test]
+fn test_table_write_rate_limit_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        test_table_write_rate_limit(ctx);
+    }
+}
+
+#[test]
+fn test_table_write_rate_limit_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        test_table_write_rate_limit(ctx);
+    }
+}
+
+fn test_table_write_rate_limit<T: EngineBuildContext>(engine_context: T) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+        let opts = [(
+            table_options::MAX_WRITE_ROWS_PER_SEC.to_string(),
+            "2".to_string(),
+        )]
+        .into_iter()
+        .collect();
+        test_ctx.try_alter_options(test_table1, opts).await.unwrap();
+        let table_ref = test_ctx.table(test_table1);
+
+        let start_ms = test_ctx.start_ms();
+        let new_rows =
+            |key: &'static str| [(key, Timestamp::new(start_ms), "tag1", 11.0, 110.0, "tag2")];
+        let write = |key| {
+            let request = WriteRequest::new(fixed_schema_table.rows_to_row_group(&new_rows(key)));
+            table_ref.write(request)
+        };
+
+        // The writes beyond the rate are rejected.
+        write("key1").await.unwrap();
+        write("key2").await.unwrap();
+        let err = write("key3").await.unwrap_err();
+        assert!(err.to_string().contains("rate limited"), "{err}");
+
+        // The rate is refilled over time.
+        thread::sleep(time::Duration::from_millis(600));
+        write("key3").await.unwrap();
+    });
+}
+
+#[test]
+fn test_write_wal_context() {
+    let build_context = RecordingWalEngineBuildContext::default();
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(build_context.clone());
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+        let table_ref = test_ctx.table(test_table1);
+
+        let start_ms = test_ctx.start_ms();
+        let new_request = |durability| {
+            let rows = [(
+                "key1",
+                Timestamp::new(start_ms),
+                "tag1",
+                11.0,
+                110.0,
+                "tag2",
+            )];
+            let mut request = WriteRequest::new(fixed_schema_table.rows_to_row_group(&rows));
+            request.durability = durability;
+            request
+        };
+        let take_wal_durabilities = || {
+            build_context
+                .take_write_contexts()
+                .into_iter()
+                .map(|ctx| ctx.durability)
+                .collect::<Vec<_>>()
+        };
+
+        // The context to write wal is derived from the durability of the request.
+        take_wal_durabilities();
+        table_ref
+            .write(new_request(Durability::Relaxed))
+            .await
+            .unwrap();
+        table_ref
+            .write(new_request(Durability::Strict))
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![WriteDurability::Relaxed, WriteDurability::Sync],
+            take_wal_durabilities()
+        );
+
+        // The logs of every write are synced once the `wal_sync` is set.
+        let opts = [(table_options::WAL_SYNC.to_string(), "true".to_string())]
+            .into_iter()
+            .collect();
+        test_ctx.try_alter_options(test_table1, opts).await.unwrap();
+        table_ref
+            .write(new_request(Durability::Relaxed))
+            .await
+            .unwrap();
+        assert_eq!(vec![WriteDurability::Sync], take_wal_durabilities());
+    });
+}
+
+#[test]
+fn test_write_many_tables_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        test_write_many_tables(ctx);
+    }
+}
+
+#[test]
+fn test_write_many_tables_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        test_write_many_tables(ctx);
+    }
+}
+
+fn test_write_many_tables<T: EngineBuildContext>(engine_context: T) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_tables = ["test_table1", "test_table2", "test_table3"];
+        let mut fixed_schema_tables = Vec::with_capacity(test_tables.len());
+        for test_table in test_tables {
+            fixed_schema_tables.push(test_ctx.create_fixed_schema_table(test_table).await);
+        }
+
+        let start_ms = test_ctx.start_ms();
+        let rows = [
+            (
+                "key1",
+                Timestamp::new(start_ms),
+                "tag1-1",
+                11.0,
+                110.0,
+                "tag2-1",
+            ),
+            (
+                "key2",
+                Timestamp::new(start_ms),
+                "tag1-2",
+                12.0,
+                120.0,
+                "tag2-2",
+            ),
+        ];
+        let last_sequences: Vec<_> = test_tables
+            .iter()
+            .map(|table| test_ctx.table(table).stats().last_sequence)
+            .collect();
+
+        let requests = test_tables
+            .iter()
+            .zip(&fixed_schema_tables)
+            .map(|(table, fixed_schema_table)| {
+                let request = WriteRequest::new(fixed_schema_table.rows_to_row_group(&rows));
+                (test_ctx.table(table).id(), request)
+            })
+            .collect();
+        let results = test_ctx.instance().write_many(requests).await;
+        assert_eq!(test_tables.len(), results.len());
+        for ((table, (table_id, res)), last_sequence) in
+            test_tables.iter().zip(results).zip(last_sequences)
+        {
+            assert_eq!(test_ctx.table(table).id(), table_id);
+            let res = res.unwrap();
+            assert_eq!(rows.len(), res.rows_written);
+            assert!(res.sequence > last_sequence);
+            assert_eq!(res.sequence, test_ctx.table(table).stats().last_sequence);
+        }
+
+        for (table, fixed_schema_table) in test_tables.iter().zip(&fixed_schema_tables) {
+            util::check_read(
+                &test_ctx,
+                fixed_schema_table,
+                "Test read after write many",
+                table,
+                &rows,
+            )
+            .await;
+        }
+    });
+}
+
+#[test]
+fn test_write_stream_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        test_write_stream(ctx);
+    }
+}
+
+#[test]
+fn test_write_stream_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        test_write_stream(ctx);
+    }
+}
+
+fn test_write_stream<T: EngineBuildContext>(engine_context: T) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table = "test_table";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table).await;
+
+        let start_ms = test_ctx.start_ms();
+        let rows = [
+            (
+                "key1",
+                Timestamp::new(start_ms),
+                "tag1-1",
+                11.0,
+                110.0,
+                "tag2-1",
+            ),
+            (
+                "key2",
+                Timestamp::new(start_ms),
+                "tag1-2",
+                12.0,
+                120.0,
+                "tag2-2",
+            ),
+            (
+                "key3",
+                Timestamp::new(start_ms + 1),
+                "tag1-3",
+                13.0,
+                130.0,
+                "tag2-3",
+            ),
+        ];
+
+        // The row group with unknown column fails, and the others are still written.
+        let incompatible_schema = table::create_schema_builder(
+            &[("key", DatumKind::String), ("ts", DatumKind::Timestamp)],
+            &[("unknown_field", DatumKind::Double)],
+        )
+        .build()
+        .unwrap();
+        let incompatible_row = Row::from_datums(vec![
+            Datum::String("key4".into()),
+            Datum::Timestamp(Timestamp::new(start_ms)),
+            Datum::Double(1.0),
+        ]);
+        let incompatible_row_group =
+            RowGroupBuilder::with_rows(incompatible_schema, vec![incompatible_row])
+                .unwrap()
+                .build();
+        let row_groups = vec![
+            fixed_schema_table.rows_to_row_group(&rows[..1]),
+            fixed_schema_table.rows_to_row_group(&rows[1..2]),
+            incompatible_row_group,
+            fixed_schema_table.rows_to_row_group(&rows[2..]),
+        ];
+
+        let table_id = test_ctx.table(test_table).id();
+        let res = test_ctx
+            .instance()
+            .write_stream(
+                table_id,
+                futures::stream::iter(row_groups),
+                Durability::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), res.rows_written);
+        assert_eq!(3, res.row_groups_written);
+        assert_eq!(1, res.errors.len());
+        assert_eq!(2, res.errors[0].0);
+        assert_eq!(
+            test_ctx.table(test_table).stats().last_sequence,
+            res.sequence
+        );
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after write stream",
+            test_table,
+            &rows,
+        )
+        .await;
+    });
+}
+
+#[test]
+fn test_table_write_empty_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        test_table_write_empty(ctx);
+    }
+}
+
+#[test]
+fn test_table_write_empty_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        test_table_write_empty(ctx);
+    }
+}
+
+fn test_table_write_empty<T: EngineBuildContext>(engine_context: T) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+        let table_ref = test_ctx.table(test_table1);
+
+        let start_ms = test_ctx.start_ms();
+        let rows = [(
+            "key1",
+            Timestamp::new(start_ms),
+            "tag1-1",
+            11.0,
+            110.0,
+            "tag2-1",
+        )];
+        test_ctx
+            .write_to_table(test_table1, fixed_schema_table.rows_to_row_group(&rows))
+            .await;
+        let sequence = test_ctx.wal_sequence_num(test_table1).await;
+
+        // No log batch is written and no sequence is consumed by the empty request.
+        let request = WriteRequest::new(fixed_schema_table.rows_to_row_group(&[]));
+        assert_eq!(0, table_ref.write(request).await.unwrap());
+        assert_eq!(sequence, test_ctx.wal_sequence_num(test_table1).await);
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after writing empty request",
+            test_table1,
+            &rows,
+        )
+        .await;
+    });
+}
+
+#[test]
+fn test_table_write_missing_timestamp_rocks() {
+    let rocksdb_ctxs = rocksdb_ctxs();
+    for ctx in rocksdb_ctxs {
+        test_table_write_missing_timestamp(ctx);
+    }
+}
+
+#[test]
+fn test_table_write_missing_timestamp_mem_wal() {
+    let memory_ctxs = memory_ctxs();
+    for ctx in memory_ctxs {
+        test_table_write_missing_timestamp(ctx);
+    }
+}
+
+fn test_table_write_missing_timestamp<T: EngineBuildContext>(engine_context: T) {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context(engine_context);
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+
+        let start_ms = test_ctx.start_ms();
+        let rows = [(
+            "key1",
+            Timestamp::new(start_ms),
+            "tag1-1",
+            11.0,
+            110.0,
+            "tag2-1",
+        )];
+        test_ctx
+            .write_to_table(test_table1, fixed_schema_table.rows_to_row_group(&rows))
+            .await;
+        let sequence = test_ctx.wal_sequence_num(test_table1).await;
+
+        // The timestamp of the second row is null.
+        let mut row_group = fixed_schema_table.rows_to_row_group(&[
+            (
+                "key2",
+                Timestamp::new(start_ms),
+                "tag1-2",
+                12.0,
+                120.0,
+                "tag2-2",
+            ),
+            (
+                "key3",
+                Timestamp::new(start_ms),
+                "tag1-3",
+                13.0,
+                130.0,
+                "tag2-3",
+            ),
+        ]);
+        row_group.get_row_mut(1).unwrap()[1] = Datum::Null;
+        let request = WriteRequest::new(row_group);
+        assert!(test_ctx.table(test_table1).write(request).await.is_err());
+
+        // Nothing is written to the wal, so the table can be read and replayed.
+        assert_eq!(sequence, test_ctx.wal_sequence_num(test_table1).await);
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after writing row without timestamp",
+            test_table1,
+            &rows,
+        )
+        .await;
+
+        test_ctx.reopen_with_tables(&[test_table1]).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after writing row without timestamp and reopen",
+            test_table1,
+            &rows,
+        )
+        .await;
+    });
+}
+
 #[test]
 fn test_table_write_get_rocks() {
     let rocksdb_ctxs = rocksdb_ctxs();
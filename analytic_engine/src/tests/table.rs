@@ -290,6 +290,14 @@ impl Builder {
         self
     }
 
+    pub fn segment_duration(mut self, duration: ReadableDuration) -> Self {
+        self.create_request.options.insert(
+            table_options::SEGMENT_DURATION.to_string(),
+            duration.to_string(),
+        );
+        self
+    }
+
     pub fn build_fixed(self) -> FixedSchemaTable {
         FixedSchemaTable {
             create_request: self.create_request,
@@ -2,8 +2,13 @@
 
 //! Test utils.
 
-use std::{collections::HashMap, future::Future, sync::Arc};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
 
+use async_trait::async_trait;
 use common_types::{
     datum::Datum,
     record_batch::RecordBatch,
@@ -29,9 +34,19 @@ use table_engine::{
     },
 };
 use tempfile::TempDir;
+use wal::{
+    log_batch::LogWriteBatch,
+    manager::{
+        self, BatchLogIteratorAdapter, ReadContext, ReadRequest, RegionId, ScanContext,
+        ScanRequest, SequenceNumber, WalLocation, WalManager, WalManagerRef, WalStats,
+        WriteContext,
+    },
+};
 
 use crate::{
-    setup::{EngineBuilder, MemWalsOpener, OpenedWals, RocksDBWalsOpener, WalsOpener},
+    engine::TableEngineImpl,
+    instance::InstanceRef,
+    setup::{self, EngineBuilder, MemWalsOpener, OpenedWals, RocksDBWalsOpener, WalsOpener},
     tests::table::{self, FixedSchemaTable, RowTuple},
     Config, RecoverMode, RocksDBConfig, WalStorageConfig,
 };
@@ -110,6 +125,7 @@ pub struct TestContext<T> {
     wals_opener: T,
     runtimes: Arc<EngineRuntimes>,
     engine: Option<TableEngineRef>,
+    instance: Option<InstanceRef>,
     opened_wals: Option<OpenedWals>,
     schema_id: SchemaId,
     last_table_seq: u32,
@@ -135,7 +151,9 @@ impl<T: WalsOpener> TestContext<T> {
             opened_wals: opened_wals.clone(),
         };
         self.opened_wals = Some(opened_wals);
-        self.engine = Some(engine_builder.build().await.unwrap());
+        let instance = engine_builder.build_instance().await.unwrap();
+        self.engine = Some(Arc::new(TableEngineImpl::new(instance.clone())));
+        self.instance = Some(instance);
     }
 
     pub async fn reopen(&mut self) {
@@ -329,7 +347,7 @@ impl<T: WalsOpener> TestContext<T> {
     pub async fn write_to_table(&self, table_name: &str, row_group: RowGroup) {
         let table = self.table(table_name);
 
-        table.write(WriteRequest { row_group }).await.unwrap();
+        table.write(WriteRequest::new(row_group)).await.unwrap();
     }
 
     pub async fn read_table(
@@ -419,11 +437,29 @@ impl<T: WalsOpener> TestContext<T> {
         self.name_to_tables.get(table_name).cloned().unwrap()
     }
 
+    /// Current sequence number of the table in the data wal.
+    pub async fn wal_sequence_num(&self, table_name: &str) -> SequenceNumber {
+        let table_id = self.table(table_name).id();
+        let location = WalLocation::new(DEFAULT_SHARD_ID as u64, table_id.as_u64());
+        self.opened_wals
+            .as_ref()
+            .unwrap()
+            .data_wal
+            .sequence_num(location)
+            .await
+            .unwrap()
+    }
+
     #[inline]
     pub fn engine(&self) -> &TableEngineRef {
         self.engine.as_ref().unwrap()
     }
 
+    #[inline]
+    pub fn instance(&self) -> &InstanceRef {
+        self.instance.as_ref().unwrap()
+    }
+
     fn next_table_id(&mut self) -> TableId {
         self.last_table_seq += 1;
         table::new_table_id(2, self.last_table_seq)
@@ -469,6 +505,7 @@ impl TestEnv {
             wals_opener,
             runtimes: self.runtimes.clone(),
             engine: None,
+            instance: None,
             opened_wals: None,
             schema_id: SchemaId::from_u32(100),
             last_table_seq: 1,
@@ -697,6 +734,138 @@ impl EngineBuildContext for MemoryEngineBuildContext {
     }
 }
 
+/// Wal recording the contexts of the writes, and all the operations are
+/// delegated to the inner wal.
+#[derive(Debug)]
+struct RecordingWal {
+    inner: WalManagerRef,
+    write_contexts: Arc<Mutex<Vec<WriteContext>>>,
+}
+
+#[async_trait]
+impl WalManager for RecordingWal {
+    async fn sequence_num(&self, location: WalLocation) -> manager::Result<SequenceNumber> {
+        self.inner.sequence_num(location).await
+    }
+
+    async fn mark_delete_entries_up_to(
+        &self,
+        location: WalLocation,
+        sequence_num: SequenceNumber,
+    ) -> manager::Result<()> {
+        self.inner
+            .mark_delete_entries_up_to(location, sequence_num)
+            .await
+    }
+
+    async fn close_region(&self, region: RegionId) -> manager::Result<()> {
+        self.inner.close_region(region).await
+    }
+
+    async fn close_gracefully(&self) -> manager::Result<()> {
+        self.inner.close_gracefully().await
+    }
+
+    async fn read_batch(
+        &self,
+        ctx: &ReadContext,
+        req: &ReadRequest,
+    ) -> manager::Result<BatchLogIteratorAdapter> {
+        self.inner.read_batch(ctx, req).await
+    }
+
+    async fn write(
+        &self,
+        ctx: &WriteContext,
+        batch: &LogWriteBatch,
+    ) -> manager::Result<SequenceNumber> {
+        self.write_contexts.lock().unwrap().push(ctx.clone());
+        self.inner.write(ctx, batch).await
+    }
+
+    async fn scan(
+        &self,
+        ctx: &ScanContext,
+        req: &ScanRequest,
+    ) -> manager::Result<BatchLogIteratorAdapter> {
+        self.inner.scan(ctx, req).await
+    }
+
+    fn get_statistics(&self) -> Option<String> {
+        self.inner.get_statistics()
+    }
+
+    fn get_structured_statistics(&self) -> Option<WalStats> {
+        self.inner.get_structured_statistics()
+    }
+
+    async fn check_writable(&self) -> manager::Result<()> {
+        self.inner.check_writable().await
+    }
+}
+
+/// Opener of the memory wals, whose data wal records the contexts of the
+/// writes.
+#[derive(Default)]
+pub struct RecordingWalsOpener {
+    inner: MemWalsOpener,
+    write_contexts: Arc<Mutex<Vec<WriteContext>>>,
+}
+
+#[async_trait]
+impl WalsOpener for RecordingWalsOpener {
+    async fn open_wals(
+        &self,
+        config: &WalStorageConfig,
+        engine_runtimes: Arc<EngineRuntimes>,
+    ) -> setup::Result<OpenedWals> {
+        let opened_wals = self.inner.open_wals(config, engine_runtimes).await?;
+        let data_wal = RecordingWal {
+            inner: opened_wals.data_wal,
+            write_contexts: self.write_contexts.clone(),
+        };
+
+        Ok(OpenedWals {
+            data_wal: Arc::new(data_wal),
+            manifest_wal: opened_wals.manifest_wal,
+        })
+    }
+}
+
+/// Context to build the engine on the memory wals, and the contexts of the
+/// writes to the data wal are recorded.
+#[derive(Clone, Default)]
+pub struct RecordingWalEngineBuildContext {
+    inner: MemoryEngineBuildContext,
+    write_contexts: Arc<Mutex<Vec<WriteContext>>>,
+}
+
+impl RecordingWalEngineBuildContext {
+    /// Take the contexts of the writes to the data wal so far.
+    pub fn take_write_contexts(&self) -> Vec<WriteContext> {
+        std::mem::take(&mut *self.write_contexts.lock().unwrap())
+    }
+}
+
+impl EngineBuildContext for RecordingWalEngineBuildContext {
+    type WalsOpener = RecordingWalsOpener;
+
+    fn wals_opener(&self) -> Self::WalsOpener {
+        RecordingWalsOpener {
+            inner: self.inner.wals_opener(),
+            write_contexts: self.write_contexts.clone(),
+        }
+    }
+
+    fn config(&self) -> Config {
+        self.inner.config()
+    }
+
+    fn open_method(&self) -> OpenTablesMethod {
+        self.inner.open_method()
+    }
+}
+
 pub fn rocksdb_ctxs() -> Vec<RocksDBEngineBuildContext> {
     vec![
         RocksDBEngineBuildContext::new(RecoverMode::TableBased, OpenTablesMethod::WithOpenTable),
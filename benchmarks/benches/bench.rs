@@ -12,6 +12,7 @@ use benchmarks::{
     scan_memtable_bench::ScanMemTableBench,
     sst_bench::SstBench,
     wal_write_bench::WalWriteBench,
+    write_bench::WriteBench,
 };
 use criterion::*;
 use pprof::criterion::{Output, PProfProfiler};
@@ -193,6 +194,62 @@ fn bench_wal_write(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_write_iter(b: &mut Bencher<'_>, bench: &WriteBench) {
+    b.iter(|| bench.run_bench())
+}
+
+fn bench_write(c: &mut Criterion) {
+    let config = init_bench();
+
+    let mut group = c.benchmark_group("write");
+
+    group.measurement_time(config.write_bench.bench_measurement_time.0);
+    group.sample_size(config.write_bench.bench_sample_size);
+
+    let mut bench = WriteBench::new(config.write_bench);
+
+    // Compare the interleaved insertion with the presorted one.
+    for presort_rows in [false, true] {
+        bench.init_for_bench(|config| config.enable_presort_write_rows = presort_rows);
+        let name = if presort_rows {
+            "presorted"
+        } else {
+            "interleaved"
+        };
+        group.bench_with_input(BenchmarkId::new("write", name), &bench, bench_write_iter);
+    }
+
+    group.finish();
+}
+
+fn bench_write_batches(c: &mut Criterion) {
+    let config = init_bench();
+
+    let mut group = c.benchmark_group("write_batches");
+
+    group.measurement_time(config.write_bench.bench_measurement_time.0);
+    group.sample_size(config.write_bench.bench_sample_size);
+
+    let max_bytes_per_write_batch = config.write_bench.max_bytes_per_write_batch;
+    let mut bench = WriteBench::new(config.write_bench);
+
+    // Compare the pipelined write of the split batches with the sequential one.
+    for pipelined in [false, true] {
+        bench.init_for_bench(|config| {
+            config.max_bytes_per_write_batch = Some(max_bytes_per_write_batch);
+            config.enable_pipelined_write_batches = pipelined;
+        });
+        let name = if pipelined { "pipelined" } else { "sequential" };
+        group.bench_with_input(
+            BenchmarkId::new("write_batches", name),
+            &bench,
+            bench_write_iter,
+        );
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
@@ -202,6 +259,8 @@ criterion_group!(
     bench_scan_memtable,
     bench_merge_memtable,
     bench_wal_write,
+    bench_write,
+    bench_write_batches,
 );
 
 criterion_main!(benches);
@@ -25,6 +25,7 @@ pub struct BenchConfig {
     pub scan_memtable_bench: ScanMemTableBenchConfig,
     pub merge_memtable_bench: MergeMemTableBenchConfig,
     pub wal_write_bench: WalWriteBenchConfig,
+    pub write_bench: WriteBenchConfig,
 }
 
 // TODO(yingwen): Maybe we can use layze static to load config first.
@@ -133,3 +134,17 @@ pub struct WalWriteBenchConfig {
     pub batch_size: usize,
     pub value_size: usize,
 }
+
+#[derive(Deserialize)]
+pub struct WriteBenchConfig {
+    pub bench_measurement_time: ReadableDuration,
+    pub bench_sample_size: usize,
+    /// Number of rows per write request.
+    pub num_rows: usize,
+    /// Number of segments the rows of a write request are interleaved across.
+    pub num_segments: usize,
+    pub segment_duration: ReadableDuration,
+    /// Max bytes per write batch to split the write request in the bench of
+    /// writing the batches.
+    pub max_bytes_per_write_batch: ReadableSize,
+}
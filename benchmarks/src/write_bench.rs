@@ -0,0 +1,96 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Table write bench.
+
+use analytic_engine::{
+    setup::MemWalsOpener,
+    tests::{
+        table::FixedSchemaTable,
+        util::{MemoryEngineBuildContext, TestContext, TestEnv},
+    },
+    Config,
+};
+use common_types::{row::RowGroup, time::Timestamp};
+use table_engine::table::{TableRef, WriteRequest};
+
+use crate::config::WriteBenchConfig;
+
+const TABLE_NAME: &str = "write_bench";
+
+pub struct WriteBench {
+    env: TestEnv,
+    config: WriteBenchConfig,
+    test_ctx: Option<TestContext<MemWalsOpener>>,
+    table: Option<TableRef>,
+    row_group: Option<RowGroup>,
+}
+
+impl WriteBench {
+    pub fn new(config: WriteBenchConfig) -> Self {
+        WriteBench {
+            env: TestEnv::builder().build(),
+            config,
+            test_ctx: None,
+            table: None,
+            row_group: None,
+        }
+    }
+
+    /// Open a new engine whose config is updated by `update_config`, and
+    /// create the table to write.
+    pub fn init_for_bench(&mut self, update_config: impl FnOnce(&mut Config)) {
+        let mut test_ctx = self.env.new_context(MemoryEngineBuildContext::default());
+        update_config(test_ctx.config_mut());
+
+        let fixed_schema_table = FixedSchemaTable::builder()
+            .table_name(TABLE_NAME.to_string())
+            .segment_duration(self.config.segment_duration)
+            .build_fixed();
+        let table = self.env.block_on(async {
+            test_ctx.open().await;
+            test_ctx
+                .engine()
+                .create_table(fixed_schema_table.create_request().clone())
+                .await
+                .unwrap()
+        });
+
+        self.row_group = Some(self.build_interleaved_rows(&fixed_schema_table));
+        self.table = Some(table);
+        self.test_ctx = Some(test_ctx);
+    }
+
+    /// Build rows whose timestamps are interleaved across the segments, that
+    /// is to say, two adjacent rows are always in different segments.
+    fn build_interleaved_rows(&self, fixed_schema_table: &FixedSchemaTable) -> RowGroup {
+        let num_segments = self.config.num_segments.max(1);
+        let segment_ms = self.config.segment_duration.as_millis() as i64;
+        let start_ms =
+            Timestamp::now().as_i64() / segment_ms * segment_ms - segment_ms * num_segments as i64;
+
+        let keys: Vec<_> = (0..self.config.num_rows)
+            .map(|i| format!("key_{i}"))
+            .collect();
+        let rows: Vec<_> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let segment = (i % num_segments) as i64;
+                let offset = (i / num_segments) as i64;
+                let timestamp = Timestamp::new(start_ms + segment * segment_ms + offset);
+                (key.as_str(), timestamp, "tag", 1.0, 2.0, "value")
+            })
+            .collect();
+
+        fixed_schema_table.rows_to_row_group(&rows)
+    }
+
+    pub fn run_bench(&self) {
+        let table = self.table.as_ref().unwrap();
+        let row_group = self.row_group.clone().unwrap();
+
+        self.env.block_on(async {
+            table.write(WriteRequest::new(row_group)).await.unwrap();
+        });
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Allocations of the repeated writes.
+//!
+//! It is a dedicated test binary because the counting allocator replaces the
+//! global allocator of the whole binary.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use benchmarks::{config::WriteBenchConfig, write_bench::WriteBench};
+use common_util::config::{ReadableDuration, ReadableSize};
+
+static NUM_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocator counting the allocations of all the threads.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        NUM_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        NUM_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the allocations of the writes except the first one, which fills the
+/// pool.
+fn count_write_allocations(
+    write_buffer_pool_size: usize,
+    num_rows: usize,
+    num_writes: usize,
+) -> usize {
+    let mut bench = WriteBench::new(WriteBenchConfig {
+        bench_measurement_time: ReadableDuration::secs(1),
+        bench_sample_size: 1,
+        num_rows,
+        num_segments: 1,
+        segment_duration: ReadableDuration::hours(2),
+        max_bytes_per_write_batch: ReadableSize::mb(1),
+    });
+    bench.init_for_bench(|config| config.write_buffer_pool_size = write_buffer_pool_size);
+
+    bench.run_bench();
+    let begin = NUM_ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 1..num_writes {
+        bench.run_bench();
+    }
+    NUM_ALLOCATIONS.load(Ordering::Relaxed) - begin
+}
+
+#[test]
+fn test_write_buffer_pool_allocations() {
+    let num_rows = 100;
+    let num_writes = 100;
+    let unpooled = count_write_allocations(0, num_rows, num_writes);
+    let pooled = count_write_allocations(4, num_rows, num_writes);
+
+    // Without the pool, every row of a write allocates a buffer to be encoded into
+    // the wal, and the allocations of the other parts of the engine are tolerated
+    // by only expecting half of them to be saved.
+    assert!(
+        pooled + (num_writes - 1) * num_rows / 2 <= unpooled,
+        "pooled:{pooled}, unpooled:{unpooled}"
+    );
+}
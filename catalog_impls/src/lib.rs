@@ -12,8 +12,11 @@ use system_catalog::{tables::Tables, SystemTableAdapter};
 
 use crate::system_tables::{SystemTables, SystemTablesBuilder};
 
+mod shard_table;
 mod system_tables;
 pub mod table_based;
+pub mod table_flusher;
+pub mod table_stats;
 pub mod volatile;
 
 /// CatalogManagerImpl is a wrapper for system and user tables
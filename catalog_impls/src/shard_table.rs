@@ -0,0 +1,231 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Table whose writes are tracked by the shards it belongs to.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use cluster::shard_tables_cache::ShardTablesCache;
+use common_types::{row::Row, schema::Schema};
+use common_util::error::BoxError;
+use meta_client::types::ShardId;
+use snafu::ResultExt;
+use table_engine::{
+    partition::PartitionInfo,
+    stream::{PartitionedStreams, SendableRecordBatchStream},
+    table::{
+        AlterSchemaRequest, FlushRequest, GetRequest, ReadRequest, Result, Table, TableId,
+        TableRef, TableStats, Write, WriteRequest,
+    },
+};
+
+/// Table on the shards of this node.
+///
+/// Every write is regarded as an in-flight operation of the shards of the
+/// table until it is done, so the draining shards reject the new writes and
+/// wait for the in-flight ones, and the shards whose lock is lost reject the
+/// new writes too. The other requests (e.g. reads) are passed to the table
+/// directly, so they still work on these shards.
+#[derive(Debug)]
+pub(crate) struct ShardTable {
+    table: TableRef,
+    shard_ids: Vec<ShardId>,
+    shard_tables_cache: ShardTablesCache,
+}
+
+impl ShardTable {
+    pub fn new(
+        table: TableRef,
+        shard_ids: Vec<ShardId>,
+        shard_tables_cache: ShardTablesCache,
+    ) -> Self {
+        Self {
+            table,
+            shard_ids,
+            shard_tables_cache,
+        }
+    }
+}
+
+#[async_trait]
+impl Table for ShardTable {
+    fn name(&self) -> &str {
+        self.table.name()
+    }
+
+    fn id(&self) -> TableId {
+        self.table.id()
+    }
+
+    fn schema(&self) -> Schema {
+        self.table.schema()
+    }
+
+    fn options(&self) -> HashMap<String, String> {
+        self.table.options()
+    }
+
+    fn partition_info(&self) -> Option<PartitionInfo> {
+        self.table.partition_info()
+    }
+
+    fn engine_type(&self) -> &str {
+        self.table.engine_type()
+    }
+
+    fn stats(&self) -> TableStats {
+        self.table.stats()
+    }
+
+    async fn write(&self, request: WriteRequest) -> Result<usize> {
+        // The operations are done once the guards are dropped after the write.
+        let _op_guards = self
+            .shard_ids
+            .iter()
+            .map(|shard_id| self.shard_tables_cache.begin_op(*shard_id))
+            .collect::<cluster::Result<Vec<_>>>()
+            .box_err()
+            .context(Write { table: self.name() })?;
+
+        self.table.write(request).await
+    }
+
+    async fn read(&self, request: ReadRequest) -> Result<SendableRecordBatchStream> {
+        self.table.read(request).await
+    }
+
+    async fn get(&self, request: GetRequest) -> Result<Option<Row>> {
+        self.table.get(request).await
+    }
+
+    async fn partitioned_read(&self, request: ReadRequest) -> Result<PartitionedStreams> {
+        self.table.partitioned_read(request).await
+    }
+
+    async fn alter_schema(&self, request: AlterSchemaRequest) -> Result<usize> {
+        self.table.alter_schema(request).await
+    }
+
+    async fn alter_options(&self, options: HashMap<String, String>) -> Result<usize> {
+        self.table.alter_options(options).await
+    }
+
+    async fn flush(&self, request: FlushRequest) -> Result<()> {
+        self.table.flush(request).await
+    }
+
+    async fn compact(&self) -> Result<()> {
+        self.table.compact().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use analytic_engine::tests::table::FixedSchemaTable;
+    use common_types::time::Timestamp;
+    use meta_client::types::{ShardInfo, TableInfo, TablesOfShard};
+    use table_engine::{
+        memory::MemoryTable,
+        table::{ReadOptions, ReadOrder},
+    };
+
+    use super::*;
+
+    fn new_shard_table(
+        fixed_schema_table: &FixedSchemaTable,
+        shard_tables_cache: ShardTablesCache,
+    ) -> ShardTable {
+        let create_request = fixed_schema_table.create_request();
+        let table = MemoryTable::new(
+            create_request.table_name.clone(),
+            create_request.table_id,
+            create_request.table_schema.clone(),
+            "memory".to_string(),
+        );
+
+        shard_tables_cache.insert(TablesOfShard {
+            shard_info: ShardInfo {
+                id: 1,
+                ..Default::default()
+            },
+            tables: vec![TableInfo {
+                id: create_request.table_id.as_u64(),
+                name: create_request.table_name.clone(),
+                schema_id: create_request.schema_id.as_u32(),
+                schema_name: create_request.schema_name.clone(),
+                partition_info: None,
+            }],
+        });
+        ShardTable::new(Arc::new(table), vec![1], shard_tables_cache)
+    }
+
+    fn new_write_request(fixed_schema_table: &FixedSchemaTable) -> WriteRequest {
+        WriteRequest::new(fixed_schema_table.rows_to_row_group(&[(
+            "key1",
+            Timestamp::new(1000),
+            "tag1",
+            1.0,
+            1.0,
+            "field1",
+        )]))
+    }
+
+    #[tokio::test]
+    async fn test_write_on_draining_shard() {
+        let fixed_schema_table = FixedSchemaTable::builder().build_fixed();
+        let shard_tables_cache = ShardTablesCache::default();
+        let table = new_shard_table(&fixed_schema_table, shard_tables_cache.clone());
+        assert_eq!(
+            1,
+            table
+                .write(new_write_request(&fixed_schema_table))
+                .await
+                .unwrap()
+        );
+
+        // The writes are rejected once the shard is draining.
+        assert!(shard_tables_cache.mark_draining(1).is_some());
+        assert!(table
+            .write(new_write_request(&fixed_schema_table))
+            .await
+            .is_err());
+
+        // The reads still work.
+        let read_request =
+            fixed_schema_table.new_read_all_request(ReadOptions::default(), ReadOrder::None);
+        assert!(table.read(read_request).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_write_on_lock_lost_shard() {
+        let fixed_schema_table = FixedSchemaTable::builder().build_fixed();
+        let shard_tables_cache = ShardTablesCache::default();
+        let table = new_shard_table(&fixed_schema_table, shard_tables_cache.clone());
+        assert_eq!(
+            1,
+            table
+                .write(new_write_request(&fixed_schema_table))
+                .await
+                .unwrap()
+        );
+
+        // The writes are rejected once the lock of the shard is lost.
+        assert!(shard_tables_cache.mark_lock_lost(1).is_some());
+        assert!(table
+            .write(new_write_request(&fixed_schema_table))
+            .await
+            .is_err());
+
+        // The writes are accepted again once the shard is reopened.
+        let table = new_shard_table(&fixed_schema_table, shard_tables_cache);
+        assert_eq!(
+            1,
+            table
+                .write(new_write_request(&fixed_schema_table))
+                .await
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! The flusher of the tables required by the cluster when draining shards.
+
+use async_trait::async_trait;
+use catalog::manager::ManagerRef;
+use cluster::TableFlusher;
+use common_util::error::{BoxError, GenericResult};
+use meta_client::types::TableInfo;
+use table_engine::table::FlushRequest;
+
+/// Flush the tables in the default catalog of the catalog manager.
+pub struct CatalogBasedTableFlusher {
+    catalog_manager: ManagerRef,
+}
+
+impl CatalogBasedTableFlusher {
+    pub fn new(catalog_manager: ManagerRef) -> Self {
+        Self { catalog_manager }
+    }
+}
+
+#[async_trait]
+impl TableFlusher for CatalogBasedTableFlusher {
+    async fn flush_table(&self, table: &TableInfo) -> GenericResult<bool> {
+        let catalog_name = self.catalog_manager.default_catalog_name();
+        let catalog = match self
+            .catalog_manager
+            .catalog_by_name(catalog_name)
+            .box_err()?
+        {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+        let schema = match catalog.schema_by_name(&table.schema_name).box_err()? {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+        let table = match schema.table_by_name(&table.name).box_err()? {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+
+        table.flush(FlushRequest::default()).await.box_err()?;
+
+        Ok(true)
+    }
+}
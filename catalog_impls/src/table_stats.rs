@@ -0,0 +1,29 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! The provider of the table stats required by the cluster.
+
+use catalog::manager::ManagerRef;
+use cluster::TableStatsProvider;
+use meta_client::types::TableInfo;
+
+/// Find the stats of the tables in the default catalog of the catalog manager.
+pub struct CatalogBasedTableStatsProvider {
+    catalog_manager: ManagerRef,
+}
+
+impl CatalogBasedTableStatsProvider {
+    pub fn new(catalog_manager: ManagerRef) -> Self {
+        Self { catalog_manager }
+    }
+}
+
+impl TableStatsProvider for CatalogBasedTableStatsProvider {
+    fn memtable_memory_usage(&self, table: &TableInfo) -> Option<u64> {
+        let catalog_name = self.catalog_manager.default_catalog_name();
+        let catalog = self.catalog_manager.catalog_by_name(catalog_name).ok()??;
+        let schema = catalog.schema_by_name(&table.schema_name).ok()??;
+        let table = schema.table_by_name(&table.name).ok()??;
+
+        Some(table.stats().memtable_memory_usage)
+    }
+}
@@ -29,6 +29,8 @@ use snafu::{ensure, OptionExt, ResultExt};
 use table_engine::table::{SchemaId, TableRef};
 use tokio::sync::Mutex;
 
+use crate::shard_table::ShardTable;
+
 /// ManagerImpl manages multiple volatile catalogs.
 pub struct ManagerImpl {
     catalogs: HashMap<String, Arc<CatalogImpl>>,
@@ -242,11 +244,36 @@ impl SchemaImpl {
         Ok(tables.get(table_name).cloned())
     }
 
+    /// Add the table, and its writes are tracked by the shards it belongs to if
+    /// it is found in the [ShardTablesCache].
     fn add_table(&self, table: TableRef) -> Option<TableRef> {
+        let table = self.with_shards(table);
         let mut tables = self.tables.write().unwrap();
         tables.insert(table.name().to_string(), table)
     }
 
+    fn with_shards(&self, table: TableRef) -> TableRef {
+        // The partition table is not stored in the cache.
+        let shard_ids = match self.shard_tables_cache.find_table_by_name(
+            &self.catalog_name,
+            &self.schema_name,
+            table.name(),
+        ) {
+            Some(v) => v
+                .shard_infos
+                .iter()
+                .map(|shard_info| shard_info.id)
+                .collect(),
+            None => return table,
+        };
+
+        Arc::new(ShardTable::new(
+            table,
+            shard_ids,
+            self.shard_tables_cache.clone(),
+        ))
+    }
+
     fn add_new_table(&self, table: TableRef) {
         let old = self.add_table(table);
 
@@ -1,8 +1,10 @@
 // Copyright 2022-2023 CeresDB Project Authors. Licensed under Apache-2.0.
 
 use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
     sync::{Arc, Mutex, RwLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
@@ -17,32 +19,49 @@ use common_types::table::ShardId;
 use common_util::{
     error::BoxError,
     runtime::{JoinHandle, Runtime},
+    time::current_time_millis,
 };
-use etcd_client::ConnectOptions;
+use etcd_client::{Client as EtcdClient, ConnectOptions};
 use log::{error, info, warn};
 use meta_client::{
     types::{
-        GetNodesRequest, GetTablesOfShardsRequest, RouteTablesRequest, RouteTablesResponse,
-        ShardInfo, TableInfo, TablesOfShard,
+        GetNodesRequest, GetTablesOfShardsRequest, GetTablesOfShardsResponse, RouteTablesRequest,
+        RouteTablesResponse, ShardInfo, ShardStats, TableInfo, TablesOfShard,
     },
     MetaClientRef,
 };
+use moka::sync::Cache;
+use prometheus::IntGauge;
+use rand::{thread_rng, Rng};
 use snafu::{ensure, OptionExt, ResultExt};
 use tokio::{
-    sync::mpsc::{self, Sender},
+    sync::{
+        broadcast,
+        mpsc::{self, Receiver, Sender},
+    },
     time,
 };
 
 use crate::{
-    config::ClusterConfig,
+    config::{ClusterConfig, EtcdClientConfig, MetaRetryConfig, RouteTablesCacheConfig},
+    metrics::{
+        ETCD_CONNECTED_GAUGE, HEARTBEAT_COUNTER, HEARTBEAT_DURATION_HISTOGRAM,
+        LAST_HEARTBEAT_TIMESTAMP_GAUGE, SHARD_VERSION_REGRESSION_COUNTER,
+    },
     shard_lock_manager::{ShardLockManager, ShardLockManagerRef},
     shard_tables_cache::ShardTablesCache,
     topology::ClusterTopology,
-    Cluster, ClusterNodesNotFound, ClusterNodesResp, EtcdClientFailureWithCause, Internal,
-    InvalidArguments, MetaClientFailure, OpenShard, OpenShardWithCause, Result, ShardNotFound,
-    TableNotFound,
+    Cluster, ClusterNodesNotFound, ClusterNodesResp, DrainShardSummary, EtcdClientFailureWithCause,
+    Internal, InvalidArguments, MetaClientFailure, OpenShard, OpenShardWithCause, Result,
+    ShardBusy, ShardChangeKind, ShardEvent, ShardEventReceiver, ShardNotFound, ShardStatus,
+    StaleShardVersion, TableFlusherRef, TableNotFound, TableStatsProviderRef,
 };
 
+/// Initial wait before retrying a failed heartbeat.
+const HEARTBEAT_BACKOFF_BASE: Duration = Duration::from_millis(500);
+/// Max number of the shard events buffered for the slowest subscriber.
+const SHARD_EVENT_CHANNEL_CAPACITY: usize = 1024;
+
 /// ClusterImpl is an implementation of [`Cluster`] based [`MetaClient`].
 ///
 /// Its functions are to:
@@ -55,6 +74,13 @@ pub struct ClusterImpl {
     config: ClusterConfig,
     heartbeat_handle: Mutex<Option<JoinHandle<()>>>,
     stop_heartbeat_tx: Mutex<Option<Sender<()>>>,
+    etcd_supervisor_handle: Mutex<Option<JoinHandle<()>>>,
+    stop_etcd_supervisor_tx: Mutex<Option<Sender<()>>>,
+    /// The instant of the last successful heartbeat.
+    last_heartbeat: Arc<Mutex<Option<Instant>>>,
+    /// The shards whose locks are lost, and they are removed once the shards
+    /// are closed or opened again.
+    lock_lost_shards: Arc<Mutex<BTreeSet<ShardId>>>,
     shard_lock_manager: ShardLockManagerRef,
 }
 
@@ -65,19 +91,41 @@ impl ClusterImpl {
         meta_client: MetaClientRef,
         config: ClusterConfig,
         runtime: Arc<Runtime>,
+        table_stats_provider: Option<TableStatsProviderRef>,
+        table_flusher: Option<TableFlusherRef>,
     ) -> Result<Self> {
         if let Err(e) = config.etcd_client.validate() {
             return InvalidArguments { msg: e }.fail();
         }
+        ensure!(
+            (0.0..1.0).contains(&config.heartbeat_jitter),
+            InvalidArguments {
+                msg: format!(
+                    "heartbeat_jitter is required in [0, 1), heartbeat_jitter:{}",
+                    config.heartbeat_jitter
+                ),
+            }
+        );
 
-        let inner = Arc::new(Inner::new(shard_tables_cache, meta_client)?);
-        let connect_options = ConnectOptions::from(&config.etcd_client);
-        let etcd_client =
-            etcd_client::Client::connect(&config.etcd_client.server_addrs, Some(connect_options))
-                .await
-                .context(EtcdClientFailureWithCause {
-                    msg: "failed to connect to etcd",
-                })?;
+        let inner = Arc::new(Inner::new(
+            shard_tables_cache,
+            meta_client,
+            &config.route_tables_cache,
+            config.meta_retry,
+            config.nodes_cache_ttl.0,
+            table_stats_provider,
+            table_flusher,
+        )?);
+        let etcd_config = &config.etcd_client;
+        let etcd_client = connect_with_retry(
+            || connect_etcd(etcd_config),
+            etcd_config.connect_max_attempts,
+            etcd_config.connect_retry_interval.0,
+        )
+        .await
+        .context(EtcdClientFailureWithCause {
+            msg: "failed to connect to etcd",
+        })?;
 
         let shard_lock_key_prefix = Self::shard_lock_key_prefix(
             &config.etcd_client.root_path,
@@ -92,33 +140,59 @@ impl ClusterImpl {
             config.etcd_client.rpc_timeout(),
             runtime.clone(),
         );
+        // Reject the writes to the shard whose lock is lost until it is closed.
+        let inner_for_lock_lost = inner.clone();
+        let lock_lost_shards = Arc::new(Mutex::new(BTreeSet::new()));
+        let lock_lost_shards_for_callback = lock_lost_shards.clone();
+        shard_lock_manager.register_lock_lost_callback(Arc::new(move |shard_id: ShardId| {
+            warn!("Shard lock is lost, reject the writes on the shard, shard_id:{shard_id}");
+            lock_lost_shards_for_callback
+                .lock()
+                .unwrap()
+                .insert(shard_id);
+            if let Err(e) = inner_for_lock_lost.mark_shard_lock_lost(shard_id) {
+                warn!("Failed to mark the shard whose lock is lost, shard_id:{shard_id}, err:{e}");
+            }
+        }));
+
         Ok(Self {
             inner,
             runtime,
             config,
             heartbeat_handle: Mutex::new(None),
             stop_heartbeat_tx: Mutex::new(None),
+            etcd_supervisor_handle: Mutex::new(None),
+            stop_etcd_supervisor_tx: Mutex::new(None),
+            last_heartbeat: Arc::new(Mutex::new(None)),
+            lock_lost_shards,
             shard_lock_manager: Arc::new(shard_lock_manager),
         })
     }
 
     fn start_heartbeat_loop(&self) {
         let interval = self.heartbeat_interval();
-        let error_wait_lease = self.error_wait_lease();
+        let lease = self.config.meta_client.lease.0;
+        let jitter = self.config.heartbeat_jitter;
+        let max_error_wait = self.max_error_wait();
         let inner = self.inner.clone();
+        let enable_shard_stats = self.config.enable_shard_stats_in_heartbeat;
+        let last_heartbeat = self.last_heartbeat.clone();
         let (tx, mut rx) = mpsc::channel(1);
 
         let handle = self.runtime.spawn(async move {
+            let mut backoff = HeartbeatBackoff::new(HEARTBEAT_BACKOFF_BASE, max_error_wait);
             loop {
-                let shard_infos = inner.shard_tables_cache.all_shard_infos();
-                info!("Node heartbeat to meta, shard infos:{:?}", shard_infos);
-
-                let resp = inner.meta_client.send_heartbeat(shard_infos).await;
+                let resp = inner.send_heartbeat(enable_shard_stats).await;
                 let wait = match resp {
-                    Ok(()) => interval,
+                    Ok(()) => {
+                        *last_heartbeat.lock().unwrap() = Some(Instant::now());
+                        backoff.reset();
+                        heartbeat_interval_with_jitter(interval, lease, jitter, thread_rng().gen())
+                    }
                     Err(e) => {
-                        error!("Send heartbeat to meta failed, err:{}", e);
-                        error_wait_lease
+                        let wait = with_jitter(backoff.next_backoff(), thread_rng().gen());
+                        error!("Send heartbeat to meta failed, wait:{:?}, err:{}", wait, e);
+                        wait
                     }
                 };
 
@@ -133,12 +207,38 @@ impl ClusterImpl {
         *self.heartbeat_handle.lock().unwrap() = Some(handle);
     }
 
+    /// Start the background supervision of the connection to etcd, and the
+    /// re-established connection is used by the shard lock manager.
+    fn start_etcd_supervisor(&self) {
+        let etcd_config = self.config.etcd_client.clone();
+        let interval = etcd_config.connection_check_interval.0;
+        let retry_interval = etcd_config.connect_retry_interval.0;
+        let shard_lock_manager = self.shard_lock_manager.clone();
+        let (tx, rx) = mpsc::channel(1);
+
+        let handle = self.runtime.spawn(supervise_etcd_connection(
+            self.shard_lock_manager.etcd_client(),
+            |mut client: EtcdClient| async move { client.status().await.map(|_| ()) },
+            move || connect_etcd(&etcd_config),
+            move |client| shard_lock_manager.update_etcd_client(client),
+            interval,
+            retry_interval,
+            ETCD_CONNECTED_GAUGE.clone(),
+            rx,
+        ));
+
+        *self.stop_etcd_supervisor_tx.lock().unwrap() = Some(tx);
+        *self.etcd_supervisor_handle.lock().unwrap() = Some(handle);
+    }
+
     // Register node every 2/3 lease
     fn heartbeat_interval(&self) -> Duration {
         Duration::from_millis(self.config.meta_client.lease.as_millis() * 2 / 3)
     }
 
-    fn error_wait_lease(&self) -> Duration {
+    /// The cap of the backoff between the failed heartbeats, which is half of
+    /// the lease so that a retry still happens before the lease expires.
+    fn max_error_wait(&self) -> Duration {
         self.config.meta_client.lease.0 / 2
     }
 
@@ -162,37 +262,423 @@ impl ClusterImpl {
     }
 }
 
+/// Exponential backoff of retrying the failed heartbeats, which starts from the
+/// base and is capped at the max.
+struct HeartbeatBackoff {
+    base: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl HeartbeatBackoff {
+    fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            next: base.min(max),
+        }
+    }
+
+    /// Returns the wait before the next retry and doubles it.
+    fn next_backoff(&mut self) -> Duration {
+        let wait = self.next;
+        self.next = (self.next * 2).min(self.max);
+        wait
+    }
+
+    fn reset(&mut self) {
+        self.next = self.base.min(self.max);
+    }
+}
+
+/// Randomize the wait into `[wait / 2, wait]` by the `ratio` in `[0, 1)`, so
+/// that the nodes won't retry in lockstep.
+fn with_jitter(wait: Duration, ratio: f64) -> Duration {
+    wait.mul_f64(1.0 - ratio / 2.0)
+}
+
+/// Randomize the heartbeat `interval` into `[interval * (1 - jitter), interval
+/// * (1 + jitter)]` by the `ratio` in `[0, 1)`, and it never exceeds the
+/// `lease`.
+fn heartbeat_interval_with_jitter(
+    interval: Duration,
+    lease: Duration,
+    jitter: f64,
+    ratio: f64,
+) -> Duration {
+    let factor = 1.0 + jitter * (2.0 * ratio - 1.0);
+    interval.mul_f64(factor).min(lease)
+}
+
+/// Connect to all the endpoints of the etcd cluster, so the requests are
+/// balanced among them.
+fn connect_etcd(
+    config: &EtcdClientConfig,
+) -> impl Future<Output = std::result::Result<EtcdClient, etcd_client::Error>> + Send + 'static {
+    let server_addrs = config.server_addrs.clone();
+    let options = ConnectOptions::from(config);
+    async move { EtcdClient::connect(server_addrs, Some(options)).await }
+}
+
+/// Connect to the etcd cluster by `connect`, and retry with the doubled wait
+/// starting from `interval` until `max_attempts` is reached.
+async fn connect_with_retry<T, E, F, Fut>(
+    mut connect: F,
+    max_attempts: usize,
+    interval: Duration,
+) -> std::result::Result<T, E>
+where
+    E: std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+{
+    let max_attempts = max_attempts.max(1);
+    let mut wait = interval;
+    let mut attempt = 1;
+    loop {
+        match connect().await {
+            Ok(v) => return Ok(v),
+            Err(e) => {
+                if attempt >= max_attempts {
+                    return Err(e);
+                }
+
+                warn!("Failed to connect to etcd and will retry, attempt:{attempt}, wait:{wait:?}, err:{e}");
+                time::sleep(wait).await;
+                wait *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Supervise the connection to etcd by checking the `client` with `check`
+/// every `interval`.
+///
+/// Once the check fails, it reconnects by `connect` with the doubled wait
+/// starting from `retry_interval` and capped at `interval` until it succeeds,
+/// and the new client is published by `on_reconnected`. The state of the
+/// connection is reported by the `connected` gauge, which is written only by
+/// the supervision, and the supervision exits once the `stop_rx` is notified or
+/// closed.
+#[allow(clippy::too_many_arguments)]
+async fn supervise_etcd_connection<T, E, Check, CheckFut, Connect, ConnectFut, OnReconnected>(
+    mut client: T,
+    mut check: Check,
+    mut connect: Connect,
+    mut on_reconnected: OnReconnected,
+    interval: Duration,
+    retry_interval: Duration,
+    connected: IntGauge,
+    mut stop_rx: Receiver<()>,
+) where
+    T: Clone,
+    E: std::fmt::Display,
+    Check: FnMut(T) -> CheckFut,
+    CheckFut: Future<Output = std::result::Result<(), E>>,
+    Connect: FnMut() -> ConnectFut,
+    ConnectFut: Future<Output = std::result::Result<T, E>>,
+    OnReconnected: FnMut(T),
+{
+    // The supervision starts with a connected client.
+    connected.set(1);
+    loop {
+        if time::timeout(interval, stop_rx.recv()).await.is_ok() {
+            info!("Receive exit command and exit etcd connection supervision");
+            return;
+        }
+
+        match check(client.clone()).await {
+            Ok(()) => {
+                connected.set(1);
+                continue;
+            }
+            Err(e) => {
+                connected.set(0);
+                warn!("Connection to etcd is broken and will reconnect, err:{e}");
+            }
+        }
+
+        let mut wait = retry_interval.min(interval);
+        loop {
+            match connect().await {
+                Ok(v) => {
+                    info!("Connection to etcd is re-established");
+                    connected.set(1);
+                    client = v.clone();
+                    on_reconnected(v);
+                    break;
+                }
+                Err(e) => {
+                    warn!("Failed to reconnect to etcd and will retry, wait:{wait:?}, err:{e}");
+                    if time::timeout(wait, stop_rx.recv()).await.is_ok() {
+                        info!("Receive exit command and exit etcd connection supervision");
+                        return;
+                    }
+                    wait = (wait * 2).min(interval);
+                }
+            }
+        }
+    }
+}
+
+/// Call the CeresMeta by `call`, and retry the transient failures with the
+/// doubled wait starting from the interval of `retry` until the max attempts
+/// are reached. The other failures (e.g. the shard is not found) are returned
+/// immediately.
+async fn call_meta_with_retry<T, F, Fut>(
+    retry: &MetaRetryConfig,
+    op: &str,
+    mut call: F,
+) -> meta_client::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = meta_client::Result<T>>,
+{
+    let max_attempts = retry.max_attempts.max(1);
+    let mut wait = retry.interval.0;
+    let mut attempt = 1;
+    loop {
+        match call().await {
+            Err(e) if e.is_transient() && attempt < max_attempts => {
+                warn!("Failed to {op} and will retry, attempt:{attempt}, wait:{wait:?}, err:{e}");
+                time::sleep(wait).await;
+                wait *= 2;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Key of the cached route result, which is the set of the requested tables.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RouteCacheKey {
+    schema_name: String,
+    /// Sorted and deduplicated table names.
+    table_names: Vec<String>,
+}
+
+impl From<&RouteTablesRequest> for RouteCacheKey {
+    fn from(req: &RouteTablesRequest) -> Self {
+        let mut table_names = req.table_names.clone();
+        table_names.sort_unstable();
+        table_names.dedup();
+
+        Self {
+            schema_name: req.schema_name.clone(),
+            table_names,
+        }
+    }
+}
+
 struct Inner {
     shard_tables_cache: ShardTablesCache,
     meta_client: MetaClientRef,
     topology: RwLock<ClusterTopology>,
+    /// Cache of the route results to reduce the pressure on the CeresMeta, and
+    /// it is invalidated once the topology version changes or the shards and
+    /// tables on this node change.
+    route_cache: Option<Cache<RouteCacheKey, RouteTablesResponse>>,
+    table_stats_provider: Option<TableStatsProviderRef>,
+    /// Flush the tables when draining the shard, and nothing is flushed if
+    /// it is not set.
+    table_flusher: Option<TableFlusherRef>,
+    /// The shards with open/close in progress, and the value is the number of
+    /// the ongoing open/close of the shard.
+    shards_in_progress: Mutex<HashMap<ShardId, usize>>,
+    /// Retry the transient failures of the calls to the CeresMeta.
+    meta_retry: MetaRetryConfig,
+    /// The fetched cluster nodes are served from the topology within it.
+    nodes_cache_ttl: Duration,
+    /// Notify the subscribers once the shards on this node are changed.
+    shard_event_tx: broadcast::Sender<ShardEvent>,
+}
+
+/// Mark the open/close of the shard as done once dropped.
+struct ShardInProgressGuard<'a> {
+    shards_in_progress: &'a Mutex<HashMap<ShardId, usize>>,
+    shard_id: ShardId,
+}
+
+impl<'a> Drop for ShardInProgressGuard<'a> {
+    fn drop(&mut self) {
+        let mut shards = self.shards_in_progress.lock().unwrap();
+        if let Some(num) = shards.get_mut(&self.shard_id) {
+            *num -= 1;
+            if *num == 0 {
+                shards.remove(&self.shard_id);
+            }
+        }
+    }
 }
 
 impl Inner {
-    fn new(shard_tables_cache: ShardTablesCache, meta_client: MetaClientRef) -> Result<Self> {
+    fn new(
+        shard_tables_cache: ShardTablesCache,
+        meta_client: MetaClientRef,
+        route_cache_config: &RouteTablesCacheConfig,
+        meta_retry: MetaRetryConfig,
+        nodes_cache_ttl: Duration,
+        table_stats_provider: Option<TableStatsProviderRef>,
+        table_flusher: Option<TableFlusherRef>,
+    ) -> Result<Self> {
+        let route_cache = if route_cache_config.enable {
+            Some(
+                Cache::builder()
+                    .time_to_live(route_cache_config.ttl.0)
+                    .max_capacity(route_cache_config.capacity)
+                    .build(),
+            )
+        } else {
+            None
+        };
+
         Ok(Self {
             shard_tables_cache,
             meta_client,
             topology: Default::default(),
+            route_cache,
+            table_stats_provider,
+            table_flusher,
+            shards_in_progress: Mutex::new(HashMap::new()),
+            meta_retry,
+            nodes_cache_ttl,
+            shard_event_tx: broadcast::channel(SHARD_EVENT_CHANNEL_CAPACITY).0,
         })
     }
 
+    fn mark_shard_in_progress(&self, shard_id: ShardId) -> ShardInProgressGuard {
+        *self
+            .shards_in_progress
+            .lock()
+            .unwrap()
+            .entry(shard_id)
+            .or_default() += 1;
+
+        ShardInProgressGuard {
+            shards_in_progress: &self.shards_in_progress,
+            shard_id,
+        }
+    }
+
+    fn try_mark_shard_in_progress(&self, shard_id: ShardId) -> Result<ShardInProgressGuard> {
+        let mut shards = self.shards_in_progress.lock().unwrap();
+        ensure!(!shards.contains_key(&shard_id), ShardBusy { shard_id });
+        shards.insert(shard_id, 1);
+
+        Ok(ShardInProgressGuard {
+            shards_in_progress: &self.shards_in_progress,
+            shard_id,
+        })
+    }
+
+    /// Collect the load stats of the shards, and the memtable memory usage is
+    /// zero if no stats provider is set.
+    fn shard_stats(&self, shard_infos: &[ShardInfo]) -> Vec<ShardStats> {
+        shard_infos
+            .iter()
+            .filter_map(|shard_info| self.shard_tables_cache.get(shard_info.id))
+            .map(|tables_of_shard| {
+                let memtable_memory_usage = match &self.table_stats_provider {
+                    Some(provider) => tables_of_shard
+                        .tables
+                        .iter()
+                        .filter_map(|table| provider.memtable_memory_usage(table))
+                        .sum(),
+                    None => 0,
+                };
+
+                ShardStats {
+                    id: tables_of_shard.shard_info.id,
+                    table_count: tables_of_shard.tables.len() as u64,
+                    memtable_memory_usage,
+                }
+            })
+            .collect()
+    }
+
     async fn route_tables(&self, req: &RouteTablesRequest) -> Result<RouteTablesResponse> {
-        // TODO: we should use self.topology to cache the route result to reduce the
-        // pressure on the CeresMeta.
-        let route_resp = self
-            .meta_client
-            .route_tables(req.clone())
-            .await
-            .context(MetaClientFailure)?;
+        let route_cache = match &self.route_cache {
+            Some(v) => v,
+            None => return self.route_tables_from_meta(req).await,
+        };
+
+        let cache_key = RouteCacheKey::from(req);
+        if let Some(route_resp) = route_cache.get(&cache_key) {
+            return Ok(route_resp);
+        }
+
+        let route_resp = self.route_tables_from_meta(req).await?;
+        // The topology may be updated during routing, and the outdated result
+        // shouldn't be cached.
+        let is_outdated = self
+            .topology
+            .read()
+            .unwrap()
+            .nodes()
+            .map(|nodes| route_resp.cluster_topology_version < nodes.version)
+            .unwrap_or(false);
+        if !is_outdated {
+            route_cache.insert(cache_key, route_resp.clone());
+        }
 
         Ok(route_resp)
     }
 
+    /// Send the heartbeat with the shards on this node to the CeresMeta, and
+    /// record the outcome in the metrics.
+    async fn send_heartbeat(&self, enable_shard_stats: bool) -> meta_client::Result<()> {
+        let shard_infos = self.shard_tables_cache.all_shard_infos();
+        info!("Node heartbeat to meta, shard infos:{:?}", shard_infos);
+
+        let shard_stats = enable_shard_stats.then(|| self.shard_stats(&shard_infos));
+        let timer = HEARTBEAT_DURATION_HISTOGRAM.start_timer();
+        let resp = self
+            .meta_client
+            .send_heartbeat(shard_infos, shard_stats)
+            .await;
+        timer.observe_duration();
+
+        match &resp {
+            Ok(()) => {
+                HEARTBEAT_COUNTER.with_label_values(&["ok"]).inc();
+                LAST_HEARTBEAT_TIMESTAMP_GAUGE.set((current_time_millis() / 1000) as i64);
+            }
+            Err(_) => HEARTBEAT_COUNTER.with_label_values(&["err"]).inc(),
+        }
+
+        resp
+    }
+
+    /// Drop all the cached route results.
+    fn invalidate_route_cache(&self) {
+        if let Some(route_cache) = &self.route_cache {
+            route_cache.invalidate_all();
+        }
+    }
+
+    /// Notify the subscribers of the change of the shard, and the event is
+    /// dropped if there is no subscriber.
+    fn notify_shard_change(&self, shard_id: ShardId, kind: ShardChangeKind) {
+        let _ = self.shard_event_tx.send(ShardEvent { shard_id, kind });
+    }
+
+    async fn route_tables_from_meta(
+        &self,
+        req: &RouteTablesRequest,
+    ) -> Result<RouteTablesResponse> {
+        call_meta_with_retry(&self.meta_retry, "route tables", || {
+            self.meta_client.route_tables(req.clone())
+        })
+        .await
+        .context(MetaClientFailure)
+    }
+
     async fn fetch_nodes(&self) -> Result<ClusterNodesResp> {
         {
             let topology = self.topology.read().unwrap();
-            let cached_node_topology = topology.nodes();
+            let cached_node_topology = topology.fresh_nodes(self.nodes_cache_ttl);
             if let Some(cached_node_topology) = cached_node_topology {
                 return Ok(ClusterNodesResp {
                     cluster_topology_version: cached_node_topology.version,
@@ -201,12 +687,11 @@ impl Inner {
             }
         }
 
-        let req = GetNodesRequest::default();
-        let resp = self
-            .meta_client
-            .get_nodes(req)
-            .await
-            .context(MetaClientFailure)?;
+        let resp = call_meta_with_retry(&self.meta_retry, "get nodes", || {
+            self.meta_client.get_nodes(GetNodesRequest::default())
+        })
+        .await
+        .context(MetaClientFailure)?;
 
         let version = resp.cluster_topology_version;
         let nodes = Arc::new(resp.node_shards);
@@ -217,6 +702,7 @@ impl Inner {
             .maybe_update_nodes(nodes.clone(), version);
 
         let resp = if updated {
+            self.invalidate_route_cache();
             ClusterNodesResp {
                 cluster_topology_version: version,
                 cluster_nodes: nodes,
@@ -236,6 +722,16 @@ impl Inner {
     }
 
     async fn open_shard(&self, shard_info: &ShardInfo) -> Result<TablesOfShard> {
+        let _guard = self.mark_shard_in_progress(shard_info.id);
+        self.do_open_shard(shard_info).await
+    }
+
+    async fn try_open_shard(&self, shard_info: &ShardInfo) -> Result<TablesOfShard> {
+        let _guard = self.try_mark_shard_in_progress(shard_info.id)?;
+        self.do_open_shard(shard_info).await
+    }
+
+    async fn do_open_shard(&self, shard_info: &ShardInfo) -> Result<TablesOfShard> {
         if let Some(tables_of_shard) = self.shard_tables_cache.get(shard_info.id) {
             if tables_of_shard.shard_info.version == shard_info.version {
                 info!(
@@ -244,6 +740,15 @@ impl Inner {
                 );
                 return Ok(tables_of_shard);
             }
+            if tables_of_shard.shard_info.version > shard_info.version {
+                warn!(
+                    "Try to open a shard with a smaller version, shard_id:{}, curr_version:{}, new_version:{}",
+                    shard_info.id, tables_of_shard.shard_info.version, shard_info.version
+                );
+                SHARD_VERSION_REGRESSION_COUNTER
+                    .with_label_values(&[&shard_info.id.to_string()])
+                    .inc();
+            }
             ensure!(
                 tables_of_shard.shard_info.version < shard_info.version,
                 OpenShard {
@@ -253,18 +758,7 @@ impl Inner {
             );
         }
 
-        let req = GetTablesOfShardsRequest {
-            shard_ids: vec![shard_info.id],
-        };
-
-        let mut resp = self
-            .meta_client
-            .get_tables_of_shards(req)
-            .await
-            .box_err()
-            .context(OpenShardWithCause {
-                shard_id: shard_info.id,
-            })?;
+        let mut resp = self.get_tables_of_shard(shard_info.id).await?;
 
         ensure!(
             resp.tables_by_shard.len() == 1,
@@ -283,47 +777,153 @@ impl Inner {
             })?;
 
         self.shard_tables_cache.insert(tables_of_shard.clone());
+        self.invalidate_route_cache();
+        self.notify_shard_change(shard_info.id, ShardChangeKind::Open);
 
         Ok(tables_of_shard)
     }
 
+    /// Fetch the tables of the shard from the CeresMeta, and the transient
+    /// failures are retried with backoff.
+    async fn get_tables_of_shard(&self, shard_id: ShardId) -> Result<GetTablesOfShardsResponse> {
+        let op = format!("get tables of shard, shard_id:{shard_id}");
+        call_meta_with_retry(&self.meta_retry, &op, || {
+            let req = GetTablesOfShardsRequest {
+                shard_ids: vec![shard_id],
+            };
+            self.meta_client.get_tables_of_shards(req)
+        })
+        .await
+        .box_err()
+        .context(OpenShardWithCause { shard_id })
+    }
+
     fn close_shard(&self, shard_id: ShardId) -> Result<TablesOfShard> {
-        self.shard_tables_cache
-            .remove(shard_id)
+        let tables_of_shard =
+            self.shard_tables_cache
+                .remove(shard_id)
+                .with_context(|| ShardNotFound {
+                    msg: format!("close non-existent shard, shard_id:{shard_id}"),
+                })?;
+        self.invalidate_route_cache();
+        self.notify_shard_change(shard_id, ShardChangeKind::Close);
+
+        Ok(tables_of_shard)
+    }
+
+    async fn drain_shard(&self, shard_id: ShardId, timeout: Duration) -> Result<DrainShardSummary> {
+        let _guard = self.mark_shard_in_progress(shard_id);
+        let (tables_of_shard, in_flight_ops) = self
+            .shard_tables_cache
+            .mark_draining(shard_id)
             .with_context(|| ShardNotFound {
-                msg: format!("close non-existent shard, shard_id:{shard_id}"),
-            })
+                msg: format!("try to drain a non-existent shard, shard_id:{shard_id}"),
+            })?;
+        info!("Shard is marked as draining, shard_id:{shard_id}");
+        self.notify_shard_change(shard_id, ShardChangeKind::Freeze);
+
+        let num_pending_ops = in_flight_ops.wait_done(timeout).await;
+        if num_pending_ops > 0 {
+            warn!(
+                "Timeout to wait for in-flight operations when draining shard, shard_id:{shard_id}, pending_ops:{num_pending_ops}, timeout:{timeout:?}"
+            );
+        }
+
+        let mut flushed_tables = Vec::new();
+        let mut failed_tables = Vec::new();
+        if let Some(table_flusher) = &self.table_flusher {
+            for table in &tables_of_shard.tables {
+                match table_flusher.flush_table(table).await {
+                    Ok(true) => flushed_tables.push(table.name.clone()),
+                    // The table is not opened, so nothing to flush.
+                    Ok(false) => (),
+                    Err(e) => {
+                        error!(
+                            "Failed to flush table when draining shard, shard_id:{shard_id}, table:{}, err:{e}",
+                            table.name
+                        );
+                        failed_tables.push(table.name.clone());
+                    }
+                }
+            }
+        }
+
+        info!(
+            "Shard is drained, shard_id:{shard_id}, flushed_tables:{flushed_tables:?}, failed_tables:{failed_tables:?}"
+        );
+
+        Ok(DrainShardSummary {
+            tables_of_shard,
+            num_pending_ops,
+            flushed_tables,
+            failed_tables,
+        })
     }
 
-    #[inline]
     fn freeze_shard(&self, shard_id: ShardId) -> Result<TablesOfShard> {
-        self.shard_tables_cache
-            .freeze(shard_id)
+        let tables_of_shard =
+            self.shard_tables_cache
+                .freeze(shard_id)
+                .with_context(|| ShardNotFound {
+                    msg: format!("try to freeze a non-existent shard, shard_id:{shard_id}"),
+                })?;
+        self.notify_shard_change(shard_id, ShardChangeKind::Freeze);
+
+        Ok(tables_of_shard)
+    }
+
+    /// Reject the writes and the updates to the shard whose lock is lost.
+    fn mark_shard_lock_lost(&self, shard_id: ShardId) -> Result<TablesOfShard> {
+        let tables_of_shard = self
+            .shard_tables_cache
+            .mark_lock_lost(shard_id)
             .with_context(|| ShardNotFound {
-                msg: format!("try to freeze a non-existent shard, shard_id:{shard_id}"),
-            })
+                msg: format!(
+                    "try to mark the lock of a non-existent shard lost, shard_id:{shard_id}"
+                ),
+            })?;
+        self.notify_shard_change(shard_id, ShardChangeKind::Freeze);
+
+        Ok(tables_of_shard)
     }
 
     fn create_table_on_shard(&self, req: &CreateTableOnShardRequest) -> Result<()> {
-        self.insert_table_to_shard(req.update_shard_info.clone(), req.table_info.clone())
+        self.insert_table_to_shard(
+            req.update_shard_info.clone(),
+            req.table_info.clone(),
+            ShardChangeKind::CreateTable,
+        )
     }
 
     fn drop_table_on_shard(&self, req: &DropTableOnShardRequest) -> Result<()> {
-        self.remove_table_from_shard(req.update_shard_info.clone(), req.table_info.clone())
+        self.remove_table_from_shard(
+            req.update_shard_info.clone(),
+            req.table_info.clone(),
+            ShardChangeKind::DropTable,
+        )
     }
 
     fn open_table_on_shard(&self, req: &OpenTableOnShardRequest) -> Result<()> {
-        self.insert_table_to_shard(req.update_shard_info.clone(), req.table_info.clone())
+        self.insert_table_to_shard(
+            req.update_shard_info.clone(),
+            req.table_info.clone(),
+            ShardChangeKind::OpenTable,
+        )
     }
 
     fn close_table_on_shard(&self, req: &CloseTableOnShardRequest) -> Result<()> {
-        self.remove_table_from_shard(req.update_shard_info.clone(), req.table_info.clone())
+        self.remove_table_from_shard(
+            req.update_shard_info.clone(),
+            req.table_info.clone(),
+            ShardChangeKind::CloseTable,
+        )
     }
 
     fn insert_table_to_shard(
         &self,
         update_shard_info: Option<UpdateShardInfo>,
         table_info: Option<TableInfoPb>,
+        change_kind: ShardChangeKind,
     ) -> Result<()> {
         let update_shard_info = update_shard_info.context(ShardNotFound {
             msg: "update shard info is missing",
@@ -334,22 +934,29 @@ impl Inner {
         let table_info = table_info.context(TableNotFound {
             msg: "table info is missing",
         })?;
+        let curr_shard_info = ShardInfo::from(&curr_shard_info);
+        self.ensure_newer_shard_version(&curr_shard_info)?;
 
         self.shard_tables_cache.try_insert_table_to_shard(
             update_shard_info.prev_version,
-            ShardInfo::from(&curr_shard_info),
+            curr_shard_info.clone(),
             TableInfo::try_from(table_info)
                 .box_err()
                 .context(Internal {
                     msg: "Failed to parse tableInfo",
                 })?,
-        )
+        )?;
+        self.invalidate_route_cache();
+        self.notify_shard_change(curr_shard_info.id, change_kind);
+
+        Ok(())
     }
 
     fn remove_table_from_shard(
         &self,
         update_shard_info: Option<UpdateShardInfo>,
         table_info: Option<TableInfoPb>,
+        change_kind: ShardChangeKind,
     ) -> Result<()> {
         let update_shard_info = update_shard_info.context(ShardNotFound {
             msg: "update shard info is missing",
@@ -360,16 +967,50 @@ impl Inner {
         let table_info = table_info.context(TableNotFound {
             msg: "table info is missing",
         })?;
+        let curr_shard_info = ShardInfo::from(&curr_shard_info);
+        self.ensure_newer_shard_version(&curr_shard_info)?;
 
         self.shard_tables_cache.try_remove_table_from_shard(
             update_shard_info.prev_version,
-            ShardInfo::from(&curr_shard_info),
+            curr_shard_info.clone(),
             TableInfo::try_from(table_info)
                 .box_err()
                 .context(Internal {
                     msg: "Failed to parse tableInfo",
                 })?,
-        )
+        )?;
+        self.invalidate_route_cache();
+        self.notify_shard_change(curr_shard_info.id, change_kind);
+
+        Ok(())
+    }
+
+    /// Ensure the shard version to update to is newer than the cached one, to
+    /// reject the stale mutation sent out of order by the meta.
+    ///
+    /// The non-existent shard is left to the shard tables cache to report.
+    fn ensure_newer_shard_version(&self, curr_shard_info: &ShardInfo) -> Result<()> {
+        if let Some(tables_of_shard) = self.shard_tables_cache.get(curr_shard_info.id) {
+            let cached_version = tables_of_shard.shard_info.version;
+            if cached_version >= curr_shard_info.version {
+                warn!(
+                    "Try to update a shard with a stale version, shard_id:{}, cached_version:{}, new_version:{}",
+                    curr_shard_info.id, cached_version, curr_shard_info.version
+                );
+                SHARD_VERSION_REGRESSION_COUNTER
+                    .with_label_values(&[&curr_shard_info.id.to_string()])
+                    .inc();
+
+                return StaleShardVersion {
+                    shard_id: curr_shard_info.id,
+                    cached_version,
+                    new_version: curr_shard_info.version,
+                }
+                .fail();
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -380,6 +1021,7 @@ impl Cluster for ClusterImpl {
 
         // start the background loop for sending heartbeat.
         self.start_heartbeat_loop();
+        self.start_etcd_supervisor();
 
         info!("Cluster has started");
         Ok(())
@@ -402,20 +1044,57 @@ impl Cluster for ClusterImpl {
             }
         }
 
+        {
+            let tx = self.stop_etcd_supervisor_tx.lock().unwrap().take();
+            if let Some(tx) = tx {
+                let _ = tx.send(()).await;
+            }
+        }
+
+        {
+            let handle = self.etcd_supervisor_handle.lock().unwrap().take();
+            if let Some(handle) = handle {
+                let _ = handle.await;
+            }
+        }
+
         info!("Cluster has stopped");
         Ok(())
     }
 
     async fn open_shard(&self, shard_info: &ShardInfo) -> Result<TablesOfShard> {
-        self.inner.open_shard(shard_info).await
+        let tables_of_shard = self.inner.open_shard(shard_info).await?;
+        self.lock_lost_shards.lock().unwrap().remove(&shard_info.id);
+        Ok(tables_of_shard)
+    }
+
+    async fn try_open_shard(&self, shard_info: &ShardInfo) -> Result<TablesOfShard> {
+        let tables_of_shard = self.inner.try_open_shard(shard_info).await?;
+        self.lock_lost_shards.lock().unwrap().remove(&shard_info.id);
+        Ok(tables_of_shard)
     }
 
     async fn close_shard(&self, shard_id: ShardId) -> Result<TablesOfShard> {
-        self.inner.close_shard(shard_id)
+        let tables_of_shard = self.inner.close_shard(shard_id)?;
+        self.lock_lost_shards.lock().unwrap().remove(&shard_id);
+        Ok(tables_of_shard)
+    }
+
+    async fn drain_shard(&self, shard_id: ShardId, timeout: Duration) -> Result<DrainShardSummary> {
+        self.inner.drain_shard(shard_id, timeout).await
     }
 
     async fn freeze_shard(&self, shard_id: ShardId) -> Result<TablesOfShard> {
-        self.inner.freeze_shard(shard_id)
+        // The shard is frozen before its tables are closed, so drain it here to
+        // let the in-flight writes finish.
+        match self.config.shard_drain_timeout {
+            Some(timeout) => self
+                .inner
+                .drain_shard(shard_id, timeout.0)
+                .await
+                .map(|summary| summary.tables_of_shard),
+            None => self.inner.freeze_shard(shard_id),
+        }
     }
 
     async fn create_table_on_shard(&self, req: &CreateTableOnShardRequest) -> Result<()> {
@@ -442,14 +1121,984 @@ impl Cluster for ClusterImpl {
         self.inner.fetch_nodes().await
     }
 
+    fn tables_of_shards(&self) -> Vec<TablesOfShard> {
+        self.inner.shard_tables_cache.all_tables_of_shards()
+    }
+
+    fn shard_status(&self) -> Vec<ShardStatus> {
+        self.inner.shard_tables_cache.all_shard_status()
+    }
+
+    fn subscribe(&self) -> ShardEventReceiver {
+        self.inner.shard_event_tx.subscribe()
+    }
+
     fn shard_lock_manager(&self) -> ShardLockManagerRef {
         self.shard_lock_manager.clone()
     }
+
+    fn is_heartbeat_alive(&self) -> bool {
+        let lease = self.config.meta_client.lease.0;
+        self.last_heartbeat
+            .lock()
+            .unwrap()
+            .map(|last| last.elapsed() < lease)
+            .unwrap_or(false)
+    }
+
+    fn is_started(&self) -> bool {
+        // The heartbeat loop is spawned once started, and taken once stopped.
+        self.heartbeat_handle.lock().unwrap().is_some()
+    }
+
+    fn lock_lost_shards(&self) -> Vec<ShardId> {
+        self.lock_lost_shards
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+    use ceresdbproto::meta_service::ShardInfo as ShardInfoPb;
+    use common_util::{config::ReadableDuration, error::GenericResult};
+    use meta_client::{
+        types::{
+            AllocSchemaIdRequest, AllocSchemaIdResponse, CreateTableRequest, CreateTableResponse,
+            DropTableRequest, DropTableResponse, GetNodesResponse, ShardVersion,
+        },
+        MetaClient,
+    };
+    use tokio::sync::Notify;
+
     use super::*;
+    use crate::{Error, TableFlusher, TableStatsProvider};
+
+    #[derive(Default)]
+    struct MockMetaClient {
+        version: AtomicU64,
+        num_route_calls: AtomicUsize,
+        num_get_nodes_calls: AtomicUsize,
+        /// The tables of shards are returned after notified.
+        tables_of_shards_ready: Notify,
+        /// Fail the heartbeats if set.
+        heartbeat_failed: AtomicBool,
+        /// Number of the following failed calls to get the tables of shards.
+        get_tables_failures: AtomicUsize,
+        /// Number of the following failed calls to route the tables.
+        route_failures: AtomicUsize,
+        /// Reject the routing of the tables if set.
+        route_rejected: AtomicBool,
+    }
+
+    #[async_trait]
+    impl MetaClient for MockMetaClient {
+        async fn alloc_schema_id(
+            &self,
+            _req: AllocSchemaIdRequest,
+        ) -> meta_client::Result<AllocSchemaIdResponse> {
+            unimplemented!()
+        }
+
+        async fn create_table(
+            &self,
+            _req: CreateTableRequest,
+        ) -> meta_client::Result<CreateTableResponse> {
+            unimplemented!()
+        }
+
+        async fn drop_table(
+            &self,
+            _req: DropTableRequest,
+        ) -> meta_client::Result<DropTableResponse> {
+            unimplemented!()
+        }
+
+        async fn get_tables_of_shards(
+            &self,
+            req: GetTablesOfShardsRequest,
+        ) -> meta_client::Result<GetTablesOfShardsResponse> {
+            if self
+                .get_tables_failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| v.checked_sub(1))
+                .is_ok()
+            {
+                return Err(meta_client::Error::FailGetTables {
+                    source: "mock error".into(),
+                });
+            }
+            self.tables_of_shards_ready.notified().await;
+            let tables_by_shard = req
+                .shard_ids
+                .into_iter()
+                .map(|shard_id| (shard_id, new_tables_of_shard(shard_id, &[])))
+                .collect();
+
+            Ok(GetTablesOfShardsResponse { tables_by_shard })
+        }
+
+        async fn route_tables(
+            &self,
+            _req: RouteTablesRequest,
+        ) -> meta_client::Result<RouteTablesResponse> {
+            self.num_route_calls.fetch_add(1, Ordering::Relaxed);
+            if self.route_rejected.load(Ordering::Relaxed) {
+                return meta_client::BadResponse {
+                    code: 404u32,
+                    msg: "shard not found",
+                }
+                .fail();
+            }
+            if self
+                .route_failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| v.checked_sub(1))
+                .is_ok()
+            {
+                return Err(meta_client::Error::FailRouteTables {
+                    source: "mock error".into(),
+                });
+            }
+            Ok(RouteTablesResponse {
+                cluster_topology_version: self.version.load(Ordering::Relaxed),
+                entries: HashMap::new(),
+            })
+        }
+
+        async fn get_nodes(&self, _req: GetNodesRequest) -> meta_client::Result<GetNodesResponse> {
+            self.num_get_nodes_calls.fetch_add(1, Ordering::Relaxed);
+            Ok(GetNodesResponse {
+                cluster_topology_version: self.version.load(Ordering::Relaxed),
+                node_shards: Vec::new(),
+            })
+        }
+
+        async fn send_heartbeat(
+            &self,
+            _shard_infos: Vec<ShardInfo>,
+            _shard_stats: Option<Vec<ShardStats>>,
+        ) -> meta_client::Result<()> {
+            if self.heartbeat_failed.load(Ordering::Relaxed) {
+                return Err(meta_client::Error::FailSendHeartbeat {
+                    cluster: "test".to_string(),
+                    source: "mock error".into(),
+                });
+            }
+
+            Ok(())
+        }
+    }
+
+    fn new_inner(enable_route_cache: bool) -> (Inner, Arc<MockMetaClient>) {
+        let config = RouteTablesCacheConfig {
+            enable: enable_route_cache,
+            ..Default::default()
+        };
+        new_inner_with_cache_config(config)
+    }
+
+    fn new_inner_with_cache_config(config: RouteTablesCacheConfig) -> (Inner, Arc<MockMetaClient>) {
+        let meta_client = Arc::new(MockMetaClient::default());
+        let inner = Inner::new(
+            ShardTablesCache::default(),
+            meta_client.clone(),
+            &config,
+            MetaRetryConfig::default(),
+            Duration::ZERO,
+            None,
+            None,
+        )
+        .unwrap();
+
+        (inner, meta_client)
+    }
+
+    fn new_route_request(table_names: &[&str]) -> RouteTablesRequest {
+        RouteTablesRequest {
+            schema_name: "public".to_string(),
+            table_names: table_names.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_tables_cache() {
+        let (inner, meta_client) = new_inner(true);
+        let num_route_calls = || meta_client.num_route_calls.load(Ordering::Relaxed);
+
+        // Miss.
+        inner
+            .route_tables(&new_route_request(&["a", "b"]))
+            .await
+            .unwrap();
+        assert_eq!(1, num_route_calls());
+
+        // Hit, the order of the tables doesn't matter.
+        inner
+            .route_tables(&new_route_request(&["b", "a", "a"]))
+            .await
+            .unwrap();
+        assert_eq!(1, num_route_calls());
+
+        // Miss because the table set is different.
+        inner
+            .route_tables(&new_route_request(&["a"]))
+            .await
+            .unwrap();
+        assert_eq!(2, num_route_calls());
+        inner
+            .route_tables(&new_route_request(&["a"]))
+            .await
+            .unwrap();
+        assert_eq!(2, num_route_calls());
+    }
+
+    #[tokio::test]
+    async fn test_route_tables_cache_invalidation() {
+        let (inner, meta_client) = new_inner(true);
+        let num_route_calls = || meta_client.num_route_calls.load(Ordering::Relaxed);
+
+        let req = new_route_request(&["a"]);
+        inner.route_tables(&req).await.unwrap();
+        inner.route_tables(&req).await.unwrap();
+        assert_eq!(1, num_route_calls());
+
+        // The cache is invalidated once the topology version changes.
+        meta_client.version.store(1, Ordering::Relaxed);
+        let nodes = inner.fetch_nodes().await.unwrap();
+        assert_eq!(1, nodes.cluster_topology_version);
+        let resp = inner.route_tables(&req).await.unwrap();
+        assert_eq!(2, num_route_calls());
+        assert_eq!(1, resp.cluster_topology_version);
+        inner.route_tables(&req).await.unwrap();
+        assert_eq!(2, num_route_calls());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_nodes_cache_ttl() {
+        let meta_client = Arc::new(MockMetaClient::default());
+        let num_get_nodes_calls = || meta_client.num_get_nodes_calls.load(Ordering::Relaxed);
+        let inner = Inner::new(
+            ShardTablesCache::default(),
+            meta_client.clone(),
+            &RouteTablesCacheConfig::default(),
+            MetaRetryConfig::default(),
+            Duration::from_millis(100),
+            None,
+            None,
+        )
+        .unwrap();
+
+        inner.fetch_nodes().await.unwrap();
+        assert_eq!(1, num_get_nodes_calls());
+
+        // The nodes are served from the cache within the ttl, even though the
+        // version in the meta is changed.
+        meta_client.version.store(1, Ordering::Relaxed);
+        let nodes = inner.fetch_nodes().await.unwrap();
+        assert_eq!(1, num_get_nodes_calls());
+        assert_eq!(0, nodes.cluster_topology_version);
+
+        // The nodes are fetched again after the ttl.
+        time::sleep(Duration::from_millis(200)).await;
+        let nodes = inner.fetch_nodes().await.unwrap();
+        assert_eq!(2, num_get_nodes_calls());
+        assert_eq!(1, nodes.cluster_topology_version);
+
+        // The cache is refreshed even if the fetched version is not newer.
+        time::sleep(Duration::from_millis(200)).await;
+        inner.fetch_nodes().await.unwrap();
+        assert_eq!(3, num_get_nodes_calls());
+        inner.fetch_nodes().await.unwrap();
+        assert_eq!(3, num_get_nodes_calls());
+    }
+
+    #[tokio::test]
+    async fn test_route_tables_cache_expired() {
+        let config = RouteTablesCacheConfig {
+            enable: true,
+            ttl: ReadableDuration::millis(100),
+            ..Default::default()
+        };
+        let (inner, meta_client) = new_inner_with_cache_config(config);
+        let num_route_calls = || meta_client.num_route_calls.load(Ordering::Relaxed);
+
+        let req = new_route_request(&["a"]);
+        inner.route_tables(&req).await.unwrap();
+        inner.route_tables(&req).await.unwrap();
+        assert_eq!(1, num_route_calls());
+
+        // The cached result is dropped after the ttl.
+        time::sleep(Duration::from_millis(200)).await;
+        inner.route_tables(&req).await.unwrap();
+        assert_eq!(2, num_route_calls());
+    }
+
+    #[tokio::test]
+    async fn test_route_tables_cache_invalidated_by_shard_change() {
+        let (inner, meta_client) = new_inner(true);
+        let num_route_calls = || meta_client.num_route_calls.load(Ordering::Relaxed);
+        inner.shard_tables_cache.insert(new_tables_of_shard(1, &[]));
+
+        let req = new_route_request(&["a"]);
+        inner.route_tables(&req).await.unwrap();
+        inner.route_tables(&req).await.unwrap();
+        assert_eq!(1, num_route_calls());
+
+        // The cache is invalidated once a table is created on the shard.
+        let create_req = CreateTableOnShardRequest {
+            update_shard_info: Some(UpdateShardInfo {
+                curr_shard_info: Some(ShardInfoPb {
+                    id: 1,
+                    version: 1,
+                    ..Default::default()
+                }),
+                prev_version: 0,
+            }),
+            table_info: Some(TableInfoPb {
+                id: 10,
+                name: "a".to_string(),
+                schema_name: "public".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        inner.create_table_on_shard(&create_req).unwrap();
+        inner.route_tables(&req).await.unwrap();
+        assert_eq!(2, num_route_calls());
+        inner.route_tables(&req).await.unwrap();
+        assert_eq!(2, num_route_calls());
+
+        // And also invalidated once the shard is closed.
+        inner.close_shard(1).unwrap();
+        inner.route_tables(&req).await.unwrap();
+        assert_eq!(3, num_route_calls());
+    }
+
+    #[tokio::test]
+    async fn test_route_tables_without_cache() {
+        let (inner, meta_client) = new_inner(false);
+
+        let req = new_route_request(&["a"]);
+        for i in 1..=3 {
+            inner.route_tables(&req).await.unwrap();
+            assert_eq!(i, meta_client.num_route_calls.load(Ordering::Relaxed));
+        }
+    }
+
+    #[test]
+    fn test_heartbeat_backoff() {
+        let mut backoff =
+            HeartbeatBackoff::new(Duration::from_millis(100), Duration::from_millis(1000));
+        // Simulate a series of failures.
+        let backoffs: Vec<_> = (0..6).map(|_| backoff.next_backoff().as_millis()).collect();
+        assert_eq!(vec![100, 200, 400, 800, 1000, 1000], backoffs);
+
+        // Reset on success.
+        backoff.reset();
+        assert_eq!(Duration::from_millis(100), backoff.next_backoff());
+        assert_eq!(Duration::from_millis(200), backoff.next_backoff());
+
+        // The base is capped at the max.
+        let mut backoff = HeartbeatBackoff::new(Duration::from_secs(10), Duration::from_secs(1));
+        assert_eq!(Duration::from_secs(1), backoff.next_backoff());
+    }
+
+    #[test]
+    fn test_heartbeat_backoff_with_jitter() {
+        let max = Duration::from_millis(1000);
+        let mut backoff = HeartbeatBackoff::new(Duration::from_millis(100), max);
+        // The jittered waits of the consecutive failures grow until the cap.
+        let mut prev_upper = Duration::ZERO;
+        for _ in 0..10 {
+            let upper = backoff.next_backoff();
+            assert!(upper >= prev_upper && upper <= max, "{upper:?}");
+            let wait = with_jitter(upper, thread_rng().gen());
+            assert!(wait > upper / 2 && wait <= upper, "{wait:?}");
+            prev_upper = upper;
+        }
+        assert_eq!(max, prev_upper);
+    }
+
+    #[test]
+    fn test_heartbeat_interval_with_jitter() {
+        let lease = Duration::from_secs(3);
+        let interval = lease * 2 / 3;
+        let jitter = 0.1;
+        let (lower, upper) = (
+            interval.mul_f64(1.0 - jitter),
+            interval.mul_f64(1.0 + jitter),
+        );
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let wait = heartbeat_interval_with_jitter(interval, lease, jitter, rng.gen());
+            assert!(wait >= lower && wait <= upper, "{wait:?}");
+        }
+        assert_eq!(
+            lower,
+            heartbeat_interval_with_jitter(interval, lease, jitter, 0.0)
+        );
+        assert_eq!(
+            interval,
+            heartbeat_interval_with_jitter(interval, lease, 0.0, 0.9)
+        );
+
+        // The interval never exceeds the lease.
+        for _ in 0..1000 {
+            let wait = heartbeat_interval_with_jitter(interval, lease, 0.9, rng.gen());
+            assert!(wait <= lease, "{wait:?}");
+        }
+    }
+
+    #[test]
+    fn test_backoff_jitter() {
+        let wait = Duration::from_millis(1000);
+        assert_eq!(wait, with_jitter(wait, 0.0));
+        assert_eq!(Duration::from_millis(750), with_jitter(wait, 0.5));
+        for _ in 0..100 {
+            let jittered = with_jitter(wait, thread_rng().gen());
+            assert!(jittered > wait / 2 && jittered <= wait, "{jittered:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_metrics() {
+        let (inner, meta_client) = new_inner(false);
+        let num_heartbeats = |result: &str| HEARTBEAT_COUNTER.with_label_values(&[result]).get();
+
+        let num_ok = num_heartbeats("ok");
+        inner.send_heartbeat(false).await.unwrap();
+        assert!(num_heartbeats("ok") > num_ok);
+        assert!(LAST_HEARTBEAT_TIMESTAMP_GAUGE.get() > 0);
+
+        meta_client.heartbeat_failed.store(true, Ordering::Relaxed);
+        let num_err = num_heartbeats("err");
+        assert!(inner.send_heartbeat(false).await.is_err());
+        assert_eq!(num_err + 1, num_heartbeats("err"));
+    }
+
+    struct MockTableStatsProvider;
+
+    impl TableStatsProvider for MockTableStatsProvider {
+        fn memtable_memory_usage(&self, table: &TableInfo) -> Option<u64> {
+            // Table `t0` isn't opened.
+            (table.name != "t0").then_some(table.id * 100)
+        }
+    }
+
+    fn new_tables_of_shard(shard_id: ShardId, table_ids: &[u64]) -> TablesOfShard {
+        TablesOfShard {
+            shard_info: ShardInfo {
+                id: shard_id,
+                ..Default::default()
+            },
+            tables: table_ids
+                .iter()
+                .map(|id| TableInfo {
+                    id: *id,
+                    name: format!("t{id}"),
+                    schema_id: 0,
+                    schema_name: "public".to_string(),
+                    partition_info: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_heartbeat_shard_stats() {
+        let shard_tables_cache = ShardTablesCache::default();
+        shard_tables_cache.insert(new_tables_of_shard(1, &[0, 1, 2]));
+        shard_tables_cache.insert(new_tables_of_shard(2, &[]));
+        let shard_infos = shard_tables_cache.all_shard_infos();
+        let expect_table_counts: HashMap<_, _> = [(1, 3), (2, 0)].into_iter().collect();
+
+        let inner = Inner::new(
+            shard_tables_cache.clone(),
+            Arc::new(MockMetaClient::default()),
+            &RouteTablesCacheConfig::default(),
+            MetaRetryConfig::default(),
+            Duration::ZERO,
+            Some(Arc::new(MockTableStatsProvider)),
+            None,
+        )
+        .unwrap();
+        let shard_stats = inner.shard_stats(&shard_infos);
+        assert_eq!(2, shard_stats.len());
+        for stats in shard_stats {
+            assert_eq!(expect_table_counts[&stats.id], stats.table_count);
+            let expect_memory_usage = if stats.id == 1 { 300 } else { 0 };
+            assert_eq!(expect_memory_usage, stats.memtable_memory_usage);
+        }
+
+        // Only the table counts are collected without the provider.
+        let inner = Inner::new(
+            shard_tables_cache,
+            Arc::new(MockMetaClient::default()),
+            &RouteTablesCacheConfig::default(),
+            MetaRetryConfig::default(),
+            Duration::ZERO,
+            None,
+            None,
+        )
+        .unwrap();
+        for stats in inner.shard_stats(&shard_infos) {
+            assert_eq!(expect_table_counts[&stats.id], stats.table_count);
+            assert_eq!(0, stats.memtable_memory_usage);
+        }
+    }
+
+    struct MockTableFlusher;
+
+    #[async_trait]
+    impl TableFlusher for MockTableFlusher {
+        async fn flush_table(&self, table: &TableInfo) -> GenericResult<bool> {
+            match table.name.as_str() {
+                // Table `t0` isn't opened.
+                "t0" => Ok(false),
+                "t1" => Err("mock flush error".into()),
+                _ => Ok(true),
+            }
+        }
+    }
+
+    fn new_drain_inner(shard_tables_cache: ShardTablesCache) -> Arc<Inner> {
+        let inner = Inner::new(
+            shard_tables_cache,
+            Arc::new(MockMetaClient::default()),
+            &RouteTablesCacheConfig::default(),
+            MetaRetryConfig::default(),
+            Duration::ZERO,
+            None,
+            Some(Arc::new(MockTableFlusher)),
+        )
+        .unwrap();
+
+        Arc::new(inner)
+    }
+
+    #[test]
+    fn test_shard_status() {
+        let shard_tables_cache = ShardTablesCache::default();
+        assert!(shard_tables_cache.all_shard_status().is_empty());
+
+        let mut tables_of_shard = new_tables_of_shard(2, &[0, 1, 2]);
+        tables_of_shard.shard_info.version = 5;
+        shard_tables_cache.insert(tables_of_shard);
+        shard_tables_cache.insert(new_tables_of_shard(1, &[3]));
+        shard_tables_cache.freeze(1).unwrap();
+
+        let expect = vec![
+            ShardStatus {
+                shard_id: 1,
+                version: 0,
+                num_tables: 1,
+                frozen: true,
+                draining: false,
+            },
+            ShardStatus {
+                shard_id: 2,
+                version: 5,
+                num_tables: 3,
+                frozen: false,
+                draining: false,
+            },
+        ];
+        assert_eq!(expect, shard_tables_cache.all_shard_status());
+    }
+
+    #[tokio::test]
+    async fn test_drain_shard() {
+        let shard_tables_cache = ShardTablesCache::default();
+        shard_tables_cache.insert(new_tables_of_shard(1, &[0, 1, 2, 3]));
+        let inner = new_drain_inner(shard_tables_cache.clone());
+
+        // A fake in-flight operation the drain waits on.
+        let op_guard = shard_tables_cache.begin_op(1).unwrap();
+        let drain_handle = {
+            let inner = inner.clone();
+            tokio::spawn(async move { inner.drain_shard(1, Duration::from_secs(10)).await })
+        };
+
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!drain_handle.is_finished());
+        // The draining shard rejects the new operations.
+        assert!(matches!(
+            shard_tables_cache.begin_op(1),
+            Err(Error::ShardDraining { shard_id: 1, .. })
+        ));
+        assert!(shard_tables_cache.get(1).is_some());
+
+        drop(op_guard);
+        let summary = drain_handle.await.unwrap().unwrap();
+        assert_eq!(0, summary.num_pending_ops);
+        assert_eq!(
+            vec!["t2".to_string(), "t3".to_string()],
+            summary.flushed_tables
+        );
+        assert_eq!(vec!["t1".to_string()], summary.failed_tables);
+        assert_eq!(4, summary.tables_of_shard.tables.len());
+        // The drained shard is kept read-only until it is closed.
+        let status = shard_tables_cache.all_shard_status();
+        assert!(status[0].frozen && status[0].draining);
+        assert!(shard_tables_cache.get(1).is_some());
+        inner.close_shard(1).unwrap();
+        assert!(shard_tables_cache.get(1).is_none());
+
+        // Drain a non-existent shard.
+        assert!(inner.drain_shard(1, Duration::from_secs(1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drain_shard_timeout() {
+        let shard_tables_cache = ShardTablesCache::default();
+        shard_tables_cache.insert(new_tables_of_shard(1, &[2]));
+        let inner = new_drain_inner(shard_tables_cache.clone());
+
+        let _op_guard = shard_tables_cache.begin_op(1).unwrap();
+        let summary = inner
+            .drain_shard(1, Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(1, summary.num_pending_ops);
+        assert_eq!(vec!["t2".to_string()], summary.flushed_tables);
+        assert!(shard_tables_cache.get(1).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_try_open_shard_busy() {
+        let (inner, meta_client) = new_inner(false);
+        let inner = Arc::new(inner);
+        let shard_info = ShardInfo {
+            id: 1,
+            ..Default::default()
+        };
+
+        let open_handle = {
+            let inner = inner.clone();
+            let shard_info = shard_info.clone();
+            tokio::spawn(async move { inner.try_open_shard(&shard_info).await })
+        };
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!open_handle.is_finished());
+
+        // The second concurrent open of the same shard is rejected.
+        assert!(matches!(
+            inner.try_open_shard(&shard_info).await,
+            Err(Error::ShardBusy { shard_id: 1, .. })
+        ));
+
+        meta_client.tables_of_shards_ready.notify_one();
+        let tables_of_shard = open_handle.await.unwrap().unwrap();
+        assert_eq!(1, tables_of_shard.shard_info.id);
+
+        // The shard can be opened again once the previous open is done.
+        inner.try_open_shard(&shard_info).await.unwrap();
+        assert!(inner.shards_in_progress.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_open_shard_retry() {
+        let meta_client = Arc::new(MockMetaClient::default());
+        let retry_config = MetaRetryConfig {
+            max_attempts: 2,
+            interval: ReadableDuration::millis(10),
+        };
+        let inner = Inner::new(
+            ShardTablesCache::default(),
+            meta_client.clone(),
+            &RouteTablesCacheConfig::default(),
+            retry_config,
+            Duration::ZERO,
+            None,
+            None,
+        )
+        .unwrap();
+        let shard_info = ShardInfo {
+            id: 1,
+            ..Default::default()
+        };
+
+        // The first call fails and the retry succeeds.
+        meta_client.get_tables_failures.store(1, Ordering::Relaxed);
+        meta_client.tables_of_shards_ready.notify_one();
+        inner.open_shard(&shard_info).await.unwrap();
+        assert!(inner.shard_tables_cache.get(1).is_some());
+
+        // Give up once the attempts are exhausted.
+        let shard_info = ShardInfo {
+            id: 2,
+            ..Default::default()
+        };
+        meta_client.get_tables_failures.store(2, Ordering::Relaxed);
+        assert!(matches!(
+            inner.open_shard(&shard_info).await,
+            Err(Error::OpenShardWithCause { shard_id: 2, .. })
+        ));
+        assert!(inner.shard_tables_cache.get(2).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_route_tables_retry() {
+        let meta_client = Arc::new(MockMetaClient::default());
+        let num_route_calls = || meta_client.num_route_calls.load(Ordering::Relaxed);
+        let retry_config = MetaRetryConfig {
+            max_attempts: 3,
+            interval: ReadableDuration::millis(10),
+        };
+        let inner = Inner::new(
+            ShardTablesCache::default(),
+            meta_client.clone(),
+            &RouteTablesCacheConfig::default(),
+            retry_config,
+            Duration::ZERO,
+            None,
+            None,
+        )
+        .unwrap();
+        let req = new_route_request(&["t1"]);
+
+        // The transient failures are retried.
+        meta_client.route_failures.store(2, Ordering::Relaxed);
+        inner.route_tables(&req).await.unwrap();
+        assert_eq!(3, num_route_calls());
+
+        // The logical failure is returned without retry.
+        meta_client.route_rejected.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            inner.route_tables(&req).await,
+            Err(Error::MetaClientFailure {
+                source: meta_client::Error::BadResponse { .. }
+            })
+        ));
+        assert_eq!(4, num_route_calls());
+    }
+
+    #[tokio::test]
+    async fn test_shard_events() {
+        let (inner, meta_client) = new_inner(false);
+        let mut shard_events = inner.shard_event_tx.subscribe();
+        let shard_info = ShardInfo {
+            id: 1,
+            ..Default::default()
+        };
+
+        meta_client.tables_of_shards_ready.notify_one();
+        inner.open_shard(&shard_info).await.unwrap();
+        let expect = ShardEvent {
+            shard_id: 1,
+            kind: ShardChangeKind::Open,
+        };
+        assert_eq!(expect, shard_events.try_recv().unwrap());
+
+        // No event is emitted if the shard is not changed.
+        inner.open_shard(&shard_info).await.unwrap();
+        assert!(shard_events.try_recv().is_err());
+
+        inner.close_shard(1).unwrap();
+        let expect = ShardEvent {
+            shard_id: 1,
+            kind: ShardChangeKind::Close,
+        };
+        assert_eq!(expect, shard_events.try_recv().unwrap());
+        assert!(shard_events.try_recv().is_err());
+    }
+
+    fn new_update_shard_info(
+        shard_id: ShardId,
+        prev_version: ShardVersion,
+        version: ShardVersion,
+    ) -> Option<UpdateShardInfo> {
+        Some(UpdateShardInfo {
+            curr_shard_info: Some(ShardInfoPb {
+                id: shard_id,
+                version,
+                ..Default::default()
+            }),
+            prev_version,
+        })
+    }
+
+    #[test]
+    fn test_reject_stale_shard_version() {
+        let (inner, _) = new_inner(false);
+        let mut tables_of_shard = new_tables_of_shard(1, &[0]);
+        tables_of_shard.shard_info.version = 5;
+        inner.shard_tables_cache.insert(tables_of_shard);
+
+        let table_info = |id: u64| {
+            Some(TableInfoPb {
+                id,
+                name: format!("t{id}"),
+                schema_name: "public".to_string(),
+                ..Default::default()
+            })
+        };
+        // The updates out of order with a version not newer than the cached one
+        // are rejected, even if the previous version matches.
+        for version in [3, 5] {
+            let create_req = CreateTableOnShardRequest {
+                update_shard_info: new_update_shard_info(1, 5, version),
+                table_info: table_info(1),
+                ..Default::default()
+            };
+            assert!(matches!(
+                inner.create_table_on_shard(&create_req),
+                Err(Error::StaleShardVersion {
+                    shard_id: 1,
+                    cached_version: 5,
+                    new_version,
+                    ..
+                }) if new_version == version
+            ));
+
+            let drop_req = DropTableOnShardRequest {
+                update_shard_info: new_update_shard_info(1, 5, version),
+                table_info: table_info(0),
+            };
+            assert!(matches!(
+                inner.drop_table_on_shard(&drop_req),
+                Err(Error::StaleShardVersion { .. })
+            ));
+        }
+        let tables_of_shard = inner.shard_tables_cache.get(1).unwrap();
+        assert_eq!(5, tables_of_shard.shard_info.version);
+        assert_eq!(1, tables_of_shard.tables.len());
+
+        // The updates with a newer version are applied.
+        let create_req = CreateTableOnShardRequest {
+            update_shard_info: new_update_shard_info(1, 5, 6),
+            table_info: table_info(1),
+            ..Default::default()
+        };
+        inner.create_table_on_shard(&create_req).unwrap();
+        let drop_req = DropTableOnShardRequest {
+            update_shard_info: new_update_shard_info(1, 6, 7),
+            table_info: table_info(0),
+        };
+        inner.drop_table_on_shard(&drop_req).unwrap();
+        let tables_of_shard = inner.shard_tables_cache.get(1).unwrap();
+        assert_eq!(7, tables_of_shard.shard_info.version);
+        assert_eq!(
+            vec![1],
+            tables_of_shard
+                .tables
+                .iter()
+                .map(|t| t.id)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry() {
+        let num_connects = &AtomicUsize::new(0);
+        // The first connection fails, and it is re-established by the retry.
+        let connect = move || async move {
+            if num_connects.fetch_add(1, Ordering::Relaxed) == 0 {
+                Err("mock connection failure")
+            } else {
+                Ok(())
+            }
+        };
+        connect_with_retry(connect, 3, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert_eq!(2, num_connects.load(Ordering::Relaxed));
+
+        // Give up once the attempts are exhausted.
+        let num_connects = &AtomicUsize::new(0);
+        let connect = move || async move {
+            num_connects.fetch_add(1, Ordering::Relaxed);
+            Err::<(), _>("mock connection failure")
+        };
+        assert!(connect_with_retry(connect, 2, Duration::from_millis(10))
+            .await
+            .is_err());
+        assert_eq!(2, num_connects.load(Ordering::Relaxed));
+    }
+
+    /// Client of etcd whose connection can be dropped after connected.
+    #[derive(Clone)]
+    struct MockEtcdClient {
+        id: usize,
+        alive: Arc<AtomicBool>,
+    }
+
+    impl MockEtcdClient {
+        fn new(id: usize) -> Self {
+            Self {
+                id,
+                alive: Arc::new(AtomicBool::new(true)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supervise_etcd_connection() {
+        let connected = IntGauge::new("test_etcd_connected", "test").unwrap();
+        let client = MockEtcdClient::new(0);
+        // The gauge observed by every reconnection.
+        let gauge_on_connects = Arc::new(Mutex::new(Vec::new()));
+        let reconnected_ids = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::channel(1);
+
+        let check = |client: MockEtcdClient| async move {
+            if client.alive.load(Ordering::Relaxed) {
+                Ok(())
+            } else {
+                Err("mock connection dropped")
+            }
+        };
+        let connect = {
+            let connected = connected.clone();
+            let gauge_on_connects = gauge_on_connects.clone();
+            move || {
+                let mut gauge_on_connects = gauge_on_connects.lock().unwrap();
+                gauge_on_connects.push(connected.get());
+                // The first reconnection fails.
+                let id = gauge_on_connects.len();
+                async move {
+                    if id == 1 {
+                        Err("mock connection failure")
+                    } else {
+                        Ok(MockEtcdClient::new(id))
+                    }
+                }
+            }
+        };
+        let on_reconnected = {
+            let reconnected_ids = reconnected_ids.clone();
+            move |client: MockEtcdClient| reconnected_ids.lock().unwrap().push(client.id)
+        };
+        let handle = tokio::spawn(supervise_etcd_connection(
+            client.clone(),
+            check,
+            connect,
+            on_reconnected,
+            Duration::from_millis(10),
+            Duration::from_millis(5),
+            connected.clone(),
+            rx,
+        ));
+
+        // Nothing is reconnected while the connection is alive.
+        time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(1, connected.get());
+        assert!(gauge_on_connects.lock().unwrap().is_empty());
+
+        // The dropped connection is re-established after a failed attempt.
+        client.alive.store(false, Ordering::Relaxed);
+        time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(vec![0, 0], *gauge_on_connects.lock().unwrap());
+        assert_eq!(vec![2], *reconnected_ids.lock().unwrap());
+        assert_eq!(1, connected.get());
+
+        tx.send(()).await.unwrap();
+        handle.await.unwrap();
+    }
 
     #[test]
     fn test_format_shard_lock_key_prefix() {
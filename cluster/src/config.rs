@@ -39,6 +39,14 @@ pub struct EtcdClientConfig {
 
     /// Timeout to connect to etcd cluster
     pub connect_timeout: ReadableDuration,
+    /// Max attempts to connect to the etcd cluster on startup
+    pub connect_max_attempts: usize,
+    /// Wait before the first retry of connecting, and it is doubled for the
+    /// following ones
+    pub connect_retry_interval: ReadableDuration,
+    /// Interval of checking the connection to the etcd cluster, and the
+    /// connection is re-established once the check fails
+    pub connection_check_interval: ReadableDuration,
 
     /// The lease of the shard lock in seconds.
     ///
@@ -88,16 +96,98 @@ impl Default for EtcdClientConfig {
             server_addrs: vec!["127.0.0.1:2379".to_string()],
             root_path: DEFAULT_ETCD_ROOT_PATH.to_string(),
             connect_timeout: ReadableDuration::secs(5),
+            connect_max_attempts: 3,
+            connect_retry_interval: ReadableDuration::secs(1),
+            connection_check_interval: ReadableDuration::secs(5),
             shard_lock_lease_ttl_sec: 30,
             shard_lock_lease_check_interval: ReadableDuration::millis(200),
         }
     }
 }
 
-#[derive(Default, Clone, Deserialize, Debug, Serialize)]
+#[derive(Clone, Deserialize, Debug, Serialize)]
+#[serde(default)]
+pub struct RouteTablesCacheConfig {
+    /// Enable the cache of the route results from CeresMeta, default false.
+    pub enable: bool,
+    /// Time to live (TTL) of the cached route results.
+    pub ttl: ReadableDuration,
+    /// How many route results can be stored in the cache.
+    pub capacity: u64,
+}
+
+impl Default for RouteTablesCacheConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            ttl: ReadableDuration::secs(5),
+            capacity: 10_000,
+        }
+    }
+}
+
+/// Policy of retrying the calls to CeresMeta, e.g. routing the tables,
+/// fetching the nodes and the tables of the opening shard.
+#[derive(Clone, Copy, Deserialize, Debug, Serialize)]
+#[serde(default)]
+pub struct MetaRetryConfig {
+    /// Max attempts of a call to CeresMeta, and only the transient failures
+    /// (e.g. the failed rpc) are retried.
+    pub max_attempts: usize,
+    /// Wait before the first retry, and it is doubled for the following ones.
+    pub interval: ReadableDuration,
+}
+
+impl Default for MetaRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            interval: ReadableDuration::millis(500),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Debug, Serialize)]
 #[serde(default)]
 pub struct ClusterConfig {
     pub cmd_channel_buffer_size: usize,
     pub meta_client: MetaClientConfig,
     pub etcd_client: EtcdClientConfig,
+    pub route_tables_cache: RouteTablesCacheConfig,
+    /// Attach the load stats of the shards to the heartbeat, default false for
+    /// the compatibility with the older CeresMeta.
+    ///
+    /// NOTE: The `NodeInfo` of ceresdbproto has no field for the stats yet, so
+    /// they are only logged by the meta client now.
+    pub enable_shard_stats_in_heartbeat: bool,
+    /// Drain the shard before closing it if set, and it is the max time to
+    /// wait for the in-flight operations on the shard.
+    pub shard_drain_timeout: Option<ReadableDuration>,
+    /// The former `open_shard_retry` is still accepted as it only covered
+    /// the opening of the shards.
+    #[serde(alias = "open_shard_retry")]
+    pub meta_retry: MetaRetryConfig,
+    /// Fraction of the heartbeat interval randomly added to or subtracted from
+    /// every wait between the heartbeats, so that the nodes restarted together
+    /// won't heartbeat in lockstep. It must be in `[0, 1)`, default 0.1.
+    pub heartbeat_jitter: f64,
+    /// The cluster nodes fetched from CeresMeta are served from the cache
+    /// without asking CeresMeta again within this duration.
+    pub nodes_cache_ttl: ReadableDuration,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            cmd_channel_buffer_size: 0,
+            meta_client: MetaClientConfig::default(),
+            etcd_client: EtcdClientConfig::default(),
+            route_tables_cache: RouteTablesCacheConfig::default(),
+            enable_shard_stats_in_heartbeat: false,
+            shard_drain_timeout: None,
+            meta_retry: MetaRetryConfig::default(),
+            heartbeat_jitter: 0.1,
+            nodes_cache_ttl: ReadableDuration::secs(1),
+        }
+    }
 }
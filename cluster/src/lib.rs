@@ -10,7 +10,7 @@
 
 #![feature(trait_alias)]
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use ceresdbproto::meta_event::{
@@ -18,16 +18,21 @@ use ceresdbproto::meta_event::{
     OpenTableOnShardRequest,
 };
 use common_types::schema::SchemaName;
-use common_util::{define_result, error::GenericError};
+use common_util::{
+    define_result,
+    error::{GenericError, GenericResult},
+};
 use meta_client::types::{
     ClusterNodesRef, RouteTablesRequest, RouteTablesResponse, ShardId, ShardInfo, ShardVersion,
-    TablesOfShard,
+    TableInfo, TablesOfShard,
 };
 use shard_lock_manager::ShardLockManagerRef;
 use snafu::{Backtrace, Snafu};
+use tokio::sync::broadcast;
 
 pub mod cluster_impl;
 pub mod config;
+mod metrics;
 pub mod shard_lock_manager;
 pub mod shard_tables_cache;
 #[allow(dead_code)]
@@ -82,6 +87,26 @@ pub enum Error {
     #[snafu(display("Shard not found, msg:{msg}.\nBacktrace:\n{backtrace}"))]
     ShardNotFound { msg: String, backtrace: Backtrace },
 
+    #[snafu(display(
+        "Shard is busy with another open/close, shard_id:{shard_id}.\nBacktrace:\n{backtrace}"
+    ))]
+    ShardBusy {
+        shard_id: ShardId,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Shard is draining, shard_id:{shard_id}.\nBacktrace:\n{backtrace}"))]
+    ShardDraining {
+        shard_id: ShardId,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Shard lock is lost, shard_id:{shard_id}.\nBacktrace:\n{backtrace}"))]
+    ShardLockLost {
+        shard_id: ShardId,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Table not found, msg:{msg}.\nBacktrace:\n{backtrace}"))]
     TableNotFound { msg: String, backtrace: Backtrace },
 
@@ -105,6 +130,16 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display(
+        "Shard version is not newer than the cached one, shard_id:{shard_id}, cached_version:{cached_version}, new_version:{new_version}.\nBacktrace:\n{backtrace}",
+    ))]
+    StaleShardVersion {
+        shard_id: ShardId,
+        cached_version: ShardVersion,
+        new_version: ShardVersion,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Update on a frozen shard, shard_id:{shard_id}.\nBacktrace:\n{backtrace}",))]
     UpdateFrozenShard {
         shard_id: ShardId,
@@ -121,6 +156,67 @@ define_result!(Error);
 
 pub type ClusterRef = Arc<dyn Cluster + Send + Sync>;
 
+/// Provide the stats of the tables on the shards from the engine.
+pub trait TableStatsProvider: Send + Sync {
+    /// Returns the approximate memtable memory usage in bytes of the table,
+    /// and None if the table is not opened.
+    fn memtable_memory_usage(&self, table: &TableInfo) -> Option<u64>;
+}
+
+pub type TableStatsProviderRef = Arc<dyn TableStatsProvider>;
+
+/// Flush the tables on the shards in the engine.
+#[async_trait]
+pub trait TableFlusher: Send + Sync {
+    /// Flush the table, returns false if the table is not opened.
+    async fn flush_table(&self, table: &TableInfo) -> GenericResult<bool>;
+}
+
+pub type TableFlusherRef = Arc<dyn TableFlusher>;
+
+/// Summary of draining a shard.
+#[derive(Clone, Debug)]
+pub struct DrainShardSummary {
+    pub tables_of_shard: TablesOfShard,
+    /// Number of the in-flight operations not done before the timeout.
+    pub num_pending_ops: usize,
+    pub flushed_tables: Vec<String>,
+    pub failed_tables: Vec<String>,
+}
+
+/// Status of a shard on this node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShardStatus {
+    pub shard_id: ShardId,
+    pub version: ShardVersion,
+    pub num_tables: usize,
+    /// Create/drop table on the shard is rejected if it is frozen.
+    pub frozen: bool,
+    /// New operations on the shard are rejected if it is draining.
+    pub draining: bool,
+}
+
+/// Kind of the change of a shard on this node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShardChangeKind {
+    Open,
+    Close,
+    Freeze,
+    CreateTable,
+    DropTable,
+    OpenTable,
+    CloseTable,
+}
+
+/// Event emitted once a shard on this node is changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShardEvent {
+    pub shard_id: ShardId,
+    pub kind: ShardChangeKind,
+}
+
+pub type ShardEventReceiver = broadcast::Receiver<ShardEvent>;
+
 #[derive(Clone, Debug)]
 pub struct ClusterNodesResp {
     pub cluster_topology_version: u64,
@@ -133,11 +229,24 @@ pub trait Cluster {
     async fn start(&self) -> Result<()>;
     async fn stop(&self) -> Result<()>;
     async fn open_shard(&self, shard_info: &ShardInfo) -> Result<TablesOfShard>;
+    /// Same as [Cluster::open_shard], but return [Error::ShardBusy]
+    /// immediately if another open/close of the same shard is in progress.
+    async fn try_open_shard(&self, shard_info: &ShardInfo) -> Result<TablesOfShard>;
     /// Close the shard.
     ///
     /// Return error if the shard is not found.
     async fn close_shard(&self, req: ShardId) -> Result<TablesOfShard>;
-    /// Freeze the shard to reject create/drop table on the shard.
+    /// Drain the shard, see [DrainShardSummary] for the result.
+    ///
+    /// The shard is frozen and the new writes on it are rejected, and the
+    /// tables of the shard are flushed after the in-flight writes are done or
+    /// the `timeout` is reached. The shard is kept read-only until it is
+    /// removed by [Cluster::close_shard].
+    ///
+    /// Return error if the shard is not found.
+    async fn drain_shard(&self, shard_id: ShardId, timeout: Duration) -> Result<DrainShardSummary>;
+    /// Freeze the shard to reject create/drop table on the shard, and the
+    /// shard is also drained if the drain timeout is configured.
     ///
     /// Return error if the shard is not found.
     async fn freeze_shard(&self, req: ShardId) -> Result<TablesOfShard>;
@@ -147,5 +256,22 @@ pub trait Cluster {
     async fn close_table_on_shard(&self, req: &CloseTableOnShardRequest) -> Result<()>;
     async fn route_tables(&self, req: &RouteTablesRequest) -> Result<RouteTablesResponse>;
     async fn fetch_nodes(&self) -> Result<ClusterNodesResp>;
+    /// Get the tables of all the shards on this node from the cache.
+    fn tables_of_shards(&self) -> Vec<TablesOfShard>;
+    /// Get the status of all the shards on this node, ordered by the shard id.
+    fn shard_status(&self) -> Vec<ShardStatus>;
+    /// Subscribe the changes of the shards on this node, and only the events
+    /// emitted after the subscription are received.
+    ///
+    /// The receiver lags and misses the oldest events if it falls too far
+    /// behind.
+    fn subscribe(&self) -> ShardEventReceiver;
     fn shard_lock_manager(&self) -> ShardLockManagerRef;
+    /// Whether the last successful heartbeat to the meta is within the lease.
+    fn is_heartbeat_alive(&self) -> bool;
+    /// Whether the cluster is started and not stopped yet.
+    fn is_started(&self) -> bool;
+    /// Get the shards whose locks are lost but not closed yet, ordered by the
+    /// shard id.
+    fn lock_lost_shards(&self) -> Vec<ShardId>;
 }
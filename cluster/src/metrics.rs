@@ -0,0 +1,39 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+use lazy_static::lazy_static;
+use prometheus::{
+    exponential_buckets, register_histogram, register_int_counter_vec, register_int_gauge,
+    Histogram, IntCounterVec, IntGauge,
+};
+
+lazy_static! {
+    pub static ref SHARD_VERSION_REGRESSION_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "cluster_shard_version_regression_total",
+        "Counter of opening shard with a smaller version than the cached one",
+        &["shard_id"]
+    )
+    .unwrap();
+    pub static ref HEARTBEAT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "cluster_heartbeat_total",
+        "Counter of the heartbeats sent to the CeresMeta",
+        &["result"]
+    )
+    .unwrap();
+    // Buckets: 1ms,2ms,4ms,...,4s
+    pub static ref HEARTBEAT_DURATION_HISTOGRAM: Histogram = register_histogram!(
+        "cluster_heartbeat_duration_seconds",
+        "Histogram for the duration of sending heartbeat to the CeresMeta in seconds",
+        exponential_buckets(0.001, 2.0, 13).unwrap()
+    )
+    .unwrap();
+    pub static ref ETCD_CONNECTED_GAUGE: IntGauge = register_int_gauge!(
+        "cluster_etcd_connected",
+        "Whether the etcd cluster is reachable by the connection supervisor, 1 for connected and 0 for disconnected"
+    )
+    .unwrap();
+    pub static ref LAST_HEARTBEAT_TIMESTAMP_GAUGE: IntGauge = register_int_gauge!(
+        "cluster_last_heartbeat_timestamp_seconds",
+        "Unix timestamp of the last successful heartbeat to the CeresMeta in seconds"
+    )
+    .unwrap();
+}
@@ -7,6 +7,7 @@ use std::{
     time::{Duration, Instant},
 };
 
+use async_trait::async_trait;
 use ceresdbproto::meta_event::ShardLockValue;
 use common_types::{bytes::Bytes, table::ShardId};
 use common_util::{
@@ -102,6 +103,13 @@ define_result!(Error);
 
 pub type ShardLockManagerRef = Arc<ShardLockManager>;
 
+/// Callback to be notified with the id of the shard whose lock is lost.
+pub type LockLostCallback = Arc<dyn Fn(ShardId) + Send + Sync>;
+
+/// The etcd client shared by the shard locks, and the latest one is always
+/// used by the following requests.
+type SharedClient = Arc<RwLock<Client>>;
+
 /// Shard lock manager is implemented based on etcd.
 ///
 /// Only with the lock held, the shard can be operated by this node.
@@ -112,11 +120,59 @@ pub struct ShardLockManager {
     lock_lease_check_interval: Duration,
     rpc_timeout: Duration,
 
-    etcd_client: Client,
+    /// The client is replaced once the connection to etcd is re-established.
+    etcd_client: SharedClient,
     runtime: RuntimeRef,
 
     // ShardID -> ShardLock
     shard_locks: Arc<AsyncRwLock<HashMap<u32, ShardLock>>>,
+    lock_lost_callbacks: Arc<RwLock<Vec<LockLostCallback>>>,
+}
+
+/// The etcd client to keep the leases alive.
+#[async_trait]
+trait LeaseKeepAliveClient: Clone + Send + Sync + 'static {
+    type Keeper: LeaseKeepAlive;
+
+    async fn lease_keep_alive(
+        &mut self,
+        lease_id: i64,
+    ) -> std::result::Result<Self::Keeper, etcd_client::Error>;
+}
+
+/// The keeper of a lease.
+#[async_trait]
+trait LeaseKeepAlive: Send + 'static {
+    fn id(&self) -> i64;
+
+    /// Keep alive the lease once, and return the ttl in seconds of the
+    /// response, which is None if no response is received.
+    async fn keep_alive(&mut self) -> std::result::Result<Option<i64>, etcd_client::Error>;
+}
+
+#[async_trait]
+impl LeaseKeepAliveClient for Client {
+    type Keeper = (LeaseKeeper, LeaseKeepAliveStream);
+
+    async fn lease_keep_alive(
+        &mut self,
+        lease_id: i64,
+    ) -> std::result::Result<Self::Keeper, etcd_client::Error> {
+        Client::lease_keep_alive(self, lease_id).await
+    }
+}
+
+#[async_trait]
+impl LeaseKeepAlive for (LeaseKeeper, LeaseKeepAliveStream) {
+    fn id(&self) -> i64 {
+        self.0.id()
+    }
+
+    async fn keep_alive(&mut self) -> std::result::Result<Option<i64>, etcd_client::Error> {
+        self.0.keep_alive().await?;
+        let resp = self.1.message().await?;
+        Ok(resp.map(|v| v.ttl()))
+    }
 }
 
 #[derive(Debug)]
@@ -205,20 +261,15 @@ impl Lease {
 
     /// Keep alive the lease once, the state will be updated whatever the
     /// keepalive result is.
-    async fn keep_alive_once(
-        keeper: &mut LeaseKeeper,
-        stream: &mut LeaseKeepAliveStream,
+    async fn keep_alive_once<K: LeaseKeepAlive>(
+        keeper: &mut K,
         state: &Arc<RwLock<LeaseState>>,
     ) -> Result<()> {
-        keeper.keep_alive().await.context(KeepAlive {
-            lease_id: keeper.id(),
-        })?;
-        match stream.message().await.context(KeepAlive {
+        match keeper.keep_alive().await.context(KeepAlive {
             lease_id: keeper.id(),
         })? {
-            Some(resp) => {
+            Some(ttl_sec) => {
                 // The ttl in the response is in seconds, let's convert it into milliseconds.
-                let ttl_sec = resp.ttl();
                 if ttl_sec <= 0 {
                     error!("Failed to keep lease alive because negative ttl is received, id:{}, ttl:{ttl_sec}s", keeper.id());
                     return KeepAliveWithExpiredTTL {
@@ -250,26 +301,26 @@ impl Lease {
         }
     }
 
-    async fn start_keepalive(
+    async fn start_keepalive<C: LeaseKeepAliveClient>(
         &self,
         mut stop_rx: oneshot::Receiver<()>,
         notifier: oneshot::Sender<KeepAliveStopReason>,
-        etcd_client: &mut Client,
+        etcd_client: &mut C,
         runtime: &RuntimeRef,
     ) -> KeepAliveResult {
-        let (mut keeper, mut stream) = match etcd_client
+        let mut keeper = match etcd_client
             .lease_keep_alive(self.id)
             .await
             .context(KeepAlive { lease_id: self.id })
         {
-            Ok((keeper, stream)) => (keeper, stream),
+            Ok(keeper) => keeper,
             Err(err) => {
                 return KeepAliveResult::Failure { err, stop_rx };
             }
         };
 
         // Update the lease state immediately.
-        if let Err(err) = Self::keep_alive_once(&mut keeper, &mut stream, &self.state).await {
+        if let Err(err) = Self::keep_alive_once(&mut keeper, &self.state).await {
             return KeepAliveResult::Failure { err, stop_rx };
         }
 
@@ -280,7 +331,7 @@ impl Lease {
         let state = self.state.clone();
         let handle = runtime.spawn(async move {
             loop {
-                if let Err(err) = Self::keep_alive_once(&mut keeper, &mut stream, &state).await {
+                if let Err(err) = Self::keep_alive_once(&mut keeper, &state).await {
                     error!("Failed to keep lease alive, id:{lease_id}, err:{err}");
                     let reason = KeepAliveStopReason::Failure {
                         err,
@@ -376,7 +427,7 @@ impl ShardLock {
     async fn grant<OnExpired, Fut>(
         &mut self,
         on_lock_expired: OnExpired,
-        etcd_client: &mut Client,
+        shared_client: &SharedClient,
         runtime: &RuntimeRef,
     ) -> Result<bool>
     where
@@ -389,6 +440,7 @@ impl ShardLock {
             return Ok(false);
         }
 
+        let mut etcd_client = shared_client.read().unwrap().clone();
         // Grant the lease first.
         let resp = etcd_client
             .lease_grant(self.ttl_sec as i64, None)
@@ -405,14 +457,15 @@ impl ShardLock {
         );
 
         let lease_id = resp.id();
-        self.acquire_lock_with_lease(lease_id, etcd_client).await?;
+        self.acquire_lock_with_lease(lease_id, &mut etcd_client)
+            .await?;
 
         let lease_expired_at = Instant::now() + Duration::from_secs(resp.ttl() as u64);
         self.keep_lease_alive(
             lease_id,
             lease_expired_at,
             on_lock_expired,
-            etcd_client,
+            shared_client.clone(),
             runtime,
         )
         .await?;
@@ -508,17 +561,18 @@ impl ShardLock {
     /// but it won't be triggered if the lock is revoked in purpose.
     /// The keepalive procedure supports retrying if the failure is caused by
     /// rpc error, and it will stop if the lease is expired.
-    async fn keep_lease_alive<OnExpired, Fut>(
+    async fn keep_lease_alive<OnExpired, Fut, C>(
         &mut self,
         lease_id: i64,
         expired_at: Instant,
         on_lock_expired: OnExpired,
-        etcd_client: &mut Client,
+        shared_client: Arc<RwLock<C>>,
         runtime: &RuntimeRef,
     ) -> Result<()>
     where
         OnExpired: FnOnce(ShardId) -> Fut + Send + 'static,
         Fut: Future<Output = ()> + Send + 'static,
+        C: LeaseKeepAliveClient,
     {
         // Try to acquire the lock to ensure there is only one running keepalive
         // procedure.
@@ -533,7 +587,6 @@ impl ShardLock {
         let rpc_timeout = self.rpc_timeout;
         let lock_lease_check_interval = self.lease_check_interval;
         let (keepalive_stop_sender, keepalive_stop_rx) = oneshot::channel();
-        let mut etcd_client = etcd_client.clone();
         let runtime_for_bg = runtime.clone();
         let lease_for_bg = lease.clone();
         let handle = runtime.spawn(async move {
@@ -573,6 +626,8 @@ impl ShardLock {
                     }
                 }
 
+                // The latest client is used in case the connection is re-established.
+                let mut etcd_client = shared_client.read().unwrap().clone();
                 let (lease_expire_notifier, mut lease_expire_rx) = oneshot::channel();
                 if let KeepAliveResult::Failure { err, stop_rx } = lease_for_bg
                     .start_keepalive(
@@ -652,12 +707,35 @@ impl ShardLockManager {
             lease_ttl_sec,
             lock_lease_check_interval,
             rpc_timeout,
-            etcd_client,
+            etcd_client: Arc::new(RwLock::new(etcd_client)),
             runtime,
             shard_locks: Arc::new(AsyncRwLock::new(HashMap::new())),
+            lock_lost_callbacks: Arc::new(RwLock::new(Vec::new())),
         }
     }
 
+    /// The etcd client used by the following requests.
+    pub fn etcd_client(&self) -> Client {
+        self.etcd_client.read().unwrap().clone()
+    }
+
+    /// Replace the etcd client with the one whose connection is re-established,
+    /// which is used by the following requests including the retries of the
+    /// lease keepalive.
+    pub fn update_etcd_client(&self, etcd_client: Client) {
+        *self.etcd_client.write().unwrap() = etcd_client;
+    }
+
+    /// Register the callback to be notified when the lock of any shard is lost
+    /// because its lease is expired.
+    ///
+    /// The callbacks are called before the `on_lock_expired` passed to the
+    /// [ShardLockManager::grant_lock], and they won't be triggered if the lock
+    /// is revoked.
+    pub fn register_lock_lost_callback(&self, callback: LockLostCallback) {
+        self.lock_lost_callbacks.write().unwrap().push(callback);
+    }
+
     /// Grant lock to the shard.
     ///
     /// If the lock is already granted, return false. The `on_lock_expired` will
@@ -674,12 +752,13 @@ impl ShardLockManager {
     {
         info!("Try to grant lock for shard, shard_id:{shard_id}");
 
+        let on_lock_expired =
+            with_lock_lost_callbacks(self.lock_lost_callbacks.clone(), on_lock_expired);
         let mut shard_locks = self.shard_locks.write().await;
         if let Some(shard_lock) = shard_locks.get_mut(&shard_id) {
-            let mut etcd_client = self.etcd_client.clone();
             warn!("The shard lock was created before, and grant it again now, shard_id:{shard_id}");
             shard_lock
-                .grant(on_lock_expired, &mut etcd_client, &self.runtime)
+                .grant(on_lock_expired, &self.etcd_client, &self.runtime)
                 .await?;
         } else {
             info!("Try to grant a new shard lock, shard_id:{shard_id}");
@@ -693,9 +772,8 @@ impl ShardLockManager {
                 self.rpc_timeout,
             );
 
-            let mut etcd_client = self.etcd_client.clone();
             shard_lock
-                .grant(on_lock_expired, &mut etcd_client, &self.runtime)
+                .grant(on_lock_expired, &self.etcd_client, &self.runtime)
                 .await?;
 
             shard_locks.insert(shard_id, shard_lock);
@@ -716,7 +794,7 @@ impl ShardLockManager {
         let shard_lock = shard_locks.remove(&shard_id);
         let res = match shard_lock {
             Some(mut v) => {
-                let mut etcd_client = self.etcd_client.clone();
+                let mut etcd_client = self.etcd_client.read().unwrap().clone();
                 v.revoke(&mut etcd_client).await?;
 
                 info!("Finish revoking lock for shard, shard_id:{shard_id}");
@@ -733,9 +811,166 @@ impl ShardLockManager {
     }
 }
 
+/// Notify the registered callbacks before calling the `on_lock_expired`.
+fn with_lock_lost_callbacks<OnExpired, Fut>(
+    callbacks: Arc<RwLock<Vec<LockLostCallback>>>,
+    on_lock_expired: OnExpired,
+) -> impl FnOnce(ShardId) -> Fut + Send + 'static
+where
+    OnExpired: FnOnce(ShardId) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    move |shard_id: ShardId| {
+        let callbacks = callbacks.read().unwrap().clone();
+        info!(
+            "Notify that the shard lock is lost, shard_id:{shard_id}, callbacks:{}",
+            callbacks.len()
+        );
+        for callback in callbacks {
+            callback(shard_id);
+        }
+
+        on_lock_expired(shard_id)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use common_util::runtime::Builder;
+    use meta_client::types::{ShardInfo, TablesOfShard};
+
     use super::*;
+    use crate::shard_tables_cache::ShardTablesCache;
+
+    /// The etcd client whose leases are expired once `expired` is set.
+    #[derive(Clone, Default)]
+    struct MockLeaseClient {
+        expired: Arc<AtomicBool>,
+    }
+
+    struct MockLeaseKeeper {
+        id: i64,
+        expired: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl LeaseKeepAliveClient for MockLeaseClient {
+        type Keeper = MockLeaseKeeper;
+
+        async fn lease_keep_alive(
+            &mut self,
+            lease_id: i64,
+        ) -> std::result::Result<MockLeaseKeeper, etcd_client::Error> {
+            Ok(MockLeaseKeeper {
+                id: lease_id,
+                expired: self.expired.clone(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl LeaseKeepAlive for MockLeaseKeeper {
+        fn id(&self) -> i64 {
+            self.id
+        }
+
+        async fn keep_alive(&mut self) -> std::result::Result<Option<i64>, etcd_client::Error> {
+            // The ttl of the expired lease is zero.
+            if self.expired.load(Ordering::Relaxed) {
+                Ok(Some(0))
+            } else {
+                Ok(Some(1))
+            }
+        }
+    }
+
+    #[test]
+    fn test_lock_lost_on_lease_expired() {
+        let runtime = Arc::new(
+            Builder::default()
+                .worker_threads(1)
+                .enable_all()
+                .build()
+                .unwrap(),
+        );
+        let shard_tables_cache = ShardTablesCache::default();
+        shard_tables_cache.insert(TablesOfShard {
+            shard_info: ShardInfo {
+                id: 7,
+                ..Default::default()
+            },
+            tables: Vec::new(),
+        });
+
+        let callbacks: Arc<RwLock<Vec<LockLostCallback>>> = Arc::default();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        {
+            let shard_tables_cache = shard_tables_cache.clone();
+            let events = events.clone();
+            callbacks
+                .write()
+                .unwrap()
+                .push(Arc::new(move |shard_id: ShardId| {
+                    shard_tables_cache.mark_lock_lost(shard_id);
+                    events.lock().unwrap().push(format!("callback:{shard_id}"));
+                }));
+        }
+        let (expired_tx, expired_rx) = oneshot::channel();
+        let on_lock_expired = {
+            let events = events.clone();
+            move |shard_id: ShardId| async move {
+                events
+                    .lock()
+                    .unwrap()
+                    .push(format!("on_lock_expired:{shard_id}"));
+                let _ = expired_tx.send(());
+            }
+        };
+        let on_lock_expired = with_lock_lost_callbacks(callbacks, on_lock_expired);
+
+        let client = MockLeaseClient::default();
+        let mut shard_lock = ShardLock::new(
+            7,
+            "/ceresdb/defaultCluster",
+            Bytes::new(),
+            1,
+            Duration::from_millis(10),
+            Duration::from_millis(100),
+        );
+        runtime.block_on(async {
+            shard_lock
+                .keep_lease_alive(
+                    1,
+                    Instant::now() + Duration::from_secs(1),
+                    on_lock_expired,
+                    Arc::new(RwLock::new(client.clone())),
+                    &runtime,
+                )
+                .await
+                .unwrap();
+            assert!(shard_tables_cache.begin_op(7).is_ok());
+
+            // The lease is expired in etcd, so the following keepalive fails until the
+            // lock is lost.
+            client.expired.store(true, Ordering::Relaxed);
+            tokio::time::timeout(Duration::from_secs(5), expired_rx)
+                .await
+                .unwrap()
+                .unwrap();
+        });
+
+        assert_eq!(
+            vec!["callback:7", "on_lock_expired:7"],
+            *events.lock().unwrap()
+        );
+        // The writes on the shard are rejected.
+        assert!(matches!(
+            shard_tables_cache.begin_op(7),
+            Err(crate::Error::ShardLockLost { shard_id: 7, .. })
+        ));
+    }
 
     #[test]
     fn test_format_shard_lock_key() {
@@ -2,15 +2,20 @@
 
 use std::{
     collections::HashMap,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
 };
 
 use meta_client::types::{ShardId, ShardInfo, ShardVersion, TableInfo, TablesOfShard};
 use snafu::{ensure, OptionExt};
+use tokio::{sync::Notify, time};
 
 use crate::{
-    Result, ShardNotFound, ShardVersionMismatch, TableAlreadyExists, TableNotFound,
-    UpdateFrozenShard,
+    Result, ShardDraining, ShardLockLost, ShardNotFound, ShardStatus, ShardVersionMismatch,
+    TableAlreadyExists, TableNotFound, UpdateFrozenShard,
 };
 
 /// [ShardTablesCache] caches the information about tables and shards, and the
@@ -44,6 +49,16 @@ impl ShardTablesCache {
         self.inner.read().unwrap().all_shard_infos()
     }
 
+    /// Get the tables of all the shards.
+    pub fn all_tables_of_shards(&self) -> Vec<TablesOfShard> {
+        self.inner.read().unwrap().all_tables_of_shards()
+    }
+
+    /// Get the status of all the shards ordered by the shard id.
+    pub fn all_shard_status(&self) -> Vec<ShardStatus> {
+        self.inner.read().unwrap().all_shard_status()
+    }
+
     // Get the shard by its id.
     pub fn get(&self, shard_id: ShardId) -> Option<TablesOfShard> {
         self.inner.read().unwrap().get(shard_id)
@@ -64,6 +79,29 @@ impl ShardTablesCache {
         self.inner.write().unwrap().freeze(shard_id)
     }
 
+    /// Mark the lock of the shard as lost to reject the new operations, and
+    /// the tables of the shard can't be changed either.
+    ///
+    /// The mark is cleared once the shard is opened again.
+    pub fn mark_lock_lost(&self, shard_id: ShardId) -> Option<TablesOfShard> {
+        self.inner.write().unwrap().mark_lock_lost(shard_id)
+    }
+
+    /// Begin an operation (e.g. write) on the shard, and the operation is
+    /// regarded as in-flight until the returned guard is dropped.
+    ///
+    /// It will fail if the shard doesn't exist, is draining or its lock is
+    /// lost.
+    pub fn begin_op(&self, shard_id: ShardId) -> Result<ShardOpGuard> {
+        self.inner.read().unwrap().begin_op(shard_id)
+    }
+
+    /// Mark the shard as draining to reject the new operations, and the
+    /// in-flight operations of the shard are returned to wait for.
+    pub fn mark_draining(&self, shard_id: ShardId) -> Option<(TablesOfShard, InFlightOpsRef)> {
+        self.inner.write().unwrap().mark_draining(shard_id)
+    }
+
     /// Try to insert a new table to the shard with a newer version.
     ///
     /// It will fail if:
@@ -105,10 +143,60 @@ impl ShardTablesCache {
     }
 }
 
+/// The in-flight operations of a shard.
+#[derive(Debug, Default)]
+pub struct InFlightOps {
+    num_ops: AtomicUsize,
+    notify: Notify,
+}
+
+pub type InFlightOpsRef = Arc<InFlightOps>;
+
+impl InFlightOps {
+    /// Wait for all the in-flight operations to be done, and returns the
+    /// number of the operations not done before the timeout.
+    pub(crate) async fn wait_done(&self, timeout: Duration) -> usize {
+        let wait = async {
+            loop {
+                // Register for the notification before checking the number to
+                // avoid missing the wakeup.
+                let notified = self.notify.notified();
+                if self.num_ops.load(Ordering::Acquire) == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+
+        let _ = time::timeout(timeout, wait).await;
+        self.num_ops.load(Ordering::Acquire)
+    }
+}
+
+/// Guard of an in-flight operation on the shard, and the operation is done
+/// once it is dropped.
+#[derive(Debug)]
+pub struct ShardOpGuard {
+    ops: InFlightOpsRef,
+}
+
+impl Drop for ShardOpGuard {
+    fn drop(&mut self) {
+        if self.ops.num_ops.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.ops.notify.notify_waiters();
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct TablesOfShardCacheEntry {
     entry: TablesOfShard,
     frozen: bool,
+    /// The shard rejects the new operations if it is draining.
+    draining: bool,
+    /// The shard rejects the new operations if its lock is lost.
+    lock_lost: bool,
+    in_flight_ops: InFlightOpsRef,
 }
 
 #[derive(Debug, Default)]
@@ -156,6 +244,30 @@ impl Inner {
             .collect()
     }
 
+    fn all_tables_of_shards(&self) -> Vec<TablesOfShard> {
+        self.tables_by_shard
+            .values()
+            .map(|v| v.entry.clone())
+            .collect()
+    }
+
+    fn all_shard_status(&self) -> Vec<ShardStatus> {
+        let mut status: Vec<_> = self
+            .tables_by_shard
+            .values()
+            .map(|v| ShardStatus {
+                shard_id: v.entry.shard_info.id,
+                version: v.entry.shard_info.version,
+                num_tables: v.entry.tables.len(),
+                frozen: v.frozen,
+                draining: v.draining,
+            })
+            .collect();
+        status.sort_by_key(|v| v.shard_id);
+
+        status
+    }
+
     fn get(&self, shard_id: ShardId) -> Option<TablesOfShard> {
         self.tables_by_shard.get(&shard_id).map(|v| v.entry.clone())
     }
@@ -171,11 +283,49 @@ impl Inner {
         Some(tables_of_shard.entry.clone())
     }
 
+    fn begin_op(&self, shard_id: ShardId) -> Result<ShardOpGuard> {
+        let tables_of_shard =
+            self.tables_by_shard
+                .get(&shard_id)
+                .with_context(|| ShardNotFound {
+                    msg: format!("begin operation on a non-existent shard, shard_id:{shard_id}"),
+                })?;
+        ensure!(!tables_of_shard.draining, ShardDraining { shard_id });
+        ensure!(!tables_of_shard.lock_lost, ShardLockLost { shard_id });
+
+        let ops = tables_of_shard.in_flight_ops.clone();
+        ops.num_ops.fetch_add(1, Ordering::AcqRel);
+        Ok(ShardOpGuard { ops })
+    }
+
+    fn mark_draining(&mut self, shard_id: ShardId) -> Option<(TablesOfShard, InFlightOpsRef)> {
+        let tables_of_shard = self.tables_by_shard.get_mut(&shard_id)?;
+        // The tables of the draining shard can't be changed either.
+        tables_of_shard.frozen = true;
+        tables_of_shard.draining = true;
+
+        Some((
+            tables_of_shard.entry.clone(),
+            tables_of_shard.in_flight_ops.clone(),
+        ))
+    }
+
+    fn mark_lock_lost(&mut self, shard_id: ShardId) -> Option<TablesOfShard> {
+        let tables_of_shard = self.tables_by_shard.get_mut(&shard_id)?;
+        tables_of_shard.frozen = true;
+        tables_of_shard.lock_lost = true;
+
+        Some(tables_of_shard.entry.clone())
+    }
+
     fn insert(&mut self, tables_of_shard: TablesOfShard) {
         let shard_id = tables_of_shard.shard_info.id;
         let entry = TablesOfShardCacheEntry {
             entry: tables_of_shard,
             frozen: false,
+            draining: false,
+            lock_lost: false,
+            in_flight_ops: Arc::new(InFlightOps::default()),
         };
         self.tables_by_shard.insert(shard_id, entry);
     }
@@ -1,6 +1,9 @@
 // Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
 
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 use common_types::{
     schema::{SchemaId, SchemaName},
@@ -42,6 +45,8 @@ pub struct NodeTopology {
 pub struct ClusterTopology {
     schemas: Option<SchemaTopologies>,
     nodes: Option<NodeTopology>,
+    /// The instant of the last time the nodes are fetched.
+    nodes_fetched_at: Option<Instant>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -139,11 +144,22 @@ impl ClusterTopology {
         self.nodes.clone()
     }
 
+    /// Get the nodes topology if the nodes are fetched within the `ttl`.
+    pub fn fresh_nodes(&self, ttl: Duration) -> Option<NodeTopology> {
+        let fetched_at = self.nodes_fetched_at?;
+        if fetched_at.elapsed() < ttl {
+            self.nodes()
+        } else {
+            None
+        }
+    }
+
     /// Try to update the nodes topology of the cluster.
     ///
     /// If the provided version is not newer, then the update will be
-    /// ignored.
+    /// ignored, but the nodes are still regarded as fetched just now.
     pub fn maybe_update_nodes(&mut self, nodes: ClusterNodesRef, version: u64) -> bool {
+        self.nodes_fetched_at = Some(Instant::now());
         if self.nodes.is_none() {
             let nodes = NodeTopology { version, nodes };
             self.nodes = Some(nodes);
@@ -222,8 +222,8 @@ impl ColumnSchema {
             self.data_type == writer_schema.data_type,
             IncompatDataType {
                 name: &self.name,
-                expect: writer_schema.data_type,
-                given: self.data_type,
+                expect: self.data_type,
+                given: writer_schema.data_type,
             }
         );
 
@@ -239,6 +239,12 @@ impl<'a> RowGroupSlicer<'a> {
         }
     }
 
+    /// Get the row by its index in the slice.
+    #[inline]
+    pub fn get_row(&self, idx: usize) -> Option<&'a Row> {
+        self.row_group.rows[self.range.start..self.range.end].get(idx)
+    }
+
     #[inline]
     pub fn slice_range(&self) -> Range<usize> {
         self.range.clone()
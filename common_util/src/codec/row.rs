@@ -166,6 +166,23 @@ pub fn encode_row_group_for_wal(
     Ok(())
 }
 
+/// Encode a single row in the format that can write to wal, see
+/// [encode_row_group_for_wal] for details of the arguments.
+pub fn encode_row_for_wal(
+    row: &Row,
+    table_schema: &Schema,
+    index_in_writer: &IndexInWriterSchema,
+    buf: &mut ByteVec,
+) -> Result<()> {
+    let row_encoder = WalRowEncoder {
+        table_schema,
+        index_in_writer,
+    };
+
+    buf.reserve(row_encoder.estimate_encoded_size(row));
+    row_encoder.encode(buf, row)
+}
+
 /// Return the next prefix key
 ///
 /// Assume there are keys like:
@@ -520,6 +520,24 @@ impl<'de> Deserialize<'de> for ReadableDuration {
     }
 }
 
+/// Serialize the secret (e.g. password) in the config as `***` to avoid
+/// exposing it, and the empty one is kept to tell it is not set.
+///
+/// It is used by `#[serde(serialize_with = "serialize_secret")]`.
+pub fn serialize_secret<S>(secret: &str, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if secret.is_empty() {
+        serializer.serialize_str(secret)
+    } else {
+        serializer.serialize_str(REDACTED_SECRET)
+    }
+}
+
+/// The placeholder of the secrets in the serialized config.
+pub const REDACTED_SECRET: &str = "***";
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,10 +2,95 @@
 
 use std::future::Future;
 
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, IntCounter, IntCounterVec};
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
 use tokio::sync::RwLock;
 
-use crate::error::GenericResult;
+use crate::error::{BoxError, GenericError, GenericResult};
 
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Failed to persist next max id, last_id:{}, max_id:{}, next_max_id:{}, err:{}",
+        last_id,
+        max_id,
+        next_max_id,
+        source
+    ))]
+    PersistMaxId {
+        last_id: u64,
+        max_id: u64,
+        next_max_id: u64,
+        source: GenericError,
+    },
+
+    #[snafu(display(
+        "Last id exceeds max id, last_id:{}, max_id:{}.\nBacktrace:\n{}",
+        last_id,
+        max_id,
+        backtrace
+    ))]
+    ExceedMaxId {
+        last_id: u64,
+        max_id: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Id space is exhausted, last_id:{}, max_id:{}.\nBacktrace:\n{}",
+        last_id,
+        max_id,
+        backtrace
+    ))]
+    IdExhausted {
+        last_id: u64,
+        max_id: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Invalid count of ids to alloc, count:{}.\nBacktrace:\n{}",
+        count,
+        backtrace
+    ))]
+    InvalidAllocCount { count: u64, backtrace: Backtrace },
+}
+
+lazy_static! {
+    static ref ID_ALLOCATOR_ALLOCATED_IDS_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "id_allocator_allocated_ids_total",
+        "Number of ids allocated by the id allocators",
+        &["name"]
+    )
+    .unwrap();
+    static ref ID_ALLOCATOR_PERSIST_MAX_ID_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "id_allocator_persist_max_id_total",
+        "Number of times the id allocators persist the next max id",
+        &["name"]
+    )
+    .unwrap();
+}
+
+/// Metrics of the id allocators, and the allocators with the same name share
+/// the same metrics.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    allocated_ids: IntCounter,
+    persist_max_id: IntCounter,
+}
+
+impl Metrics {
+    pub fn new(name: &str) -> Self {
+        Self {
+            allocated_ids: ID_ALLOCATOR_ALLOCATED_IDS_COUNTER.with_label_values(&[name]),
+            persist_max_id: ID_ALLOCATOR_PERSIST_MAX_ID_COUNTER.with_label_values(&[name]),
+        }
+    }
+}
+
+/// The ids in `(last_id, max_id]` can be allocated without persisting a new
+/// max id, so `last_id <= max_id` always holds.
 struct Inner {
     last_id: u64,
     max_id: u64,
@@ -16,6 +101,10 @@ impl Inner {
     /// New a allocator.
     pub fn new(last_id: u64, max_id: u64, alloc_step: u64) -> Self {
         assert!(alloc_step > 0);
+        assert!(
+            last_id <= max_id,
+            "last id exceeds max id, last_id:{last_id}, max_id:{max_id}"
+        );
         Self {
             last_id,
             max_id,
@@ -34,29 +123,135 @@ impl Inner {
             return Ok(self.last_id);
         }
 
-        // Update new max id.
-        let next_max_id = self.last_id + self.alloc_step;
+        // The ids larger than the persisted max id may be allocated again after
+        // restart, so never hand them out.
+        ensure!(
+            self.last_id == self.max_id,
+            ExceedMaxId {
+                last_id: self.last_id,
+                max_id: self.max_id,
+            }
+        );
 
-        // persist new max id.
-        persist_next_max_id(next_max_id).await?;
+        // Update new max id, and never wrap around the id space.
+        let next_max_id = self
+            .last_id
+            .checked_add(self.alloc_step)
+            .context(IdExhausted {
+                last_id: self.last_id,
+                max_id: self.max_id,
+            })
+            .box_err()?;
+
+        // Persist new max id, and the memory is untouched if it fails so the next
+        // allocation will retry to persist the same max id.
+        persist_next_max_id(next_max_id)
+            .await
+            .context(PersistMaxId {
+                last_id: self.last_id,
+                max_id: self.max_id,
+                next_max_id,
+            })
+            .box_err()?;
 
         // Update memory.
         self.max_id = next_max_id;
 
         self.last_id += 1;
+        debug_assert!(self.last_id <= self.max_id);
         Ok(self.last_id)
     }
+
+    /// Alloc `count` contiguous ids, and returns the inclusive range of them.
+    pub async fn alloc_ids<F, T>(
+        &mut self,
+        count: u64,
+        persist_next_max_id: F,
+    ) -> GenericResult<(u64, u64)>
+    where
+        F: FnOnce(u64) -> T,
+        T: Future<Output = GenericResult<()>>,
+    {
+        ensure!(count > 0, InvalidAllocCount { count });
+        ensure!(
+            self.last_id <= self.max_id,
+            ExceedMaxId {
+                last_id: self.last_id,
+                max_id: self.max_id,
+            }
+        );
+
+        let id_exhausted = IdExhausted {
+            last_id: self.last_id,
+            max_id: self.max_id,
+        };
+        let end_id = self
+            .last_id
+            .checked_add(count)
+            .context(id_exhausted)
+            .box_err()?;
+        if end_id > self.max_id {
+            // Grow the max id by the multiple of the step to cover the whole range,
+            // so it is persisted only once.
+            let num_steps = (end_id - self.max_id - 1) / self.alloc_step + 1;
+            let next_max_id = num_steps
+                .checked_mul(self.alloc_step)
+                .and_then(|v| self.max_id.checked_add(v))
+                .context(id_exhausted)
+                .box_err()?;
+
+            persist_next_max_id(next_max_id)
+                .await
+                .context(PersistMaxId {
+                    last_id: self.last_id,
+                    max_id: self.max_id,
+                    next_max_id,
+                })
+                .box_err()?;
+
+            self.max_id = next_max_id;
+        }
+
+        let start_id = self.last_id + 1;
+        self.last_id = end_id;
+        debug_assert!(self.last_id <= self.max_id);
+        Ok((start_id, end_id))
+    }
 }
 
 pub struct IdAllocator {
     inner: RwLock<Inner>,
+    metrics: Option<Metrics>,
 }
 
 impl IdAllocator {
-    /// New a id allocator.
+    /// New a id allocator starting from `last_id`, and `max_id` is the
+    /// persisted max id.
+    ///
+    /// Panics if `last_id` exceeds `max_id` or `alloc_step` is zero.
     pub fn new(last_id: u64, max_id: u64, alloc_step: u64) -> Self {
         Self {
             inner: RwLock::new(Inner::new(last_id, max_id, alloc_step)),
+            metrics: None,
+        }
+    }
+
+    /// Collect the allocated ids and the persistence of the max id into the
+    /// `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn on_persist_max_id(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.persist_max_id.inc();
+        }
+    }
+
+    fn on_ids_allocated(&self, count: u64) {
+        if let Some(metrics) = &self.metrics {
+            metrics.allocated_ids.inc_by(count);
         }
     }
 
@@ -66,16 +261,75 @@ impl IdAllocator {
         F: FnOnce(u64) -> T,
         T: Future<Output = GenericResult<()>>,
     {
-        self.inner.write().await.alloc_id(persist_next_max_id).await
+        let persist_next_max_id = |next_max_id| {
+            self.on_persist_max_id();
+            persist_next_max_id(next_max_id)
+        };
+        let id = self
+            .inner
+            .write()
+            .await
+            .alloc_id(persist_next_max_id)
+            .await?;
+        self.on_ids_allocated(1);
+
+        Ok(id)
+    }
+
+    /// Alloc `count` contiguous ids in one go, and returns the inclusive range
+    /// `(start, end)` of them.
+    ///
+    /// The new max id is persisted at most once even if the range spans
+    /// multiple steps.
+    pub async fn alloc_ids<F, T>(
+        &self,
+        count: u64,
+        persist_next_max_id: F,
+    ) -> GenericResult<(u64, u64)>
+    where
+        F: FnOnce(u64) -> T,
+        T: Future<Output = GenericResult<()>>,
+    {
+        let persist_next_max_id = |next_max_id| {
+            self.on_persist_max_id();
+            persist_next_max_id(next_max_id)
+        };
+        let range = self
+            .inner
+            .write()
+            .await
+            .alloc_ids(count, persist_next_max_id)
+            .await?;
+        self.on_ids_allocated(count);
+
+        Ok(range)
+    }
+
+    /// Returns the current `(last_id, max_id)` of the allocator.
+    pub async fn current(&self) -> (u64, u64) {
+        let inner = self.inner.read().await;
+        (inner.last_id, inner.max_id)
+    }
+
+    /// Returns the number of ids that can be allocated without persisting a new
+    /// max id.
+    pub async fn remaining(&self) -> u64 {
+        let (last_id, max_id) = self.current().await;
+        max_id.saturating_sub(last_id)
     }
 }
 
 #[cfg(test)]
 
 mod test {
+    use std::{
+        io::ErrorKind,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
     use tokio::runtime::Runtime;
 
-    use super::IdAllocator;
+    use super::{IdAllocator, Metrics};
 
     #[test]
     fn test_alloc_id() {
@@ -104,4 +358,213 @@ mod test {
             }
         });
     }
+
+    #[test]
+    fn test_alloc_id_watermark() {
+        let rt = Runtime::new().unwrap();
+        let allocator = IdAllocator::new(0, 0, 10);
+
+        rt.block_on(async move {
+            assert_eq!((0, 0), allocator.current().await);
+            assert_eq!(0, allocator.remaining().await);
+
+            let persist_max_file_id = move |_| async move { Ok(()) };
+            for _ in 0..3 {
+                allocator.alloc_id(persist_max_file_id).await.unwrap();
+            }
+            assert_eq!((3, 10), allocator.current().await);
+            assert_eq!(7, allocator.remaining().await);
+
+            for _ in 0..8 {
+                allocator.alloc_id(persist_max_file_id).await.unwrap();
+            }
+            assert_eq!((11, 20), allocator.current().await);
+            assert_eq!(9, allocator.remaining().await);
+        });
+    }
+
+    #[test]
+    fn test_alloc_id_persist_failed() {
+        let rt = Runtime::new().unwrap();
+        let allocator = IdAllocator::new(0, 0, 100);
+
+        rt.block_on(async move {
+            let persist_max_file_id = move |next_max_file_id| async move {
+                assert_eq!(next_max_file_id, 100);
+                Err(Box::new(std::io::Error::new(ErrorKind::Other, "mock error")) as _)
+            };
+            for _ in 0..2 {
+                let err = allocator.alloc_id(persist_max_file_id).await.unwrap_err();
+                assert!(err.to_string().contains("next_max_id:100"), "{err}");
+            }
+
+            // The failed persist doesn't change the allocator.
+            let persist_max_file_id = move |next_max_file_id| async move {
+                assert_eq!(next_max_file_id, 100);
+                Ok(())
+            };
+            for i in 1..=100 {
+                let res = allocator.alloc_id(persist_max_file_id).await.unwrap();
+                assert_eq!(res, i);
+            }
+
+            let persist_max_file_id = move |next_max_file_id| async move {
+                assert_eq!(next_max_file_id, 200);
+                Err(Box::new(std::io::Error::new(ErrorKind::Other, "mock error")) as _)
+            };
+            assert!(allocator.alloc_id(persist_max_file_id).await.is_err());
+
+            let persist_max_file_id = move |next_max_file_id| async move {
+                assert_eq!(next_max_file_id, 200);
+                Ok(())
+            };
+            let res = allocator.alloc_id(persist_max_file_id).await.unwrap();
+            assert_eq!(res, 101);
+        });
+    }
+
+    #[should_panic(expected = "last id exceeds max id")]
+    #[test]
+    fn test_alloc_id_exceed_max_id() {
+        IdAllocator::new(10, 5, 100);
+    }
+
+    #[test]
+    fn test_alloc_id_from_last_id() {
+        let rt = Runtime::new().unwrap();
+        let allocator = IdAllocator::new(5, 10, 100);
+
+        rt.block_on(async move {
+            assert_eq!((5, 10), allocator.current().await);
+
+            let persist_max_file_id = move |_| async move { Ok(()) };
+            assert_eq!(6, allocator.alloc_id(persist_max_file_id).await.unwrap());
+            assert_eq!((6, 10), allocator.current().await);
+        });
+    }
+
+    #[test]
+    fn test_alloc_ids() {
+        let rt = Runtime::new().unwrap();
+        let allocator = IdAllocator::new(0, 0, 10);
+        let num_persists = &AtomicUsize::new(0);
+
+        rt.block_on(async move {
+            let persist_max_file_id = |expect_max_file_id| {
+                move |next_max_file_id| async move {
+                    num_persists.fetch_add(1, Ordering::Relaxed);
+                    assert_eq!(expect_max_file_id, next_max_file_id);
+                    Ok(())
+                }
+            };
+
+            let res = allocator.alloc_ids(3, persist_max_file_id(10)).await;
+            assert_eq!((1, 3), res.unwrap());
+            assert_eq!(1, num_persists.load(Ordering::Relaxed));
+
+            // The range within the max id needs no persist.
+            let res = allocator.alloc_ids(5, persist_max_file_id(0)).await;
+            assert_eq!((4, 8), res.unwrap());
+            assert_eq!(1, num_persists.load(Ordering::Relaxed));
+
+            // The range spanning the step boundaries persists the max id only once.
+            let res = allocator.alloc_ids(25, persist_max_file_id(40)).await;
+            assert_eq!((9, 33), res.unwrap());
+            assert_eq!(2, num_persists.load(Ordering::Relaxed));
+            assert_eq!((33, 40), allocator.current().await);
+
+            // The single id allocation continues after the range.
+            let res = allocator.alloc_id(persist_max_file_id(0)).await;
+            assert_eq!(34, res.unwrap());
+
+            let err = allocator
+                .alloc_ids(0, persist_max_file_id(0))
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("count:0"), "{err}");
+        });
+    }
+
+    #[test]
+    fn test_alloc_ids_persist_failed() {
+        let rt = Runtime::new().unwrap();
+        let allocator = IdAllocator::new(0, 0, 10);
+
+        rt.block_on(async move {
+            let persist_ok = move |_| async move { Ok(()) };
+            assert_eq!((1, 5), allocator.alloc_ids(5, persist_ok).await.unwrap());
+
+            // The failed persist leaves the allocator unchanged.
+            let persist_failed = move |next_max_file_id| async move {
+                assert_eq!(next_max_file_id, 30);
+                Err(Box::new(std::io::Error::new(ErrorKind::Other, "mock error")) as _)
+            };
+            for _ in 0..2 {
+                let err = allocator.alloc_ids(20, persist_failed).await.unwrap_err();
+                assert!(err.to_string().contains("next_max_id:30"), "{err}");
+                assert_eq!((5, 10), allocator.current().await);
+            }
+            assert!(allocator.alloc_id(persist_ok).await.is_ok());
+            assert_eq!((6, 10), allocator.current().await);
+
+            // The next allocation resumes without gaps or duplicate ids.
+            let persist_max_file_id = move |next_max_file_id| async move {
+                assert_eq!(next_max_file_id, 30);
+                Ok(())
+            };
+            let res = allocator.alloc_ids(20, persist_max_file_id).await;
+            assert_eq!((7, 26), res.unwrap());
+            assert_eq!((26, 30), allocator.current().await);
+        });
+    }
+
+    #[test]
+    fn test_alloc_id_exhausted() {
+        let rt = Runtime::new().unwrap();
+        let allocator = IdAllocator::new(u64::MAX - 2, u64::MAX - 1, 10);
+
+        rt.block_on(async move {
+            let persist_max_file_id = move |_| async move { Ok(()) };
+            let res = allocator.alloc_id(persist_max_file_id).await.unwrap();
+            assert_eq!(u64::MAX - 1, res);
+
+            // The max id can't be grown by the step without overflow.
+            for _ in 0..2 {
+                let err = allocator.alloc_id(persist_max_file_id).await.unwrap_err();
+                assert!(err.to_string().contains("Id space is exhausted"), "{err}");
+            }
+            assert_eq!((u64::MAX - 1, u64::MAX - 1), allocator.current().await);
+
+            for count in [1, u64::MAX] {
+                let err = allocator
+                    .alloc_ids(count, persist_max_file_id)
+                    .await
+                    .unwrap_err();
+                assert!(err.to_string().contains("Id space is exhausted"), "{err}");
+            }
+            assert_eq!((u64::MAX - 1, u64::MAX - 1), allocator.current().await);
+        });
+    }
+
+    #[test]
+    fn test_alloc_id_metrics() {
+        let rt = Runtime::new().unwrap();
+        let metrics = Metrics::new("test_alloc_id_metrics");
+        let allocator = IdAllocator::new(0, 0, 100).with_metrics(metrics.clone());
+
+        rt.block_on(async move {
+            let persist_max_file_id = move |_| async move { Ok(()) };
+            // The max id is persisted only once the step boundary is crossed.
+            for (num_ids, expect_persists) in [(1, 1), (100, 1), (101, 2), (200, 2), (250, 3)] {
+                while metrics.allocated_ids.get() < num_ids {
+                    allocator.alloc_id(persist_max_file_id).await.unwrap();
+                }
+                assert_eq!(expect_persists, metrics.persist_max_id.get());
+            }
+
+            allocator.alloc_ids(60, persist_max_file_id).await.unwrap();
+            assert_eq!(310, metrics.allocated_ids.get());
+            assert_eq!(4, metrics.persist_max_id.get());
+        });
+    }
 }
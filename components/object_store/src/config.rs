@@ -2,7 +2,7 @@
 
 use std::time::Duration;
 
-use common_util::config::{ReadableDuration, ReadableSize};
+use common_util::config::{serialize_secret, ReadableDuration, ReadableSize};
 use serde::{Deserialize, Serialize};
 use table_kv::config::ObkvConfig;
 
@@ -58,7 +58,9 @@ pub struct LocalOptions {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AliyunOptions {
+    #[serde(serialize_with = "serialize_secret")]
     pub key_id: String,
+    #[serde(serialize_with = "serialize_secret")]
     pub key_secret: String,
     pub endpoint: String,
     pub bucket: String,
@@ -105,7 +107,9 @@ impl ObkvOptions {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct S3Options {
     pub region: String,
+    #[serde(serialize_with = "serialize_secret")]
     pub key_id: String,
+    #[serde(serialize_with = "serialize_secret")]
     pub key_secret: String,
     pub endpoint: String,
     pub bucket: String,
@@ -14,6 +14,7 @@ use std::{
 
 use jemalloc_ctl::{Access, AsName};
 use log::{error, info};
+use pprof::protos::Message;
 
 #[derive(Debug)]
 pub enum Error {
@@ -32,6 +33,16 @@ impl std::error::Error for Error {}
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Output format of the cpu profiling data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CpuProfileFormat {
+    /// Protobuf encoded pprof profile.
+    #[default]
+    Pprof,
+    /// Flamegraph rendered in svg.
+    Flamegraph,
+}
+
 #[global_allocator]
 static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
@@ -39,7 +50,6 @@ const PROF_ACTIVE: &[u8] = b"prof.active\0";
 const PROF_DUMP: &[u8] = b"prof.dump\0";
 const PROFILE_HEAP_OUTPUT_FILE_OS_PATH: &[u8] = b"/tmp/profile_heap.out\0";
 const PROFILE_HEAP_OUTPUT_FILE_PATH: &str = "/tmp/profile_heap.out";
-const PROFILE_CPU_OUTPUT_FILE_PATH: &str = "/tmp/flamegraph_cpu.svg";
 
 fn set_prof_active(active: bool) -> Result<()> {
     let name = PROF_ACTIVE.name();
@@ -144,7 +154,9 @@ impl Profiler {
         Ok(buffer)
     }
 
-    pub fn dump_cpu_prof(&self, seconds: u64) -> Result<()> {
+    // dump_cpu_prof collects cpu profiling data in `seconds` and returns it in
+    // the given `format`.
+    pub fn dump_cpu_prof(&self, seconds: u64, format: CpuProfileFormat) -> Result<Vec<u8>> {
         let guard = pprof::ProfilerGuardBuilder::default()
             .frequency(100)
             .blocklist(&["libc", "libgcc", "pthread", "vdso"])
@@ -158,13 +170,55 @@ impl Profiler {
         let report = guard.report().build().map_err(|e| Error::Internal {
             msg: format!("Report build, err:{e}"),
         })?;
-        let file = File::create(PROFILE_CPU_OUTPUT_FILE_PATH).map_err(|e| {
-            error!("Failed to create cpu profile svg file, err:{}", e);
-            Error::IO(e)
-        })?;
-        report.flamegraph(file).map_err(|e| Error::Internal {
-            msg: format!("Flamegraph output, err:{e}"),
-        })?;
-        Ok(())
+
+        let mut buffer = Vec::new();
+        match format {
+            CpuProfileFormat::Pprof => {
+                let profile = report.pprof().map_err(|e| Error::Internal {
+                    msg: format!("Pprof output, err:{e}"),
+                })?;
+                profile.encode(&mut buffer).map_err(|e| Error::Internal {
+                    msg: format!("Pprof encode, err:{e}"),
+                })?;
+            }
+            CpuProfileFormat::Flamegraph => {
+                report
+                    .flamegraph(&mut buffer)
+                    .map_err(|e| Error::Internal {
+                        msg: format!("Flamegraph output, err:{e}"),
+                    })?;
+            }
+        }
+
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn busy_loop(duration: Duration) {
+        let start = time::Instant::now();
+        let mut n = 0u64;
+        while start.elapsed() < duration {
+            n = n.wrapping_add(1);
+            std::hint::black_box(n);
+        }
+    }
+
+    #[test]
+    fn test_dump_cpu_prof() {
+        let profiler = Profiler::new();
+        for format in [CpuProfileFormat::Pprof, CpuProfileFormat::Flamegraph] {
+            let worker = thread::spawn(|| busy_loop(Duration::from_secs(1)));
+            let data = profiler.dump_cpu_prof(1, format).unwrap();
+            worker.join().unwrap();
+
+            assert!(!data.is_empty(), "format:{format:?}");
+            if format == CpuProfileFormat::Flamegraph {
+                assert!(String::from_utf8_lossy(&data).contains("<svg"));
+            }
+        }
     }
 }
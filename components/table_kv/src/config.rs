@@ -2,7 +2,7 @@
 
 //! Config of table kv.
 
-use common_util::config::ReadableDuration;
+use common_util::config::{serialize_secret, ReadableDuration};
 use serde::{Deserialize, Serialize};
 
 // TODO: use test conf to control which environments to test.
@@ -18,6 +18,7 @@ const TEST_SYS_PASSWORD: &str = "";
 pub struct ObkvConfig {
     pub full_user_name: String,
     pub param_url: String,
+    #[serde(serialize_with = "serialize_secret")]
     pub password: String,
     pub check_batch_result_num: bool,
     pub enable_purge_recyclebin: bool,
@@ -96,6 +97,7 @@ impl From<u16> for ObLogLevel {
 #[serde(default)]
 pub struct ClientConfig {
     pub sys_user_name: String,
+    #[serde(serialize_with = "serialize_secret")]
     pub sys_password: String,
     pub metadata_refresh_interval: ReadableDuration,
     pub ocp_model_cache_file: String,
@@ -108,7 +108,7 @@ impl Interpreter for InsertInterpreter {
         // Context is unused now
         let _ctx = self.ctx;
 
-        let request = WriteRequest { row_group: rows };
+        let request = WriteRequest::new(rows);
 
         let num_rows = table
             .write(request)
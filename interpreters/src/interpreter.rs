@@ -3,8 +3,11 @@
 //! Interpreter trait
 
 use async_trait::async_trait;
+use common_types::record_batch::RecordBatch;
+use futures::{stream, stream::BoxStream, StreamExt};
 use query_engine::executor::RecordBatchVec;
 use snafu::Snafu;
+use table_engine::stream::SendableRecordBatchStream;
 
 // Make the variant closer to actual error code like invalid arguments.
 #[derive(Debug, Snafu)]
@@ -70,10 +73,34 @@ impl TryFrom<Output> for RecordBatchVec {
     }
 }
 
+/// The interpreter output whose record batches are yielded one by one
+pub enum StreamOutput {
+    /// Affected rows number
+    AffectedRows(usize),
+    /// A stream of RecordBatch
+    Records(BoxStream<'static, table_engine::stream::Result<RecordBatch>>),
+}
+
 /// Interpreter executes the plan it holds
 #[async_trait]
-pub trait Interpreter {
+pub trait Interpreter: Send {
     async fn execute(self: Box<Self>) -> Result<Output>;
+
+    /// Execute the plan and yield the record batches of the output as they are
+    /// produced.
+    ///
+    /// Only the select interpreter streams its output, and the outputs of the
+    /// others are small so they are collected by [Interpreter::execute].
+    async fn execute_stream(self: Box<Self>) -> Result<StreamOutput> {
+        let output = match self.execute().await? {
+            Output::AffectedRows(n) => StreamOutput::AffectedRows(n),
+            Output::Records(records) => {
+                StreamOutput::Records(stream::iter(records.into_iter().map(Ok)).boxed())
+            }
+        };
+
+        Ok(output)
+    }
 }
 
 /// A pointer to Interpreter
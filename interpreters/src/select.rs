@@ -3,6 +3,7 @@
 //! Interpreter for select statement
 
 use async_trait::async_trait;
+use futures::StreamExt;
 use log::debug;
 use query_engine::executor::{Executor, Query};
 use query_frontend::plan::QueryPlan;
@@ -10,7 +11,9 @@ use snafu::{ResultExt, Snafu};
 
 use crate::{
     context::Context,
-    interpreter::{Interpreter, InterpreterPtr, Output, Result as InterpreterResult, Select},
+    interpreter::{
+        Interpreter, InterpreterPtr, Output, Result as InterpreterResult, Select, StreamOutput,
+    },
 };
 
 #[derive(Debug, Snafu)]
@@ -72,4 +75,27 @@ impl<T: Executor> Interpreter for SelectInterpreter<T> {
 
         Ok(Output::Records(record_batches))
     }
+
+    async fn execute_stream(self: Box<Self>) -> InterpreterResult<StreamOutput> {
+        let request_id = self.ctx.request_id();
+        debug!(
+            "Interpreter execute select in stream begin, request_id:{}, plan:{:?}",
+            request_id, self.plan
+        );
+
+        let query_ctx = self
+            .ctx
+            .new_query_context()
+            .context(CreateQueryContext)
+            .context(Select)?;
+        let query = Query::new(self.plan);
+        let stream = self
+            .executor
+            .execute_logical_plan_stream(query_ctx, query)
+            .await
+            .context(ExecutePlan)
+            .context(Select)?;
+
+        Ok(StreamOutput::Records(stream.boxed()))
+    }
 }
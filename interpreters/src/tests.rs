@@ -10,6 +10,7 @@ use catalog::{
 };
 use catalog_impls::table_based::TableBasedManager;
 use common_types::request_id::RequestId;
+use futures::TryStreamExt;
 use query_engine::{executor::ExecutorImpl, Config as QueryConfig};
 use query_frontend::{
     parser::Parser, plan::Plan, planner::Planner, provider::MetaProvider, tests::MockMetaProvider,
@@ -19,7 +20,7 @@ use table_engine::engine::TableEngineRef;
 use crate::{
     context::Context,
     factory::Factory,
-    interpreter::{Output, Result},
+    interpreter::{Output, Result, StreamOutput},
     table_manipulator::{catalog_based::TableManipulatorImpl, TableManipulatorRef},
 };
 
@@ -271,6 +272,26 @@ where
             .unwrap();
     }
 
+    async fn test_select_table_stream(&self) {
+        let ctx = Context::builder(RequestId::next_id(), None)
+            .default_catalog_and_schema(DEFAULT_CATALOG.to_string(), DEFAULT_SCHEMA.to_string())
+            .build();
+        let plan = sql_to_plan(&self.meta_provider, "select count(*) from test_table");
+        let interpreter = self.build_factory().await.create(ctx, plan).unwrap();
+        let records = match interpreter.execute_stream().await.unwrap() {
+            StreamOutput::Records(records) => records.try_collect().await.unwrap(),
+            StreamOutput::AffectedRows(_) => panic!("Unexpected affected rows"),
+        };
+        let expected = vec![
+            "+-----------------+",
+            "| COUNT(UInt8(1)) |",
+            "+-----------------+",
+            "| 2               |",
+            "+-----------------+",
+        ];
+        common_util::record_batch::assert_record_batches_eq(&expected, records);
+    }
+
     async fn test_show_create_table(&self) {
         let sql = "show create table test_table";
         let output = self.sql_to_output(sql).await.unwrap();
@@ -365,6 +386,7 @@ async fn test_interpreters<T: EngineBuildContext>(engine_context: T) {
     env.test_exists_table().await;
     env.test_insert_table().await;
     env.test_select_table().await;
+    env.test_select_table_stream().await;
     env.test_show_create_table().await;
     env.test_alter_table().await;
     env.test_drop_table().await;
@@ -9,7 +9,7 @@ use types::{
     AllocSchemaIdRequest, AllocSchemaIdResponse, CreateTableRequest, CreateTableResponse,
     DropTableRequest, DropTableResponse, GetNodesRequest, GetNodesResponse,
     GetTablesOfShardsRequest, GetTablesOfShardsResponse, RouteTablesRequest, RouteTablesResponse,
-    ShardInfo,
+    ShardInfo, ShardStats,
 };
 
 pub mod meta_impl;
@@ -78,6 +78,23 @@ pub enum Error {
 
 define_result!(Error);
 
+impl Error {
+    /// Whether the error is caused by a failed rpc to the CeresMeta, which may
+    /// succeed if retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Error::FailConnect { .. }
+                | Error::FailSendHeartbeat { .. }
+                | Error::FailAllocSchemaId { .. }
+                | Error::FailCreateTable { .. }
+                | Error::FailDropTable { .. }
+                | Error::FailGetTables { .. }
+                | Error::FailRouteTables { .. }
+        )
+    }
+}
+
 /// MetaClient is the abstraction of client used to communicate with CeresMeta
 /// cluster.
 #[async_trait]
@@ -97,7 +114,13 @@ pub trait MetaClient: Send + Sync {
 
     async fn get_nodes(&self, req: GetNodesRequest) -> Result<GetNodesResponse>;
 
-    async fn send_heartbeat(&self, req: Vec<ShardInfo>) -> Result<()>;
+    /// Send the heartbeat with the shards on the node, and the load stats of
+    /// the shards are attached if provided.
+    async fn send_heartbeat(
+        &self,
+        shard_infos: Vec<ShardInfo>,
+        shard_stats: Option<Vec<ShardStats>>,
+    ) -> Result<()>;
 }
 
 pub type MetaClientRef = Arc<dyn MetaClient>;
@@ -17,7 +17,7 @@ use crate::{
         AllocSchemaIdRequest, AllocSchemaIdResponse, CreateTableRequest, CreateTableResponse,
         DropTableRequest, DropTableResponse, GetNodesRequest, GetNodesResponse,
         GetTablesOfShardsRequest, GetTablesOfShardsResponse, NodeInfo, NodeMetaInfo, RequestHeader,
-        RouteTablesRequest, RouteTablesResponse, ShardInfo,
+        RouteTablesRequest, RouteTablesResponse, ShardInfo, ShardStats,
     },
     BadResponse, FailAllocSchemaId, FailConnect, FailCreateTable, FailDropTable, FailGetTables,
     FailRouteTables, FailSendHeartbeat, MetaClient, MetaClientRef, MissingHeader, Result,
@@ -220,10 +220,19 @@ impl MetaClient for MetaClientImpl {
         GetNodesResponse::try_from(pb_resp)
     }
 
-    async fn send_heartbeat(&self, shard_infos: Vec<ShardInfo>) -> Result<()> {
+    async fn send_heartbeat(
+        &self,
+        shard_infos: Vec<ShardInfo>,
+        shard_stats: Option<Vec<ShardStats>>,
+    ) -> Result<()> {
+        if let Some(shard_stats) = &shard_stats {
+            debug!("Meta client try to send heartbeat with shard stats:{shard_stats:?}");
+        }
+
         let node_info = NodeInfo {
             node_meta_info: self.node_meta_info.clone(),
             shard_infos,
+            shard_stats,
         };
         let pb_req = meta_service::NodeHeartbeatRequest {
             header: Some(self.request_header().into()),
@@ -159,6 +159,7 @@ impl NodeMetaInfo {
 pub struct NodeInfo {
     pub node_meta_info: NodeMetaInfo,
     pub shard_infos: Vec<ShardInfo>,
+    pub shard_stats: Option<Vec<ShardStats>>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
@@ -175,6 +176,15 @@ impl ShardInfo {
     }
 }
 
+/// Load stats of a shard, which helps CeresMeta to rebalance the shards.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ShardStats {
+    pub id: ShardId,
+    pub table_count: u64,
+    /// Approximate memory usage in bytes of the memtables of the tables
+    pub memtable_memory_usage: u64,
+}
+
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
 pub enum ShardRole {
     #[default]
@@ -209,6 +219,8 @@ impl Default for MetaClientConfig {
 }
 
 impl From<NodeInfo> for meta_service_pb::NodeInfo {
+    // TODO: carry the shard stats after the field is added to the `NodeInfo` of
+    // ceresdbproto, and they are only logged by the meta client now.
     fn from(node_info: NodeInfo) -> Self {
         let shard_infos = node_info
             .shard_infos
@@ -163,7 +163,7 @@ impl Table for PartitionTableImpl {
 
             let request = RemoteWriteRequest {
                 table: sub_table_ident,
-                write_request: WriteRequest { row_group },
+                write_request: WriteRequest::new(row_group),
             };
             request_batch.push(request);
         }
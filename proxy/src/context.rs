@@ -4,6 +4,7 @@
 
 use std::time::Duration;
 
+use common_types::request_id::RequestId;
 use common_util::define_result;
 use snafu::{ensure, Backtrace, Snafu};
 
@@ -39,6 +40,11 @@ pub struct RequestContext {
     pub enable_partition_table_access: bool,
     /// Request timeout
     pub timeout: Option<Duration>,
+    /// Id assigned to the request by the server
+    pub request_id: RequestId,
+    /// Id to trace the request across services, carried by the `X-Request-Id`
+    /// header of the http request
+    pub trace_id: Option<String>,
 }
 
 impl RequestContext {
@@ -53,6 +59,7 @@ pub struct Builder {
     schema: String,
     enable_partition_table_access: bool,
     timeout: Option<Duration>,
+    trace_id: Option<String>,
 }
 
 impl Builder {
@@ -76,6 +83,11 @@ impl Builder {
         self
     }
 
+    pub fn trace_id(mut self, trace_id: Option<String>) -> Self {
+        self.trace_id = trace_id;
+        self
+    }
+
     pub fn build(self) -> Result<RequestContext> {
         ensure!(!self.catalog.is_empty(), MissingCatalog);
         ensure!(!self.schema.is_empty(), MissingSchema);
@@ -85,6 +97,8 @@ impl Builder {
             schema: self.schema,
             enable_partition_table_access: self.enable_partition_table_access,
             timeout: self.timeout,
+            request_id: RequestId::next_id(),
+            trace_id: self.trace_id,
         })
     }
 }
@@ -12,7 +12,7 @@ use ceresdbproto::{
         ArrowPayload, SqlQueryRequest, SqlQueryResponse,
     },
 };
-use common_types::record_batch::RecordBatch;
+use common_types::{record_batch::RecordBatch, request_id::RequestId};
 use common_util::error::BoxError;
 use futures::{stream, stream::BoxStream, FutureExt, StreamExt};
 use http::StatusCode;
@@ -29,7 +29,7 @@ use crate::{
     error::{self, ErrNoCause, ErrWithCause, Error, Result},
     forward::{ForwardRequest, ForwardResult},
     metrics::GRPC_HANDLER_COUNTER_VEC,
-    read::SqlResponse,
+    read::{SqlResponse, StreamOutput},
     Context, Proxy,
 };
 
@@ -69,7 +69,10 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
 
         let req_context = req.context.as_ref().unwrap();
         let schema = &req_context.database;
-        match self.handle_sql(ctx, schema, &req.sql).await? {
+        match self
+            .handle_sql(ctx, RequestId::next_id(), schema, &req.sql)
+            .await?
+        {
             SqlResponse::Forwarded(resp) => Ok(resp),
             SqlResponse::Local(output) => convert_output(&output, self.resp_compress_min_length),
         }
@@ -130,11 +133,11 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
         let resp_compress_min_length = self.resp_compress_min_length;
         let output = self
             .as_ref()
-            .fetch_sql_query_output(ctx, &schema, &req.sql)
+            .fetch_sql_query_stream(ctx, RequestId::next_id(), &schema, &req.sql)
             .await?;
         runtime.spawn(async move {
             match output {
-                Output::AffectedRows(rows) => {
+                StreamOutput::AffectedRows(rows) => {
                     let resp =
                         QueryResponseBuilder::with_ok_header().build_with_affected_rows(rows);
                     if tx.send(resp).await.is_err() {
@@ -144,12 +147,13 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
                         .query_affected_row
                         .inc_by(rows as u64);
                 }
-                Output::Records(batches) => {
+                StreamOutput::Records(mut batches) => {
                     let mut num_rows = 0;
-                    for batch in &batches {
+                    while let Some(batch) = batches.next().await {
+                        let batch = batch?;
                         let resp = {
                             let mut writer = QueryResponseWriter::new(resp_compress_min_length);
-                            writer.write(batch)?;
+                            writer.write(&batch)?;
                             writer.finish()
                         }?;
 
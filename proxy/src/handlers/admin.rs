@@ -2,7 +2,9 @@
 
 use std::collections::BTreeSet;
 
-use crate::{handlers::prelude::*, limiter::BlockRule};
+use log::info;
+
+use crate::{handlers::prelude::*, limiter::BlockRule, running_query::RunningQueryInfo};
 
 #[derive(Debug, Deserialize)]
 pub enum Operation {
@@ -62,3 +64,33 @@ pub async fn handle_block<Q: QueryExecutor + 'static>(
         block_rules: limiter.get_block_rules().into_iter().collect(),
     })
 }
+
+pub async fn handle_list_queries<Q: QueryExecutor + 'static>(
+    _ctx: RequestContext,
+    instance: InstanceRef<Q>,
+) -> Result<Vec<RunningQueryInfo>> {
+    Ok(instance.running_queries.list())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelQueryRequest {
+    pub id: u64,
+}
+
+#[derive(Serialize)]
+pub struct CancelQueryResponse {
+    cancelled: bool,
+}
+
+pub async fn handle_cancel_query<Q: QueryExecutor + 'static>(
+    _ctx: RequestContext,
+    instance: InstanceRef<Q>,
+    request: CancelQueryRequest,
+) -> Result<CancelQueryResponse> {
+    let cancelled = instance.running_queries.cancel(request.id);
+    if cancelled {
+        info!("Query is cancelled by admin, request_id:{}", request.id);
+    }
+
+    Ok(CancelQueryResponse { cancelled })
+}
@@ -1,5 +1,7 @@
 // Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
 
 pub mod prom;
+mod prom_chunk;
+pub mod prom_query;
 pub mod route;
 pub mod sql;
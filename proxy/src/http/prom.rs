@@ -7,10 +7,12 @@
 use std::{
     collections::HashMap,
     result::Result as StdResult,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
+use bytes::Bytes;
 use catalog::consts::DEFAULT_CATALOG;
 use ceresdbproto::storage::{
     value, Field, FieldGroup, PrometheusRemoteQueryRequest, PrometheusRemoteQueryResponse,
@@ -23,11 +25,13 @@ use common_types::{
     schema::{RecordSchema, TSID_COLUMN},
 };
 use common_util::{error::BoxError, time::InstantExt};
+use futures::{stream, Stream, TryStreamExt};
 use http::StatusCode;
 use interpreters::interpreter::Output;
 use log::debug;
 use prom_remote_api::types::{
-    Label, LabelMatcher, Query, QueryResult, RemoteStorage, Sample, TimeSeries, WriteRequest,
+    read_request::ResponseType, Label, LabelMatcher, Query, QueryResult, ReadRequest,
+    RemoteStorage, Sample, TimeSeries, WriteRequest,
 };
 use prost::Message;
 use query_engine::executor::{Executor as QueryExecutor, RecordBatchVec};
@@ -43,10 +47,15 @@ use crate::{
     context::RequestContext,
     error::{build_ok_header, ErrNoCause, ErrWithCause, Error, Internal, InternalNoCause, Result},
     forward::ForwardResult,
+    http::prom_chunk,
     metrics::HTTP_HANDLER_COUNTER_VEC,
     Context as ProxyContext, Proxy,
 };
 
+/// Content type of the streamed XOR chunks response of the remote read.
+pub const STREAMED_CHUNKS_CONTENT_TYPE: &str =
+    "application/x-streamed-protobuf; proto=prometheus.ChunkedReadResponse";
+
 impl reject::Reject for Error {}
 
 impl<Q: QueryExecutor + 'static> Proxy<Q> {
@@ -242,6 +251,56 @@ impl<Q: QueryExecutor + 'static> RemoteStorage for Proxy<Q> {
     }
 }
 
+impl<Q: QueryExecutor + 'static> Proxy<Q> {
+    /// Handle the read request with the streamed XOR chunks response, every
+    /// item of the returned stream is a frame of `ChunkedReadResponse`.
+    ///
+    /// The queries are processed one by one so that only the series of one
+    /// query are kept in memory.
+    pub fn handle_prom_read_streamed_chunks(
+        self: Arc<Self>,
+        ctx: RequestContext,
+        req: ReadRequest,
+    ) -> impl Stream<Item = Result<Bytes>> + Send {
+        let state = (self, ctx, req.queries.into_iter().enumerate());
+        stream::unfold(state, |(proxy, ctx, mut queries)| async move {
+            let (query_index, query) = queries.next()?;
+            let frames = proxy
+                .process_query(&ctx, query)
+                .await
+                .map(|result| convert_query_result_to_frames(query_index as i64, result));
+
+            Some((frames, (proxy, ctx, queries)))
+        })
+        .map_ok(|frames| stream::iter(frames.into_iter().map(Ok)))
+        .try_flatten()
+    }
+}
+
+/// Returns true if the client prefers the streamed XOR chunks response.
+///
+/// The response types accepted by the client are ordered by its preference,
+/// and the unknown types are skipped.
+pub fn accept_streamed_chunks(req: &ReadRequest) -> bool {
+    req.accepted_response_types
+        .iter()
+        .find_map(|v| ResponseType::from_i32(*v))
+        .map(|v| v == ResponseType::StreamedXorChunks)
+        .unwrap_or(false)
+}
+
+fn convert_query_result_to_frames(query_index: i64, result: QueryResult) -> Vec<Bytes> {
+    result
+        .timeseries
+        .into_iter()
+        .flat_map(|mut series| {
+            series.labels.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+            series.samples.sort_by_key(|sample| sample.timestamp);
+            prom_chunk::encode_series_frames(query_index, series.labels, &series.samples)
+        })
+        .collect()
+}
+
 /// Converter converts Arrow's RecordBatch into Prometheus's QueryResult
 struct Converter {
     tsid_idx: usize,
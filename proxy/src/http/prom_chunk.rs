@@ -0,0 +1,443 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Encoder of the streamed XOR chunks for the prometheus remote read.
+//!
+//! The chunk is encoded in the same way as the XOR chunk of the prometheus
+//! tsdb, and the frame is encoded in the same way as the `ChunkedWriter` of
+//! prometheus, see:
+//!   - https://github.com/prometheus/prometheus/blob/main/tsdb/chunkenc/xor.go
+//!   - https://github.com/prometheus/prometheus/blob/main/storage/remote/chunked.go
+
+use bytes::{BufMut, Bytes, BytesMut};
+use crc::{Crc, CRC_32_ISCSI};
+use prom_remote_api::types::{
+    chunk::Encoding, Chunk, ChunkedReadResponse, ChunkedSeries, Label, Sample,
+};
+use prost::Message;
+
+/// Max samples in a chunk, the same as prometheus.
+const MAX_SAMPLES_PER_CHUNK: usize = 120;
+/// Max bytes of the chunks in a frame, the same as prometheus.
+const MAX_BYTES_IN_FRAME: usize = 1024 * 1024;
+
+const CASTAGNOLI: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+/// Writer of a bit stream, the bits are written from the most significant bit
+/// of each byte.
+#[derive(Default)]
+struct BitWriter {
+    buf: Vec<u8>,
+    /// Number of bits available in the last byte.
+    remaining: u8,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.remaining == 0 {
+            self.buf.push(0);
+            self.remaining = 8;
+        }
+        if bit {
+            *self.buf.last_mut().unwrap() |= 1 << (self.remaining - 1);
+        }
+        self.remaining -= 1;
+    }
+
+    /// Write the lowest `nbits` bits of `value`.
+    fn write_bits(&mut self, value: u64, nbits: u8) {
+        for i in (0..nbits).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    fn write_uvarint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.write_bits((value as u8 | 0x80) as u64, 8);
+            value >>= 7;
+        }
+        self.write_bits(value, 8);
+    }
+
+    fn write_varint(&mut self, value: i64) {
+        self.write_uvarint(((value << 1) ^ (value >> 63)) as u64);
+    }
+}
+
+/// Returns true if `value` can be encoded in `nbits` bits as the delta of delta
+/// of the timestamps.
+fn in_bit_range(value: i64, nbits: u8) -> bool {
+    -((1 << (nbits - 1)) - 1) <= value && value <= 1 << (nbits - 1)
+}
+
+/// Encoder of the XOR chunk.
+struct XorChunkEncoder {
+    writer: BitWriter,
+    num_samples: u16,
+    min_time: i64,
+    prev_timestamp: i64,
+    prev_delta: u64,
+    prev_value: f64,
+    /// Leading zeros of the previous value delta, 0xff means no previous delta.
+    leading: u8,
+    trailing: u8,
+}
+
+impl XorChunkEncoder {
+    fn new() -> Self {
+        Self {
+            writer: BitWriter::default(),
+            num_samples: 0,
+            min_time: 0,
+            prev_timestamp: 0,
+            prev_delta: 0,
+            prev_value: 0.0,
+            leading: 0xff,
+            trailing: 0,
+        }
+    }
+
+    fn append(&mut self, timestamp: i64, value: f64) {
+        match self.num_samples {
+            0 => {
+                self.min_time = timestamp;
+                self.writer.write_varint(timestamp);
+                self.writer.write_bits(value.to_bits(), 64);
+            }
+            1 => {
+                let delta = timestamp.wrapping_sub(self.prev_timestamp) as u64;
+                self.writer.write_uvarint(delta);
+                self.write_value_delta(value);
+                self.prev_delta = delta;
+            }
+            _ => {
+                let delta = timestamp.wrapping_sub(self.prev_timestamp) as u64;
+                let dod = delta.wrapping_sub(self.prev_delta) as i64;
+                if dod == 0 {
+                    self.writer.write_bit(false);
+                } else if in_bit_range(dod, 14) {
+                    self.writer.write_bits(0b10, 2);
+                    self.writer.write_bits(dod as u64, 14);
+                } else if in_bit_range(dod, 17) {
+                    self.writer.write_bits(0b110, 3);
+                    self.writer.write_bits(dod as u64, 17);
+                } else if in_bit_range(dod, 20) {
+                    self.writer.write_bits(0b1110, 4);
+                    self.writer.write_bits(dod as u64, 20);
+                } else {
+                    self.writer.write_bits(0b1111, 4);
+                    self.writer.write_bits(dod as u64, 64);
+                }
+                self.write_value_delta(value);
+                self.prev_delta = delta;
+            }
+        }
+
+        self.prev_timestamp = timestamp;
+        self.prev_value = value;
+        self.num_samples += 1;
+    }
+
+    fn write_value_delta(&mut self, value: f64) {
+        let delta = value.to_bits() ^ self.prev_value.to_bits();
+        if delta == 0 {
+            self.writer.write_bit(false);
+            return;
+        }
+        self.writer.write_bit(true);
+
+        // The leading zeros are encoded in 5 bits.
+        let leading = (delta.leading_zeros() as u8).min(31);
+        let trailing = delta.trailing_zeros() as u8;
+        if self.leading != 0xff && leading >= self.leading && trailing >= self.trailing {
+            // The meaningful bits fall within the previous ones.
+            self.writer.write_bit(false);
+            self.writer
+                .write_bits(delta >> self.trailing, 64 - self.leading - self.trailing);
+        } else {
+            self.leading = leading;
+            self.trailing = trailing;
+            let sig_bits = 64 - leading - trailing;
+            self.writer.write_bit(true);
+            self.writer.write_bits(leading as u64, 5);
+            // The 64 significant bits overflow to 0, and it is never ambiguous
+            // because the delta is not zero.
+            self.writer.write_bits(sig_bits as u64, 6);
+            self.writer.write_bits(delta >> trailing, sig_bits);
+        }
+    }
+
+    fn finish(self) -> Chunk {
+        let mut data = Vec::with_capacity(2 + self.writer.buf.len());
+        data.extend_from_slice(&self.num_samples.to_be_bytes());
+        data.extend_from_slice(&self.writer.buf);
+
+        Chunk {
+            min_time_ms: self.min_time,
+            max_time_ms: self.prev_timestamp,
+            r#type: Encoding::Xor as i32,
+            data,
+        }
+    }
+}
+
+/// Encode the samples of a series into XOR chunks.
+///
+/// REQUIRE: the samples are sorted by timestamp.
+fn encode_xor_chunks(samples: &[Sample]) -> Vec<Chunk> {
+    samples
+        .chunks(MAX_SAMPLES_PER_CHUNK)
+        .map(|samples| {
+            let mut encoder = XorChunkEncoder::new();
+            for sample in samples {
+                encoder.append(sample.timestamp, sample.value);
+            }
+            encoder.finish()
+        })
+        .collect()
+}
+
+/// Encode a frame of the streamed response, which is the uvarint length of the
+/// message, followed by the big endian CRC32 (Castagnoli) of the message, and
+/// then the message itself.
+fn encode_frame(resp: &ChunkedReadResponse) -> Bytes {
+    let msg = resp.encode_to_vec();
+    let mut buf = BytesMut::with_capacity(msg.len() + 14);
+    prost::encoding::encode_varint(msg.len() as u64, &mut buf);
+    buf.put_u32(CASTAGNOLI.checksum(&msg));
+    buf.put_slice(&msg);
+
+    buf.freeze()
+}
+
+/// Encode a series of the query into frames, and the series is splitted into
+/// multiple frames if its chunks are too large.
+///
+/// REQUIRE: the labels are sorted by name and the samples are sorted by
+/// timestamp.
+pub(crate) fn encode_series_frames(
+    query_index: i64,
+    labels: Vec<Label>,
+    samples: &[Sample],
+) -> Vec<Bytes> {
+    let mut frames = Vec::new();
+    let mut chunks = Vec::new();
+    let mut frame_bytes = 0;
+    for chunk in encode_xor_chunks(samples) {
+        frame_bytes += chunk.data.len();
+        chunks.push(chunk);
+
+        if frame_bytes >= MAX_BYTES_IN_FRAME {
+            frames.push(encode_frame(&ChunkedReadResponse {
+                chunked_series: vec![ChunkedSeries {
+                    labels: labels.clone(),
+                    chunks: std::mem::take(&mut chunks),
+                }],
+                query_index,
+            }));
+            frame_bytes = 0;
+        }
+    }
+
+    if !chunks.is_empty() {
+        frames.push(encode_frame(&ChunkedReadResponse {
+            chunked_series: vec![ChunkedSeries { labels, chunks }],
+            query_index,
+        }));
+    }
+
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader of the bit stream written by [BitWriter].
+    struct BitReader<'a> {
+        buf: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> BitReader<'a> {
+        fn read_bit(&mut self) -> bool {
+            let bit = (self.buf[self.pos / 8] >> (7 - self.pos % 8)) & 1 == 1;
+            self.pos += 1;
+            bit
+        }
+
+        fn read_bits(&mut self, nbits: u8) -> u64 {
+            (0..nbits).fold(0, |acc, _| (acc << 1) | self.read_bit() as u64)
+        }
+
+        fn read_uvarint(&mut self) -> u64 {
+            let mut value = 0;
+            for shift in (0..).step_by(7) {
+                let byte = self.read_bits(8);
+                value |= (byte & 0x7f) << shift;
+                if byte < 0x80 {
+                    break;
+                }
+            }
+            value
+        }
+
+        fn read_varint(&mut self) -> i64 {
+            let value = self.read_uvarint();
+            ((value >> 1) as i64) ^ -((value & 1) as i64)
+        }
+    }
+
+    fn sign_extend(value: u64, nbits: u8) -> i64 {
+        let shift = 64 - nbits;
+        ((value << shift) as i64) >> shift
+    }
+
+    /// Decode the XOR chunk in the same way as prometheus.
+    fn decode_xor_chunk(data: &[u8]) -> Vec<(i64, f64)> {
+        let num_samples = u16::from_be_bytes([data[0], data[1]]);
+        let mut reader = BitReader {
+            buf: &data[2..],
+            pos: 0,
+        };
+
+        let mut samples = Vec::new();
+        let (mut timestamp, mut value_bits, mut delta) = (0i64, 0u64, 0u64);
+        let (mut leading, mut trailing) = (0u8, 0u8);
+        for i in 0..num_samples {
+            if i == 0 {
+                timestamp = reader.read_varint();
+                value_bits = reader.read_bits(64);
+                samples.push((timestamp, f64::from_bits(value_bits)));
+                continue;
+            }
+
+            if i == 1 {
+                delta = reader.read_uvarint();
+            } else {
+                let dod = if !reader.read_bit() {
+                    0
+                } else if !reader.read_bit() {
+                    sign_extend(reader.read_bits(14), 14)
+                } else if !reader.read_bit() {
+                    sign_extend(reader.read_bits(17), 17)
+                } else if !reader.read_bit() {
+                    sign_extend(reader.read_bits(20), 20)
+                } else {
+                    reader.read_bits(64) as i64
+                };
+                delta = (delta as i64 + dod) as u64;
+            }
+            timestamp += delta as i64;
+
+            if reader.read_bit() {
+                if reader.read_bit() {
+                    leading = reader.read_bits(5) as u8;
+                    let mut sig_bits = reader.read_bits(6) as u8;
+                    if sig_bits == 0 {
+                        sig_bits = 64;
+                    }
+                    trailing = 64 - leading - sig_bits;
+                }
+                let sig_bits = 64 - leading - trailing;
+                value_bits ^= reader.read_bits(sig_bits) << trailing;
+            }
+            samples.push((timestamp, f64::from_bits(value_bits)));
+        }
+
+        samples
+    }
+
+    #[test]
+    fn test_encode_xor_chunks() {
+        let timestamps = [
+            1000,
+            2000,
+            3000,
+            3001,
+            10000,
+            20000,
+            1_000_000,
+            1_000_000_000,
+            1_000_000_001,
+        ];
+        let values = [
+            1.0,
+            1.0,
+            2.5,
+            -3.75,
+            0.0,
+            1e100,
+            f64::MIN_POSITIVE,
+            42.0,
+            42.5,
+        ];
+        let mut samples: Vec<_> = timestamps
+            .iter()
+            .zip(values)
+            .map(|(timestamp, value)| Sample {
+                value,
+                timestamp: *timestamp,
+            })
+            .collect();
+        // Make the samples cross multiple chunks.
+        for i in 0..MAX_SAMPLES_PER_CHUNK as i64 * 2 {
+            samples.push(Sample {
+                value: i as f64 * 0.5,
+                timestamp: 2_000_000_000 + i * 15000,
+            });
+        }
+
+        let chunks = encode_xor_chunks(&samples);
+        assert_eq!(3, chunks.len());
+
+        let mut decoded = Vec::new();
+        for chunk in &chunks {
+            assert_eq!(Encoding::Xor as i32, chunk.r#type);
+            let chunk_samples = decode_xor_chunk(&chunk.data);
+            assert_eq!(chunk.min_time_ms, chunk_samples.first().unwrap().0);
+            assert_eq!(chunk.max_time_ms, chunk_samples.last().unwrap().0);
+            decoded.extend(chunk_samples);
+        }
+        let expect: Vec<_> = samples.iter().map(|s| (s.timestamp, s.value)).collect();
+        assert_eq!(expect, decoded);
+    }
+
+    #[test]
+    fn test_encode_series_frames() {
+        let labels = vec![Label {
+            name: "__name__".to_string(),
+            value: "up".to_string(),
+        }];
+        let samples = vec![
+            Sample {
+                value: 1.0,
+                timestamp: 1000,
+            },
+            Sample {
+                value: 0.0,
+                timestamp: 2000,
+            },
+        ];
+
+        let frames = encode_series_frames(3, labels.clone(), &samples);
+        assert_eq!(1, frames.len());
+
+        let mut frame = frames[0].clone();
+        let len = prost::encoding::decode_varint(&mut frame).unwrap() as usize;
+        let crc = u32::from_be_bytes(frame[..4].try_into().unwrap());
+        let msg = &frame[4..];
+        assert_eq!(len, msg.len());
+        assert_eq!(CASTAGNOLI.checksum(msg), crc);
+
+        let resp = ChunkedReadResponse::decode(msg).unwrap();
+        assert_eq!(3, resp.query_index);
+        assert_eq!(1, resp.chunked_series.len());
+        assert_eq!(labels, resp.chunked_series[0].labels);
+        assert_eq!(
+            vec![(1000, 1.0), (2000, 0.0)],
+            decode_xor_chunk(&resp.chunked_series[0].chunks[0].data)
+        );
+
+        assert!(encode_series_frames(0, labels, &[]).is_empty());
+    }
+}
@@ -0,0 +1,556 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! This module implements the [instant query][1] and [range query][2] of
+//! Prometheus HTTP API.
+//!
+//! Only the vector selector like `metric{label="value"}` is supported, which
+//! is executed by the remote storage query.
+//!
+//! [1]: https://prometheus.io/docs/prometheus/latest/querying/api/#instant-queries
+//! [2]: https://prometheus.io/docs/prometheus/latest/querying/api/#range-queries
+
+use std::collections::BTreeMap;
+
+use common_types::time::Timestamp;
+use common_util::config::ReadableDuration;
+use http::StatusCode;
+use log::debug;
+use prom_remote_api::types::{label_matcher, LabelMatcher, Query, RemoteStorage, Sample};
+use query_engine::executor::Executor as QueryExecutor;
+use query_frontend::promql::NAME_LABEL;
+use serde::{Deserialize, Serialize};
+use snafu::ensure;
+
+use crate::{
+    context::RequestContext,
+    error::{ErrNoCause, Error, Result},
+    Proxy,
+};
+
+/// The max time a sample is looked back for an evaluation timestamp, same as
+/// the default `query.lookback-delta` of Prometheus.
+const LOOKBACK_DELTA_MS: i64 = 5 * 60 * 1000;
+/// The max number of points of one series returned by range query, same as
+/// Prometheus.
+const MAX_POINTS_PER_SERIES: i64 = 11_000;
+
+#[derive(Debug, Deserialize)]
+pub struct InstantQueryRequest {
+    pub query: String,
+    /// Evaluation timestamp, defaults to now.
+    pub time: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RangeQueryRequest {
+    pub query: String,
+    pub start: String,
+    pub end: String,
+    pub step: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct QueryResponse {
+    status: &'static str,
+    data: QueryData,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "resultType", content = "result", rename_all = "lowercase")]
+pub enum QueryData {
+    Vector(Vec<InstantSeries>),
+    Matrix(Vec<RangeSeries>),
+}
+
+/// Sample point in the form of `[unix_seconds, "value"]`.
+type Point = (f64, String);
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct InstantSeries {
+    metric: BTreeMap<String, String>,
+    value: Point,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RangeSeries {
+    metric: BTreeMap<String, String>,
+    values: Vec<Point>,
+}
+
+impl QueryResponse {
+    fn success(data: QueryData) -> Self {
+        Self {
+            status: "success",
+            data,
+        }
+    }
+}
+
+impl<Q: QueryExecutor + 'static> Proxy<Q> {
+    pub async fn handle_prom_instant_query(
+        &self,
+        ctx: RequestContext,
+        req: InstantQueryRequest,
+    ) -> Result<QueryResponse> {
+        let matchers = parse_selector(&req.query)?;
+        let time = match &req.time {
+            Some(v) => parse_timestamp(v)?,
+            None => Timestamp::now().as_i64(),
+        };
+
+        let query = Query {
+            start_timestamp_ms: time - LOOKBACK_DELTA_MS + 1,
+            end_timestamp_ms: time,
+            matchers,
+            hints: None,
+        };
+        let result = self.process_query(&ctx, query).await?;
+        let series = result
+            .timeseries
+            .into_iter()
+            .filter_map(|mut series| {
+                series.samples.sort_by_key(|sample| sample.timestamp);
+                let value = evaluate_at(&series.samples, time)?;
+                Some(InstantSeries {
+                    metric: convert_labels(series.labels),
+                    value: (to_unix_seconds(time), format_value(value)),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        debug!(
+            "Prom instant query finished, query:{}, time:{time}, trace_id:{:?}, series:{}",
+            req.query,
+            ctx.trace_id,
+            series.len()
+        );
+
+        Ok(QueryResponse::success(QueryData::Vector(sort_series(
+            series,
+            |v| &v.metric,
+        ))))
+    }
+
+    pub async fn handle_prom_range_query(
+        &self,
+        ctx: RequestContext,
+        req: RangeQueryRequest,
+    ) -> Result<QueryResponse> {
+        let matchers = parse_selector(&req.query)?;
+        let start = parse_timestamp(&req.start)?;
+        let end = parse_timestamp(&req.end)?;
+        let step = parse_step(&req.step)?;
+        ensure!(
+            start <= end,
+            ErrNoCause {
+                code: StatusCode::BAD_REQUEST,
+                msg: format!(
+                    "End timestamp must not be before start time, start:{start}, end:{end}"
+                ),
+            }
+        );
+        ensure!(
+            (end - start) / step < MAX_POINTS_PER_SERIES,
+            ErrNoCause {
+                code: StatusCode::BAD_REQUEST,
+                msg: format!(
+                    "Exceeded maximum resolution of {MAX_POINTS_PER_SERIES} points per timeseries, start:{start}, end:{end}, step:{step}ms"
+                ),
+            }
+        );
+
+        let query = Query {
+            start_timestamp_ms: start - LOOKBACK_DELTA_MS + 1,
+            end_timestamp_ms: end,
+            matchers,
+            hints: None,
+        };
+        let result = self.process_query(&ctx, query).await?;
+        let series = result
+            .timeseries
+            .into_iter()
+            .filter_map(|mut series| {
+                series.samples.sort_by_key(|sample| sample.timestamp);
+                let values = evaluate_range(&series.samples, start, end, step);
+                if values.is_empty() {
+                    return None;
+                }
+
+                Some(RangeSeries {
+                    metric: convert_labels(series.labels),
+                    values,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        debug!(
+            "Prom range query finished, query:{}, start:{start}, end:{end}, step:{step}, trace_id:{:?}, series:{}",
+            req.query,
+            ctx.trace_id,
+            series.len()
+        );
+
+        Ok(QueryResponse::success(QueryData::Matrix(sort_series(
+            series,
+            |v| &v.metric,
+        ))))
+    }
+}
+
+/// Parse the vector selector into label matchers, the metric name can be
+/// given either before the braces or by the `__name__` label.
+fn parse_selector(query: &str) -> Result<Vec<LabelMatcher>> {
+    let query = query.trim();
+    let (metric, rest) = match query.find('{') {
+        Some(idx) => (query[..idx].trim(), &query[idx..]),
+        None => (query, ""),
+    };
+
+    let mut matchers = Vec::new();
+    if !metric.is_empty() {
+        ensure!(
+            is_valid_metric_name(metric),
+            ErrNoCause {
+                code: StatusCode::BAD_REQUEST,
+                msg: format!("Unsupported query, only vector selector is supported, query:{query}"),
+            }
+        );
+        matchers.push(new_matcher(NAME_LABEL, metric, label_matcher::Type::Eq));
+    }
+    if !rest.is_empty() {
+        matchers.extend(parse_label_matchers(rest).map_err(|msg| Error::ErrNoCause {
+            code: StatusCode::BAD_REQUEST,
+            msg: format!("Invalid label matchers, query:{query}, err:{msg}"),
+        })?);
+    }
+
+    let has_metric = matchers
+        .iter()
+        .any(|m| m.name == NAME_LABEL && m.r#type() == label_matcher::Type::Eq);
+    ensure!(
+        has_metric,
+        ErrNoCause {
+            code: StatusCode::BAD_REQUEST,
+            msg: format!("Metric name is required in query, query:{query}"),
+        }
+    );
+
+    Ok(matchers)
+}
+
+/// Parse label matchers like `{a="b", c!~"d.*"}`.
+fn parse_label_matchers(input: &str) -> std::result::Result<Vec<LabelMatcher>, String> {
+    let inner = input
+        .strip_prefix('{')
+        .and_then(|v| v.strip_suffix('}'))
+        .ok_or_else(|| "label matchers must be enclosed in braces".to_string())?;
+
+    let mut matchers = Vec::new();
+    let mut chars = inner.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c);
+        }
+        if name.is_empty() {
+            return Err(format!(
+                "invalid label name at: {}",
+                chars.collect::<String>()
+            ));
+        }
+
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let matcher_type = match (chars.next(), chars.peek()) {
+            (Some('='), Some('~')) => {
+                chars.next();
+                label_matcher::Type::Re
+            }
+            (Some('='), _) => label_matcher::Type::Eq,
+            (Some('!'), Some('=')) => {
+                chars.next();
+                label_matcher::Type::Neq
+            }
+            (Some('!'), Some('~')) => {
+                chars.next();
+                label_matcher::Type::Nre
+            }
+            _ => return Err(format!("invalid match operator of label:{name}")),
+        };
+
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.next() != Some('"') {
+            return Err(format!("label value must be quoted, label:{name}"));
+        }
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some(c) => value.push(c),
+                    None => return Err(format!("unterminated label value, label:{name}")),
+                },
+                Some(c) => value.push(c),
+                None => return Err(format!("unterminated label value, label:{name}")),
+            }
+        }
+        matchers.push(new_matcher(&name, &value, matcher_type));
+
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            Some(',') | None => {}
+            Some(c) => return Err(format!("unexpected character:{c}")),
+        }
+    }
+
+    Ok(matchers)
+}
+
+fn is_valid_metric_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+fn new_matcher(name: &str, value: &str, matcher_type: label_matcher::Type) -> LabelMatcher {
+    LabelMatcher {
+        r#type: matcher_type as i32,
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
+
+/// Parse the timestamp in unix seconds (fraction allowed) into milliseconds.
+fn parse_timestamp(v: &str) -> Result<i64> {
+    v.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .map(|v| (v * 1000.0).round() as i64)
+        .ok_or_else(|| Error::ErrNoCause {
+            code: StatusCode::BAD_REQUEST,
+            msg: format!("Invalid timestamp, only unix seconds is supported, timestamp:{v}"),
+        })
+}
+
+/// Parse the step in seconds or duration like `15s` into milliseconds.
+fn parse_step(v: &str) -> Result<i64> {
+    let step = match v.trim().parse::<f64>() {
+        Ok(secs) if secs.is_finite() => (secs * 1000.0).round() as i64,
+        Ok(_) => 0,
+        Err(_) => v
+            .parse::<ReadableDuration>()
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0),
+    };
+    ensure!(
+        step > 0,
+        ErrNoCause {
+            code: StatusCode::BAD_REQUEST,
+            msg: format!("Invalid step, it must be a positive duration or number, step:{v}"),
+        }
+    );
+
+    Ok(step)
+}
+
+/// Returns the value of the latest sample within the lookback delta before
+/// `time`, the samples must be sorted by timestamp.
+fn evaluate_at(samples: &[Sample], time: i64) -> Option<f64> {
+    let idx = samples.partition_point(|sample| sample.timestamp <= time);
+    let sample = samples.get(idx.checked_sub(1)?)?;
+    (sample.timestamp > time - LOOKBACK_DELTA_MS).then_some(sample.value)
+}
+
+fn evaluate_range(samples: &[Sample], start: i64, end: i64, step: i64) -> Vec<Point> {
+    (start..=end)
+        .step_by(step as usize)
+        .filter_map(|time| {
+            evaluate_at(samples, time).map(|v| (to_unix_seconds(time), format_value(v)))
+        })
+        .collect()
+}
+
+fn convert_labels(labels: Vec<prom_remote_api::types::Label>) -> BTreeMap<String, String> {
+    labels.into_iter().map(|l| (l.name, l.value)).collect()
+}
+
+fn sort_series<T, F>(mut series: Vec<T>, metric: F) -> Vec<T>
+where
+    F: Fn(&T) -> &BTreeMap<String, String>,
+{
+    series.sort_by(|a, b| metric(a).cmp(metric(b)));
+    series
+}
+
+fn to_unix_seconds(time: i64) -> f64 {
+    time as f64 / 1000.0
+}
+
+/// Format the sample value the same as Prometheus.
+fn format_value(v: f64) -> String {
+    if v.is_nan() {
+        "NaN".to_string()
+    } else if v == f64::INFINITY {
+        "+Inf".to_string()
+    } else if v == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prom_remote_api::types::Label;
+    use serde_json::json;
+
+    use super::*;
+
+    fn make_samples(tuples: Vec<(i64, f64)>) -> Vec<Sample> {
+        tuples
+            .into_iter()
+            .map(|(timestamp, value)| Sample { timestamp, value })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_selector() {
+        let matchers =
+            parse_selector(r#"cpu{host="a", region!="b",idc=~"c.*", app!~"d\"e"}"#).unwrap();
+        assert_eq!(
+            vec![
+                new_matcher(NAME_LABEL, "cpu", label_matcher::Type::Eq),
+                new_matcher("host", "a", label_matcher::Type::Eq),
+                new_matcher("region", "b", label_matcher::Type::Neq),
+                new_matcher("idc", "c.*", label_matcher::Type::Re),
+                new_matcher("app", "d\"e", label_matcher::Type::Nre),
+            ],
+            matchers
+        );
+
+        let matchers = parse_selector(r#"{__name__="cpu"}"#).unwrap();
+        assert_eq!(
+            vec![new_matcher(NAME_LABEL, "cpu", label_matcher::Type::Eq)],
+            matchers
+        );
+
+        for query in [
+            r#"{host="a"}"#,
+            r#"rate(cpu[5m])"#,
+            r#"cpu{host="a""#,
+            r#"cpu{host=a}"#,
+            r#"cpu{host<"a"}"#,
+        ] {
+            assert!(parse_selector(query).is_err(), "query:{query}");
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamp_and_step() {
+        assert_eq!(1680000000000, parse_timestamp("1680000000").unwrap());
+        assert_eq!(1680000000500, parse_timestamp("1680000000.5").unwrap());
+        assert!(parse_timestamp("2023-03-28T12:00:00Z").is_err());
+
+        assert_eq!(15000, parse_step("15").unwrap());
+        assert_eq!(15000, parse_step("15s").unwrap());
+        assert_eq!(60000, parse_step("1m").unwrap());
+        assert!(parse_step("0").is_err());
+        assert!(parse_step("-1").is_err());
+    }
+
+    #[test]
+    fn test_instant_query_response() {
+        let samples = make_samples(vec![(1000, 1.0), (61000, 2.5)]);
+        assert_eq!(Some(2.5), evaluate_at(&samples, 62000));
+        assert_eq!(Some(1.0), evaluate_at(&samples, 60999));
+        assert_eq!(None, evaluate_at(&samples, 999));
+        assert_eq!(None, evaluate_at(&samples, 61000 + LOOKBACK_DELTA_MS));
+
+        let series = InstantSeries {
+            metric: convert_labels(vec![
+                Label {
+                    name: NAME_LABEL.to_string(),
+                    value: "cpu".to_string(),
+                },
+                Label {
+                    name: "host".to_string(),
+                    value: "a".to_string(),
+                },
+            ]),
+            value: (
+                to_unix_seconds(62000),
+                format_value(evaluate_at(&samples, 62000).unwrap()),
+            ),
+        };
+        let resp = QueryResponse::success(QueryData::Vector(vec![series]));
+        assert_eq!(
+            json!({
+                "status": "success",
+                "data": {
+                    "resultType": "vector",
+                    "result": [
+                        {
+                            "metric": {"__name__": "cpu", "host": "a"},
+                            "value": [62.0, "2.5"]
+                        }
+                    ]
+                }
+            }),
+            serde_json::to_value(resp).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_range_query_response() {
+        let samples = make_samples(vec![(10000, 1.0), (25000, 2.0), (400000, 3.0)]);
+        let values = evaluate_range(&samples, 10000, 40000, 15000);
+        assert_eq!(
+            vec![
+                (10.0, "1".to_string()),
+                (25.0, "2".to_string()),
+                (40.0, "2".to_string()),
+            ],
+            values
+        );
+        // No sample within the lookback delta.
+        assert!(evaluate_range(&samples, 330000, 390000, 30000).is_empty());
+
+        let series = RangeSeries {
+            metric: convert_labels(vec![Label {
+                name: NAME_LABEL.to_string(),
+                value: "cpu".to_string(),
+            }]),
+            values,
+        };
+        let resp = QueryResponse::success(QueryData::Matrix(vec![series]));
+        assert_eq!(
+            json!({
+                "status": "success",
+                "data": {
+                    "resultType": "matrix",
+                    "result": [
+                        {
+                            "metric": {"__name__": "cpu"},
+                            "values": [[10.0, "1"], [25.0, "2"], [40.0, "2"]]
+                        }
+                    ]
+                }
+            }),
+            serde_json::to_value(resp).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!("1", format_value(1.0));
+        assert_eq!("0.25", format_value(0.25));
+        assert_eq!("NaN", format_value(f64::NAN));
+        assert_eq!("+Inf", format_value(f64::INFINITY));
+        assert_eq!("-Inf", format_value(f64::NEG_INFINITY));
+    }
+}
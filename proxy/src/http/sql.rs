@@ -1,8 +1,18 @@
 // Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
 
-use std::io::Cursor;
+use std::{
+    io::{self, Cursor, Write},
+    sync::{Arc, Mutex},
+};
 
-use arrow::{ipc::reader::StreamReader, record_batch::RecordBatch as ArrowRecordBatch};
+use arrow::{
+    array::UInt64Array,
+    csv::WriterBuilder,
+    datatypes::{DataType, Field, Schema},
+    ipc::{reader::StreamReader, writer::StreamWriter},
+    record_batch::RecordBatch as ArrowRecordBatch,
+};
+use bytes::Bytes;
 use ceresdbproto::storage::{
     arrow_payload::Compression, sql_query_response::Output as OutputPb, ArrowPayload,
     SqlQueryResponse,
@@ -12,8 +22,12 @@ use common_types::{
     record_batch::RecordBatch,
 };
 use common_util::error::BoxError;
+use futures::{future, stream, stream::BoxStream, StreamExt, TryStreamExt};
 use interpreters::interpreter::Output;
-use query_engine::executor::{Executor as QueryExecutor, RecordBatchVec};
+use query_engine::{
+    executor::{Executor as QueryExecutor, RecordBatchVec},
+    explain::{AnalyzedPlan, ExplainedPlan},
+};
 use serde::{
     ser::{SerializeMap, SerializeSeq},
     Deserialize, Serialize,
@@ -23,7 +37,8 @@ use snafu::{OptionExt, ResultExt};
 use crate::{
     context::RequestContext,
     error::{Internal, InternalNoCause, Result},
-    read::SqlResponse,
+    forward::ForwardResult,
+    read::{SqlResponse, StreamOutput},
     Context, Proxy,
 };
 
@@ -40,12 +55,74 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
             forwarded_from: None,
         };
 
-        match self.handle_sql(context, &ctx.schema, &req.query).await? {
+        match self
+            .handle_sql(context, ctx.request_id, &ctx.schema, &req.query)
+            .await?
+        {
             SqlResponse::Forwarded(resp) => convert_sql_response_to_output(resp),
             SqlResponse::Local(output) => Ok(output),
         }
     }
+
+    /// Handle the sql query and yield the record batches of the output one by
+    /// one, which shares the implementation with the streaming sql query of
+    /// the gRPC service.
+    pub async fn handle_http_stream_sql_query(
+        &self,
+        ctx: &RequestContext,
+        req: Request,
+    ) -> Result<StreamOutput> {
+        let context = Context {
+            timeout: ctx.timeout,
+            runtime: self.engine_runtimes.read_runtime.clone(),
+            enable_partition_table_access: true,
+            forwarded_from: None,
+        };
+
+        if let Some(ForwardResult::Forwarded(resp)) = self
+            .maybe_forward_sql_query(context.clone(), &ctx.schema, &req.query)
+            .await?
+        {
+            return convert_sql_response_to_output(resp?).map(collected_output_to_stream);
+        }
+
+        self.fetch_sql_query_stream(context, ctx.request_id, &ctx.schema, &req.query)
+            .await
+    }
+
+    pub async fn handle_http_explain_sql_query(
+        &self,
+        ctx: &RequestContext,
+        req: Request,
+    ) -> Result<ExplainedPlan> {
+        let context = Context {
+            timeout: ctx.timeout,
+            runtime: self.engine_runtimes.read_runtime.clone(),
+            enable_partition_table_access: true,
+            forwarded_from: None,
+        };
+
+        self.explain_sql_query(context, ctx.request_id, &ctx.schema, &req.query)
+            .await
+    }
+
+    pub async fn handle_http_analyze_sql_query(
+        &self,
+        ctx: &RequestContext,
+        req: Request,
+    ) -> Result<AnalyzedPlan> {
+        let context = Context {
+            timeout: ctx.timeout,
+            runtime: self.engine_runtimes.read_runtime.clone(),
+            enable_partition_table_access: true,
+            forwarded_from: None,
+        };
+
+        self.analyze_sql_query(context, ctx.request_id, &ctx.schema, &req.query)
+            .await
+    }
 }
+
 #[derive(Debug, Deserialize)]
 pub struct Request {
     pub query: String,
@@ -161,6 +238,318 @@ fn convert_records(records: RecordBatchVec) -> Response {
     })
 }
 
+/// Content type of the Arrow IPC stream format.
+pub const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// Convert the output into chunks of an Arrow IPC stream.
+///
+/// The record batches are encoded once they are yielded, so each chunk only
+/// holds one encoded record batch instead of the whole result. The
+/// [StreamOutput::AffectedRows] is converted into a record batch with a single
+/// `affected_rows` column, and an empty body is returned if there is no record
+/// batch.
+pub fn convert_output_to_arrow_stream(output: StreamOutput) -> BoxStream<'static, Result<Bytes>> {
+    convert_output_to_arrow_batches(output)
+        .map_ok(Some)
+        .chain(stream::once(future::ready(Ok(None))))
+        .scan(ArrowStreamEncoder::default(), |encoder, batch| {
+            future::ready(encoder.encode(batch))
+        })
+        .boxed()
+}
+
+/// Convert the output into chunks of CSV.
+///
+/// The header is only written in the chunk of the first record batch, and the
+/// output is converted in the same way as [convert_output_to_arrow_stream].
+/// Values are quoted as RFC 4180 requires and nulls are written as empty
+/// fields.
+pub fn convert_output_to_csv(output: StreamOutput) -> BoxStream<'static, Result<Bytes>> {
+    convert_output_to_arrow_batches(output)
+        .enumerate()
+        .map(|(i, batch)| {
+            let batch = batch?;
+            let mut buf = Vec::new();
+            let mut writer = WriterBuilder::new().has_headers(i == 0).build(&mut buf);
+            writer.write(&batch).box_err().context(Internal {
+                msg: "encode record batch to csv",
+            })?;
+            drop(writer);
+
+            Ok(Bytes::from(buf))
+        })
+        .boxed()
+}
+
+fn convert_output_to_arrow_batches(
+    output: StreamOutput,
+) -> BoxStream<'static, Result<ArrowRecordBatch>> {
+    match output {
+        StreamOutput::AffectedRows(n) => {
+            let schema = Schema::new(vec![Field::new("affected_rows", DataType::UInt64, false)]);
+            let batch = ArrowRecordBatch::try_new(
+                Arc::new(schema),
+                vec![Arc::new(UInt64Array::from(vec![n as u64]))],
+            )
+            .box_err()
+            .context(Internal {
+                msg: "build affected rows record batch",
+            });
+            stream::once(future::ready(batch)).boxed()
+        }
+        StreamOutput::Records(batches) => {
+            batches.map_ok(RecordBatch::into_arrow_record_batch).boxed()
+        }
+    }
+}
+
+/// Content type of the newline delimited JSON.
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+/// Content type of the CSV.
+pub const CSV_CONTENT_TYPE: &str = "text/csv";
+
+/// Format of the response of the sql query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    /// The whole output is returned as a JSON object.
+    #[default]
+    Json,
+    /// Every row is returned as a line of JSON object.
+    Ndjson,
+    /// The output is returned as an Arrow IPC stream.
+    Arrow,
+    /// The output is returned as CSV with a header.
+    Csv,
+}
+
+impl ResponseFormat {
+    fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "application/json" => Some(ResponseFormat::Json),
+            NDJSON_CONTENT_TYPE => Some(ResponseFormat::Ndjson),
+            ARROW_STREAM_CONTENT_TYPE => Some(ResponseFormat::Arrow),
+            CSV_CONTENT_TYPE => Some(ResponseFormat::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "application/json",
+            ResponseFormat::Ndjson => NDJSON_CONTENT_TYPE,
+            ResponseFormat::Arrow => ARROW_STREAM_CONTENT_TYPE,
+            ResponseFormat::Csv => CSV_CONTENT_TYPE,
+        }
+    }
+}
+
+/// Mode to explain the sql query rather than returning the results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExplainMode {
+    /// The query is executed, and the physical plan with the metrics of every
+    /// operator is returned.
+    Analyze,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct QueryParams {
+    pub format: Option<ResponseFormat>,
+    pub explain: Option<ExplainMode>,
+}
+
+impl QueryParams {
+    /// Decide the response format, the `format` param takes precedence over the
+    /// `Accept` header, and the first supported type in the `Accept` header is
+    /// chosen.
+    pub fn response_format(&self, accept: Option<&str>) -> ResponseFormat {
+        if let Some(format) = self.format {
+            return format;
+        }
+
+        accept
+            .and_then(|accept| {
+                accept.split(',').find_map(|v| {
+                    ResponseFormat::from_content_type(v.split(';').next().unwrap().trim())
+                })
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Convert the output whose record batches are collected already into the
+/// stream output, e.g. the output of the forwarded sql query which is responded
+/// as a whole by the remote node.
+fn collected_output_to_stream(output: Output) -> StreamOutput {
+    match output {
+        Output::AffectedRows(n) => StreamOutput::AffectedRows(n),
+        Output::Records(records) => {
+            StreamOutput::Records(stream::iter(records.into_iter().map(Ok)).boxed())
+        }
+    }
+}
+
+/// Convert the output into chunks of the given format.
+///
+/// The record batches are converted once they are yielded, except for the
+/// JSON format whose body is built after all the record batches are
+/// collected.
+pub fn convert_output_to_chunks(
+    output: StreamOutput,
+    format: ResponseFormat,
+) -> BoxStream<'static, Result<Bytes>> {
+    match format {
+        ResponseFormat::Json => stream::once(async move {
+            let output = match output {
+                StreamOutput::AffectedRows(n) => Output::AffectedRows(n),
+                StreamOutput::Records(batches) => Output::Records(batches.try_collect().await?),
+            };
+            let body = serde_json::to_vec(&convert_output(output))
+                .box_err()
+                .context(Internal {
+                    msg: "serialize response to json",
+                })?;
+
+            Ok(Bytes::from(body))
+        })
+        .boxed(),
+        ResponseFormat::Ndjson => convert_output_to_ndjson_stream(output),
+        ResponseFormat::Arrow => convert_output_to_arrow_stream(output),
+        ResponseFormat::Csv => convert_output_to_csv(output),
+    }
+}
+
+/// Convert the output into chunks of newline delimited JSON.
+///
+/// Every row is serialized into a line of JSON object keyed by the column
+/// names, and the rows of a record batch are serialized into a chunk once it
+/// is yielded. The [StreamOutput::AffectedRows] is serialized into a line of
+/// `{"affected_rows":n}`.
+pub fn convert_output_to_ndjson_stream(output: StreamOutput) -> BoxStream<'static, Result<Bytes>> {
+    match output {
+        StreamOutput::AffectedRows(n) => stream::once(future::ready(
+            serde_json::to_vec(&Response::AffectedRows(n))
+                .box_err()
+                .context(Internal {
+                    msg: "serialize response to json",
+                })
+                .map(|mut line| {
+                    line.push(b'\n');
+                    Bytes::from(line)
+                }),
+        ))
+        .boxed(),
+        StreamOutput::Records(batches) => batches
+            .map(|record_batch| convert_record_batch_to_ndjson(&record_batch?))
+            .boxed(),
+    }
+}
+
+fn convert_record_batch_to_ndjson(record_batch: &RecordBatch) -> Result<Bytes> {
+    let schema = record_batch.schema();
+    let column_names: Vec<_> = (0..record_batch.num_columns())
+        .map(|col_idx| schema.column(col_idx).name.clone())
+        .collect();
+
+    let mut buf = Vec::new();
+    for row_idx in 0..record_batch.num_rows() {
+        let datums: Vec<_> = (0..column_names.len())
+            .map(|col_idx| record_batch.column(col_idx).datum(row_idx))
+            .collect();
+        let row = Row(column_names.iter().zip(datums.iter()).collect());
+        serde_json::to_writer(&mut buf, &row)
+            .box_err()
+            .context(Internal {
+                msg: "serialize row to json",
+            })?;
+        buf.push(b'\n');
+    }
+
+    Ok(Bytes::from(buf))
+}
+
+/// Buffer shared with the [StreamWriter] to take out the encoded bytes.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Bytes {
+        Bytes::from(std::mem::take(&mut *self.0.lock().unwrap()))
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Encoder yielding a chunk of the Arrow IPC stream for every record batch.
+///
+/// The encoder stops after the first error, so the stream is aborted instead
+/// of ending with a truncated but valid-looking body.
+#[derive(Default)]
+struct ArrowStreamEncoder {
+    buffer: SharedBuffer,
+    writer: Option<StreamWriter<SharedBuffer>>,
+    finished: bool,
+}
+
+impl ArrowStreamEncoder {
+    /// Encode the record batch, and the stream is finished by a `None` batch.
+    ///
+    /// Returns `None` if there is no more chunk.
+    fn encode(&mut self, batch: Result<Option<ArrowRecordBatch>>) -> Option<Result<Bytes>> {
+        if self.finished {
+            return None;
+        }
+
+        let res = batch.and_then(|batch| self.encode_next(batch));
+        if res.is_err() {
+            self.finished = true;
+        }
+        res.transpose()
+    }
+
+    fn encode_next(&mut self, batch: Option<ArrowRecordBatch>) -> Result<Option<Bytes>> {
+        match batch {
+            Some(batch) => {
+                let writer = match &mut self.writer {
+                    Some(writer) => writer,
+                    None => {
+                        let writer = StreamWriter::try_new(self.buffer.clone(), &batch.schema())
+                            .box_err()
+                            .context(Internal {
+                                msg: "create arrow stream writer",
+                            })?;
+                        self.writer.insert(writer)
+                    }
+                };
+                writer.write(&batch).box_err().context(Internal {
+                    msg: "encode record batch to arrow stream",
+                })?;
+            }
+            None => {
+                self.finished = true;
+                match &mut self.writer {
+                    Some(writer) => writer.finish().box_err().context(Internal {
+                        msg: "finish arrow stream",
+                    })?,
+                    None => return Ok(None),
+                }
+            }
+        }
+
+        Ok(Some(self.buffer.take()))
+    }
+}
+
 fn convert_sql_response_to_output(sql_query_response: SqlQueryResponse) -> Result<Output> {
     let output_pb = sql_query_response.output.context(InternalNoCause {
         msg: "Output is empty in sql query response".to_string(),
@@ -235,3 +624,250 @@ fn decode_arrow_payload(arrow_payload: ArrowPayload) -> Result<Vec<ArrowRecordBa
 
     Ok(record_batches)
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Int64Array, StringArray};
+    use common_types::{
+        column_schema,
+        tests::{build_record_batch_with_key_by_rows, build_rows},
+    };
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    use super::*;
+
+    #[test]
+    fn test_response_format() {
+        let params = QueryParams::default();
+        assert_eq!(ResponseFormat::Json, params.response_format(None));
+        assert_eq!(
+            ResponseFormat::Json,
+            params.response_format(Some("application/json"))
+        );
+        assert_eq!(
+            ResponseFormat::Ndjson,
+            params.response_format(Some("application/json, application/x-ndjson; q=0.9"))
+        );
+
+        assert_eq!(
+            ResponseFormat::Csv,
+            params.response_format(Some("text/html, text/csv, application/json"))
+        );
+        assert_eq!(
+            ResponseFormat::Arrow,
+            params.response_format(Some(ARROW_STREAM_CONTENT_TYPE))
+        );
+
+        let params = QueryParams {
+            format: Some(ResponseFormat::Json),
+            ..Default::default()
+        };
+        assert_eq!(
+            ResponseFormat::Json,
+            params.response_format(Some(NDJSON_CONTENT_TYPE))
+        );
+
+        for format in [
+            ResponseFormat::Json,
+            ResponseFormat::Ndjson,
+            ResponseFormat::Arrow,
+            ResponseFormat::Csv,
+        ] {
+            assert_eq!(
+                Some(format),
+                ResponseFormat::from_content_type(format.content_type())
+            );
+        }
+    }
+
+    fn build_multi_batch_output() -> (Output, usize) {
+        let records = vec![
+            build_record_batch_with_key_by_rows(build_rows()).into_record_batch(),
+            build_record_batch_with_key_by_rows(build_rows()).into_record_batch(),
+        ];
+        let num_rows = records.iter().map(|v| v.num_rows()).sum();
+
+        (Output::Records(records), num_rows)
+    }
+
+    #[tokio::test]
+    async fn test_convert_output_to_arrow() {
+        let (output, num_rows) = build_multi_batch_output();
+        let chunks =
+            convert_output_to_chunks(collected_output_to_stream(output), ResponseFormat::Arrow)
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+        let body: Vec<u8> = chunks.iter().flatten().copied().collect();
+
+        let reader = StreamReader::try_new(Cursor::new(body), None).unwrap();
+        let decoded_rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(num_rows, decoded_rows);
+    }
+
+    #[tokio::test]
+    async fn test_convert_output_to_arrow_stream_incrementally() {
+        let (output, num_rows) = build_multi_batch_output();
+        let records = match output {
+            Output::Records(records) => records,
+            Output::AffectedRows(_) => unreachable!(),
+        };
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut chunks = convert_output_to_arrow_stream(StreamOutput::Records(
+            UnboundedReceiverStream::new(rx).boxed(),
+        ));
+
+        // The received body can be decoded before the stream is finished.
+        let mut body = Vec::new();
+        let mut decoded_rows = 0;
+        for (i, record_batch) in records.into_iter().enumerate() {
+            let batch_rows = record_batch.num_rows();
+            tx.send(Ok(record_batch)).unwrap();
+            body.extend_from_slice(&chunks.next().await.unwrap().unwrap());
+
+            let mut reader = StreamReader::try_new(Cursor::new(body.clone()), None).unwrap();
+            let decoded = reader.nth(i).unwrap().unwrap();
+            assert_eq!(batch_rows, decoded.num_rows());
+            decoded_rows += batch_rows;
+        }
+        assert_eq!(num_rows, decoded_rows);
+
+        // The stream is aborted after the first error.
+        tx.send(InternalNoCause { msg: "mock error" }.fail())
+            .unwrap();
+        assert!(chunks.next().await.unwrap().is_err());
+        assert!(chunks.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_convert_output_to_csv() {
+        let (output, num_rows) = build_multi_batch_output();
+        let chunks =
+            convert_output_to_chunks(collected_output_to_stream(output), ResponseFormat::Csv)
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+        assert_eq!(2, chunks.len());
+        let body = String::from_utf8(chunks.concat()).unwrap();
+
+        // The header is only written once.
+        let lines: Vec<_> = body.lines().collect();
+        assert_eq!(num_rows + 1, lines.len());
+        assert!(lines[0].starts_with("key1,key2"), "{}", lines[0]);
+
+        let chunks = convert_output_to_chunks(StreamOutput::AffectedRows(3), ResponseFormat::Csv)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(vec![Bytes::from("affected_rows\n3\n")], chunks);
+    }
+
+    #[tokio::test]
+    async fn test_convert_output_to_csv_with_special_chars() {
+        let columns = [("id", DatumKind::Int64), ("value", DatumKind::String)]
+            .into_iter()
+            .map(|(name, kind)| {
+                let column = column_schema::Builder::new(name.to_string(), kind)
+                    .is_nullable(true)
+                    .build()
+                    .unwrap();
+                Field::from(&column)
+            })
+            .collect();
+        let batch = ArrowRecordBatch::try_new(
+            Arc::new(Schema::new(columns)),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+                Arc::new(StringArray::from(vec![
+                    Some("a,b"),
+                    Some("say \"hi\""),
+                    Some("line\nbreak"),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+        let output = Output::Records(vec![RecordBatch::try_from(batch).unwrap()]);
+
+        let chunks =
+            convert_output_to_chunks(collected_output_to_stream(output), ResponseFormat::Csv)
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+        let body = String::from_utf8(chunks.concat()).unwrap();
+        assert_eq!(
+            "id,value\n1,\"a,b\"\n2,\"say \"\"hi\"\"\"\n3,\"line\nbreak\"\n4,\n",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn test_convert_output_to_ndjson_stream() {
+        let (output, num_rows) = build_multi_batch_output();
+        let chunks = convert_output_to_ndjson_stream(collected_output_to_stream(output))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        // Every record batch is streamed as a chunk.
+        assert_eq!(2, chunks.len());
+        let body = String::from_utf8(chunks.concat()).unwrap();
+        let lines: Vec<serde_json::Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(num_rows, lines.len());
+
+        // Every line is the same as the row of the default JSON output.
+        let (output, _) = build_multi_batch_output();
+        let expect = serde_json::to_value(convert_output(output)).unwrap();
+        assert_eq!(expect["rows"].as_array().unwrap(), &lines);
+
+        let chunks = convert_output_to_ndjson_stream(StreamOutput::AffectedRows(3))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(vec![Bytes::from("{\"affected_rows\":3}\n")], chunks);
+    }
+
+    #[tokio::test]
+    async fn test_convert_multi_batch_output_to_chunks() {
+        // The chunks of every streaming format are produced batch by batch.
+        for (format, expect_chunks) in [
+            (ResponseFormat::Ndjson, 2),
+            (ResponseFormat::Csv, 2),
+            // The end of the stream is encoded into the last chunk.
+            (ResponseFormat::Arrow, 3),
+        ] {
+            let (output, _) = build_multi_batch_output();
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            let mut chunks = convert_output_to_chunks(
+                StreamOutput::Records(UnboundedReceiverStream::new(rx).boxed()),
+                format,
+            );
+
+            // Every record batch is converted once it is yielded.
+            if let Output::Records(records) = output {
+                for record_batch in records {
+                    tx.send(Ok(record_batch)).unwrap();
+                    assert!(!chunks.next().await.unwrap().unwrap().is_empty());
+                }
+            }
+            drop(tx);
+
+            let num_chunks = 2 + chunks.try_collect::<Vec<_>>().await.unwrap().len();
+            assert_eq!(expect_chunks, num_chunks, "format:{format:?}");
+        }
+
+        // The JSON body is built after all the record batches are collected.
+        let (output, num_rows) = build_multi_batch_output();
+        let chunks =
+            convert_output_to_chunks(collected_output_to_stream(output), ResponseFormat::Json)
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+        assert_eq!(1, chunks.len());
+        let body: serde_json::Value = serde_json::from_slice(&chunks[0]).unwrap();
+        assert_eq!(num_rows, body["rows"].as_array().unwrap().len());
+    }
+}
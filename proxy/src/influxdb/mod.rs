@@ -92,8 +92,8 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
                 }
 
                 debug!(
-                    "Influxdb write finished, catalog:{}, schema:{}, result:{result:?}",
-                    ctx.catalog, ctx.schema
+                    "Influxdb write finished, catalog:{}, schema:{}, trace_id:{:?}, result:{result:?}",
+                    ctx.catalog, ctx.schema, ctx.trace_id
                 );
 
                 Ok(())
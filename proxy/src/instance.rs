@@ -9,7 +9,7 @@ use df_operator::registry::FunctionRegistryRef;
 use interpreters::table_manipulator::TableManipulatorRef;
 use table_engine::{engine::TableEngineRef, remote::RemoteEngineRef};
 
-use crate::limiter::Limiter;
+use crate::{limiter::Limiter, running_query::RunningQueriesRef};
 
 /// A cluster instance. Usually there is only one instance per cluster
 ///
@@ -24,6 +24,8 @@ pub struct Instance<Q> {
     pub limiter: Limiter,
     pub table_manipulator: TableManipulatorRef,
     pub remote_engine_ref: RemoteEngineRef,
+    /// Registry of the running queries.
+    pub running_queries: RunningQueriesRef,
 }
 
 /// A reference counted instance pointer
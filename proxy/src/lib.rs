@@ -20,6 +20,7 @@ pub mod limiter;
 mod metrics;
 pub mod opentsdb;
 mod read;
+pub mod running_query;
 pub mod schema_config_provider;
 mod util;
 mod write;
@@ -27,6 +28,7 @@ mod write;
 pub const FORWARDED_FROM: &str = "forwarded-from";
 
 use std::{
+    future::Future,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -45,7 +47,7 @@ use futures::FutureExt;
 use interpreters::{
     context::Context as InterpreterContext,
     factory::Factory,
-    interpreter::{InterpreterPtr, Output},
+    interpreter::{InterpreterPtr, Output, StreamOutput as InterpreterStreamOutput},
 };
 use log::{error, info};
 use query_engine::executor::Executor as QueryExecutor;
@@ -397,6 +399,36 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
         Self::interpreter_execute_plan(interpreter, deadline).await
     }
 
+    /// Execute the plan and yield the record batches of the output as they are
+    /// produced.
+    async fn execute_plan_stream(
+        &self,
+        request_id: RequestId,
+        catalog: &str,
+        schema: &str,
+        plan: Plan,
+        deadline: Option<Instant>,
+        enable_partition_table_access: bool,
+    ) -> Result<InterpreterStreamOutput> {
+        self.instance
+            .limiter
+            .try_limit(&plan)
+            .box_err()
+            .context(Internal {
+                msg: "Request is blocked",
+            })?;
+
+        let interpreter = self.build_interpreter(
+            request_id,
+            catalog,
+            schema,
+            plan,
+            deadline,
+            enable_partition_table_access,
+        )?;
+        Self::execute_with_deadline(interpreter.execute_stream(), deadline).await
+    }
+
     fn build_interpreter(
         &self,
         request_id: RequestId,
@@ -429,23 +461,27 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
         interpreter: InterpreterPtr,
         deadline: Option<Instant>,
     ) -> Result<Output> {
+        Self::execute_with_deadline(interpreter.execute(), deadline).await
+    }
+
+    async fn execute_with_deadline<T>(
+        execute: impl Future<Output = interpreters::interpreter::Result<T>>,
+        deadline: Option<Instant>,
+    ) -> Result<T> {
         if let Some(deadline) = deadline {
-            tokio::time::timeout_at(
-                tokio::time::Instant::from_std(deadline),
-                interpreter.execute(),
-            )
-            .await
-            .box_err()
-            .context(Internal {
-                msg: "Plan execution timeout",
-            })
-            .and_then(|v| {
-                v.box_err().context(Internal {
-                    msg: "Failed to execute interpreter",
+            tokio::time::timeout_at(tokio::time::Instant::from_std(deadline), execute)
+                .await
+                .box_err()
+                .context(Internal {
+                    msg: "Plan execution timeout",
+                })
+                .and_then(|v| {
+                    v.box_err().context(Internal {
+                        msg: "Failed to execute interpreter",
+                    })
                 })
-            })
         } else {
-            interpreter.execute().await.box_err().context(Internal {
+            execute.await.box_err().context(Internal {
                 msg: "Failed to execute interpreter",
             })
         }
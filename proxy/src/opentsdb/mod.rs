@@ -1,20 +1,26 @@
 // Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
 
-//! This module implements [put][1] for OpenTSDB
+//! This module implements [put][1] and [query][2] for OpenTSDB
 //! [1]: http://opentsdb.net/docs/build/html/api_http/put.html
+//! [2]: http://opentsdb.net/docs/build/html/api_http/query/index.html
 
 use ceresdbproto::storage::{
     RequestContext as GrpcRequestContext, WriteRequest as GrpcWriteRequest,
 };
+use common_types::{request_id::RequestId, time::Timestamp};
 use http::StatusCode;
 use log::debug;
 use query_engine::executor::Executor as QueryExecutor;
+use snafu::OptionExt;
 
 use crate::{
     context::RequestContext,
     error::{ErrNoCause, Result},
     metrics::HTTP_HANDLER_COUNTER_VEC,
-    opentsdb::types::{convert_put_request, PutRequest, PutResponse},
+    opentsdb::types::{
+        convert_put_request, convert_query_request, PutRequest, PutResponse, QueryRequest,
+        QueryResponse,
+    },
     Context, Proxy,
 };
 
@@ -69,8 +75,8 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
                 }
 
                 debug!(
-                    "OpenTSDB write finished, catalog:{}, schema:{}, result:{result:?}",
-                    ctx.catalog, ctx.schema
+                    "OpenTSDB write finished, catalog:{}, schema:{}, trace_id:{:?}, result:{result:?}",
+                    ctx.catalog, ctx.schema, ctx.trace_id
                 );
 
                 Ok(())
@@ -84,4 +90,47 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
             }
         }
     }
+
+    pub async fn handle_opentsdb_query(
+        &self,
+        ctx: RequestContext,
+        req: QueryRequest,
+    ) -> Result<QueryResponse> {
+        let queries = convert_query_request(req, Timestamp::now().as_i64())?;
+
+        let mut results = Vec::new();
+        for query in queries {
+            let table = self
+                .try_get_table(&ctx.catalog, &ctx.schema, &query.metric)?
+                .with_context(|| ErrNoCause {
+                    code: StatusCode::BAD_REQUEST,
+                    msg: format!("Metric not found, metric:{}", query.metric),
+                })?;
+            let schema = table.schema();
+            let tag_columns = query.tag_columns(&schema)?;
+            let timestamp_column = &schema.column(schema.timestamp_index()).name;
+            let sql = query.to_sql(timestamp_column, &tag_columns);
+
+            let proxy_context = Context {
+                timeout: ctx.timeout,
+                runtime: self.engine_runtimes.read_runtime.clone(),
+                enable_partition_table_access: true,
+                forwarded_from: None,
+            };
+            let output = self
+                .fetch_sql_query_output(proxy_context, RequestId::next_id(), &ctx.schema, &sql)
+                .await?;
+            results.extend(query.convert_output(&tag_columns, output)?);
+        }
+
+        debug!(
+            "OpenTSDB query finished, catalog:{}, schema:{}, trace_id:{:?}, series:{}",
+            ctx.catalog,
+            ctx.schema,
+            ctx.trace_id,
+            results.len()
+        );
+
+        Ok(results)
+    }
 }
@@ -1,23 +1,29 @@
 // Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::Debug,
+    str::FromStr,
 };
 
 use bytes::Bytes;
 use ceresdbproto::storage::{
     value, Field, FieldGroup, Tag, Value as ProtoValue, WriteSeriesEntry, WriteTableRequest,
 };
-use common_util::{error::BoxError, time::try_to_millis};
+use common_types::schema::Schema;
+use common_util::{config::ReadableDuration, error::BoxError, time::try_to_millis};
 use http::StatusCode;
-use serde::Deserialize;
+use interpreters::interpreter::Output;
+use serde::{Deserialize, Serialize};
 use serde_json::from_slice;
-use snafu::{OptionExt, ResultExt};
+use snafu::{ensure, OptionExt, ResultExt};
 
-use crate::error::{ErrNoCause, ErrWithCause, Result};
+use crate::error::{ErrNoCause, ErrWithCause, InternalNoCause, Result};
 
 const OPENTSDB_DEFAULT_FIELD: &str = "value";
+/// The timestamps below it are regarded as seconds when the unit is detected
+/// automatically.
+const SECONDS_TIMESTAMP_UPPER_BOUND: i64 = 1_000_000_000_000;
 
 #[derive(Debug)]
 pub struct PutRequest {
@@ -27,6 +33,7 @@ pub struct PutRequest {
     pub details: Option<String>,
     pub sync: Option<String>,
     pub sync_timeout: i32,
+    pub timestamp_unit: TimestampUnit,
 }
 
 impl PutRequest {
@@ -37,6 +44,7 @@ impl PutRequest {
             details: params.details,
             sync: params.sync,
             sync_timeout: params.sync_timeout,
+            timestamp_unit: params.timestamp_unit,
         }
     }
 }
@@ -50,7 +58,10 @@ pub type PutResponse = ();
 ///     http://opentsdb.net/docs/build/html/api_http/put.html#requests
 ///
 /// NOTE:
-///     - all the params is unimplemented.
+///     - all the params of OpenTSDB is unimplemented.
+///     - `timestamp_unit` is an extension to specify the unit of the
+///       timestamps, which is detected by the magnitude of every timestamp by
+///       default.
 #[derive(Debug, Default, Deserialize)]
 #[serde(default)]
 pub struct PutParams {
@@ -58,6 +69,39 @@ pub struct PutParams {
     pub details: Option<String>,
     pub sync: Option<String>,
     pub sync_timeout: i32,
+    pub timestamp_unit: TimestampUnit,
+}
+
+/// Unit of the timestamps of the put points.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampUnit {
+    /// Timestamps below 10^12 are seconds, and the others are milliseconds.
+    #[default]
+    Auto,
+    Seconds,
+    Milliseconds,
+}
+
+impl TimestampUnit {
+    /// Normalize the `timestamp` into milliseconds, returns None if it is
+    /// invalid.
+    fn to_millis(self, timestamp: i64) -> Option<i64> {
+        if timestamp < 0 {
+            return None;
+        }
+
+        let is_seconds = match self {
+            TimestampUnit::Auto => timestamp < SECONDS_TIMESTAMP_UPPER_BOUND,
+            TimestampUnit::Seconds => true,
+            TimestampUnit::Milliseconds => false,
+        };
+        if is_seconds {
+            timestamp.checked_mul(1000)
+        } else {
+            Some(timestamp)
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -130,13 +174,14 @@ pub(crate) fn convert_put_request(req: PutRequest) -> Result<Vec<WriteTableReque
         };
 
         for point in points {
-            let timestamp = point.timestamp;
-            let timestamp = try_to_millis(timestamp)
+            // The unit of every timestamp is detected independently.
+            let timestamp = req
+                .timestamp_unit
+                .to_millis(point.timestamp)
                 .with_context(|| ErrNoCause {
                     code: StatusCode::BAD_REQUEST,
                     msg: format!("Invalid timestamp: {}", point.timestamp),
-                })?
-                .as_i64();
+                })?;
 
             let mut tags = Vec::with_capacity(point.tags.len());
             for (tag_name, tag_value) in point.tags {
@@ -197,3 +242,798 @@ pub(crate) fn validate(points: &[Point]) -> Result<()> {
 
     Ok(())
 }
+
+/// Request of the query api
+///
+/// It's derived from the request of query described in doc of OpenTSDB 2.4:
+///     http://opentsdb.net/docs/build/html/api_http/query/index.html#requests
+///
+/// NOTE:
+///     - only the `sum`, `avg` and `none` aggregators are supported.
+///     - the values are not interpolated when aggregating multiple series.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRequest {
+    pub start: QueryTime,
+    #[serde(default)]
+    pub end: Option<QueryTime>,
+    pub queries: Vec<SubQuery>,
+    #[serde(default)]
+    pub ms_resolution: bool,
+}
+
+/// Absolute timestamp in seconds or milliseconds, or relative time like
+/// `1h-ago`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum QueryTime {
+    Timestamp(i64),
+    Text(String),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubQuery {
+    pub aggregator: String,
+    pub metric: String,
+    /// Downsampling specifier like `1m-avg`.
+    #[serde(default)]
+    pub downsample: Option<String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResult {
+    pub metric: String,
+    pub tags: BTreeMap<String, String>,
+    pub aggregate_tags: Vec<String>,
+    pub dps: BTreeMap<i64, f64>,
+}
+
+pub type QueryResponse = Vec<QueryResult>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Aggregator {
+    Sum,
+    Avg,
+    /// Don't aggregate, and the last value is kept if multiple values fall
+    /// into the same timestamp.
+    None,
+}
+
+impl Aggregator {
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "sum" => Ok(Aggregator::Sum),
+            "avg" => Ok(Aggregator::Avg),
+            "none" => Ok(Aggregator::None),
+            _ => ErrNoCause {
+                code: StatusCode::BAD_REQUEST,
+                msg: format!(
+                    "Unsupported aggregator:{name}, only sum, avg and none are supported now"
+                ),
+            }
+            .fail(),
+        }
+    }
+
+    /// REQUIRE: values is not empty
+    fn aggregate(&self, values: &[f64]) -> f64 {
+        match self {
+            Aggregator::Sum => values.iter().sum(),
+            Aggregator::Avg => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregator::None => *values.last().unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Downsample {
+    interval_ms: i64,
+    aggregator: Aggregator,
+}
+
+impl Downsample {
+    fn parse(spec: &str) -> Result<Self> {
+        let invalid_spec = || ErrNoCause {
+            code: StatusCode::BAD_REQUEST,
+            msg: format!("Invalid downsample:{spec}, expect format like 1m-avg"),
+        };
+
+        let (interval, aggregator) = spec.split_once('-').with_context(invalid_spec)?;
+        let interval_ms = ReadableDuration::from_str(interval)
+            .ok()
+            .map(|v| v.as_millis() as i64)
+            .filter(|v| *v > 0)
+            .with_context(invalid_spec)?;
+        let aggregator = Aggregator::parse(aggregator)?;
+        ensure!(
+            aggregator != Aggregator::None,
+            ErrNoCause {
+                code: StatusCode::BAD_REQUEST,
+                msg: format!("Downsample aggregator must not be none, downsample:{spec}"),
+            }
+        );
+
+        Ok(Self {
+            interval_ms,
+            aggregator,
+        })
+    }
+}
+
+/// Values of a tag to filter, and the tag is grouped by if it is `*` or
+/// contains `|`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TagFilter {
+    name: String,
+    /// Empty means any value.
+    values: Vec<String>,
+    group_by: bool,
+}
+
+impl TagFilter {
+    fn new(name: String, value: &str) -> Self {
+        if value == "*" {
+            Self {
+                name,
+                values: Vec::new(),
+                group_by: true,
+            }
+        } else if value.contains('|') {
+            Self {
+                name,
+                values: value.split('|').map(|v| v.to_string()).collect(),
+                group_by: true,
+            }
+        } else {
+            Self {
+                name,
+                values: vec![value.to_string()],
+                group_by: false,
+            }
+        }
+    }
+}
+
+/// The validated sub query.
+#[derive(Debug)]
+pub(crate) struct QueryPlan {
+    pub metric: String,
+    aggregator: Aggregator,
+    downsample: Option<Downsample>,
+    tag_filters: Vec<TagFilter>,
+    start_ms: i64,
+    end_ms: i64,
+    ms_resolution: bool,
+}
+
+pub(crate) fn convert_query_request(req: QueryRequest, now_ms: i64) -> Result<Vec<QueryPlan>> {
+    ensure!(
+        !req.queries.is_empty(),
+        ErrNoCause {
+            code: StatusCode::BAD_REQUEST,
+            msg: "At least one sub query must be supplied",
+        }
+    );
+
+    let start_ms = parse_query_time(&req.start, now_ms)?;
+    let end_ms = match &req.end {
+        Some(end) => parse_query_time(end, now_ms)?,
+        None => now_ms,
+    };
+    ensure!(
+        start_ms <= end_ms,
+        ErrNoCause {
+            code: StatusCode::BAD_REQUEST,
+            msg: format!("Start time must not be after end time, start:{start_ms}, end:{end_ms}"),
+        }
+    );
+
+    req.queries
+        .into_iter()
+        .map(|query| {
+            ensure!(
+                !query.metric.is_empty(),
+                ErrNoCause {
+                    code: StatusCode::BAD_REQUEST,
+                    msg: "Metric must not be empty",
+                }
+            );
+
+            let downsample = query
+                .downsample
+                .as_deref()
+                .map(Downsample::parse)
+                .transpose()?;
+            let mut tag_filters: Vec<_> = query
+                .tags
+                .into_iter()
+                .map(|(name, value)| TagFilter::new(name, &value))
+                .collect();
+            tag_filters.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+            Ok(QueryPlan {
+                metric: query.metric,
+                aggregator: Aggregator::parse(&query.aggregator)?,
+                downsample,
+                tag_filters,
+                start_ms,
+                end_ms,
+                ms_resolution: req.ms_resolution,
+            })
+        })
+        .collect()
+}
+
+fn parse_query_time(time: &QueryTime, now_ms: i64) -> Result<i64> {
+    let timestamp = match time {
+        QueryTime::Timestamp(v) => *v,
+        QueryTime::Text(text) => {
+            if let Some(duration) = text.strip_suffix("-ago") {
+                let duration =
+                    ReadableDuration::from_str(duration)
+                        .ok()
+                        .with_context(|| ErrNoCause {
+                            code: StatusCode::BAD_REQUEST,
+                            msg: format!("Invalid relative time:{text}"),
+                        })?;
+                return Ok(now_ms - duration.as_millis() as i64);
+            }
+
+            text.parse::<i64>().ok().with_context(|| ErrNoCause {
+                code: StatusCode::BAD_REQUEST,
+                msg: format!("Invalid time:{text}"),
+            })?
+        }
+    };
+
+    try_to_millis(timestamp)
+        .map(|v| v.as_i64())
+        .with_context(|| ErrNoCause {
+            code: StatusCode::BAD_REQUEST,
+            msg: format!("Invalid timestamp:{timestamp}"),
+        })
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("`{}`", ident.replace('`', "``"))
+}
+
+fn quote_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+impl QueryPlan {
+    /// Tag columns of the table, which are queried along with the timestamp and
+    /// value.
+    pub(crate) fn tag_columns(&self, schema: &Schema) -> Result<Vec<String>> {
+        let tag_columns: Vec<_> = schema
+            .columns()
+            .iter()
+            .filter(|column| column.is_tag)
+            .map(|column| column.name.clone())
+            .collect();
+        ensure!(
+            schema.index_of(OPENTSDB_DEFAULT_FIELD).is_some(),
+            ErrNoCause {
+                code: StatusCode::BAD_REQUEST,
+                msg: format!(
+                    "Field {OPENTSDB_DEFAULT_FIELD} not found, metric:{}",
+                    self.metric
+                ),
+            }
+        );
+        for filter in &self.tag_filters {
+            ensure!(
+                tag_columns.contains(&filter.name),
+                ErrNoCause {
+                    code: StatusCode::BAD_REQUEST,
+                    msg: format!("Tag not found, metric:{}, tag:{}", self.metric, filter.name),
+                }
+            );
+        }
+
+        Ok(tag_columns)
+    }
+
+    /// Build sql to select the timestamp, value and the tag columns in order.
+    pub(crate) fn to_sql(&self, timestamp_column: &str, tag_columns: &[String]) -> String {
+        let timestamp_column = quote_ident(timestamp_column);
+        let mut projection = vec![
+            timestamp_column.clone(),
+            quote_ident(OPENTSDB_DEFAULT_FIELD),
+        ];
+        projection.extend(tag_columns.iter().map(|tag| quote_ident(tag)));
+
+        let mut predicates = vec![
+            format!("{timestamp_column} >= {}", self.start_ms),
+            format!("{timestamp_column} <= {}", self.end_ms),
+        ];
+        for filter in &self.tag_filters {
+            let tag = quote_ident(&filter.name);
+            match filter.values.as_slice() {
+                [] => (),
+                [value] => predicates.push(format!("{tag} = {}", quote_string(value))),
+                values => {
+                    let values: Vec<_> = values.iter().map(|v| quote_string(v)).collect();
+                    predicates.push(format!("{tag} IN ({})", values.join(", ")));
+                }
+            }
+        }
+
+        format!(
+            "SELECT {} FROM {} WHERE {}",
+            projection.join(", "),
+            quote_ident(&self.metric),
+            predicates.join(" AND ")
+        )
+    }
+
+    /// Convert the output of the sql built by [QueryPlan::to_sql] into results.
+    ///
+    /// Each series is downsampled first, and then the series are aggregated
+    /// by the grouped tags.
+    pub(crate) fn convert_output(
+        &self,
+        tag_columns: &[String],
+        output: Output,
+    ) -> Result<Vec<QueryResult>> {
+        let records = match output {
+            Output::Records(records) => records,
+            Output::AffectedRows(_) => {
+                return InternalNoCause {
+                    msg: "output in opentsdb query should not be affected rows",
+                }
+                .fail()
+            }
+        };
+
+        // Points of each series, which is identified by its tags.
+        let mut series: BTreeMap<BTreeMap<String, String>, BTreeMap<i64, Vec<f64>>> =
+            BTreeMap::new();
+        for record in records {
+            for row_idx in 0..record.num_rows() {
+                let (timestamp, value) = match (
+                    record.column(0).datum(row_idx).as_timestamp(),
+                    record.column(1).datum(row_idx).as_f64(),
+                ) {
+                    (Some(timestamp), Some(value)) => (timestamp, value),
+                    // Skip the null values.
+                    _ => continue,
+                };
+
+                let mut tags = BTreeMap::new();
+                for (i, tag) in tag_columns.iter().enumerate() {
+                    if let Some(tag_value) = record.column(i + 2).datum(row_idx).as_str() {
+                        tags.insert(tag.clone(), tag_value.to_string());
+                    }
+                }
+
+                let timestamp = match &self.downsample {
+                    Some(downsample) => {
+                        let ts = timestamp.as_i64();
+                        ts - ts.rem_euclid(downsample.interval_ms)
+                    }
+                    None => timestamp.as_i64(),
+                };
+                series
+                    .entry(tags)
+                    .or_default()
+                    .entry(timestamp)
+                    .or_default()
+                    .push(value);
+            }
+        }
+
+        let series_aggregator = self
+            .downsample
+            .map(|v| v.aggregator)
+            .unwrap_or(Aggregator::None);
+        let series = series.into_iter().map(|(tags, points)| {
+            let points: BTreeMap<_, _> = points
+                .into_iter()
+                .map(|(timestamp, values)| (timestamp, series_aggregator.aggregate(&values)))
+                .collect();
+            (tags, points)
+        });
+
+        if self.aggregator == Aggregator::None {
+            return Ok(series
+                .map(|(tags, points)| QueryResult {
+                    metric: self.metric.clone(),
+                    tags,
+                    aggregate_tags: Vec::new(),
+                    dps: self.convert_dps(points.into_iter()),
+                })
+                .collect());
+        }
+
+        let group_by_tags: Vec<_> = self
+            .tag_filters
+            .iter()
+            .filter(|filter| filter.group_by)
+            .map(|filter| filter.name.as_str())
+            .collect();
+        let mut groups: BTreeMap<Vec<Option<String>>, Vec<_>> = BTreeMap::new();
+        for (tags, points) in series {
+            let group_key = group_by_tags
+                .iter()
+                .map(|tag| tags.get(*tag).cloned())
+                .collect();
+            groups.entry(group_key).or_default().push((tags, points));
+        }
+
+        Ok(groups
+            .into_values()
+            .map(|group| self.aggregate_group(group))
+            .collect())
+    }
+
+    fn aggregate_group(
+        &self,
+        group: Vec<(BTreeMap<String, String>, BTreeMap<i64, f64>)>,
+    ) -> QueryResult {
+        // The tags shared by all the series are kept, and others are aggregated.
+        let mut tags = group[0].0.clone();
+        let mut tag_names = HashSet::new();
+        for (series_tags, _) in &group {
+            tags.retain(|name, value| series_tags.get(name) == Some(value));
+            tag_names.extend(series_tags.keys());
+        }
+        let mut aggregate_tags: Vec<_> = tag_names
+            .into_iter()
+            .filter(|name| !tags.contains_key(*name))
+            .cloned()
+            .collect();
+        aggregate_tags.sort_unstable();
+
+        let mut points: BTreeMap<i64, Vec<f64>> = BTreeMap::new();
+        for (_, series_points) in group {
+            for (timestamp, value) in series_points {
+                points.entry(timestamp).or_default().push(value);
+            }
+        }
+        let points = points
+            .into_iter()
+            .map(|(timestamp, values)| (timestamp, self.aggregator.aggregate(&values)));
+
+        QueryResult {
+            metric: self.metric.clone(),
+            tags,
+            aggregate_tags,
+            dps: self.convert_dps(points),
+        }
+    }
+
+    fn convert_dps(&self, points: impl Iterator<Item = (i64, f64)>) -> BTreeMap<i64, f64> {
+        if self.ms_resolution {
+            points.collect()
+        } else {
+            points
+                .map(|(timestamp, value)| (timestamp.div_euclid(1000), value))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common_types::{
+        column::ColumnBlockBuilder,
+        column_schema,
+        datum::{Datum, DatumKind},
+        record_batch::RecordBatch,
+        schema,
+        string::StringBytes,
+    };
+
+    use super::*;
+
+    const NOW_MS: i64 = 1_680_000_000_000;
+
+    fn build_test_schema() -> Schema {
+        schema::Builder::new()
+            .auto_increment_column_id(true)
+            .add_key_column(
+                column_schema::Builder::new("timestamp".to_string(), DatumKind::Timestamp)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .add_normal_column(
+                column_schema::Builder::new("value".to_string(), DatumKind::Double)
+                    .is_nullable(true)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .add_normal_column(
+                column_schema::Builder::new("dc".to_string(), DatumKind::String)
+                    .is_tag(true)
+                    .is_nullable(true)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .add_normal_column(
+                column_schema::Builder::new("host".to_string(), DatumKind::String)
+                    .is_tag(true)
+                    .is_nullable(true)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    // Format of rows: (timestamp, value, dc, host)
+    fn build_test_output(rows: &[(i64, f64, &str, &str)]) -> Output {
+        let schema = build_test_schema();
+        let mut builders = vec![
+            ColumnBlockBuilder::with_capacity(&DatumKind::Timestamp, rows.len(), false),
+            ColumnBlockBuilder::with_capacity(&DatumKind::Double, rows.len(), false),
+            ColumnBlockBuilder::with_capacity(&DatumKind::String, rows.len(), false),
+            ColumnBlockBuilder::with_capacity(&DatumKind::String, rows.len(), false),
+        ];
+        for (timestamp, value, dc, host) in rows {
+            builders[0]
+                .append(Datum::Timestamp((*timestamp).into()))
+                .unwrap();
+            builders[1].append(Datum::Double(*value)).unwrap();
+            builders[2]
+                .append(Datum::String(StringBytes::copy_from_str(dc)))
+                .unwrap();
+            builders[3]
+                .append(Datum::String(StringBytes::copy_from_str(host)))
+                .unwrap();
+        }
+        let column_blocks = builders.iter_mut().map(|v| v.build()).collect();
+        let record_batch = RecordBatch::new(schema.to_record_schema(), column_blocks).unwrap();
+
+        Output::Records(vec![record_batch])
+    }
+
+    fn build_test_query(body: &str) -> QueryPlan {
+        let req: QueryRequest = serde_json::from_str(body).unwrap();
+        let mut queries = convert_query_request(req, NOW_MS).unwrap();
+        assert_eq!(1, queries.len());
+        queries.remove(0)
+    }
+
+    fn build_test_put_request(body: &str, timestamp_unit: TimestampUnit) -> PutRequest {
+        PutRequest::new(
+            Bytes::copy_from_slice(body.as_bytes()),
+            PutParams {
+                timestamp_unit,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_convert_put_request_timestamp_unit() {
+        // The units of the points in one request are detected independently.
+        let body = r#"[
+            {"metric": "sys.cpu", "timestamp": 1680000000, "value": 1, "tags": {"host": "a"}},
+            {"metric": "sys.cpu", "timestamp": 1680000000000, "value": 2, "tags": {"host": "b"}}
+        ]"#;
+        let requests =
+            convert_put_request(build_test_put_request(body, TimestampUnit::Auto)).unwrap();
+        assert_eq!(1, requests.len());
+        let timestamps: Vec<_> = requests[0]
+            .entries
+            .iter()
+            .map(|entry| entry.field_groups[0].timestamp)
+            .collect();
+        assert_eq!(vec![NOW_MS, NOW_MS], timestamps);
+
+        // The unit is overridden by the params.
+        let body =
+            r#"{"metric": "sys.cpu", "timestamp": 1680000000, "value": 1, "tags": {"host": "a"}}"#;
+        let requests =
+            convert_put_request(build_test_put_request(body, TimestampUnit::Milliseconds)).unwrap();
+        assert_eq!(
+            1_680_000_000,
+            requests[0].entries[0].field_groups[0].timestamp
+        );
+        let requests =
+            convert_put_request(build_test_put_request(body, TimestampUnit::Seconds)).unwrap();
+        assert_eq!(NOW_MS, requests[0].entries[0].field_groups[0].timestamp);
+
+        let body = r#"{"metric": "sys.cpu", "timestamp": -1, "value": 1, "tags": {"host": "a"}}"#;
+        assert!(convert_put_request(build_test_put_request(body, TimestampUnit::Auto)).is_err());
+    }
+
+    #[test]
+    fn test_convert_query_request() {
+        let query = build_test_query(
+            r#"{
+                "start": "1h-ago",
+                "queries": [{
+                    "aggregator": "sum",
+                    "metric": "sys.cpu",
+                    "downsample": "1m-avg",
+                    "tags": {"host": "*", "dc": "lga|sjc"}
+                }]
+            }"#,
+        );
+        assert_eq!(NOW_MS - 3_600_000, query.start_ms);
+        assert_eq!(NOW_MS, query.end_ms);
+        assert_eq!(
+            Some(Downsample {
+                interval_ms: 60_000,
+                aggregator: Aggregator::Avg,
+            }),
+            query.downsample
+        );
+
+        let schema = build_test_schema();
+        let tag_columns = query.tag_columns(&schema).unwrap();
+        assert_eq!(vec!["dc".to_string(), "host".to_string()], tag_columns);
+        assert_eq!(
+            format!(
+                "SELECT `timestamp`, `value`, `dc`, `host` FROM `sys.cpu` \
+                WHERE `timestamp` >= {} AND `timestamp` <= {NOW_MS} AND `dc` IN ('lga', 'sjc')",
+                NOW_MS - 3_600_000
+            ),
+            query.to_sql("timestamp", &tag_columns)
+        );
+    }
+
+    #[test]
+    fn test_convert_invalid_query_request() {
+        let cases = [
+            // Unsupported aggregator.
+            r#"{"start": 1680000000, "queries": [{"aggregator": "max", "metric": "m"}]}"#,
+            // Unsupported downsample aggregator.
+            r#"{"start": 1680000000, "queries": [{"aggregator": "sum", "metric": "m", "downsample": "1m-none"}]}"#,
+            // Invalid downsample interval.
+            r#"{"start": 1680000000, "queries": [{"aggregator": "sum", "metric": "m", "downsample": "1x-avg"}]}"#,
+            // Start is after end.
+            r#"{"start": 1680000060, "end": 1680000000, "queries": [{"aggregator": "sum", "metric": "m"}]}"#,
+            // No sub query.
+            r#"{"start": 1680000000, "queries": []}"#,
+        ];
+        for body in cases {
+            let req: QueryRequest = serde_json::from_str(body).unwrap();
+            let err = convert_query_request(req, NOW_MS).unwrap_err();
+            assert_eq!(StatusCode::BAD_REQUEST, err.code(), "body:{body}");
+        }
+
+        // Unknown tag.
+        let query = build_test_query(
+            r#"{"start": 1680000000, "queries": [{"aggregator": "sum", "metric": "m", "tags": {"unknown": "*"}}]}"#,
+        );
+        assert!(query.tag_columns(&build_test_schema()).is_err());
+    }
+
+    #[test]
+    fn test_convert_query_output() {
+        let rows = [
+            (1_680_000_000_000, 1.0, "lga", "web01"),
+            (1_680_000_030_000, 3.0, "lga", "web01"),
+            (1_680_000_060_000, 5.0, "lga", "web01"),
+            (1_680_000_000_000, 10.0, "lga", "web02"),
+            (1_680_000_060_000, 20.0, "lga", "web02"),
+            (1_680_000_000_000, 100.0, "sjc", "web03"),
+        ];
+        let tag_columns = vec!["dc".to_string(), "host".to_string()];
+
+        // Downsample each series by avg, then sum the series of each dc.
+        let query = build_test_query(
+            r#"{
+                "start": 1680000000,
+                "queries": [{
+                    "aggregator": "sum",
+                    "metric": "sys.cpu",
+                    "downsample": "1m-avg",
+                    "tags": {"dc": "*"}
+                }]
+            }"#,
+        );
+        let results = query
+            .convert_output(&tag_columns, build_test_output(&rows))
+            .unwrap();
+        assert_eq!(
+            vec![
+                QueryResult {
+                    metric: "sys.cpu".to_string(),
+                    tags: BTreeMap::from([("dc".to_string(), "lga".to_string())]),
+                    aggregate_tags: vec!["host".to_string()],
+                    dps: BTreeMap::from([(1_680_000_000, 12.0), (1_680_000_060, 25.0)]),
+                },
+                QueryResult {
+                    metric: "sys.cpu".to_string(),
+                    tags: BTreeMap::from([
+                        ("dc".to_string(), "sjc".to_string()),
+                        ("host".to_string(), "web03".to_string()),
+                    ]),
+                    aggregate_tags: vec![],
+                    dps: BTreeMap::from([(1_680_000_000, 100.0)]),
+                },
+            ],
+            results
+        );
+
+        // Average all the series.
+        let query = build_test_query(
+            r#"{
+                "start": 1680000000,
+                "msResolution": true,
+                "queries": [{"aggregator": "avg", "metric": "sys.cpu"}]
+            }"#,
+        );
+        let results = query
+            .convert_output(&tag_columns, build_test_output(&rows))
+            .unwrap();
+        assert_eq!(1, results.len());
+        assert!(results[0].tags.is_empty());
+        assert_eq!(
+            vec!["dc".to_string(), "host".to_string()],
+            results[0].aggregate_tags
+        );
+        assert_eq!(
+            BTreeMap::from([
+                (1_680_000_000_000, 37.0),
+                (1_680_000_030_000, 3.0),
+                (1_680_000_060_000, 12.5),
+            ]),
+            results[0].dps
+        );
+
+        // Every series is returned as it is.
+        let query = build_test_query(
+            r#"{"start": 1680000000, "queries": [{"aggregator": "none", "metric": "sys.cpu"}]}"#,
+        );
+        let results = query
+            .convert_output(&tag_columns, build_test_output(&rows))
+            .unwrap();
+        assert_eq!(3, results.len());
+        assert_eq!(
+            BTreeMap::from([
+                (1_680_000_000, 1.0),
+                (1_680_000_030, 3.0),
+                (1_680_000_060, 5.0),
+            ]),
+            results[0].dps
+        );
+    }
+
+    #[test]
+    fn test_convert_single_metric_query_output() {
+        let rows = [
+            (1_680_000_000_000, 1.0, "lga", "web01"),
+            (1_680_000_030_000, 3.0, "lga", "web01"),
+            (1_680_000_000_000, 10.0, "lga", "web02"),
+            (1_680_000_030_000, 2.0, "lga", "web02"),
+        ];
+        let tag_columns = vec!["dc".to_string(), "host".to_string()];
+
+        let query = build_test_query(
+            r#"{
+                "start": 1680000000,
+                "end": 1680000060,
+                "queries": [{"aggregator": "sum", "metric": "sys.cpu", "tags": {"dc": "lga"}}]
+            }"#,
+        );
+        assert_eq!(
+            "SELECT `ts`, `value`, `dc`, `host` FROM `sys.cpu` WHERE `ts` >= 1680000000000 AND `ts` <= 1680000060000 AND `dc` = 'lga'",
+            query.to_sql("ts", &tag_columns)
+        );
+        let results = query
+            .convert_output(&tag_columns, build_test_output(&rows))
+            .unwrap();
+        assert_eq!(
+            vec![QueryResult {
+                metric: "sys.cpu".to_string(),
+                tags: BTreeMap::from([("dc".to_string(), "lga".to_string())]),
+                aggregate_tags: vec!["host".to_string()],
+                dps: BTreeMap::from([(1_680_000_000, 11.0), (1_680_000_030, 5.0)]),
+            }],
+            results
+        );
+    }
+}
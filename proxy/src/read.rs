@@ -2,30 +2,36 @@
 
 //! Contains common methods used by the read process.
 
-use std::time::Instant;
+use std::{sync::Arc, time::Instant};
 
 use ceresdbproto::storage::{
     storage_service_client::StorageServiceClient, RequestContext, SqlQueryRequest, SqlQueryResponse,
 };
-use common_types::request_id::RequestId;
+use common_types::{record_batch::RecordBatch, request_id::RequestId};
 use common_util::{error::BoxError, time::InstantExt};
-use futures::FutureExt;
+use futures::{stream::BoxStream, FutureExt, StreamExt};
 use http::StatusCode;
-use interpreters::interpreter::Output;
+use interpreters::interpreter::{Output, StreamOutput as InterpreterStreamOutput};
 use log::{error, info, warn};
-use query_engine::executor::Executor as QueryExecutor;
+use query_engine::{
+    context::Context as QueryContext,
+    executor::{Executor as QueryExecutor, Query},
+    explain::{AnalyzedPlan, ExplainedPlan},
+};
 use query_frontend::{
     frontend,
     frontend::{Context as SqlContext, Frontend},
+    plan::Plan,
     provider::CatalogMetaProvider,
 };
 use router::endpoint::Endpoint;
-use snafu::{ensure, ResultExt};
+use snafu::{ensure, OptionExt, ResultExt};
 use tonic::{transport::Channel, IntoRequest};
 
 use crate::{
     error::{ErrNoCause, ErrWithCause, Error, Internal, Result},
     forward::{ForwardRequest, ForwardResult},
+    running_query::Cancelled,
     Context, Proxy,
 };
 
@@ -34,10 +40,18 @@ pub enum SqlResponse {
     Local(Output),
 }
 
+/// Output of the sql query whose record batches are yielded one by one, so
+/// that they can be encoded and sent incrementally.
+pub enum StreamOutput {
+    AffectedRows(usize),
+    Records(BoxStream<'static, Result<RecordBatch>>),
+}
+
 impl<Q: QueryExecutor + 'static> Proxy<Q> {
     pub(crate) async fn handle_sql(
         &self,
         ctx: Context,
+        request_id: RequestId,
         schema: &str,
         sql: &str,
     ) -> Result<SqlResponse> {
@@ -52,23 +66,141 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
         };
 
         Ok(SqlResponse::Local(
-            self.fetch_sql_query_output(ctx, schema, sql).await?,
+            self.fetch_sql_query_output(ctx, request_id, schema, sql)
+                .await?,
         ))
     }
 
+    /// Handle the sql query on this node and yield the record batches of the
+    /// output as they are produced by the executor, which is shared by the
+    /// streaming sql query of both the gRPC and HTTP services.
+    ///
+    /// The query is kept registered as running until the returned stream ends
+    /// or is dropped, so it can be cancelled while it is streamed.
+    pub(crate) async fn fetch_sql_query_stream(
+        &self,
+        ctx: Context,
+        request_id: RequestId,
+        schema: &str,
+        sql: &str,
+    ) -> Result<StreamOutput> {
+        let begin_instant = Instant::now();
+        let deadline = ctx.timeout.map(|t| begin_instant + t);
+        let catalog = self.instance.catalog_manager.default_catalog_name();
+
+        info!("Handle sql query in stream, request_id:{request_id}, schema:{schema}, sql:{sql}");
+
+        let plan = self
+            .plan_sql_query(request_id, deadline, catalog, schema, sql)
+            .await?;
+
+        let mut running_query = self
+            .instance
+            .running_queries
+            .register(request_id, sql, catalog, schema);
+        let output = running_query
+            .run_until_cancelled(self.execute_plan_stream(
+                request_id,
+                catalog,
+                schema,
+                plan,
+                deadline,
+                ctx.enable_partition_table_access,
+            ))
+            .await
+            .ok()
+            .with_context(|| ErrNoCause {
+                code: StatusCode::INTERNAL_SERVER_ERROR,
+                msg: format!("Query is cancelled, request_id:{request_id}, sql:{sql}"),
+            })?;
+        let output = output.box_err().with_context(|| ErrWithCause {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            msg: format!("Failed to execute plan, sql:{sql}"),
+        })?;
+
+        let cost = begin_instant.saturating_elapsed();
+        info!("Handle sql query in stream started, catalog:{catalog}, schema:{schema}, request_id:{request_id}, cost:{cost:?}, sql:{sql:?}");
+
+        let records = match output {
+            InterpreterStreamOutput::AffectedRows(n) => return Ok(StreamOutput::AffectedRows(n)),
+            InterpreterStreamOutput::Records(records) => records,
+        };
+        let sql = sql.to_string();
+        let records = running_query
+            .run_stream(records)
+            .map(move |record_batch| match record_batch {
+                Ok(record_batch) => record_batch.box_err().with_context(|| ErrWithCause {
+                    code: StatusCode::INTERNAL_SERVER_ERROR,
+                    msg: format!("Failed to fetch record batch, sql:{sql}"),
+                }),
+                Err(Cancelled) => ErrNoCause {
+                    code: StatusCode::INTERNAL_SERVER_ERROR,
+                    msg: format!("Query is cancelled, request_id:{request_id}, sql:{sql}"),
+                }
+                .fail(),
+            })
+            .boxed();
+
+        Ok(StreamOutput::Records(records))
+    }
+
     pub(crate) async fn fetch_sql_query_output(
         &self,
         ctx: Context,
+        request_id: RequestId,
         schema: &str,
         sql: &str,
     ) -> Result<Output> {
-        let request_id = RequestId::next_id();
         let begin_instant = Instant::now();
         let deadline = ctx.timeout.map(|t| begin_instant + t);
         let catalog = self.instance.catalog_manager.default_catalog_name();
 
         info!("Handle sql query, request_id:{request_id}, schema:{schema}, sql:{sql}");
 
+        let plan = self
+            .plan_sql_query(request_id, deadline, catalog, schema, sql)
+            .await?;
+
+        let running_query = self
+            .instance
+            .running_queries
+            .register(request_id, sql, catalog, schema);
+        let output = if ctx.enable_partition_table_access {
+            running_query
+                .run(self.execute_plan_involving_partition_table(
+                    request_id, catalog, schema, plan, deadline,
+                ))
+                .await
+        } else {
+            running_query
+                .run(self.execute_plan(request_id, catalog, schema, plan, deadline))
+                .await
+        };
+        let output = output.ok().with_context(|| ErrNoCause {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            msg: format!("Query is cancelled, request_id:{request_id}, sql:{sql}"),
+        })?;
+        let output = output.box_err().with_context(|| ErrWithCause {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            msg: format!("Failed to execute plan, sql:{sql}"),
+        })?;
+
+        let cost = begin_instant.saturating_elapsed();
+        info!("Handle sql query success, catalog:{catalog}, schema:{schema}, request_id:{request_id}, cost:{cost:?}, sql:{sql:?}");
+
+        Ok(output)
+    }
+
+    /// Parse the sql and create its logical plan, only one statement is
+    /// supported now.
+    async fn plan_sql_query(
+        &self,
+        request_id: RequestId,
+        deadline: Option<Instant>,
+        catalog: &str,
+        schema: &str,
+        sql: &str,
+    ) -> Result<Plan> {
         let instance = &self.instance;
         // TODO(yingwen): Privilege check, cannot access data of other tenant
         // TODO(yingwen): Maybe move MetaProvider to instance
@@ -132,25 +264,91 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
                 msg: format!("Failed to create plan, query:{sql}"),
             })?;
 
-        let output = if ctx.enable_partition_table_access {
-            self.execute_plan_involving_partition_table(request_id, catalog, schema, plan, deadline)
-                .await
-        } else {
-            self.execute_plan(request_id, catalog, schema, plan, deadline)
-                .await
-        };
-        let output = output.box_err().with_context(|| ErrWithCause {
-            code: StatusCode::INTERNAL_SERVER_ERROR,
-            msg: format!("Failed to execute plan, sql:{sql}"),
-        })?;
+        Ok(plan)
+    }
 
-        let cost = begin_instant.saturating_elapsed();
-        info!("Handle sql query success, catalog:{catalog}, schema:{schema}, request_id:{request_id}, cost:{cost:?}, sql:{sql:?}");
+    /// Optimize the query into the physical plan without executing it.
+    pub(crate) async fn explain_sql_query(
+        &self,
+        ctx: Context,
+        request_id: RequestId,
+        schema: &str,
+        sql: &str,
+    ) -> Result<ExplainedPlan> {
+        let deadline = ctx.timeout.map(|t| Instant::now() + t);
+        let catalog = self.instance.catalog_manager.default_catalog_name();
 
-        Ok(output)
+        info!("Explain sql query, request_id:{request_id}, schema:{schema}, sql:{sql}");
+
+        let plan = self
+            .plan_sql_query(request_id, deadline, catalog, schema, sql)
+            .await?;
+        let (query, query_ctx) =
+            build_query_to_explain(plan, request_id, deadline, catalog, schema, sql)?;
+        self.instance
+            .query_executor
+            .explain_logical_plan(query_ctx, query)
+            .await
+            .box_err()
+            .with_context(|| ErrWithCause {
+                code: StatusCode::INTERNAL_SERVER_ERROR,
+                msg: format!("Failed to explain plan, sql:{sql}"),
+            })
     }
 
-    async fn maybe_forward_sql_query(
+    /// Execute the query and returns the physical plan with the metrics of the
+    /// execution rather than the results.
+    ///
+    /// The query is checked by the limiter and registered as running just like
+    /// the normal sql query, so it can be listed and cancelled.
+    pub(crate) async fn analyze_sql_query(
+        &self,
+        ctx: Context,
+        request_id: RequestId,
+        schema: &str,
+        sql: &str,
+    ) -> Result<AnalyzedPlan> {
+        let deadline = ctx.timeout.map(|t| Instant::now() + t);
+        let catalog = self.instance.catalog_manager.default_catalog_name();
+
+        info!("Analyze sql query, request_id:{request_id}, schema:{schema}, sql:{sql}");
+
+        let plan = self
+            .plan_sql_query(request_id, deadline, catalog, schema, sql)
+            .await?;
+        self.instance
+            .limiter
+            .try_limit(&plan)
+            .box_err()
+            .context(Internal {
+                msg: "Request is blocked",
+            })?;
+        let (query, query_ctx) =
+            build_query_to_explain(plan, request_id, deadline, catalog, schema, sql)?;
+
+        let running_query = self
+            .instance
+            .running_queries
+            .register(request_id, sql, catalog, schema);
+        let analyzed = running_query
+            .run(
+                self.instance
+                    .query_executor
+                    .analyze_logical_plan(query_ctx, query),
+            )
+            .await
+            .ok()
+            .with_context(|| ErrNoCause {
+                code: StatusCode::INTERNAL_SERVER_ERROR,
+                msg: format!("Query is cancelled, request_id:{request_id}, sql:{sql}"),
+            })?;
+        analyzed.box_err().with_context(|| ErrWithCause {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            msg: format!("Failed to analyze plan, sql:{sql}"),
+        })
+    }
+
+    pub(crate) async fn maybe_forward_sql_query(
         &self,
         ctx: Context,
         schema: &str,
@@ -209,3 +407,31 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
         })
     }
 }
+
+fn build_query_to_explain(
+    plan: Plan,
+    request_id: RequestId,
+    deadline: Option<Instant>,
+    catalog: &str,
+    schema: &str,
+    sql: &str,
+) -> Result<(Query, Arc<QueryContext>)> {
+    let plan = match plan {
+        Plan::Query(plan) => plan,
+        _ => {
+            return ErrNoCause {
+                code: StatusCode::BAD_REQUEST,
+                msg: format!("Only query statement can be explained, sql:{sql}"),
+            }
+            .fail()
+        }
+    };
+
+    let query_ctx = Arc::new(QueryContext {
+        request_id,
+        deadline,
+        default_catalog: catalog.to_string(),
+        default_schema: schema.to_string(),
+    });
+    Ok((Query::new(plan), query_ctx))
+}
@@ -0,0 +1,275 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Registry of the running queries
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+use common_types::request_id::RequestId;
+use common_util::time::InstantExt;
+use futures::{stream::BoxStream, Stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+/// Info of a running query.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunningQueryInfo {
+    pub id: u64,
+    pub sql: String,
+    pub catalog: String,
+    pub schema: String,
+    /// Elapsed time since the query starts in milliseconds
+    pub elapsed_ms: u64,
+}
+
+struct RunningQuery {
+    sql: String,
+    catalog: String,
+    schema: String,
+    begin_instant: Instant,
+    cancel_tx: oneshot::Sender<()>,
+}
+
+/// Registry of the running queries, which supports cancelling a running query
+/// by its id.
+#[derive(Default)]
+pub struct RunningQueries {
+    queries: RwLock<HashMap<u64, RunningQuery>>,
+}
+
+pub type RunningQueriesRef = Arc<RunningQueries>;
+
+/// The query is cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl RunningQueries {
+    /// Register a running query, and it will be removed from the registry once
+    /// the returned guard is dropped.
+    pub fn register(
+        self: &Arc<Self>,
+        request_id: RequestId,
+        sql: &str,
+        catalog: &str,
+        schema: &str,
+    ) -> RunningQueryGuard {
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let query = RunningQuery {
+            sql: sql.to_string(),
+            catalog: catalog.to_string(),
+            schema: schema.to_string(),
+            begin_instant: Instant::now(),
+            cancel_tx,
+        };
+        self.queries
+            .write()
+            .unwrap()
+            .insert(request_id.as_u64(), query);
+
+        RunningQueryGuard {
+            id: request_id.as_u64(),
+            registry: self.clone(),
+            cancel_rx,
+        }
+    }
+
+    /// List the running queries ordered by id.
+    pub fn list(&self) -> Vec<RunningQueryInfo> {
+        let queries = self.queries.read().unwrap();
+        let mut infos: Vec<_> = queries
+            .iter()
+            .map(|(id, query)| RunningQueryInfo {
+                id: *id,
+                sql: query.sql.clone(),
+                catalog: query.catalog.clone(),
+                schema: query.schema.clone(),
+                elapsed_ms: query.begin_instant.saturating_elapsed().as_millis() as u64,
+            })
+            .collect();
+        infos.sort_unstable_by_key(|info| info.id);
+
+        infos
+    }
+
+    /// Cancel the running query, returns false if the query is not found.
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.queries.write().unwrap().remove(&id) {
+            Some(query) => {
+                // The query may finish concurrently, so the error is ignored.
+                let _ = query.cancel_tx.send(());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Guard of a registered running query.
+pub struct RunningQueryGuard {
+    id: u64,
+    registry: RunningQueriesRef,
+    cancel_rx: oneshot::Receiver<()>,
+}
+
+impl RunningQueryGuard {
+    /// Run the query until it finishes or is cancelled, and the query future
+    /// is dropped once it is cancelled.
+    pub async fn run<F: Future>(mut self, query: F) -> Result<F::Output, Cancelled> {
+        self.run_until_cancelled(query).await
+    }
+
+    /// Yield the items of the query stream until it ends or is cancelled, and
+    /// the query is kept registered until the stream ends or is dropped.
+    ///
+    /// The stream ends after yielding [Cancelled] once it is cancelled.
+    pub fn run_stream<S>(self, stream: S) -> BoxStream<'static, Result<S::Item, Cancelled>>
+    where
+        S: Stream + Send + Unpin + 'static,
+        S::Item: Send,
+    {
+        futures::stream::unfold(Some((self, stream)), |state| async move {
+            let (mut guard, mut stream) = state?;
+            match guard.run_until_cancelled(stream.next()).await {
+                Ok(Some(item)) => Some((Ok(item), Some((guard, stream)))),
+                Ok(None) => None,
+                Err(Cancelled) => Some((Err(Cancelled), None)),
+            }
+        })
+        .boxed()
+    }
+
+    /// Run the query until it finishes or is cancelled without deregistering
+    /// it.
+    pub async fn run_until_cancelled<F: Future>(
+        &mut self,
+        query: F,
+    ) -> Result<F::Output, Cancelled> {
+        tokio::select! {
+            // The cancellation wins if the query is ready at the same time.
+            biased;
+
+            res = &mut self.cancel_rx => match res {
+                Ok(()) => Err(Cancelled),
+                // Unreachable because the sender is only dropped after sending or
+                // when the guard is dropped.
+                Err(_) => Err(Cancelled),
+            },
+            output = query => Ok(output),
+        }
+    }
+}
+
+impl Drop for RunningQueryGuard {
+    fn drop(&mut self) {
+        self.registry.queries.write().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_register_running_query() {
+        let registry = Arc::new(RunningQueries::default());
+        let guard = registry.register(RequestId::from(1), "select 1", "ceresdb", "public");
+        let queries = registry.list();
+        assert_eq!(1, queries.len());
+        assert_eq!(1, queries[0].id);
+        assert_eq!("select 1", queries[0].sql);
+        assert_eq!("ceresdb", queries[0].catalog);
+        assert_eq!("public", queries[0].schema);
+
+        assert_eq!(Ok(1), guard.run(async { 1 }).await);
+        assert!(registry.list().is_empty());
+        assert!(!registry.cancel(1));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_query() {
+        let registry = Arc::new(RunningQueries::default());
+        let guard = registry.register(RequestId::from(2), "select * from t", "ceresdb", "public");
+        let (tx, mut rx) = mpsc::channel(1);
+        let query = async move {
+            let mut i = 0;
+            loop {
+                if tx.send(i).await.is_err() {
+                    return;
+                }
+                i += 1;
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        };
+        let handle = tokio::spawn(guard.run(query));
+
+        // The query is producing output.
+        assert_eq!(Some(0), rx.recv().await);
+        assert_eq!(Some(1), rx.recv().await);
+
+        assert!(registry.cancel(2));
+        assert_eq!(Err(Cancelled), handle.await.unwrap());
+        assert!(registry.list().is_empty());
+
+        // The query stops producing output once it is cancelled.
+        while rx.recv().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn test_cancel_pending_query() {
+        let registry = Arc::new(RunningQueries::default());
+        let guard = registry.register(RequestId::from(3), "select * from t", "ceresdb", "public");
+        let handle = tokio::spawn(guard.run(futures::future::pending::<()>()));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!handle.is_finished());
+
+        // The query which never finishes is resolved once it is cancelled.
+        assert!(registry.cancel(3));
+        assert_eq!(Err(Cancelled), handle.await.unwrap());
+        assert!(!registry.cancel(3));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_query_stream() {
+        let registry = Arc::new(RunningQueries::default());
+        let guard = registry.register(RequestId::from(4), "select * from t", "ceresdb", "public");
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut stream = guard.run_stream(UnboundedReceiverStream::new(rx));
+
+        // The items are yielded as they are produced, and the query is kept
+        // registered meanwhile.
+        tx.send(1).unwrap();
+        assert_eq!(Some(Ok(1)), stream.next().await);
+        assert_eq!(1, registry.list().len());
+
+        // The stream ends once the query is cancelled, even if the next item is ready.
+        assert!(registry.cancel(4));
+        tx.send(2).unwrap();
+        assert_eq!(Some(Err(Cancelled)), stream.next().await);
+        assert_eq!(None, stream.next().await);
+
+        // The query is deregistered once the stream ends.
+        let guard = registry.register(RequestId::from(5), "select * from t", "ceresdb", "public");
+        let (tx, rx) = mpsc::unbounded_channel::<i32>();
+        let mut stream = guard.run_stream(UnboundedReceiverStream::new(rx));
+        drop(tx);
+        assert_eq!(None, stream.next().await);
+        assert!(registry.list().is_empty());
+
+        // The query is deregistered once the stream is dropped.
+        let guard = registry.register(RequestId::from(6), "select * from t", "ceresdb", "public");
+        let (_tx, rx) = mpsc::unbounded_channel::<i32>();
+        let stream = guard.run_stream(UnboundedReceiverStream::new(rx));
+        assert_eq!(1, registry.list().len());
+        drop(stream);
+        assert!(registry.list().is_empty());
+    }
+}
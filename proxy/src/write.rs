@@ -595,7 +595,7 @@ impl<Q: QueryExecutor + 'static> Proxy<Q> {
         })
     }
 
-    fn try_get_table(
+    pub(crate) fn try_get_table(
         &self,
         catalog: &str,
         schema: &str,
@@ -17,6 +17,7 @@ use table_engine::stream::SendableRecordBatchStream;
 use crate::{
     config::Config,
     context::{Context, ContextRef},
+    explain::{self, AnalyzedPlan, ExplainedPlan, PlanNode},
     logical_optimizer::{LogicalOptimizer, LogicalOptimizerImpl},
     physical_optimizer::{PhysicalOptimizer, PhysicalOptimizerImpl},
     physical_plan::PhysicalPlanPtr,
@@ -78,6 +79,31 @@ pub trait Executor: Clone + Send + Sync {
     /// REQUIRE: The meta data of tables in query should be found from
     /// ContextRef
     async fn execute_logical_plan(&self, ctx: ContextRef, query: Query) -> Result<RecordBatchVec>;
+
+    /// Execute the query, returning the stream of the query results which are
+    /// yielded as they are produced rather than collected
+    ///
+    /// REQUIRE: The meta data of tables in query should be found from
+    /// ContextRef
+    async fn execute_logical_plan_stream(
+        &self,
+        ctx: ContextRef,
+        query: Query,
+    ) -> Result<SendableRecordBatchStream>;
+
+    /// Optimize the query into the physical plan without executing it,
+    /// returning the optimized plans
+    ///
+    /// REQUIRE: The meta data of tables in query should be found from
+    /// ContextRef
+    async fn explain_logical_plan(&self, ctx: ContextRef, query: Query) -> Result<ExplainedPlan>;
+
+    /// Execute the query and discard the results, returning the physical plan
+    /// annotated with the metrics of the execution
+    ///
+    /// REQUIRE: The meta data of tables in query should be found from
+    /// ContextRef
+    async fn analyze_logical_plan(&self, ctx: ContextRef, query: Query) -> Result<AnalyzedPlan>;
 }
 
 #[derive(Clone, Default)]
@@ -89,19 +115,24 @@ impl ExecutorImpl {
     pub fn new(config: Config) -> Self {
         Self { config }
     }
-}
-
-#[async_trait]
-impl Executor for ExecutorImpl {
-    async fn execute_logical_plan(&self, ctx: ContextRef, query: Query) -> Result<RecordBatchVec> {
-        let plan = query.plan;
 
+    fn build_df_session_ctx(&self, ctx: &Context, plan: &QueryPlan) -> SessionContext {
         // Register catalogs to datafusion execution context.
         let catalogs = CatalogProviderAdapter::new_adapters(plan.tables.clone());
         let df_ctx = ctx.build_df_session_ctx(&self.config, ctx.request_id, ctx.deadline);
         for (name, catalog) in catalogs {
             df_ctx.register_catalog(&name, Arc::new(catalog));
         }
+
+        df_ctx
+    }
+}
+
+#[async_trait]
+impl Executor for ExecutorImpl {
+    async fn execute_logical_plan(&self, ctx: ContextRef, query: Query) -> Result<RecordBatchVec> {
+        let plan = query.plan;
+        let df_ctx = self.build_df_session_ctx(&ctx, &plan);
         let begin_instant = Instant::now();
 
         let physical_plan = optimize_plan(&ctx, df_ctx, plan).await?;
@@ -126,6 +157,84 @@ impl Executor for ExecutorImpl {
 
         Ok(record_batches)
     }
+
+    async fn execute_logical_plan_stream(
+        &self,
+        ctx: ContextRef,
+        query: Query,
+    ) -> Result<SendableRecordBatchStream> {
+        let plan = query.plan;
+        let df_ctx = self.build_df_session_ctx(&ctx, &plan);
+
+        let physical_plan = optimize_plan(&ctx, df_ctx, plan).await?;
+
+        debug!(
+            "Executor physical optimization finished, request_id:{}, physical_plan: {:?}",
+            ctx.request_id, physical_plan
+        );
+
+        physical_plan.execute().context(ExecutePhysical)
+    }
+
+    async fn explain_logical_plan(&self, ctx: ContextRef, query: Query) -> Result<ExplainedPlan> {
+        let plan = query.plan;
+        let df_ctx = self.build_df_session_ctx(&ctx, &plan);
+
+        let mut logical_optimizer = LogicalOptimizerImpl::with_context(df_ctx.clone());
+        let plan = logical_optimizer.optimize(plan).context(LogicalOptimize)?;
+        let logical_plan = PlanNode::from_logical_plan(&plan.df_plan);
+        let tables = explain::scanned_tables(&plan.df_plan);
+
+        let mut physical_optimizer = PhysicalOptimizerImpl::with_context(df_ctx);
+        let physical_plan = physical_optimizer
+            .optimize(plan)
+            .await
+            .context(PhysicalOptimize)?;
+        let statistics = physical_plan.statistics();
+
+        debug!(
+            "Executor explained plan, request_id:{}, physical_plan: {:?}",
+            ctx.request_id, physical_plan
+        );
+
+        Ok(ExplainedPlan {
+            logical_plan,
+            physical_plan: physical_plan.plan_tree(),
+            tables,
+            estimated_num_rows: statistics.num_rows,
+            estimated_total_byte_size: statistics.total_byte_size,
+        })
+    }
+
+    async fn analyze_logical_plan(&self, ctx: ContextRef, query: Query) -> Result<AnalyzedPlan> {
+        let plan = query.plan;
+        let df_ctx = self.build_df_session_ctx(&ctx, &plan);
+        let begin_instant = Instant::now();
+
+        let physical_plan = optimize_plan(&ctx, df_ctx, plan).await?;
+        let stream = physical_plan.execute().context(ExecutePhysical)?;
+        // Only the number of rows is needed, so drop each batch once counted.
+        let num_rows = stream
+            .try_fold(0, |num_rows, batch| async move {
+                Ok(num_rows + batch.num_rows())
+            })
+            .await
+            .context(Collect)?;
+        let elapsed = begin_instant.saturating_elapsed();
+
+        info!(
+            "Executor analyzed plan, request_id:{}, cost:{}ms, plan_and_metrics: {}",
+            ctx.request_id,
+            elapsed.as_millis(),
+            physical_plan.metrics_to_string()
+        );
+
+        Ok(AnalyzedPlan {
+            physical_plan: physical_plan.analyzed_plan_tree(),
+            num_rows,
+            elapsed_ms: elapsed.as_millis() as u64,
+        })
+    }
 }
 
 async fn optimize_plan(
@@ -151,3 +260,88 @@ async fn optimize_plan(
 async fn collect(stream: SendableRecordBatchStream) -> Result<RecordBatchVec> {
     stream.try_collect().await.context(Collect)
 }
+
+#[cfg(test)]
+mod tests {
+    use catalog::consts::{DEFAULT_CATALOG, DEFAULT_SCHEMA};
+    use common_types::request_id::RequestId;
+    use futures::StreamExt;
+    use query_frontend::{parser::Parser, plan::Plan, planner::Planner, tests::MockMetaProvider};
+
+    use super::*;
+    use crate::explain::AnalyzedPlanNode;
+
+    fn build_query_and_ctx(sql: &str) -> (Query, ContextRef) {
+        let provider = MockMetaProvider::default();
+        let planner = Planner::new(&provider, RequestId::next_id(), 1);
+        let mut statements = Parser::parse_sql(sql).unwrap();
+        let plan = match planner.statement_to_plan(statements.remove(0)).unwrap() {
+            Plan::Query(plan) => plan,
+            plan => panic!("Unexpected plan:{plan:?}"),
+        };
+
+        let ctx = Arc::new(Context {
+            request_id: RequestId::next_id(),
+            deadline: None,
+            default_catalog: DEFAULT_CATALOG.to_string(),
+            default_schema: DEFAULT_SCHEMA.to_string(),
+        });
+        (Query::new(plan), ctx)
+    }
+
+    #[tokio::test]
+    async fn test_explain_logical_plan() {
+        let (query, ctx) = build_query_and_ctx("select * from test_table");
+        let explained = ExecutorImpl::default()
+            .explain_logical_plan(ctx, query)
+            .await
+            .unwrap();
+
+        assert_eq!(vec!["test_table".to_string()], explained.tables);
+        let physical_plan = format!("{:?}", explained.physical_plan);
+        assert!(
+            physical_plan.contains("ScanTable: table=test_table"),
+            "{physical_plan}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_logical_plan_stream() {
+        let (query, ctx) = build_query_and_ctx("select count(*) from test_table");
+        let mut stream = ExecutorImpl::default()
+            .execute_logical_plan_stream(ctx, query)
+            .await
+            .unwrap();
+        assert_eq!(1, stream.schema().num_columns());
+
+        let mut num_rows = 0;
+        while let Some(batch) = stream.next().await {
+            num_rows += batch.unwrap().num_rows();
+        }
+        assert_eq!(1, num_rows);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_logical_plan() {
+        let (query, ctx) = build_query_and_ctx("select count(*) from test_table");
+        let analyzed = ExecutorImpl::default()
+            .analyze_logical_plan(ctx, query)
+            .await
+            .unwrap();
+        assert_eq!(1, analyzed.num_rows);
+
+        // The aggregation records its output rows and elapsed time.
+        fn find_aggregate(node: &AnalyzedPlanNode) -> Option<&AnalyzedPlanNode> {
+            if node.desc.starts_with("AggregateExec") {
+                return Some(node);
+            }
+            node.children.iter().find_map(find_aggregate)
+        }
+        let aggregate = find_aggregate(&analyzed.physical_plan).unwrap();
+        assert!(aggregate.output_rows.is_some(), "{aggregate:?}");
+        assert!(aggregate.elapsed_compute_ns.is_some(), "{aggregate:?}");
+
+        let json = serde_json::to_string(&analyzed).unwrap();
+        assert!(json.contains("\"elapsed_compute_ns\""), "{json}");
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Explained plans of the query
+
+use datafusion::{
+    logical_expr::LogicalPlan,
+    physical_plan::{display::DisplayableExecutionPlan, ExecutionPlan},
+};
+use serde::Serialize;
+
+/// A node of the plan tree
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PlanNode {
+    /// One line description of the node
+    pub desc: String,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    pub fn from_logical_plan(plan: &LogicalPlan) -> Self {
+        Self {
+            desc: plan.display().to_string(),
+            children: plan
+                .inputs()
+                .into_iter()
+                .map(Self::from_logical_plan)
+                .collect(),
+        }
+    }
+
+    pub fn from_execution_plan(plan: &dyn ExecutionPlan) -> Self {
+        Self {
+            desc: DisplayableExecutionPlan::new(plan).one_line().to_string(),
+            children: plan
+                .children()
+                .iter()
+                .map(|child| Self::from_execution_plan(child.as_ref()))
+                .collect(),
+        }
+    }
+}
+
+/// A node of the executed plan tree with its metrics
+#[derive(Clone, Debug, Serialize)]
+pub struct AnalyzedPlanNode {
+    /// One line description of the node
+    pub desc: String,
+    /// Number of rows output by the node, None if not recorded
+    pub output_rows: Option<usize>,
+    /// Elapsed compute time of the node in nanoseconds, None if not recorded
+    pub elapsed_compute_ns: Option<usize>,
+    /// All the metrics of the node aggregated over the partitions
+    pub metrics: String,
+    pub children: Vec<AnalyzedPlanNode>,
+}
+
+impl AnalyzedPlanNode {
+    /// Build the tree from the plan which is already executed.
+    pub fn from_execution_plan(plan: &dyn ExecutionPlan) -> Self {
+        let metrics = plan.metrics().map(|metrics| metrics.aggregate_by_name());
+        Self {
+            desc: DisplayableExecutionPlan::new(plan).one_line().to_string(),
+            output_rows: metrics.as_ref().and_then(|v| v.output_rows()),
+            elapsed_compute_ns: metrics.as_ref().and_then(|v| v.elapsed_compute()),
+            metrics: metrics
+                .map(|v| v.sorted_for_display().timestamps_removed().to_string())
+                .unwrap_or_default(),
+            children: plan
+                .children()
+                .iter()
+                .map(|child| Self::from_execution_plan(child.as_ref()))
+                .collect(),
+        }
+    }
+}
+
+/// The optimized plans of a query, which is not executed
+#[derive(Clone, Debug, Serialize)]
+pub struct ExplainedPlan {
+    pub logical_plan: PlanNode,
+    pub physical_plan: PlanNode,
+    /// Tables scanned by the query
+    pub tables: Vec<String>,
+    /// Estimated number of output rows, None if unknown
+    pub estimated_num_rows: Option<usize>,
+    /// Estimated total bytes of output, None if unknown
+    pub estimated_total_byte_size: Option<usize>,
+}
+
+/// The physical plan of a query annotated with the metrics of the execution
+#[derive(Clone, Debug, Serialize)]
+pub struct AnalyzedPlan {
+    pub physical_plan: AnalyzedPlanNode,
+    /// Number of rows returned by the query
+    pub num_rows: usize,
+    /// Elapsed time of the whole query in milliseconds
+    pub elapsed_ms: u64,
+}
+
+/// Collect the names of the tables scanned by the logical plan.
+pub fn scanned_tables(plan: &LogicalPlan) -> Vec<String> {
+    let mut tables = Vec::new();
+    collect_scanned_tables(plan, &mut tables);
+    tables
+}
+
+fn collect_scanned_tables(plan: &LogicalPlan, tables: &mut Vec<String>) {
+    if let LogicalPlan::TableScan(scan) = plan {
+        let table = scan.table_name.to_string();
+        if !tables.contains(&table) {
+            tables.push(table);
+        }
+    }
+
+    for input in plan.inputs() {
+        collect_scanned_tables(input, tables);
+    }
+}
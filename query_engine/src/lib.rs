@@ -15,6 +15,7 @@ pub mod context;
 pub mod df_execution_extension;
 pub mod df_planner_extension;
 pub mod executor;
+pub mod explain;
 pub mod logical_optimizer;
 pub mod physical_optimizer;
 pub mod physical_plan;
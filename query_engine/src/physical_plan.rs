@@ -13,13 +13,15 @@ use datafusion::{
     execution::context::TaskContext,
     physical_plan::{
         coalesce_partitions::CoalescePartitionsExec, display::DisplayableExecutionPlan,
-        ExecutionPlan,
+        ExecutionPlan, Statistics,
     },
     prelude::SessionContext,
 };
 use snafu::{Backtrace, ResultExt, Snafu};
 use table_engine::stream::{FromDfStream, SendableRecordBatchStream};
 
+use crate::explain::{AnalyzedPlanNode, PlanNode};
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display(
@@ -45,6 +47,16 @@ pub trait PhysicalPlan: std::fmt::Debug {
 
     /// Convert internal metrics to string.
     fn metrics_to_string(&self) -> String;
+
+    /// Convert this plan to a tree of nodes.
+    fn plan_tree(&self) -> PlanNode;
+
+    /// Convert this plan to a tree of nodes with the metrics collected during
+    /// the execution.
+    fn analyzed_plan_tree(&self) -> AnalyzedPlanNode;
+
+    /// Estimated statistics of the output of this plan.
+    fn statistics(&self) -> Statistics;
 }
 
 pub type PhysicalPlanPtr = Box<dyn PhysicalPlan + Send + Sync>;
@@ -96,4 +108,16 @@ impl PhysicalPlan for DataFusionPhysicalPlan {
             .indent()
             .to_string()
     }
+
+    fn plan_tree(&self) -> PlanNode {
+        PlanNode::from_execution_plan(&*self.plan)
+    }
+
+    fn analyzed_plan_tree(&self) -> AnalyzedPlanNode {
+        AnalyzedPlanNode::from_execution_plan(&*self.plan)
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.plan.statistics()
+    }
 }
@@ -169,7 +169,10 @@ mod tests {
         },
         storage::RequestContext,
     };
-    use cluster::{shard_lock_manager::ShardLockManagerRef, Cluster, ClusterNodesResp};
+    use cluster::{
+        shard_lock_manager::ShardLockManagerRef, Cluster, ClusterNodesResp, DrainShardSummary,
+        ShardEventReceiver, ShardStatus,
+    };
     use common_types::table::ShardId;
     use common_util::config::ReadableDuration;
     use meta_client::types::{
@@ -195,10 +198,18 @@ mod tests {
             unimplemented!();
         }
 
+        async fn try_open_shard(&self, _: &ShardInfo) -> cluster::Result<TablesOfShard> {
+            unimplemented!();
+        }
+
         async fn close_shard(&self, _: ShardId) -> cluster::Result<TablesOfShard> {
             unimplemented!();
         }
 
+        async fn drain_shard(&self, _: ShardId, _: Duration) -> cluster::Result<DrainShardSummary> {
+            unimplemented!();
+        }
+
         async fn freeze_shard(&self, _: ShardId) -> cluster::Result<TablesOfShard> {
             unimplemented!();
         }
@@ -263,9 +274,33 @@ mod tests {
             unimplemented!();
         }
 
+        fn tables_of_shards(&self) -> Vec<TablesOfShard> {
+            unimplemented!();
+        }
+
+        fn shard_status(&self) -> Vec<ShardStatus> {
+            unimplemented!();
+        }
+
+        fn subscribe(&self) -> ShardEventReceiver {
+            unimplemented!();
+        }
+
         fn shard_lock_manager(&self) -> ShardLockManagerRef {
             unimplemented!();
         }
+
+        fn is_heartbeat_alive(&self) -> bool {
+            unimplemented!();
+        }
+
+        fn is_started(&self) -> bool {
+            unimplemented!();
+        }
+
+        fn lock_lost_shards(&self) -> Vec<ShardId> {
+            unimplemented!();
+        }
     }
 
     #[tokio::test]
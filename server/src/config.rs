@@ -6,7 +6,7 @@ use std::collections::HashMap;
 
 use cluster::config::SchemaConfig;
 use common_types::schema::TIMESTAMP_COLUMN;
-use common_util::config::{ReadableDuration, ReadableSize};
+use common_util::config::{serialize_secret, ReadableDuration, ReadableSize};
 use meta_client::types::ShardId;
 use proxy::{forward, hotspot};
 use router::{
@@ -98,7 +98,17 @@ pub struct ServerConfig {
     pub grpc_port: u16,
 
     pub timeout: Option<ReadableDuration>,
+    /// The ceiling of the timeout overridden by the http request header, the
+    /// request with a larger timeout is rejected.
+    pub http_max_timeout: ReadableDuration,
     pub http_max_body_size: ReadableSize,
+    /// The ceiling of the duration of the cpu and heap profiling through the
+    /// http debug endpoints.
+    pub http_max_profile_duration: ReadableDuration,
+    /// The max time to wait for the in-flight http requests when the server is
+    /// stopping, and the connections are forced to close after it. No limit is
+    /// applied if it is not set.
+    pub http_drain_timeout: Option<ReadableDuration>,
     pub grpc_server_cq_count: usize,
     /// The minimum length of the response body to compress.
     pub resp_compress_min_length: ReadableSize,
@@ -120,6 +130,18 @@ pub struct ServerConfig {
 
     /// Config of remote engine client
     pub remote_client: remote_engine_client::Config,
+
+    /// Config of the api-key authentication of the http service, the requests
+    /// are not authenticated if it is not set.
+    pub http_auth: Option<HttpAuthConfig>,
+
+    /// Config of the per-client rate limit of the http write endpoints, the
+    /// requests are not limited if it is not set.
+    pub http_rate_limit: Option<HttpRateLimitConfig>,
+
+    /// Config of the tls of the http service, the http service is served in
+    /// plaintext if it is not set.
+    pub http_tls: Option<HttpTlsConfig>,
 }
 
 impl Default for ServerConfig {
@@ -130,7 +152,10 @@ impl Default for ServerConfig {
             mysql_port: 3307,
             grpc_port: 8831,
             timeout: None,
+            http_max_timeout: ReadableDuration::minutes(10),
             http_max_body_size: ReadableSize::mb(64),
+            http_max_profile_duration: ReadableDuration::minutes(5),
+            http_drain_timeout: Some(ReadableDuration::secs(30)),
             grpc_server_cq_count: 20,
             resp_compress_min_length: ReadableSize::mb(4),
             forward: forward::Config::default(),
@@ -139,10 +164,73 @@ impl Default for ServerConfig {
             route_cache: router::RouteCacheConfig::default(),
             hotspot: hotspot::Config::default(),
             remote_client: remote_engine_client::Config::default(),
+            http_auth: None,
+            http_rate_limit: None,
+            http_tls: None,
         }
     }
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpAuthConfig {
+    /// The valid api keys.
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// Whether to authenticate the requests to the `/debug` routes.
+    pub enable_debug_auth: bool,
+}
+
+/// Token bucket of every client, which is identified by the api key if the
+/// auth is enabled, otherwise by the ip.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpRateLimitConfig {
+    /// The rate of refilling the tokens.
+    pub requests_per_second: u32,
+    /// The capacity of the bucket, that is the max number of requests allowed
+    /// in a burst.
+    pub burst: u32,
+}
+
+impl Default for HttpRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 100,
+            burst: 200,
+        }
+    }
+}
+
+/// The certificate chain and private key files in the pem format.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpTlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ApiKeyConfig {
+    #[serde(serialize_with = "serialize_secret")]
+    pub key: String,
+    /// The catalogs allowed to access by the key, all catalogs are allowed if
+    /// empty.
+    pub allowed_catalogs: Vec<String>,
+    /// The schemas allowed to access by the key, all schemas are allowed if
+    /// empty.
+    pub allowed_schemas: Vec<String>,
+}
+
+impl ApiKeyConfig {
+    pub fn allows(&self, catalog: &str, schema: &str) -> bool {
+        let allowed =
+            |names: &[String], name: &str| names.is_empty() || names.iter().any(|v| v == name);
+
+        allowed(&self.allowed_catalogs, catalog) && allowed(&self.allowed_schemas, schema)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
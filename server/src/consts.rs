@@ -8,3 +8,9 @@ pub const CATALOG_HEADER: &str = "x-ceresdb-catalog";
 pub const SCHEMA_HEADER: &str = "x-ceresdb-schema";
 /// Header of tenant name
 pub const TENANT_HEADER: &str = "x-ceresdb-access-tenant";
+/// Header of request timeout in milliseconds
+pub const TIMEOUT_HEADER: &str = "x-ceresdb-timeout-ms";
+/// Header of the query id assigned by the server
+pub const QUERY_ID_HEADER: &str = "x-ceresdb-query-id";
+/// Header of the id to trace the request across services
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
@@ -58,6 +58,9 @@ pub enum StatusCode {
     #[allow(dead_code)]
     NotFound = 404,
     Internal = 500,
+    /// The request can be retried later, e.g. the shard is busy with another
+    /// open/close.
+    Busy = 503,
 }
 
 impl StatusCode {
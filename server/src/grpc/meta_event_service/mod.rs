@@ -249,15 +249,17 @@ impl HandlerContext {
 async fn do_open_shard(ctx: HandlerContext, shard_info: ShardInfo) -> Result<()> {
     ctx.acquire_shard_lock(shard_info.id).await?;
 
-    let tables_of_shard = ctx
-        .cluster
-        .open_shard(&shard_info)
-        .await
-        .box_err()
-        .context(ErrWithCause {
-            code: StatusCode::Internal,
-            msg: "fail to open shards in cluster",
-        })?;
+    // Return immediately if the shard is being opened/closed to avoid the duplicate
+    // opens piling up, and the meta can retry it later.
+    let open_result = ctx.cluster.try_open_shard(&shard_info).await;
+    let code = match &open_result {
+        Err(cluster::Error::ShardBusy { .. }) => StatusCode::Busy,
+        _ => StatusCode::Internal,
+    };
+    let tables_of_shard = open_result.box_err().context(ErrWithCause {
+        code,
+        msg: "fail to open shards in cluster",
+    })?;
 
     let catalog_name = &ctx.default_catalog;
     let shard_info = tables_of_shard.shard_info;
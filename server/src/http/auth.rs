@@ -0,0 +1,162 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Api key authentication of the http service
+
+use std::{collections::HashMap, sync::Arc};
+
+use snafu::{ensure, OptionExt};
+use warp::{header, http::header::AUTHORIZATION, reject, Filter};
+
+use crate::{
+    config::{ApiKeyConfig, HttpAuthConfig},
+    consts,
+    http::{AccessDenied, InvalidApiKey, MissingApiKey, Result},
+};
+
+/// Api key authentication, the key is carried by the `Authorization` header
+/// in the form of `Bearer <key>`.
+pub struct ApiKeyAuth {
+    api_keys: HashMap<String, ApiKeyConfig>,
+}
+
+impl ApiKeyAuth {
+    pub fn new(config: HttpAuthConfig) -> Self {
+        let api_keys = config
+            .api_keys
+            .into_iter()
+            .map(|v| (v.key.clone(), v))
+            .collect();
+
+        Self { api_keys }
+    }
+
+    fn authenticate(&self, authorization: Option<&str>, catalog: &str, schema: &str) -> Result<()> {
+        let key = authorization
+            .and_then(|v| v.trim().strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .context(MissingApiKey)?;
+        let config = self.api_keys.get(key).context(InvalidApiKey)?;
+        ensure!(
+            config.allows(catalog, schema),
+            AccessDenied { catalog, schema }
+        );
+
+        Ok(())
+    }
+}
+
+/// Authenticate the request with the catalog and schema in the headers, and
+/// all requests are passed if `auth` is None.
+pub fn auth_filter(
+    auth: Option<Arc<ApiKeyAuth>>,
+    default_catalog: String,
+    default_schema: String,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    header::optional::<String>(AUTHORIZATION.as_str())
+        .and(header::optional::<String>(consts::CATALOG_HEADER))
+        .and(header::optional::<String>(consts::SCHEMA_HEADER))
+        .and_then(
+            move |authorization: Option<String>,
+                  catalog: Option<String>,
+                  schema: Option<String>| {
+                let result = match &auth {
+                    Some(auth) => auth.authenticate(
+                        authorization.as_deref(),
+                        catalog.as_deref().unwrap_or(&default_catalog),
+                        schema.as_deref().unwrap_or(&default_schema),
+                    ),
+                    None => Ok(()),
+                };
+                async move { result.map_err(reject::custom) }
+            },
+        )
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::http::StatusCode;
+
+    use super::*;
+    use crate::http::{error_to_status_code, Error};
+
+    #[tokio::test]
+    async fn test_api_key_auth() {
+        let config = HttpAuthConfig {
+            api_keys: vec![
+                ApiKeyConfig {
+                    key: "key1".to_string(),
+                    ..Default::default()
+                },
+                ApiKeyConfig {
+                    key: "key2".to_string(),
+                    allowed_catalogs: vec!["ceresdb".to_string()],
+                    allowed_schemas: vec!["public".to_string()],
+                },
+            ],
+            enable_debug_auth: false,
+        };
+        let auth = Some(Arc::new(ApiKeyAuth::new(config)));
+        let filter = auth_filter(auth, "ceresdb".to_string(), "public".to_string())
+            .and(warp::path!("sql"))
+            .map(warp::reply);
+        let request = |authorization: Option<&str>, schema: Option<&str>| {
+            let mut request = warp::test::request().path("/sql");
+            if let Some(v) = authorization {
+                request = request.header(AUTHORIZATION, v);
+            }
+            if let Some(v) = schema {
+                request = request.header(consts::SCHEMA_HEADER, v);
+            }
+            request
+        };
+        let status_of = |rejection: warp::Rejection| {
+            let err: &Error = rejection.find().unwrap();
+            error_to_status_code(err)
+        };
+
+        // Missing key.
+        for authorization in [None, Some(""), Some("Bearer "), Some("Basic key1")] {
+            let rejection = request(authorization, None)
+                .filter(&filter)
+                .await
+                .unwrap_err();
+            assert_eq!(StatusCode::UNAUTHORIZED, status_of(rejection));
+        }
+
+        // Invalid key.
+        let rejection = request(Some("Bearer key3"), None)
+            .filter(&filter)
+            .await
+            .unwrap_err();
+        let err: &Error = rejection.find().unwrap();
+        assert!(matches!(err, Error::InvalidApiKey { .. }));
+        assert_eq!(StatusCode::UNAUTHORIZED, error_to_status_code(err));
+
+        // Valid key.
+        for authorization in ["Bearer key1", "Bearer key2", " Bearer  key2 "] {
+            assert!(request(Some(authorization), None)
+                .filter(&filter)
+                .await
+                .is_ok());
+        }
+        assert!(request(Some("Bearer key1"), Some("test"))
+            .filter(&filter)
+            .await
+            .is_ok());
+
+        // Valid key without the permission to the schema.
+        let rejection = request(Some("Bearer key2"), Some("test"))
+            .filter(&filter)
+            .await
+            .unwrap_err();
+        assert_eq!(StatusCode::FORBIDDEN, status_of(rejection));
+
+        // No auth.
+        let filter = auth_filter(None, "ceresdb".to_string(), "public".to_string())
+            .and(warp::path!("sql"))
+            .map(warp::reply);
+        assert!(request(None, None).filter(&filter).await.is_ok());
+    }
+}
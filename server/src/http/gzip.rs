@@ -0,0 +1,246 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Gzip compression of the request and response bodies
+
+use std::io::{self, Read, Write};
+
+use common_types::bytes::Bytes;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::StreamExt;
+use snafu::{ensure, ResultExt};
+use warp::{
+    header,
+    http::{
+        header::{CONTENT_ENCODING, CONTENT_LENGTH, VARY},
+        HeaderValue, Response,
+    },
+    hyper::Body,
+    reject,
+    reply::Reply,
+    Filter,
+};
+
+use crate::http::{DecompressBody, DecompressedBodyTooLarge, Result, UnsupportedContentEncoding};
+
+/// Extract the body decompressed according to the `Content-Encoding` header,
+/// the decompressed body is limited to `max_body_size` bytes.
+pub fn decoded_body(
+    max_body_size: u64,
+) -> impl Filter<Extract = (Bytes,), Error = warp::Rejection> + Clone {
+    header::optional::<String>(CONTENT_ENCODING.as_str())
+        .and(warp::body::bytes())
+        .and_then(move |encoding: Option<String>, body| async move {
+            decode_body(encoding.as_deref(), body, max_body_size).map_err(reject::custom)
+        })
+}
+
+/// Decompress the body according to the `Content-Encoding` header, only `gzip`
+/// and `identity` are supported.
+///
+/// The decompressed body must not exceed `max_size` bytes, so a small
+/// compressed body can't exhaust the memory.
+fn decode_body(encoding: Option<&str>, body: Bytes, max_size: u64) -> Result<Bytes> {
+    let encoding = match encoding {
+        Some(v) => v.trim(),
+        None => return Ok(body),
+    };
+
+    if encoding.eq_ignore_ascii_case("identity") {
+        Ok(body)
+    } else if encoding.eq_ignore_ascii_case("gzip") {
+        let mut decoded = Vec::new();
+        GzDecoder::new(body.as_ref())
+            .take(max_size.saturating_add(1))
+            .read_to_end(&mut decoded)
+            .context(DecompressBody { encoding })?;
+        ensure!(
+            decoded.len() as u64 <= max_size,
+            DecompressedBodyTooLarge { encoding, max_size }
+        );
+        Ok(Bytes::from(decoded))
+    } else {
+        UnsupportedContentEncoding { encoding }.fail()
+    }
+}
+
+/// Returns true if `gzip` is accepted by the `Accept-Encoding` header.
+fn accept_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|v| {
+        let mut parts = v.split(';');
+        let coding = parts.next().unwrap_or_default().trim();
+        let rejected = parts.any(|param| {
+            param
+                .trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .map(|q| q == 0.0)
+                .unwrap_or(false)
+        });
+        coding.eq_ignore_ascii_case("gzip") && !rejected
+    })
+}
+
+/// Compress the body of the reply by gzip if the client accepts it and the
+/// body isn't encoded yet.
+pub fn compress_reply(reply: impl Reply, accept_encoding: Option<String>) -> Response<Body> {
+    let resp = reply.into_response();
+    let accepted = accept_encoding.as_deref().map(accept_gzip).unwrap_or(false);
+    if !accepted || resp.headers().contains_key(CONTENT_ENCODING) {
+        return resp;
+    }
+
+    let (mut parts, body) = resp.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept-encoding"));
+
+    Response::from_parts(parts, gzip_body(body))
+}
+
+/// Compress the body by gzip in a streaming way, the compressed data is
+/// flushed for every chunk so the streamed response is not delayed.
+fn gzip_body(body: Body) -> Body {
+    let encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let compressed = futures::stream::unfold(Some((body, encoder)), |state| async move {
+        let (mut body, mut encoder) = state?;
+        match body.next().await {
+            Some(Ok(chunk)) => {
+                let res = encoder
+                    .write_all(&chunk)
+                    .and_then(|_| encoder.flush())
+                    .map(|_| std::mem::take(encoder.get_mut()));
+                Some((res, Some((body, encoder))))
+            }
+            Some(Err(e)) => Some((Err(io::Error::new(io::ErrorKind::Other, e)), None)),
+            None => Some((encoder.finish(), None)),
+        }
+    });
+
+    Body::wrap_stream(compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use proxy::influxdb::types::{WriteParams, WriteRequest};
+    use warp::http::{header::ACCEPT_ENCODING, StatusCode};
+
+    use super::*;
+    use crate::http::{error_to_status_code, extract_sql_request, Error};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_decode_gzip_influxdb_write_body() {
+        let lines = "demo,tag1=t1 field1=90 1678675992000\ndemo,tag1=t2 field1=91 1678675993000";
+        let gzipped = Bytes::from(gzip(lines.as_bytes()));
+        let max_size = lines.len() as u64;
+
+        let expect = WriteRequest::new(
+            decode_body(None, Bytes::from(lines), max_size).unwrap(),
+            WriteParams::default(),
+        );
+        for encoding in ["gzip", "GZIP"] {
+            let request = WriteRequest::new(
+                decode_body(Some(encoding), gzipped.clone(), max_size).unwrap(),
+                WriteParams::default(),
+            );
+            assert_eq!(expect.lines, request.lines);
+            assert_eq!(expect.db, request.db);
+        }
+        let request = WriteRequest::new(
+            decode_body(Some("identity"), Bytes::from(lines), max_size).unwrap(),
+            WriteParams::default(),
+        );
+        assert_eq!(expect.lines, request.lines);
+
+        let err = decode_body(Some("br"), gzipped.clone(), max_size).unwrap_err();
+        assert_eq!(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            error_to_status_code(&err)
+        );
+        let err = decode_body(Some("gzip"), Bytes::from(lines), max_size).unwrap_err();
+        assert_eq!(StatusCode::BAD_REQUEST, error_to_status_code(&err));
+
+        // The limit applies to the decompressed size.
+        let err = decode_body(Some("gzip"), gzipped, max_size - 1).unwrap_err();
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, error_to_status_code(&err));
+        let bomb = Bytes::from(gzip(&vec![0; 1024 * 1024]));
+        assert!(bomb.len() < 4096);
+        let err = decode_body(Some("gzip"), bomb, 4096).unwrap_err();
+        assert!(matches!(err, Error::DecompressedBodyTooLarge { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_extract_gzip_sql_request() {
+        let filter = warp::path!("sql").and(extract_sql_request(1024));
+
+        let query = "SELECT * FROM t";
+        let bodies = [
+            gzip(query.as_bytes()),
+            gzip(&serde_json::to_vec(&serde_json::json!({ "query": query })).unwrap()),
+        ];
+        for body in bodies {
+            let request = warp::test::request()
+                .method("POST")
+                .path("/sql")
+                .header(CONTENT_ENCODING, "gzip")
+                .body(body)
+                .filter(&filter)
+                .await
+                .unwrap();
+            assert_eq!(query, request.query);
+        }
+
+        let request = warp::test::request()
+            .method("POST")
+            .path("/sql")
+            .body(query)
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(query, request.query);
+    }
+
+    #[test]
+    fn test_accept_gzip() {
+        for accept_encoding in ["gzip", "GZIP", "deflate, gzip;q=1.0, *;q=0.5", "br,gzip"] {
+            assert!(accept_gzip(accept_encoding), "{accept_encoding}");
+        }
+        for accept_encoding in ["", "identity", "deflate, br", "gzip;q=0", "gzip; q=0.0"] {
+            assert!(!accept_gzip(accept_encoding), "{accept_encoding}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compress_reply() {
+        let filter = warp::path!("hello")
+            .map(|| "hello world")
+            .and(header::optional::<String>(ACCEPT_ENCODING.as_str()))
+            .map(compress_reply);
+
+        let resp = warp::test::request()
+            .path("/hello")
+            .header(ACCEPT_ENCODING, "gzip, deflate")
+            .reply(&filter)
+            .await;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("gzip", resp.headers()[CONTENT_ENCODING]);
+        let mut decoded = String::new();
+        GzDecoder::new(resp.body().as_ref())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!("hello world", decoded);
+
+        let resp = warp::test::request().path("/hello").reply(&filter).await;
+        assert!(resp.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!("hello world".as_bytes(), resp.body().as_ref());
+    }
+}
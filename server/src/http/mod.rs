@@ -0,0 +1,2968 @@
+// Copyright 2022-2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Http service
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::Infallible,
+    error::Error as StdError,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use analytic_engine::setup::OpenedWals;
+use catalog::manager::ManagerRef;
+use cluster::ClusterRef;
+use common_types::{bytes::Bytes, table::ShardId};
+use common_util::{
+    error::{BoxError, GenericError},
+    runtime::{JoinHandle, Runtime},
+};
+use log::{error, info};
+use logger::RuntimeLevel;
+use meta_client::types::TablesOfShard;
+use profile::{CpuProfileFormat, Profiler};
+use prom_remote_api::{types::ReadRequest, web};
+use proxy::{
+    context::RequestContext,
+    handlers::{self, admin::CancelQueryRequest},
+    http::{
+        prom::{accept_streamed_chunks, STREAMED_CHUNKS_CONTENT_TYPE},
+        prom_query::{InstantQueryRequest, RangeQueryRequest},
+        sql::{
+            convert_output, convert_output_to_arrow_stream, convert_output_to_chunks, ExplainMode,
+            QueryParams, Request, ResponseFormat, ARROW_STREAM_CONTENT_TYPE,
+        },
+    },
+    influxdb::types::{InfluxqlParams, InfluxqlRequest, WriteParams, WriteRequest},
+    instance::InstanceRef,
+    opentsdb::types::{PutParams, PutRequest, QueryRequest as OpentsdbQueryRequest},
+    Proxy,
+};
+use query_engine::executor::Executor as QueryExecutor;
+use router::endpoint::Endpoint;
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+use table_engine::{
+    engine::EngineRuntimes,
+    table::{FlushRequest, TableId, TableRef},
+};
+use tokio::sync::oneshot::{self, Receiver, Sender};
+use tokio_rustls::{rustls, TlsAcceptor};
+use uuid::Uuid;
+use wal::manager::WalStats;
+use warp::{
+    header,
+    http::{
+        header::{ACCEPT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER, TRANSFER_ENCODING},
+        HeaderName, HeaderValue, Method, Request as HttpRequest, Response, StatusCode,
+    },
+    hyper::{service::Service as HyperService, Body},
+    reject,
+    reply::{self, Reply},
+    Filter,
+};
+
+use crate::{
+    config::{HttpAuthConfig, HttpRateLimitConfig, HttpTlsConfig},
+    consts, error_util,
+    http::{
+        auth::{auth_filter, ApiKeyAuth},
+        gzip::{compress_reply, decoded_body},
+        rate_limit::{rate_limit_filter, RateLimiter},
+        serve::{drain_server, load_tls_config, serve},
+    },
+    metrics::{self, MetricsFormat, HTTP_HANDLER_DURATION_HISTOGRAM_VEC},
+};
+
+mod auth;
+mod gzip;
+mod rate_limit;
+mod serve;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to create request context, err:{}", source))]
+    CreateContext { source: proxy::context::Error },
+
+    #[snafu(display("Failed to handle request, err:{}", source))]
+    HandleRequest { source: GenericError },
+
+    #[snafu(display("Failed to handle update log level, err:{}", msg))]
+    HandleUpdateLogLevel { msg: String },
+
+    #[snafu(display("Missing engine runtimes to build service.\nBacktrace:\n{}", backtrace))]
+    MissingEngineRuntimes { backtrace: Backtrace },
+
+    #[snafu(display("Missing log runtime to build service.\nBacktrace:\n{}", backtrace))]
+    MissingLogRuntime { backtrace: Backtrace },
+
+    #[snafu(display("Missing instance to build service.\nBacktrace:\n{}", backtrace))]
+    MissingInstance { backtrace: Backtrace },
+
+    #[snafu(display("Missing schema config provider.\nBacktrace:\n{}", backtrace))]
+    MissingSchemaConfigProvider { backtrace: Backtrace },
+
+    #[snafu(display("Missing proxy.\nBacktrace:\n{}", backtrace))]
+    MissingProxy { backtrace: Backtrace },
+
+    #[snafu(display(
+        "Fail to do heap profiling, err:{}.\nBacktrace:\n{}",
+        source,
+        backtrace
+    ))]
+    ProfileHeap {
+        source: profile::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Fail to do cpu profiling, err:{}.\nBacktrace:\n{}", source, backtrace))]
+    ProfileCPU {
+        source: profile::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Fail to join async task, err:{}.", source))]
+    JoinAsyncTask { source: common_util::runtime::Error },
+
+    #[snafu(display(
+        "Failed to parse ip addr, ip:{}, err:{}.\nBacktrace:\n{}",
+        ip,
+        source,
+        backtrace
+    ))]
+    ParseIpAddr {
+        ip: String,
+        source: std::net::AddrParseError,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Table not found, catalog:{}, schema:{}, table:{}.\nBacktrace:\n{}",
+        catalog,
+        schema,
+        table,
+        backtrace
+    ))]
+    TableNotFound {
+        catalog: String,
+        schema: String,
+        table: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to bind http server, addr:{}, err:{}", addr, source))]
+    BindServer {
+        addr: SocketAddr,
+        source: GenericError,
+    },
+
+    #[snafu(display("Failed to read tls file, path:{}, err:{}", path, source))]
+    ReadTlsFile {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display(
+        "Invalid tls file, path:{}, msg:{}.\nBacktrace:\n{}",
+        path,
+        msg,
+        backtrace
+    ))]
+    InvalidTlsFile {
+        path: String,
+        msg: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to build tls config, err:{}", source))]
+    BuildTlsConfig { source: rustls::Error },
+
+    #[snafu(display("Internal err:{}.", source))]
+    Internal {
+        source: Box<dyn StdError + Send + Sync>,
+    },
+
+    #[snafu(display("Server already started.\nBacktrace:\n{}", backtrace))]
+    AlreadyStarted { backtrace: Backtrace },
+
+    #[snafu(display("Missing router.\nBacktrace:\n{}", backtrace))]
+    MissingRouter { backtrace: Backtrace },
+
+    #[snafu(display("Missing wal.\nBacktrace:\n{}", backtrace))]
+    MissingWal { backtrace: Backtrace },
+
+    #[snafu(display(
+        "Unsupported content encoding, encoding:{}.\nBacktrace:\n{}",
+        encoding,
+        backtrace
+    ))]
+    UnsupportedContentEncoding {
+        encoding: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Failed to decompress body, encoding:{}, err:{}.\nBacktrace:\n{}",
+        encoding,
+        source,
+        backtrace
+    ))]
+    DecompressBody {
+        encoding: String,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Timeout in the header exceeds the max timeout, timeout:{:?}, max_timeout:{:?}.\nBacktrace:\n{}",
+        timeout,
+        max_timeout,
+        backtrace
+    ))]
+    TimeoutExceedsMax {
+        timeout: Duration,
+        max_timeout: Duration,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Profile duration exceeds the max duration, duration:{:?}, max_duration:{:?}.\nBacktrace:\n{}",
+        duration,
+        max_duration,
+        backtrace
+    ))]
+    ProfileDurationExceedsMax {
+        duration: Duration,
+        max_duration: Duration,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Query string of the sql request is too long, len:{}, max_len:{}.\nBacktrace:\n{}",
+        len,
+        max_len,
+        backtrace
+    ))]
+    SqlQueryStringTooLong {
+        len: usize,
+        max_len: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Invalid timeout in the header, value:{}, err:{}.\nBacktrace:\n{}",
+        value,
+        source,
+        backtrace
+    ))]
+    InvalidTimeoutHeader {
+        value: String,
+        source: std::num::ParseIntError,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Api key is missing in the authorization header.\nBacktrace:\n{}",
+        backtrace
+    ))]
+    MissingApiKey { backtrace: Backtrace },
+
+    #[snafu(display(
+        "Api key in the authorization header is invalid.\nBacktrace:\n{}",
+        backtrace
+    ))]
+    InvalidApiKey { backtrace: Backtrace },
+
+    #[snafu(display(
+        "Api key is not allowed to access the schema, catalog:{}, schema:{}.\nBacktrace:\n{}",
+        catalog,
+        schema,
+        backtrace
+    ))]
+    AccessDenied {
+        catalog: String,
+        schema: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Decompressed body is too large, encoding:{}, max_size:{}.\nBacktrace:\n{}",
+        encoding,
+        max_size,
+        backtrace
+    ))]
+    DecompressedBodyTooLarge {
+        encoding: String,
+        max_size: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Too many requests from the client, client:{}, retry_after:{:?}.\nBacktrace:\n{}",
+        client,
+        retry_after,
+        backtrace
+    ))]
+    RateLimited {
+        client: String,
+        retry_after: Duration,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Invalid flush memtable request, err:{}", source))]
+    InvalidFlushRequest { source: serde_json::Error },
+}
+
+define_result!(Error);
+
+impl reject::Reject for Error {}
+
+/// Max length of the query string of the `GET /sql` request.
+const MAX_SQL_QUERY_STRING_LEN: usize = 16 * 1024;
+
+/// Http service
+///
+/// Endpoints beginning with /debug are for internal use, and may subject to
+/// breaking changes.
+pub struct Service<Q> {
+    proxy: Arc<Proxy<Q>>,
+    engine_runtimes: Arc<EngineRuntimes>,
+    log_runtime: Arc<RuntimeLevel>,
+    profiler: Arc<Profiler>,
+    tx: Option<Sender<()>>,
+    rx: Option<Receiver<()>>,
+    /// The task serving the connections, which is set once started.
+    server: Option<JoinHandle<()>>,
+    config: HttpConfig,
+    config_content: String,
+    /// The server config in json, whose secrets are redacted.
+    config_json: serde_json::Value,
+    opened_wals: OpenedWals,
+    /// Shared by the write routes, and requests are not limited if it is None.
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Only available in the cluster mode.
+    cluster: Option<ClusterRef>,
+    /// The tables under the manual compaction.
+    compacting_tables: CompactingTables,
+}
+
+impl<Q: QueryExecutor + 'static> Service<Q> {
+    pub async fn start(&mut self) -> Result<()> {
+        let ip_addr: IpAddr = self
+            .config
+            .endpoint
+            .addr
+            .parse()
+            .with_context(|| ParseIpAddr {
+                ip: self.config.endpoint.addr.to_string(),
+            })?;
+        let rx = self.rx.take().context(AlreadyStarted)?;
+
+        info!(
+            "HTTP server tries to listen on {}",
+            &self.config.endpoint.to_string()
+        );
+
+        // Register filters to warp and rejection handler, and serve them by hyper
+        // to assign the request id before the filters.
+        let routes = self.routes().recover(handle_rejection);
+        let service = warp::service(routes);
+        let addr = SocketAddr::new(ip_addr, self.config.endpoint.port);
+        // Fail to start if the certificate or the key is invalid.
+        let acceptor = match &self.config.tls {
+            Some(tls_config) => Some(TlsAcceptor::from(Arc::new(load_tls_config(tls_config)?))),
+            None => None,
+        };
+        let listener = std::net::TcpListener::bind(addr)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .box_err()
+            .context(BindServer { addr })?;
+
+        let server = self
+            .engine_runtimes
+            .default_runtime
+            .spawn(serve(listener, acceptor, service, rx));
+        self.server = Some(server);
+
+        Ok(())
+    }
+
+    /// Stop accepting the new connections immediately, and the returned future
+    /// resolves once the in-flight requests are done.
+    ///
+    /// If the `drain_timeout` is set, the connections are forced to close once
+    /// it elapses.
+    pub async fn stop(&mut self, drain_timeout: Option<Duration>) {
+        if let Some(tx) = self.tx.take() {
+            if let Err(e) = tx.send(()) {
+                error!("Failed to send http service stop message, err:{:?}", e);
+            }
+        }
+
+        if let Some(server) = self.server.take() {
+            drain_server(server, drain_timeout).await;
+        }
+    }
+}
+
+impl<Q: QueryExecutor + 'static> Service<Q> {
+    fn routes(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        // public APIs
+        let public_apis = self
+            .metrics()
+            .or(self.sql())
+            .or(self.sql_arrow())
+            .or(self.influxdb_api())
+            .or(self.opentsdb_api())
+            .or(self.prom_api())
+            .or(self.prom_query_api())
+            .or(self.route());
+        let admin_apis = self
+            .admin_block()
+            .or(self.admin_list_queries())
+            .or(self.admin_cancel_query())
+            .or(self.admin_cancel_query_by_id())
+            .or(self.admin_compact());
+        let debug_apis = self
+            .flush_memtable()
+            .or(self.query_plan())
+            .or(self.update_log_level())
+            .or(self.profile_cpu())
+            .or(self.profile_heap())
+            .or(self.server_config())
+            .or(self.server_config_json())
+            .or(self.stats())
+            .or(self.wal_stats())
+            .or(self.flush_stats())
+            .or(self.tables())
+            .or(self.shards());
+
+        // The home and the health are used as the health check so they are never
+        // authenticated.
+        let enable_debug_auth = self
+            .config
+            .auth
+            .as_ref()
+            .map(|v| v.enable_debug_auth)
+            .unwrap_or(false);
+        self.home()
+            .or(self.health())
+            .or(self.with_auth(true).and(public_apis.or(admin_apis)))
+            .or(self.with_auth(enable_debug_auth).and(debug_apis))
+            .and(header::optional::<String>(ACCEPT_ENCODING.as_str()))
+            .map(compress_reply)
+            .with(warp::log::custom(|info| {
+                let path = info.path();
+                // Don't record /debug API
+                if path.starts_with("/debug") {
+                    return;
+                }
+
+                HTTP_HANDLER_DURATION_HISTOGRAM_VEC
+                    .with_label_values(&[path, info.status().as_str()])
+                    .observe(info.elapsed().as_secs_f64())
+            }))
+    }
+
+    /// Expose `/prom/v1/read` and `/prom/v1/write` to serve Prometheus remote
+    /// storage request
+    fn prom_api(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let write_api = warp::path!("write")
+            .and(web::warp::with_remote_storage(self.proxy.clone()))
+            .and(self.with_context())
+            .and(web::warp::protobuf_body())
+            .and_then(web::warp::write);
+        // The streamed XOR chunks response is returned if the client prefers
+        // it, otherwise fallback to the samples response.
+        let query_api = warp::path!("read")
+            .and(web::warp::with_remote_storage(self.proxy.clone()))
+            .and(self.with_context())
+            .and(web::warp::protobuf_body())
+            .and_then(
+                |proxy: Arc<Proxy<Q>>, ctx: RequestContext, req: ReadRequest| async move {
+                    if !accept_streamed_chunks(&req) {
+                        return web::warp::read(proxy, ctx, req)
+                            .await
+                            .map(|reply| Box::new(reply) as Box<dyn Reply>);
+                    }
+
+                    let frames = proxy.handle_prom_read_streamed_chunks(ctx, req);
+                    let reply = reply::with_header(
+                        warp::http::Response::new(Body::wrap_stream(frames)),
+                        CONTENT_TYPE,
+                        STREAMED_CHUNKS_CONTENT_TYPE,
+                    );
+                    Ok(Box::new(reply) as Box<dyn Reply>)
+                },
+            );
+
+        warp::path!("prom" / "v1" / ..)
+            .and(warp::post())
+            .and(warp::body::content_length_limit(self.config.max_body_size))
+            .and(write_api.or(query_api))
+    }
+
+    // GET/POST /prom/api/v1/query
+    // GET/POST /prom/api/v1/query_range
+    fn prom_query_api(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let body_limit = warp::body::content_length_limit(self.config.max_body_size);
+
+        let instant_params = warp::get()
+            .and(warp::query::<InstantQueryRequest>())
+            .or(warp::post()
+                .and(body_limit)
+                .and(warp::body::form::<InstantQueryRequest>()))
+            .unify();
+        let instant_api = warp::path!("query")
+            .and(self.with_context())
+            .and(instant_params)
+            .and(self.with_proxy())
+            .and_then(|ctx, req, proxy: Arc<Proxy<Q>>| async move {
+                let result = proxy.handle_prom_instant_query(ctx, req).await;
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            });
+
+        let range_params = warp::get()
+            .and(warp::query::<RangeQueryRequest>())
+            .or(warp::post()
+                .and(body_limit)
+                .and(warp::body::form::<RangeQueryRequest>()))
+            .unify();
+        let range_api = warp::path!("query_range")
+            .and(self.with_context())
+            .and(range_params)
+            .and(self.with_proxy())
+            .and_then(|ctx, req, proxy: Arc<Proxy<Q>>| async move {
+                let result = proxy.handle_prom_range_query(ctx, req).await;
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            });
+
+        warp::path!("prom" / "api" / "v1" / ..).and(instant_api.or(range_api))
+    }
+
+    // GET /
+    fn home(&self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path::end().and(warp::get()).map(|| {
+            let mut resp = HashMap::new();
+            resp.insert("status", "ok");
+            reply::json(&resp)
+        })
+    }
+
+    // GET /health
+    //
+    // Unlike the cheap liveness probe of the home, the wals and the cluster (the
+    // heartbeat and the shard locks) are checked, and 503 is returned if any of
+    // them is unhealthy.
+    fn health(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let opened_wals = self.opened_wals.clone();
+        let cluster = self.cluster.clone();
+        warp::path!("health").and(warp::get()).and_then(move || {
+            let opened_wals = opened_wals.clone();
+            let cluster = cluster.clone();
+            async move {
+                let health = check_health(&opened_wals, cluster.as_ref()).await;
+                let status = if health.healthy {
+                    StatusCode::OK
+                } else {
+                    StatusCode::SERVICE_UNAVAILABLE
+                };
+                Ok::<_, warp::Rejection>(reply::with_status(reply::json(&health), status))
+            }
+        })
+    }
+
+    // POST /sql
+    // GET /sql?query=...
+    //
+    // The output format is decided by the `format` param or the `Accept` header,
+    // which can be one of `json` (default), `ndjson`, `arrow` and `csv`. Formats
+    // other than `json` are streamed in chunked transfer encoding.
+    //
+    // The id of the query is returned in the [consts::QUERY_ID_HEADER] header,
+    // and the running query can be cancelled by `POST /admin/cancel/{id}`.
+    //
+    // With `explain=analyze`, the query is executed and the physical plan
+    // annotated with the metrics of every operator is returned in JSON rather
+    // than the results.
+    fn sql(&self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let post_request = warp::post()
+            .and(warp::body::content_length_limit(self.config.max_body_size))
+            .and(extract_sql_request(self.config.max_body_size));
+        let get_request = warp::get().and(extract_sql_request_from_query());
+
+        warp::path!("sql")
+            .and(self.with_rate_limit())
+            .and(post_request.or(get_request).unify())
+            .and(
+                warp::query::<QueryParams>()
+                    .and(header::optional::<String>("accept"))
+                    .map(|params: QueryParams, accept: Option<String>| {
+                        (params.response_format(accept.as_deref()), params.explain)
+                    }),
+            )
+            .and(self.with_context())
+            .and(self.with_proxy())
+            .and_then(
+                |req, (format, explain), ctx, proxy: Arc<Proxy<Q>>| async move {
+                    if let Some(ExplainMode::Analyze) = explain {
+                        let plan = proxy
+                            .handle_http_analyze_sql_query(&ctx, req)
+                            .await
+                            .box_err()
+                            .context(HandleRequest)
+                            .map_err(reject::custom)?;
+                        return Ok::<_, warp::Rejection>(reply::with_header(
+                            Box::new(reply::json(&plan)) as Box<dyn Reply>,
+                            consts::QUERY_ID_HEADER,
+                            ctx.request_id.to_string(),
+                        ));
+                    }
+
+                    let reply: Box<dyn Reply> = match format {
+                        ResponseFormat::Json => {
+                            let output = proxy
+                                .handle_http_sql_query(&ctx, req)
+                                .await
+                                .box_err()
+                                .context(HandleRequest)
+                                .map_err(reject::custom)?;
+                            Box::new(reply::json(&convert_output(output)))
+                        }
+                        format => {
+                            let output = proxy
+                                .handle_http_stream_sql_query(&ctx, req)
+                                .await
+                                .box_err()
+                                .context(HandleRequest)
+                                .map_err(reject::custom)?;
+                            let chunks = convert_output_to_chunks(output, format);
+                            Box::new(reply::with_header(
+                                warp::http::Response::new(Body::wrap_stream(chunks)),
+                                CONTENT_TYPE,
+                                format.content_type(),
+                            ))
+                        }
+                    };
+                    Ok::<_, warp::Rejection>(reply::with_header(
+                        reply,
+                        consts::QUERY_ID_HEADER,
+                        ctx.request_id.to_string(),
+                    ))
+                },
+            )
+    }
+
+    // POST /sql/arrow
+    //
+    // The output is returned as an Arrow IPC stream in chunked transfer
+    // encoding, and the response is aborted if it fails to encode the output.
+    fn sql_arrow(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("sql" / "arrow")
+            .and(self.with_rate_limit())
+            .and(warp::post())
+            .and(warp::body::content_length_limit(self.config.max_body_size))
+            .and(extract_sql_request(self.config.max_body_size))
+            .and(self.with_context())
+            .and(self.with_proxy())
+            .and_then(|req, ctx, proxy: Arc<Proxy<Q>>| async move {
+                let result = proxy
+                    .handle_http_stream_sql_query(&ctx, req)
+                    .await
+                    .box_err()
+                    .context(HandleRequest);
+                match result {
+                    Ok(output) => {
+                        let body = Body::wrap_stream(convert_output_to_arrow_stream(output));
+                        let reply = reply::with_header(
+                            warp::http::Response::new(body),
+                            CONTENT_TYPE,
+                            ARROW_STREAM_CONTENT_TYPE,
+                        );
+                        Ok(reply::with_header(
+                            reply,
+                            consts::QUERY_ID_HEADER,
+                            ctx.request_id.to_string(),
+                        ))
+                    }
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    // GET /route
+    fn route(&self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("route" / String)
+            .and(warp::get())
+            .and(self.with_context())
+            .and(self.with_proxy())
+            .and_then(|table: String, ctx, proxy: Arc<Proxy<Q>>| async move {
+                let result = proxy
+                    .handle_http_route(&ctx, table)
+                    .await
+                    .box_err()
+                    .context(HandleRequest);
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    /// for write api:
+    ///     POST `/influxdb/v1/write`
+    ///
+    /// for query api:
+    ///     POST/GET `/influxdb/v1/query`
+    ///
+    /// It's derived from the influxdb 1.x query api described doc of 1.8:
+    ///     https://docs.influxdata.com/influxdb/v1.8/tools/api/#query-http-endpoint
+    fn influxdb_api(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let body_limit = warp::body::content_length_limit(self.config.max_body_size);
+
+        let write_api = warp::path!("write")
+            .and(warp::post())
+            .and(self.with_rate_limit())
+            .and(body_limit)
+            .and(self.with_context())
+            .and(warp::query::<WriteParams>())
+            .and(decoded_body(self.config.max_body_size))
+            .and(self.with_proxy())
+            .and_then(|ctx, params, lines, proxy: Arc<Proxy<Q>>| async move {
+                let request = WriteRequest::new(lines, params);
+                let result = proxy.handle_influxdb_write(ctx, request).await;
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            });
+
+        let query_api = warp::path!("query")
+            .and(influxql_query_body(self.config.max_body_size))
+            .and(self.with_context())
+            .and(warp::query::<InfluxqlParams>())
+            .and(self.with_proxy())
+            .and_then(
+                |(method, body), ctx, params, proxy: Arc<Proxy<Q>>| async move {
+                    let request =
+                        InfluxqlRequest::try_new(method, body, params).map_err(reject::custom)?;
+                    let result = proxy
+                        .handle_influxdb_query(ctx, request)
+                        .await
+                        .box_err()
+                        .context(HandleRequest);
+                    match result {
+                        Ok(res) => Ok(reply::json(&res)),
+                        Err(e) => Err(reject::custom(e)),
+                    }
+                },
+            );
+
+        warp::path!("influxdb" / "v1" / ..).and(write_api.or(query_api))
+    }
+
+    // POST /opentsdb/api/put
+    // POST /opentsdb/api/query
+    fn opentsdb_api(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let body_limit = warp::body::content_length_limit(self.config.max_body_size);
+
+        let put_api = warp::path!("put")
+            .and(warp::post())
+            .and(self.with_rate_limit())
+            .and(body_limit)
+            .and(self.with_context())
+            .and(warp::query::<PutParams>())
+            .and(decoded_body(self.config.max_body_size))
+            .and(self.with_proxy())
+            .and_then(|ctx, params, points, proxy: Arc<Proxy<Q>>| async move {
+                let request = PutRequest::new(points, params);
+                let result = proxy.handle_opentsdb_put(ctx, request).await;
+                match result {
+                    Ok(_res) => Ok(reply::with_status(warp::reply(), StatusCode::NO_CONTENT)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            });
+
+        let query_api = warp::path!("query")
+            .and(warp::post())
+            .and(body_limit)
+            .and(self.with_context())
+            .and(warp::body::json())
+            .and(self.with_proxy())
+            .and_then(
+                |ctx, req: OpentsdbQueryRequest, proxy: Arc<Proxy<Q>>| async move {
+                    let result = proxy.handle_opentsdb_query(ctx, req).await;
+                    match result {
+                        Ok(res) => Ok(reply::json(&res)),
+                        Err(e) => Err(reject::custom(e)),
+                    }
+                },
+            );
+
+        warp::path!("opentsdb" / "api" / ..).and(put_api.or(query_api))
+    }
+
+    // POST /debug/flush_memtable
+    fn flush_memtable(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("debug" / "flush_memtable")
+            .and(warp::post())
+            .and(optional_body(self.config.max_body_size))
+            .and(self.with_instance())
+            .and_then(|body: Bytes, instance: InstanceRef<Q>| async move {
+                let result = match parse_flush_memtable_request(&body) {
+                    Ok(req) => flush_memtable(&instance.catalog_manager, req).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(result) => Ok(reply::json(&result)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    // POST /debug/query_plan
+    //
+    // Returns the optimized logical and physical plans of the query without
+    // executing it.
+    fn query_plan(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("debug" / "query_plan")
+            .and(warp::post())
+            .and(warp::body::content_length_limit(self.config.max_body_size))
+            .and(extract_sql_request(self.config.max_body_size))
+            .and(self.with_context())
+            .and(self.with_proxy())
+            .and_then(|req, ctx, proxy: Arc<Proxy<Q>>| async move {
+                let result = proxy
+                    .handle_http_explain_sql_query(&ctx, req)
+                    .await
+                    .box_err()
+                    .context(HandleRequest);
+                match result {
+                    Ok(plan) => Ok(reply::json(&plan)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    // GET /metrics
+    //
+    // The metrics are in the OpenMetrics format if it is accepted by the
+    // `Accept` header, otherwise in the legacy prometheus format.
+    fn metrics(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("metrics")
+            .and(warp::get())
+            .and(header::optional::<String>("accept"))
+            .map(|accept: Option<String>| {
+                let format = MetricsFormat::from_accept(accept.as_deref());
+                reply::with_header(metrics::dump(format), CONTENT_TYPE, format.content_type())
+            })
+    }
+
+    // GET /debug/profile/cpu/{seconds}?format={pprof|flamegraph}
+    fn profile_cpu(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let max_profile_duration = self.config.max_profile_duration;
+        warp::path!("debug" / "profile" / "cpu" / ..)
+            .and(warp::path::param::<u64>())
+            .and(warp::get())
+            .and(warp::query::<CpuProfileParams>())
+            .and(self.with_profiler())
+            .and(self.with_runtime())
+            .and_then(
+                move |duration_sec: u64,
+                      params: CpuProfileParams,
+                      profiler: Arc<Profiler>,
+                      runtime: Arc<Runtime>| async move {
+                    check_profile_duration(duration_sec, max_profile_duration)
+                        .map_err(reject::custom)?;
+                    let format = params.format.unwrap_or_default();
+                    let handle = runtime.spawn_blocking(move || {
+                        profiler
+                            .dump_cpu_prof(duration_sec, format.into())
+                            .context(ProfileCPU)
+                    });
+                    let result = handle.await.context(JoinAsyncTask);
+                    match result {
+                        Ok(Ok(prof_data)) => Ok(reply::with_header(
+                            prof_data,
+                            CONTENT_TYPE,
+                            format.content_type(),
+                        )),
+                        Ok(Err(e)) => Err(reject::custom(e)),
+                        Err(e) => Err(reject::custom(e)),
+                    }
+                },
+            )
+    }
+
+    // GET /debug/profile/heap/{seconds}
+    fn profile_heap(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let max_profile_duration = self.config.max_profile_duration;
+        warp::path!("debug" / "profile" / "heap" / ..)
+            .and(warp::path::param::<u64>())
+            .and(warp::get())
+            .and(self.with_profiler())
+            .and(self.with_runtime())
+            .and_then(
+                move |duration_sec: u64,
+                      profiler: Arc<Profiler>,
+                      runtime: Arc<Runtime>| async move {
+                    check_profile_duration(duration_sec, max_profile_duration)
+                        .map_err(reject::custom)?;
+                    let handle = runtime.spawn_blocking(move || {
+                        profiler.dump_heap_prof(duration_sec).context(ProfileHeap)
+                    });
+                    let result = handle.await.context(JoinAsyncTask);
+                    match result {
+                        Ok(Ok(prof_data)) => Ok(prof_data.into_response()),
+                        Ok(Err(e)) => Err(reject::custom(e)),
+                        Err(e) => Err(reject::custom(e)),
+                    }
+                },
+            )
+    }
+
+    // GET /debug/config
+    fn server_config(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let server_config_content = self.config_content.clone();
+        warp::path!("debug" / "config")
+            .and(warp::get())
+            .map(move || server_config_content.clone())
+    }
+
+    // GET /debug/config/json
+    fn server_config_json(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let server_config_json = self.config_json.clone();
+        warp::path!("debug" / "config" / "json")
+            .and(warp::get())
+            .map(move || reply::json(&server_config_json))
+    }
+
+    // GET /debug/shards
+    // GET /debug/shards?shard_id=...
+    fn shards(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let cluster = self.cluster.clone();
+        warp::path!("debug" / "shards")
+            .and(warp::get())
+            .and(warp::query::<ShardsParams>())
+            .map(move |params: ShardsParams| {
+                // No shards on the node in the standalone mode.
+                let tables_of_shards = cluster
+                    .as_ref()
+                    .map(|v| v.tables_of_shards())
+                    .unwrap_or_default();
+                reply::json(&shards_debug_info(tables_of_shards, params.shard_id))
+            })
+    }
+
+    // GET /debug/stats
+    fn stats(&self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let opened_wals = self.opened_wals.clone();
+        warp::path!("debug" / "stats")
+            .and(warp::get())
+            .map(move || {
+                [
+                    "Data wal stats:",
+                    &opened_wals
+                        .data_wal
+                        .get_statistics()
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                    "Manifest wal stats:",
+                    &opened_wals
+                        .manifest_wal
+                        .get_statistics()
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                ]
+                .join("\n")
+            })
+    }
+
+    // GET /debug/stats/wal
+    fn wal_stats(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let opened_wals = self.opened_wals.clone();
+        warp::path!("debug" / "stats" / "wal")
+            .and(warp::get())
+            .map(move || reply::json(&WalStatsResponse::new(&opened_wals)))
+    }
+
+    // GET /debug/stats/flush
+    fn flush_stats(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("debug" / "stats" / "flush")
+            .and(warp::get())
+            .and(self.with_instance())
+            .and_then(|instance: InstanceRef<Q>| async move {
+                let get_all_flush_stats = || {
+                    let mut flush_stats = HashMap::new();
+                    for TableWithSchema { table, .. } in all_tables(&instance.catalog_manager)? {
+                        let stats = table.stats();
+                        flush_stats.insert(
+                            table.name().to_string(),
+                            FlushStats {
+                                waiting_flush: stats.num_write_waiting_flush > 0,
+                                total_stall_ms: stats.write_flush_wait_ms,
+                            },
+                        );
+                    }
+                    Result::Ok(flush_stats)
+                };
+                match get_all_flush_stats() {
+                    Ok(flush_stats) => Ok(reply::json(&flush_stats)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    // GET /debug/tables
+    fn tables(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("debug" / "tables")
+            .and(warp::get())
+            .and(self.with_instance())
+            .and_then(|instance: InstanceRef<Q>| async move {
+                match tables_debug_info(&instance.catalog_manager) {
+                    Ok(tables) => Ok(reply::json(&tables)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    // PUT /debug/log_level/{level}
+    fn update_log_level(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("debug" / "log_level" / String)
+            .and(warp::put())
+            .and(self.with_log_runtime())
+            .and_then(
+                |log_level: String, log_runtime: Arc<RuntimeLevel>| async move {
+                    let result = log_runtime
+                        .set_level_by_str(log_level.as_str())
+                        .map_err(|e| Error::HandleUpdateLogLevel { msg: e });
+                    match result {
+                        Ok(()) => Ok(reply::json(&log_level)),
+                        Err(e) => Err(reject::custom(e)),
+                    }
+                },
+            )
+    }
+
+    // POST /admin/block
+    fn admin_block(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "block")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|req, ctx, instance| async {
+                let result = handlers::admin::handle_block(ctx, instance, req)
+                    .await
+                    .box_err()
+                    .context(HandleRequest);
+
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    // GET /admin/queries
+    fn admin_list_queries(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "queries")
+            .and(warp::get())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|ctx, instance| async {
+                let result = handlers::admin::handle_list_queries(ctx, instance)
+                    .await
+                    .box_err()
+                    .context(HandleRequest);
+
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    // POST /admin/queries/cancel
+    fn admin_cancel_query(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "queries" / "cancel")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|req, ctx, instance| async {
+                let result = handlers::admin::handle_cancel_query(ctx, instance, req)
+                    .await
+                    .box_err()
+                    .context(HandleRequest);
+
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    // POST /admin/cancel/{query_id}
+    fn admin_cancel_query_by_id(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "cancel" / u64)
+            .and(warp::post())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|id, ctx, instance| async move {
+                let req = CancelQueryRequest { id };
+                let result = handlers::admin::handle_cancel_query(ctx, instance, req)
+                    .await
+                    .box_err()
+                    .context(HandleRequest);
+
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    // POST /admin/compact
+    fn admin_compact(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let runtime = self.engine_runtimes.compact_runtime.clone();
+        let compacting_tables = self.compacting_tables.clone();
+        warp::path!("admin" / "compact")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(
+                move |req: CompactRequest, ctx: RequestContext, instance: InstanceRef<Q>| {
+                    let runtime = runtime.clone();
+                    let compacting_tables = compacting_tables.clone();
+                    async move {
+                        let result = schedule_compaction(
+                            &instance.catalog_manager,
+                            &runtime,
+                            &compacting_tables,
+                            &ctx,
+                            req,
+                        );
+                        match result {
+                            Ok(res) => Ok(reply::json(&res)),
+                            Err(e) => Err(reject::custom(e)),
+                        }
+                    }
+                },
+            )
+    }
+
+    fn with_context(
+        &self,
+    ) -> impl Filter<Extract = (RequestContext,), Error = warp::Rejection> + Clone {
+        let default_catalog = self
+            .proxy
+            .instance()
+            .catalog_manager
+            .default_catalog_name()
+            .to_string();
+        let default_schema = self
+            .proxy
+            .instance()
+            .catalog_manager
+            .default_schema_name()
+            .to_string();
+        let timeout = self.config.timeout;
+        let max_timeout = self.config.max_timeout;
+
+        header::optional::<String>(consts::CATALOG_HEADER)
+            .and(header::optional::<String>(consts::SCHEMA_HEADER))
+            .and(header::optional::<String>(consts::TENANT_HEADER))
+            .and(header::optional::<String>(consts::TIMEOUT_HEADER))
+            .and(header::optional::<String>(consts::REQUEST_ID_HEADER))
+            .and_then(
+                move |catalog: Option<_>,
+                      schema: Option<_>,
+                      _tenant: Option<_>,
+                      timeout_ms: Option<String>,
+                      trace_id: Option<String>| {
+                    // Clone the captured variables
+                    let default_catalog = default_catalog.clone();
+                    let schema = schema.unwrap_or_else(|| default_schema.clone());
+                    async move {
+                        let timeout = resolve_timeout(timeout, timeout_ms.as_deref(), max_timeout)
+                            .map_err(reject::custom)?;
+                        RequestContext::builder()
+                            .catalog(catalog.unwrap_or(default_catalog))
+                            .schema(schema)
+                            .timeout(timeout)
+                            .trace_id(Some(trace_id.unwrap_or_else(new_request_id)))
+                            .enable_partition_table_access(true)
+                            .build()
+                            .context(CreateContext)
+                            .map_err(reject::custom)
+                    }
+                },
+            )
+    }
+
+    /// Authenticate the request by the api key in the `Authorization` header if
+    /// the auth is configured and `enable` is true.
+    fn with_auth(
+        &self,
+        enable: bool,
+    ) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+        let auth = self
+            .config
+            .auth
+            .clone()
+            .filter(|_| enable)
+            .map(|config| Arc::new(ApiKeyAuth::new(config)));
+        let catalog_manager = &self.proxy.instance().catalog_manager;
+
+        auth_filter(
+            auth,
+            catalog_manager.default_catalog_name().to_string(),
+            catalog_manager.default_schema_name().to_string(),
+        )
+    }
+
+    fn with_rate_limit(&self) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+        rate_limit_filter(self.rate_limiter.clone())
+    }
+
+    fn with_profiler(&self) -> impl Filter<Extract = (Arc<Profiler>,), Error = Infallible> + Clone {
+        let profiler = self.profiler.clone();
+        warp::any().map(move || profiler.clone())
+    }
+
+    fn with_proxy(&self) -> impl Filter<Extract = (Arc<Proxy<Q>>,), Error = Infallible> + Clone {
+        let proxy = self.proxy.clone();
+        warp::any().map(move || proxy.clone())
+    }
+
+    fn with_runtime(&self) -> impl Filter<Extract = (Arc<Runtime>,), Error = Infallible> + Clone {
+        let runtime = self.engine_runtimes.default_runtime.clone();
+        warp::any().map(move || runtime.clone())
+    }
+
+    fn with_instance(
+        &self,
+    ) -> impl Filter<Extract = (InstanceRef<Q>,), Error = Infallible> + Clone {
+        let instance = self.proxy.instance();
+        warp::any().map(move || instance.clone())
+    }
+
+    fn with_log_runtime(
+        &self,
+    ) -> impl Filter<Extract = (Arc<RuntimeLevel>,), Error = Infallible> + Clone {
+        let log_runtime = self.log_runtime.clone();
+        warp::any().map(move || log_runtime.clone())
+    }
+}
+
+/// Service builder
+pub struct Builder<Q> {
+    config: HttpConfig,
+    engine_runtimes: Option<Arc<EngineRuntimes>>,
+    log_runtime: Option<Arc<RuntimeLevel>>,
+    config_content: Option<String>,
+    config_json: Option<serde_json::Value>,
+    proxy: Option<Arc<Proxy<Q>>>,
+    opened_wals: Option<OpenedWals>,
+    cluster: Option<ClusterRef>,
+}
+
+impl<Q> Builder<Q> {
+    pub fn new(config: HttpConfig) -> Self {
+        Self {
+            config,
+            engine_runtimes: None,
+            log_runtime: None,
+            config_content: None,
+            config_json: None,
+            proxy: None,
+            opened_wals: None,
+            cluster: None,
+        }
+    }
+
+    pub fn engine_runtimes(mut self, engine_runtimes: Arc<EngineRuntimes>) -> Self {
+        self.engine_runtimes = Some(engine_runtimes);
+        self
+    }
+
+    pub fn log_runtime(mut self, log_runtime: Arc<RuntimeLevel>) -> Self {
+        self.log_runtime = Some(log_runtime);
+        self
+    }
+
+    pub fn config_content(mut self, content: String) -> Self {
+        self.config_content = Some(content);
+        self
+    }
+
+    /// The server config in json, whose secrets should be redacted.
+    pub fn config_json(mut self, config_json: serde_json::Value) -> Self {
+        self.config_json = Some(config_json);
+        self
+    }
+
+    pub fn proxy(mut self, proxy: Arc<Proxy<Q>>) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn opened_wals(mut self, opened_wals: OpenedWals) -> Self {
+        self.opened_wals = Some(opened_wals);
+        self
+    }
+
+    pub fn cluster(mut self, cluster: Option<ClusterRef>) -> Self {
+        self.cluster = cluster;
+        self
+    }
+}
+
+impl<Q: QueryExecutor + 'static> Builder<Q> {
+    /// Build and start the service
+    pub fn build(self) -> Result<Service<Q>> {
+        let engine_runtimes = self.engine_runtimes.context(MissingEngineRuntimes)?;
+        let log_runtime = self.log_runtime.context(MissingLogRuntime)?;
+        let config_content = self.config_content.context(MissingInstance)?;
+        let config_json = self.config_json.context(MissingInstance)?;
+        let proxy = self.proxy.context(MissingProxy)?;
+        let opened_wals = self.opened_wals.context(MissingWal)?;
+
+        let (tx, rx) = oneshot::channel();
+        let rate_limiter = self.config.rate_limit.as_ref().map(|config| {
+            // The api key identifies the client only if it is authenticated.
+            Arc::new(RateLimiter::new(config, self.config.auth.is_some()))
+        });
+
+        let service = Service {
+            proxy,
+            engine_runtimes,
+            log_runtime,
+            profiler: Arc::new(Profiler::default()),
+            tx: Some(tx),
+            rx: Some(rx),
+            server: None,
+            config: self.config,
+            config_content,
+            config_json,
+            opened_wals,
+            rate_limiter,
+            cluster: self.cluster,
+            compacting_tables: Arc::new(Mutex::new(HashSet::new())),
+        };
+
+        Ok(service)
+    }
+}
+
+/// Http service config
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub endpoint: Endpoint,
+    pub max_body_size: u64,
+    pub timeout: Option<Duration>,
+    /// The ceiling of the timeout overridden by the request header
+    pub max_timeout: Duration,
+    /// The ceiling of the profiling duration
+    pub max_profile_duration: Duration,
+    pub auth: Option<HttpAuthConfig>,
+    pub rate_limit: Option<HttpRateLimitConfig>,
+    /// The service is served in plaintext if it is not set.
+    pub tls: Option<HttpTlsConfig>,
+}
+
+/// A table with the names of the catalog and schema it belongs to.
+struct TableWithSchema {
+    catalog: String,
+    schema: String,
+    table: TableRef,
+}
+
+/// Walk through the tables of all the schemas of all the catalogs.
+fn all_tables(catalog_manager: &ManagerRef) -> Result<Vec<TableWithSchema>> {
+    let mut tables = Vec::new();
+    for catalog in catalog_manager.all_catalogs().box_err().context(Internal)? {
+        for schema in catalog.all_schemas().box_err().context(Internal)? {
+            for table in schema.all_tables().box_err().context(Internal)? {
+                tables.push(TableWithSchema {
+                    catalog: catalog.name().to_string(),
+                    schema: schema.name().to_string(),
+                    table,
+                });
+            }
+        }
+    }
+
+    Ok(tables)
+}
+
+/// Request of `POST /debug/flush_memtable`, only the tables matching all the
+/// given conditions are flushed, and all tables are flushed if nothing is set.
+#[derive(Debug, Default, Deserialize)]
+struct FlushMemtableRequest {
+    /// The catalog of the tables to flush.
+    catalog: Option<String>,
+    /// The schema of the tables to flush.
+    schema: Option<String>,
+    /// The tables to flush in the form of `schema.table`, and the table
+    /// without schema is in the `schema` or the default schema.
+    tables: Option<Vec<String>>,
+}
+
+fn parse_flush_memtable_request(body: &[u8]) -> Result<FlushMemtableRequest> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(FlushMemtableRequest::default());
+    }
+
+    serde_json::from_slice(body).context(InvalidFlushRequest)
+}
+
+/// Find the table named `schema.table` in the `catalog`, and the default
+/// catalog and `default_schema` are used if they are not given.
+fn find_table(
+    catalog_manager: &ManagerRef,
+    catalog: Option<&str>,
+    default_schema: Option<&str>,
+    name: &str,
+) -> Result<Option<TableRef>> {
+    let default_schema = default_schema.unwrap_or(catalog_manager.default_schema_name());
+    let (schema_name, table_name) = name.split_once('.').unwrap_or((default_schema, name));
+    get_table(
+        catalog_manager,
+        catalog.unwrap_or(catalog_manager.default_catalog_name()),
+        schema_name,
+        table_name,
+    )
+}
+
+/// Get the table by the names of the catalog, schema and table.
+fn get_table(
+    catalog_manager: &ManagerRef,
+    catalog_name: &str,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<Option<TableRef>> {
+    let catalog = match catalog_manager
+        .catalog_by_name(catalog_name)
+        .box_err()
+        .context(Internal)?
+    {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let schema = match catalog
+        .schema_by_name(schema_name)
+        .box_err()
+        .context(Internal)?
+    {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    schema.table_by_name(table_name).box_err().context(Internal)
+}
+
+/// Flush the requested tables, and returns the flushed, failed and not found
+/// tables.
+async fn flush_memtable(
+    catalog_manager: &ManagerRef,
+    req: FlushMemtableRequest,
+) -> Result<HashMap<&'static str, Vec<String>>> {
+    let mut tables = Vec::new();
+    let mut not_found_tables = Vec::new();
+    match req.tables {
+        Some(names) => {
+            for name in names {
+                match find_table(
+                    catalog_manager,
+                    req.catalog.as_deref(),
+                    req.schema.as_deref(),
+                    &name,
+                )? {
+                    Some(table) => tables.push((name, table)),
+                    None => not_found_tables.push(name),
+                }
+            }
+        }
+        None => {
+            for TableWithSchema {
+                catalog,
+                schema,
+                table,
+            } in all_tables(catalog_manager)?
+            {
+                let matched = req.catalog.as_ref().map_or(true, |v| *v == catalog)
+                    && req.schema.as_ref().map_or(true, |v| *v == schema);
+                if matched {
+                    tables.push((table.name().to_string(), table));
+                }
+            }
+        }
+    }
+
+    let mut failed_tables = Vec::new();
+    let mut success_tables = Vec::new();
+    for (table_name, table) in tables {
+        if let Err(e) = table.flush(FlushRequest::default()).await {
+            error!("flush {} failed, err:{}", &table_name, e);
+            failed_tables.push(table_name);
+        } else {
+            success_tables.push(table_name);
+        }
+    }
+
+    let mut result = HashMap::new();
+    result.insert("success", success_tables);
+    result.insert("failed", failed_tables);
+    result.insert("not_found", not_found_tables);
+    Ok(result)
+}
+
+/// Request of `POST /admin/compact`, the catalog and schema of the request
+/// context are used if they are not set.
+#[derive(Debug, Deserialize)]
+struct CompactRequest {
+    catalog: Option<String>,
+    schema: Option<String>,
+    table: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CompactResponse {
+    catalog: String,
+    schema: String,
+    table: String,
+    /// False if the table is already under the manual compaction.
+    scheduled: bool,
+}
+
+type CompactingTables = Arc<Mutex<HashSet<TableId>>>;
+
+/// Schedule the compaction of the requested table in the `runtime` without
+/// waiting for it to finish, at most one manual compaction is scheduled for a
+/// table.
+fn schedule_compaction(
+    catalog_manager: &ManagerRef,
+    runtime: &Runtime,
+    compacting_tables: &CompactingTables,
+    ctx: &RequestContext,
+    req: CompactRequest,
+) -> Result<CompactResponse> {
+    let catalog = req.catalog.unwrap_or_else(|| ctx.catalog.clone());
+    let schema = req.schema.unwrap_or_else(|| ctx.schema.clone());
+    let table = get_table(catalog_manager, &catalog, &schema, &req.table)?.with_context(|| {
+        TableNotFound {
+            catalog: catalog.clone(),
+            schema: schema.clone(),
+            table: req.table.clone(),
+        }
+    })?;
+
+    let table_id = table.id();
+    let scheduled = compacting_tables.lock().unwrap().insert(table_id);
+    if scheduled {
+        let compacting_tables = compacting_tables.clone();
+        runtime.spawn(async move {
+            let begin = Instant::now();
+            match table.compact().await {
+                Ok(()) => info!(
+                    "Manual compaction finished, table:{}, cost:{:?}",
+                    table.name(),
+                    begin.elapsed()
+                ),
+                Err(e) => error!(
+                    "Manual compaction failed, table:{}, err:{}",
+                    table.name(),
+                    e
+                ),
+            }
+            compacting_tables.lock().unwrap().remove(&table_id);
+        });
+    }
+
+    Ok(CompactResponse {
+        catalog,
+        schema,
+        table: req.table,
+        scheduled,
+    })
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CpuProfileOutput {
+    #[default]
+    Pprof,
+    Flamegraph,
+}
+
+impl CpuProfileOutput {
+    fn content_type(&self) -> &'static str {
+        match self {
+            CpuProfileOutput::Pprof => "application/octet-stream",
+            CpuProfileOutput::Flamegraph => "image/svg+xml",
+        }
+    }
+}
+
+impl From<CpuProfileOutput> for CpuProfileFormat {
+    fn from(output: CpuProfileOutput) -> Self {
+        match output {
+            CpuProfileOutput::Pprof => CpuProfileFormat::Pprof,
+            CpuProfileOutput::Flamegraph => CpuProfileFormat::Flamegraph,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CpuProfileParams {
+    format: Option<CpuProfileOutput>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ShardsParams {
+    shard_id: Option<ShardId>,
+}
+
+/// Info of a shard and its tables in the shard tables cache.
+#[derive(Debug, Serialize)]
+struct ShardDebugInfo {
+    id: ShardId,
+    version: u64,
+    role: String,
+    tables: Vec<ShardTableDebugInfo>,
+}
+
+#[derive(Debug, Serialize)]
+struct ShardTableDebugInfo {
+    id: u64,
+    name: String,
+    schema: String,
+}
+
+/// The shards are sorted by id, and only the shard with `shard_id` is
+/// returned if it is set.
+fn shards_debug_info(
+    tables_of_shards: Vec<TablesOfShard>,
+    shard_id: Option<ShardId>,
+) -> Vec<ShardDebugInfo> {
+    let mut shards: Vec<_> = tables_of_shards
+        .into_iter()
+        .filter(|v| shard_id.map(|id| id == v.shard_info.id).unwrap_or(true))
+        .map(|v| ShardDebugInfo {
+            id: v.shard_info.id,
+            version: v.shard_info.version,
+            role: format!("{:?}", v.shard_info.role),
+            tables: v
+                .tables
+                .into_iter()
+                .map(|table| ShardTableDebugInfo {
+                    id: table.id,
+                    name: table.name,
+                    schema: table.schema_name,
+                })
+                .collect(),
+        })
+        .collect();
+    shards.sort_unstable_by_key(|v| v.id);
+
+    shards
+}
+
+/// Basic info and stats of a table.
+#[derive(Debug, Serialize)]
+struct TableDebugInfo {
+    catalog: String,
+    schema: String,
+    name: String,
+    id: u64,
+    /// Approximate memory usage in bytes of the memtables
+    memtable_memory_usage: u64,
+    last_sequence: u64,
+}
+
+fn tables_debug_info(catalog_manager: &ManagerRef) -> Result<Vec<TableDebugInfo>> {
+    let tables = all_tables(catalog_manager)?
+        .into_iter()
+        .map(
+            |TableWithSchema {
+                 catalog,
+                 schema,
+                 table,
+             }| {
+                let stats = table.stats();
+                TableDebugInfo {
+                    catalog,
+                    schema,
+                    name: table.name().to_string(),
+                    id: table.id().as_u64(),
+                    memtable_memory_usage: stats.memtable_memory_usage,
+                    last_sequence: stats.last_sequence,
+                }
+            },
+        )
+        .collect();
+
+    Ok(tables)
+}
+
+/// Flush stats of a table in the write path.
+#[derive(Debug, Serialize)]
+struct FlushStats {
+    /// Whether any write request is waiting for the flush of the table
+    waiting_flush: bool,
+    /// Total time in milliseconds of the write requests waiting for the flush
+    total_stall_ms: u64,
+}
+
+/// Health of a component checked by `GET /health`.
+#[derive(Debug, Serialize)]
+struct ComponentHealth {
+    healthy: bool,
+    /// Why the component is unhealthy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    msg: Option<String>,
+}
+
+impl ComponentHealth {
+    fn healthy() -> Self {
+        Self {
+            healthy: true,
+            msg: None,
+        }
+    }
+
+    fn unhealthy(msg: String) -> Self {
+        Self {
+            healthy: false,
+            msg: Some(msg),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    healthy: bool,
+    components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+/// Check whether the wals are writable, and in the cluster mode, whether the
+/// cluster is started, its heartbeat is alive and no shard lock is lost.
+async fn check_health(opened_wals: &OpenedWals, cluster: Option<&ClusterRef>) -> HealthResponse {
+    let mut components = BTreeMap::new();
+    for (name, wal) in [
+        ("data_wal", &opened_wals.data_wal),
+        ("manifest_wal", &opened_wals.manifest_wal),
+    ] {
+        let health = match wal.check_writable().await {
+            Ok(()) => ComponentHealth::healthy(),
+            Err(e) => ComponentHealth::unhealthy(format!("Wal is not writable, err:{e}")),
+        };
+        components.insert(name, health);
+    }
+
+    if let Some(cluster) = cluster {
+        let health = if cluster.is_started() {
+            ComponentHealth::healthy()
+        } else {
+            ComponentHealth::unhealthy("Cluster is not started".to_string())
+        };
+        components.insert("cluster", health);
+
+        let health = if cluster.is_heartbeat_alive() {
+            ComponentHealth::healthy()
+        } else {
+            ComponentHealth::unhealthy("No successful heartbeat within the lease".to_string())
+        };
+        components.insert("cluster_heartbeat", health);
+
+        let lock_lost_shards = cluster.lock_lost_shards();
+        let health = if lock_lost_shards.is_empty() {
+            ComponentHealth::healthy()
+        } else {
+            ComponentHealth::unhealthy(format!(
+                "Shard locks are lost, shard_ids:{lock_lost_shards:?}"
+            ))
+        };
+        components.insert("shard_locks", health);
+    }
+
+    HealthResponse {
+        healthy: components.values().all(|v| v.healthy),
+        components,
+    }
+}
+
+/// Structured statistics of the wals, and the statistics of a wal not
+/// supporting the structured form are null.
+#[derive(Debug, Serialize)]
+struct WalStatsResponse {
+    data_wal: Option<WalStats>,
+    manifest_wal: Option<WalStats>,
+}
+
+impl WalStatsResponse {
+    fn new(opened_wals: &OpenedWals) -> Self {
+        Self {
+            data_wal: opened_wals.data_wal.get_structured_statistics(),
+            manifest_wal: opened_wals.manifest_wal.get_structured_statistics(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    code: u16,
+    message: String,
+}
+
+/// Extract the sql request from the (maybe compressed) body in json or plain
+/// text.
+fn extract_sql_request(
+    max_body_size: u64,
+) -> impl Filter<Extract = (Request,), Error = warp::Rejection> + Clone {
+    decoded_body(max_body_size).map(|v: Bytes| {
+        serde_json::from_slice(&v).unwrap_or_else(|_| Request {
+            query: String::from_utf8_lossy(&v).to_string(),
+        })
+    })
+}
+
+/// Extract the method and the form body of the InfluxQL query request, and only
+/// the POST request has a body, which is limited to `max_body_size` bytes.
+///
+/// The GET request is served separately because it has no `Content-Length`,
+/// and would be rejected by the body limit.
+fn influxql_query_body(
+    max_body_size: u64,
+) -> impl Filter<Extract = ((Method, HashMap<String, String>),), Error = warp::Rejection> + Clone {
+    let get = warp::get().map(|| (Method::GET, HashMap::new()));
+    let post = warp::post()
+        .and(warp::body::content_length_limit(max_body_size))
+        .and(warp::body::form::<HashMap<String, String>>())
+        .map(|body| (Method::POST, body));
+
+    get.or(post).unify()
+}
+
+/// Extract the optional body, which is limited to `max_body_size` bytes.
+///
+/// The request without body is served separately because it has no
+/// `Content-Length`, and would be rejected by the body limit.
+fn optional_body(
+    max_body_size: u64,
+) -> impl Filter<Extract = (Bytes,), Error = warp::Rejection> + Clone {
+    let without_body = header::optional::<String>(CONTENT_LENGTH.as_str())
+        .and(header::optional::<String>(TRANSFER_ENCODING.as_str()))
+        .and_then(
+            |length: Option<String>, encoding: Option<String>| async move {
+                if length.is_none() && encoding.is_none() {
+                    Ok(Bytes::new())
+                } else {
+                    Err(warp::reject())
+                }
+            },
+        );
+    let with_body = warp::body::content_length_limit(max_body_size).and(warp::body::bytes());
+
+    without_body.or(with_body).unify()
+}
+
+/// Extract the sql request from the `query` param of the query string, which is
+/// limited to [MAX_SQL_QUERY_STRING_LEN] bytes.
+fn extract_sql_request_from_query(
+) -> impl Filter<Extract = (Request,), Error = warp::Rejection> + Clone {
+    warp::query::raw()
+        .and_then(|query_string: String| async move {
+            check_sql_query_string(&query_string).map_err(reject::custom)
+        })
+        .untuple_one()
+        .and(warp::query::<Request>())
+}
+
+fn check_sql_query_string(query_string: &str) -> Result<()> {
+    ensure!(
+        query_string.len() <= MAX_SQL_QUERY_STRING_LEN,
+        SqlQueryStringTooLong {
+            len: query_string.len(),
+            max_len: MAX_SQL_QUERY_STRING_LEN,
+        }
+    );
+
+    Ok(())
+}
+
+/// Check the profiling duration in seconds doesn't exceed the `max_duration`.
+fn check_profile_duration(duration_sec: u64, max_duration: Duration) -> Result<()> {
+    let duration = Duration::from_secs(duration_sec);
+    ensure!(
+        duration <= max_duration,
+        ProfileDurationExceedsMax {
+            duration,
+            max_duration,
+        }
+    );
+
+    Ok(())
+}
+
+/// Resolve the timeout of the request, the default timeout is overridden by the
+/// one in the [consts::TIMEOUT_HEADER] header, which must not exceed the
+/// `max_timeout`.
+fn resolve_timeout(
+    default_timeout: Option<Duration>,
+    timeout_ms: Option<&str>,
+    max_timeout: Duration,
+) -> Result<Option<Duration>> {
+    let timeout_ms = match timeout_ms {
+        Some(v) => v,
+        None => return Ok(default_timeout),
+    };
+
+    let timeout = timeout_ms
+        .trim()
+        .parse::<u64>()
+        .map(Duration::from_millis)
+        .context(InvalidTimeoutHeader { value: timeout_ms })?;
+    ensure!(
+        timeout <= max_timeout,
+        TimeoutExceedsMax {
+            timeout,
+            max_timeout,
+        }
+    );
+
+    Ok(Some(timeout))
+}
+
+fn error_to_status_code(err: &Error) -> StatusCode {
+    match err {
+        Error::CreateContext { .. }
+        | Error::InvalidFlushRequest { .. }
+        | Error::DecompressBody { .. }
+        | Error::InvalidTimeoutHeader { .. }
+        | Error::TimeoutExceedsMax { .. }
+        | Error::ProfileDurationExceedsMax { .. } => StatusCode::BAD_REQUEST,
+        Error::UnsupportedContentEncoding { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        Error::SqlQueryStringTooLong { .. } => StatusCode::URI_TOO_LONG,
+        Error::DecompressedBodyTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        Error::MissingApiKey { .. } | Error::InvalidApiKey { .. } => StatusCode::UNAUTHORIZED,
+        Error::AccessDenied { .. } => StatusCode::FORBIDDEN,
+        Error::TableNotFound { .. } => StatusCode::NOT_FOUND,
+        Error::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        // TODO(yingwen): Map handle request error to more accurate status code
+        Error::HandleRequest { .. }
+        | Error::MissingEngineRuntimes { .. }
+        | Error::MissingLogRuntime { .. }
+        | Error::MissingInstance { .. }
+        | Error::MissingSchemaConfigProvider { .. }
+        | Error::MissingProxy { .. }
+        | Error::ParseIpAddr { .. }
+        | Error::BindServer { .. }
+        | Error::ReadTlsFile { .. }
+        | Error::InvalidTlsFile { .. }
+        | Error::BuildTlsConfig { .. }
+        | Error::ProfileHeap { .. }
+        | Error::ProfileCPU { .. }
+        | Error::Internal { .. }
+        | Error::JoinAsyncTask { .. }
+        | Error::AlreadyStarted { .. }
+        | Error::MissingRouter { .. }
+        | Error::MissingWal { .. }
+        | Error::HandleUpdateLogLevel { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Remote address of the connection, which is carried by the extensions of the
+/// request as warp doesn't know it when serving through hyper.
+#[derive(Clone, Copy, Debug)]
+struct RemoteAddr(SocketAddr);
+
+tokio::task_local! {
+    /// Id of the request being served, which is only available in the filters.
+    static REQUEST_ID: String;
+}
+
+fn new_request_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Returns the id of the request being served.
+fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|v| v.clone()).ok()
+}
+
+/// Serve the request by `service` with the request id in the
+/// [consts::REQUEST_ID_HEADER] header, which is generated if absent and echoed
+/// in the response.
+async fn serve_with_request_id<S>(
+    mut service: S,
+    remote_addr: SocketAddr,
+    mut req: HttpRequest<Body>,
+) -> std::result::Result<Response<Body>, Infallible>
+where
+    S: HyperService<HttpRequest<Body>, Response = Response<Body>, Error = Infallible>,
+{
+    let begin = Instant::now();
+    let header_name = HeaderName::from_static(consts::REQUEST_ID_HEADER);
+    let request_id = match req.headers().get(&header_name) {
+        Some(v) => v.clone(),
+        None => {
+            let v = HeaderValue::from_str(&new_request_id()).expect("uuid is a valid header value");
+            req.headers_mut().insert(header_name.clone(), v.clone());
+            v
+        }
+    };
+    req.extensions_mut().insert(RemoteAddr(remote_addr));
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let request_id_str = request_id.to_str().unwrap_or_default().to_string();
+    let mut resp = REQUEST_ID
+        .scope(request_id_str.clone(), service.call(req))
+        .await?;
+    info!(
+        target: "http_requests",
+        "{} \"{} {}\" {} request_id:{} {:?}",
+        remote_addr,
+        method,
+        path,
+        resp.status().as_u16(),
+        request_id_str,
+        begin.elapsed()
+    );
+
+    resp.headers_mut().insert(header_name, request_id);
+    Ok(resp)
+}
+
+async fn handle_rejection(
+    rejection: warp::Rejection,
+) -> std::result::Result<(impl warp::Reply,), Infallible> {
+    let code;
+    let message;
+    let mut retry_after = None;
+
+    if rejection.is_not_found() {
+        code = StatusCode::NOT_FOUND;
+        message = String::from("NOT_FOUND");
+    } else if let Some(err) = rejection.find::<reject::InvalidQuery>() {
+        code = StatusCode::BAD_REQUEST;
+        message = err.to_string();
+    } else if let Some(err) = rejection.find::<reject::PayloadTooLarge>() {
+        code = StatusCode::PAYLOAD_TOO_LARGE;
+        message = err.to_string();
+    } else if let Some(err) = rejection.find() {
+        code = error_to_status_code(err);
+        if let Error::RateLimited { retry_after: v, .. } = err {
+            retry_after = Some(v.as_secs());
+        }
+        let err_string = err.to_string();
+        message = error_util::remove_backtrace_from_err(&err_string).to_string();
+    } else {
+        code = StatusCode::INTERNAL_SERVER_ERROR;
+        message = error_util::remove_backtrace_from_err(&format!("UNKNOWN_ERROR: {rejection:?}"))
+            .to_string();
+    }
+
+    if code.as_u16() >= 500 {
+        error!(
+            "HTTP handle error, request_id:{:?}, err:{:?}",
+            current_request_id(),
+            rejection
+        );
+    }
+    let json = reply::json(&ErrorResponse {
+        code: code.as_u16(),
+        message,
+    });
+    let mut resp = reply::with_status(json, code).into_response();
+    if let Some(secs) = retry_after {
+        resp.headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(secs));
+    }
+
+    Ok((resp,))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use async_trait::async_trait;
+    use catalog::{
+        manager::{self, Manager},
+        schema::{
+            self, CreateOptions, CreateTableRequest, DropOptions, DropTableRequest, NameRef,
+            Schema, SchemaRef,
+        },
+        Catalog, CatalogRef,
+    };
+    use ceresdbproto::meta_event::{
+        CloseTableOnShardRequest, CreateTableOnShardRequest, DropTableOnShardRequest,
+        OpenTableOnShardRequest,
+    };
+    use cluster::{
+        shard_lock_manager::ShardLockManagerRef, shard_tables_cache::ShardTablesCache, Cluster,
+        ClusterNodesResp, DrainShardSummary, ShardEventReceiver, ShardStatus,
+    };
+    use common_types::tests::build_schema;
+    use meta_client::types::{
+        RouteTablesRequest, RouteTablesResponse, ShardInfo, ShardRole, TableInfo,
+    };
+    use table_engine::{
+        memory::MemoryTable,
+        table::{SchemaId, TableId},
+    };
+    use wal::{
+        log_batch::LogWriteBatch,
+        manager::{
+            BatchLogIteratorAdapter, ReadContext, ReadRequest, RegionId, ScanContext, ScanRequest,
+            SequenceNumber, WalLocation, WalManager, WriteContext,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_resolve_timeout() {
+        let default_timeout = Some(Duration::from_secs(10));
+        let max_timeout = Duration::from_secs(60);
+        let cases = [
+            (None, default_timeout),
+            (Some("100"), Some(Duration::from_millis(100))),
+            (Some("30000"), Some(Duration::from_secs(30))),
+            (Some("60000"), Some(max_timeout)),
+        ];
+        for (timeout_ms, expect) in cases {
+            assert_eq!(
+                expect,
+                resolve_timeout(default_timeout, timeout_ms, max_timeout).unwrap()
+            );
+        }
+        assert_eq!(
+            Some(Duration::from_millis(100)),
+            resolve_timeout(None, Some("100"), max_timeout).unwrap()
+        );
+
+        let err = resolve_timeout(default_timeout, Some("1s"), max_timeout).unwrap_err();
+        assert_eq!(StatusCode::BAD_REQUEST, error_to_status_code(&err));
+        let err = resolve_timeout(default_timeout, Some("60001"), max_timeout).unwrap_err();
+        assert!(matches!(err, Error::TimeoutExceedsMax { .. }));
+        assert_eq!(StatusCode::BAD_REQUEST, error_to_status_code(&err));
+    }
+
+    #[test]
+    fn test_check_profile_duration() {
+        let max_duration = Duration::from_secs(60);
+        for duration_sec in [0, 1, 60] {
+            check_profile_duration(duration_sec, max_duration).unwrap();
+        }
+
+        for duration_sec in [61, 10000] {
+            let err = check_profile_duration(duration_sec, max_duration).unwrap_err();
+            assert!(matches!(err, Error::ProfileDurationExceedsMax { .. }));
+            assert_eq!(StatusCode::BAD_REQUEST, error_to_status_code(&err));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_with_request_id() {
+        let config = HttpRateLimitConfig {
+            requests_per_second: 1,
+            burst: 1,
+        };
+        let limiter = Some(Arc::new(RateLimiter::new(&config, false)));
+        let echo = warp::path!("echo")
+            .and(header::optional::<String>(consts::REQUEST_ID_HEADER))
+            .map(|request_id: Option<String>| {
+                // The request id is available during serving the request.
+                assert_eq!(request_id, current_request_id());
+                request_id.unwrap_or_default()
+            });
+        let limited = warp::path!("limited")
+            .and(rate_limit_filter(limiter))
+            .map(|| String::from("ok"));
+        let service = warp::service(echo.or(limited).unify().recover(handle_rejection));
+        let remote_addr = SocketAddr::from(([10, 0, 0, 1], 1234));
+        assert!(current_request_id().is_none());
+        let call = |path: &str, request_id: Option<&str>| {
+            let mut builder = HttpRequest::builder().uri(path);
+            if let Some(v) = request_id {
+                builder = builder.header(consts::REQUEST_ID_HEADER, v);
+            }
+            serve_with_request_id(
+                service.clone(),
+                remote_addr,
+                builder.body(Body::empty()).unwrap(),
+            )
+        };
+        let body_string = |resp: Response<Body>| async move {
+            let body = warp::hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        };
+
+        // The provided request id is passed to the filters and echoed.
+        let resp = call("/echo", Some("test-request-id")).await.unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("test-request-id", resp.headers()[consts::REQUEST_ID_HEADER]);
+        assert_eq!("test-request-id", body_string(resp).await);
+
+        // The request id is generated if missing.
+        let resp = call("/echo", None).await.unwrap();
+        let request_id = resp.headers()[consts::REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(Uuid::parse_str(&request_id).is_ok(), "{request_id}");
+        assert_eq!(request_id, body_string(resp).await);
+
+        // The request id is echoed on the error path too.
+        let resp = call("/not_found", Some("not-found-id")).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        assert_eq!("not-found-id", resp.headers()[consts::REQUEST_ID_HEADER]);
+
+        // The remote address is still known by the rate limiter.
+        let resp = call("/limited", Some("limited-1")).await.unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = call("/limited", Some("limited-2")).await.unwrap();
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, resp.status());
+        assert_eq!("limited-2", resp.headers()[consts::REQUEST_ID_HEADER]);
+    }
+
+    struct MockSchema {
+        name: String,
+        tables: Vec<TableRef>,
+    }
+
+    #[async_trait]
+    impl Schema for MockSchema {
+        fn name(&self) -> NameRef {
+            &self.name
+        }
+
+        fn id(&self) -> SchemaId {
+            SchemaId::from_u32(0)
+        }
+
+        fn table_by_name(&self, name: NameRef) -> schema::Result<Option<TableRef>> {
+            Ok(self.tables.iter().find(|v| v.name() == name).cloned())
+        }
+
+        async fn create_table(
+            &self,
+            _request: CreateTableRequest,
+            _opts: CreateOptions,
+        ) -> schema::Result<TableRef> {
+            unimplemented!()
+        }
+
+        async fn drop_table(
+            &self,
+            _request: DropTableRequest,
+            _opts: DropOptions,
+        ) -> schema::Result<bool> {
+            unimplemented!()
+        }
+
+        fn all_tables(&self) -> schema::Result<Vec<TableRef>> {
+            Ok(self.tables.clone())
+        }
+
+        fn register_table(&self, _table: TableRef) {
+            unimplemented!()
+        }
+
+        fn unregister_table(&self, _table_name: &str) {
+            unimplemented!()
+        }
+    }
+
+    struct MockCatalog {
+        schemas: Vec<SchemaRef>,
+    }
+
+    #[async_trait]
+    impl Catalog for MockCatalog {
+        fn name(&self) -> NameRef {
+            "ceresdb"
+        }
+
+        fn schema_by_name(&self, name: NameRef) -> catalog::Result<Option<SchemaRef>> {
+            Ok(self.schemas.iter().find(|v| v.name() == name).cloned())
+        }
+
+        async fn create_schema<'a>(&'a self, _name: NameRef<'a>) -> catalog::Result<()> {
+            unimplemented!()
+        }
+
+        fn all_schemas(&self) -> catalog::Result<Vec<SchemaRef>> {
+            Ok(self.schemas.clone())
+        }
+    }
+
+    struct MockCatalogManager {
+        catalog: CatalogRef,
+    }
+
+    impl Manager for MockCatalogManager {
+        fn default_catalog_name(&self) -> NameRef {
+            "ceresdb"
+        }
+
+        fn default_schema_name(&self) -> NameRef {
+            "public"
+        }
+
+        fn catalog_by_name(&self, name: NameRef) -> manager::Result<Option<CatalogRef>> {
+            Ok((name == self.catalog.name()).then(|| self.catalog.clone()))
+        }
+
+        fn all_catalogs(&self) -> manager::Result<Vec<CatalogRef>> {
+            Ok(vec![self.catalog.clone()])
+        }
+    }
+
+    fn new_mock_catalog_manager() -> ManagerRef {
+        let new_table = |name: &str, id: u64| -> TableRef {
+            Arc::new(MemoryTable::new(
+                name.to_string(),
+                TableId::from(id),
+                build_schema(),
+                "Memory".to_string(),
+            ))
+        };
+        let schemas: Vec<SchemaRef> = vec![
+            Arc::new(MockSchema {
+                name: "public".to_string(),
+                tables: vec![new_table("t1", 1), new_table("t2", 2)],
+            }),
+            Arc::new(MockSchema {
+                name: "test".to_string(),
+                tables: vec![new_table("t3", 3)],
+            }),
+        ];
+        Arc::new(MockCatalogManager {
+            catalog: Arc::new(MockCatalog { schemas }),
+        })
+    }
+
+    #[test]
+    fn test_tables_debug_info() {
+        let catalog_manager = new_mock_catalog_manager();
+        let tables = tables_debug_info(&catalog_manager).unwrap();
+        let tables = serde_json::to_value(tables).unwrap();
+        let tables = tables.as_array().unwrap();
+        assert_eq!(3, tables.len());
+        for (table, (schema, name, id)) in
+            tables
+                .iter()
+                .zip([("public", "t1", 1), ("public", "t2", 2), ("test", "t3", 3)])
+        {
+            assert_eq!("ceresdb", table["catalog"]);
+            assert_eq!(schema, table["schema"]);
+            assert_eq!(name, table["name"]);
+            assert_eq!(id, table["id"]);
+            assert_eq!(0, table["memtable_memory_usage"]);
+            assert_eq!(0, table["last_sequence"]);
+        }
+    }
+
+    #[test]
+    fn test_shards_debug_info() {
+        let new_tables_of_shard =
+            |shard_id: ShardId, version: u64, table_ids: &[u64]| TablesOfShard {
+                shard_info: ShardInfo {
+                    id: shard_id,
+                    role: ShardRole::Leader,
+                    version,
+                },
+                tables: table_ids
+                    .iter()
+                    .map(|id| TableInfo {
+                        id: *id,
+                        name: format!("t{id}"),
+                        schema_id: 0,
+                        schema_name: "public".to_string(),
+                        partition_info: None,
+                    })
+                    .collect(),
+            };
+        let shard_tables_cache = ShardTablesCache::default();
+        shard_tables_cache.insert(new_tables_of_shard(2, 5, &[3]));
+        shard_tables_cache.insert(new_tables_of_shard(1, 3, &[1, 2]));
+
+        let shards = shards_debug_info(shard_tables_cache.all_tables_of_shards(), None);
+        let expected = serde_json::json!([
+            {
+                "id": 1,
+                "version": 3,
+                "role": "Leader",
+                "tables": [
+                    {"id": 1, "name": "t1", "schema": "public"},
+                    {"id": 2, "name": "t2", "schema": "public"},
+                ],
+            },
+            {
+                "id": 2,
+                "version": 5,
+                "role": "Leader",
+                "tables": [{"id": 3, "name": "t3", "schema": "public"}],
+            },
+        ]);
+        assert_eq!(expected, serde_json::to_value(shards).unwrap());
+
+        // Filter by the shard id.
+        let shards = shards_debug_info(shard_tables_cache.all_tables_of_shards(), Some(2));
+        assert_eq!(
+            expected[1],
+            serde_json::to_value(shards).unwrap().as_array().unwrap()[0]
+        );
+        let shards = shards_debug_info(shard_tables_cache.all_tables_of_shards(), Some(3));
+        assert!(shards.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flush_memtable_by_name() {
+        let catalog_manager = new_mock_catalog_manager();
+
+        let req =
+            parse_flush_memtable_request(br#"{"tables": ["test.t3", "t1", "public.t3"]}"#).unwrap();
+        let result = flush_memtable(&catalog_manager, req).await.unwrap();
+        // Only the named tables are flushed, and the memory table doesn't support
+        // flush.
+        assert!(result["success"].is_empty());
+        assert_eq!(vec!["test.t3", "t1"], result["failed"]);
+        assert_eq!(vec!["public.t3"], result["not_found"]);
+
+        // Flush a single table.
+        let req = parse_flush_memtable_request(br#"{"tables": ["public.t2"]}"#).unwrap();
+        let result = flush_memtable(&catalog_manager, req).await.unwrap();
+        assert_eq!(vec!["public.t2"], result["failed"]);
+        assert!(result["not_found"].is_empty());
+
+        // Flush all tables without body.
+        let req = parse_flush_memtable_request(b"").unwrap();
+        let result = flush_memtable(&catalog_manager, req).await.unwrap();
+        assert_eq!(vec!["t1", "t2", "t3"], result["failed"]);
+
+        let err = parse_flush_memtable_request(b"{").unwrap_err();
+        assert_eq!(StatusCode::BAD_REQUEST, error_to_status_code(&err));
+    }
+
+    #[tokio::test]
+    async fn test_flush_memtable_with_filter() {
+        let catalog_manager = new_mock_catalog_manager();
+        let flush = |body: &'static [u8]| {
+            let catalog_manager = catalog_manager.clone();
+            async move {
+                let req = parse_flush_memtable_request(body).unwrap();
+                flush_memtable(&catalog_manager, req).await.unwrap()
+            }
+        };
+
+        // Only the tables of the schema are flushed.
+        let result = flush(br#"{"schema": "test"}"#).await;
+        assert_eq!(vec!["t3"], result["failed"]);
+
+        let result = flush(br#"{"catalog": "ceresdb", "schema": "public"}"#).await;
+        assert_eq!(vec!["t1", "t2"], result["failed"]);
+
+        // No table matches the unknown catalog.
+        let result = flush(br#"{"catalog": "unknown"}"#).await;
+        assert!(result["failed"].is_empty());
+        assert!(result["success"].is_empty());
+
+        // The tables without schema are in the given schema.
+        let result = flush(br#"{"schema": "test", "tables": ["t1", "t3", "public.t2"]}"#).await;
+        assert_eq!(vec!["t3", "public.t2"], result["failed"]);
+        assert_eq!(vec!["t1"], result["not_found"]);
+
+        let result = flush(br#"{"catalog": "unknown", "tables": ["t1"]}"#).await;
+        assert!(result["failed"].is_empty());
+        assert_eq!(vec!["t1"], result["not_found"]);
+    }
+
+    #[test]
+    fn test_schedule_compaction() {
+        let catalog_manager = new_mock_catalog_manager();
+        let runtime = common_util::runtime::Builder::default()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+        let compacting_tables: CompactingTables = Arc::new(Mutex::new(HashSet::new()));
+        let ctx = RequestContext::builder()
+            .catalog("ceresdb".to_string())
+            .schema("public".to_string())
+            .build()
+            .unwrap();
+        let compact = |req: &str| {
+            let req = serde_json::from_str(req).unwrap();
+            schedule_compaction(&catalog_manager, &runtime, &compacting_tables, &ctx, req)
+        };
+
+        // The schema of the context is used if not set.
+        let resp = compact(r#"{"table": "t1"}"#).unwrap();
+        assert_eq!(
+            ("ceresdb", "public", "t1", true),
+            (
+                resp.catalog.as_str(),
+                resp.schema.as_str(),
+                resp.table.as_str(),
+                resp.scheduled
+            )
+        );
+
+        let resp = compact(r#"{"schema": "test", "table": "t3"}"#).unwrap();
+        assert_eq!(("test", "t3"), (resp.schema.as_str(), resp.table.as_str()));
+        assert!(resp.scheduled);
+
+        // The table is removed from the compacting tables after compaction.
+        while !compacting_tables.lock().unwrap().is_empty() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // Only one manual compaction is scheduled for a table.
+        compacting_tables.lock().unwrap().insert(TableId::from(2));
+        let resp = compact(r#"{"table": "t2"}"#).unwrap();
+        assert!(!resp.scheduled);
+
+        let err = compact(r#"{"schema": "test", "table": "t1"}"#).unwrap_err();
+        assert_eq!(StatusCode::NOT_FOUND, error_to_status_code(&err));
+    }
+
+    #[tokio::test]
+    async fn test_influxql_query_body() {
+        let filter = influxql_query_body(16);
+
+        // The GET request without body isn't limited.
+        let (method, body) = warp::test::request()
+            .method("GET")
+            .path("/query?q=select%201")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(Method::GET, method);
+        assert!(body.is_empty());
+
+        let (method, body) = warp::test::request()
+            .method("POST")
+            .body("q=select+1")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(Method::POST, method);
+        assert_eq!("select 1", body["q"]);
+
+        let filter = filter.map(|_| warp::reply()).recover(handle_rejection);
+        let resp = warp::test::request()
+            .method("POST")
+            .body(format!("q={}", "a".repeat(16)))
+            .reply(&filter)
+            .await;
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, resp.status());
+    }
+
+    #[tokio::test]
+    async fn test_optional_body() {
+        let filter = optional_body(16);
+
+        // The request without body isn't limited.
+        let body = warp::test::request()
+            .method("POST")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        let body = warp::test::request()
+            .method("POST")
+            .body(r#"{"schema":"t"}"#)
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(r#"{"schema":"t"}"#.as_bytes(), &body[..]);
+
+        let filter = filter.map(|_| warp::reply()).recover(handle_rejection);
+        let resp = warp::test::request()
+            .method("POST")
+            .body("a".repeat(17))
+            .reply(&filter)
+            .await;
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, resp.status());
+    }
+
+    #[tokio::test]
+    async fn test_extract_sql_request_from_query() {
+        let filter = warp::path!("sql").and(extract_sql_request_from_query());
+
+        let request = warp::test::request()
+            .method("GET")
+            .path("/sql?query=SELECT%20*%20FROM%20t%20WHERE%20name%3D%27a%26b%27&format=csv")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!("SELECT * FROM t WHERE name='a&b'", request.query);
+
+        let path = format!("/sql?query={}", "a".repeat(MAX_SQL_QUERY_STRING_LEN));
+        let rejection = warp::test::request()
+            .method("GET")
+            .path(&path)
+            .filter(&filter)
+            .await
+            .unwrap_err();
+        let err: &Error = rejection.find().unwrap();
+        assert_eq!(StatusCode::URI_TOO_LONG, error_to_status_code(err));
+
+        let rejection = warp::test::request()
+            .method("GET")
+            .path("/sql?format=csv")
+            .filter(&filter)
+            .await
+            .unwrap_err();
+        assert!(rejection.find::<reject::InvalidQuery>().is_some());
+    }
+
+    #[derive(Debug)]
+    struct MockWal {
+        writable: bool,
+    }
+
+    #[async_trait]
+    impl WalManager for MockWal {
+        async fn sequence_num(&self, _: WalLocation) -> wal::manager::Result<SequenceNumber> {
+            unimplemented!();
+        }
+
+        async fn mark_delete_entries_up_to(
+            &self,
+            _: WalLocation,
+            _: SequenceNumber,
+        ) -> wal::manager::Result<()> {
+            unimplemented!();
+        }
+
+        async fn close_region(&self, _: RegionId) -> wal::manager::Result<()> {
+            unimplemented!();
+        }
+
+        async fn close_gracefully(&self) -> wal::manager::Result<()> {
+            unimplemented!();
+        }
+
+        async fn read_batch(
+            &self,
+            _: &ReadContext,
+            _: &ReadRequest,
+        ) -> wal::manager::Result<BatchLogIteratorAdapter> {
+            unimplemented!();
+        }
+
+        async fn write(
+            &self,
+            _: &WriteContext,
+            _: &LogWriteBatch,
+        ) -> wal::manager::Result<SequenceNumber> {
+            unimplemented!();
+        }
+
+        async fn scan(
+            &self,
+            _: &ScanContext,
+            _: &ScanRequest,
+        ) -> wal::manager::Result<BatchLogIteratorAdapter> {
+            unimplemented!();
+        }
+
+        async fn check_writable(&self) -> wal::manager::Result<()> {
+            if self.writable {
+                return Ok(());
+            }
+
+            Err(io::Error::new(io::ErrorKind::Other, "disk is full"))
+                .box_err()
+                .context(wal::manager::Write)
+        }
+
+        fn get_structured_statistics(&self) -> Option<WalStats> {
+            self.writable.then(|| WalStats {
+                total_size: Some(1024),
+                num_segments: Some(2),
+                oldest_sequence: Some(10),
+                newest_sequence: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_health() {
+        let opened_wals = OpenedWals {
+            data_wal: Arc::new(MockWal { writable: true }),
+            manifest_wal: Arc::new(MockWal { writable: true }),
+        };
+        let health = check_health(&opened_wals, None).await;
+        assert!(health.healthy);
+        assert_eq!(
+            vec!["data_wal", "manifest_wal"],
+            health.components.keys().copied().collect::<Vec<_>>()
+        );
+
+        let opened_wals = OpenedWals {
+            data_wal: Arc::new(MockWal { writable: false }),
+            manifest_wal: Arc::new(MockWal { writable: true }),
+        };
+        let health = check_health(&opened_wals, None).await;
+        assert!(!health.healthy);
+        let data_wal = &health.components["data_wal"];
+        assert!(!data_wal.healthy);
+        assert!(
+            data_wal.msg.as_ref().unwrap().contains("disk is full"),
+            "{data_wal:?}"
+        );
+        assert!(health.components["manifest_wal"].healthy);
+    }
+
+    #[test]
+    fn test_wal_stats_response() {
+        let opened_wals = OpenedWals {
+            data_wal: Arc::new(MockWal { writable: true }),
+            manifest_wal: Arc::new(MockWal { writable: false }),
+        };
+        let response = serde_json::to_value(WalStatsResponse::new(&opened_wals)).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "data_wal": {
+                    "total_size": 1024,
+                    "num_segments": 2,
+                    "oldest_sequence": 10,
+                    "newest_sequence": null,
+                },
+                "manifest_wal": null,
+            }),
+            response
+        );
+    }
+
+    #[derive(Default)]
+    struct MockCluster {
+        started: bool,
+        heartbeat_alive: bool,
+        lock_lost_shards: Vec<ShardId>,
+    }
+
+    #[async_trait]
+    impl Cluster for MockCluster {
+        async fn start(&self) -> cluster::Result<()> {
+            unimplemented!();
+        }
+
+        async fn stop(&self) -> cluster::Result<()> {
+            unimplemented!();
+        }
+
+        async fn open_shard(&self, _: &ShardInfo) -> cluster::Result<TablesOfShard> {
+            unimplemented!();
+        }
+
+        async fn try_open_shard(&self, _: &ShardInfo) -> cluster::Result<TablesOfShard> {
+            unimplemented!();
+        }
+
+        async fn close_shard(&self, _: ShardId) -> cluster::Result<TablesOfShard> {
+            unimplemented!();
+        }
+
+        async fn drain_shard(&self, _: ShardId, _: Duration) -> cluster::Result<DrainShardSummary> {
+            unimplemented!();
+        }
+
+        async fn freeze_shard(&self, _: ShardId) -> cluster::Result<TablesOfShard> {
+            unimplemented!();
+        }
+
+        async fn create_table_on_shard(
+            &self,
+            _: &CreateTableOnShardRequest,
+        ) -> cluster::Result<()> {
+            unimplemented!();
+        }
+
+        async fn drop_table_on_shard(&self, _: &DropTableOnShardRequest) -> cluster::Result<()> {
+            unimplemented!();
+        }
+
+        async fn open_table_on_shard(&self, _: &OpenTableOnShardRequest) -> cluster::Result<()> {
+            unimplemented!();
+        }
+
+        async fn close_table_on_shard(&self, _: &CloseTableOnShardRequest) -> cluster::Result<()> {
+            unimplemented!();
+        }
+
+        async fn route_tables(
+            &self,
+            _: &RouteTablesRequest,
+        ) -> cluster::Result<RouteTablesResponse> {
+            unimplemented!();
+        }
+
+        async fn fetch_nodes(&self) -> cluster::Result<ClusterNodesResp> {
+            unimplemented!();
+        }
+
+        fn tables_of_shards(&self) -> Vec<TablesOfShard> {
+            unimplemented!();
+        }
+
+        fn shard_status(&self) -> Vec<ShardStatus> {
+            unimplemented!();
+        }
+
+        fn subscribe(&self) -> ShardEventReceiver {
+            unimplemented!();
+        }
+
+        fn shard_lock_manager(&self) -> ShardLockManagerRef {
+            unimplemented!();
+        }
+
+        fn is_heartbeat_alive(&self) -> bool {
+            self.heartbeat_alive
+        }
+
+        fn is_started(&self) -> bool {
+            self.started
+        }
+
+        fn lock_lost_shards(&self) -> Vec<ShardId> {
+            self.lock_lost_shards.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_cluster_health() {
+        let opened_wals = OpenedWals {
+            data_wal: Arc::new(MockWal { writable: true }),
+            manifest_wal: Arc::new(MockWal { writable: true }),
+        };
+        let healthy_cluster = MockCluster {
+            started: true,
+            heartbeat_alive: true,
+            lock_lost_shards: vec![],
+        };
+        let cluster: ClusterRef = Arc::new(healthy_cluster);
+        let health = check_health(&opened_wals, Some(&cluster)).await;
+        assert!(health.healthy);
+        assert_eq!(
+            vec![
+                "cluster",
+                "cluster_heartbeat",
+                "data_wal",
+                "manifest_wal",
+                "shard_locks"
+            ],
+            health.components.keys().copied().collect::<Vec<_>>()
+        );
+
+        let cases = [
+            (
+                MockCluster {
+                    heartbeat_alive: true,
+                    ..Default::default()
+                },
+                "cluster",
+                "Cluster is not started",
+            ),
+            (
+                MockCluster {
+                    started: true,
+                    ..Default::default()
+                },
+                "cluster_heartbeat",
+                "No successful heartbeat",
+            ),
+            (
+                MockCluster {
+                    started: true,
+                    heartbeat_alive: true,
+                    lock_lost_shards: vec![1, 2],
+                },
+                "shard_locks",
+                "shard_ids:[1, 2]",
+            ),
+        ];
+        for (mock_cluster, unhealthy_component, msg) in cases {
+            let cluster: ClusterRef = Arc::new(mock_cluster);
+            let health = check_health(&opened_wals, Some(&cluster)).await;
+            assert!(!health.healthy);
+            for (name, component) in &health.components {
+                if *name == unhealthy_component {
+                    assert!(!component.healthy);
+                    assert!(
+                        component.msg.as_ref().unwrap().contains(msg),
+                        "{component:?}"
+                    );
+                } else {
+                    assert!(component.healthy, "{name}:{component:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Rate limit of the requests of every client
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use warp::{header, http::header::AUTHORIZATION, reject, Filter};
+
+use crate::{
+    config::HttpRateLimitConfig,
+    http::{RateLimited, RemoteAddr, Result},
+};
+
+/// The idle clients are evicted from the rate limiter once the number of the
+/// tracked clients reaches it.
+const MAX_RATE_LIMITED_CLIENTS: usize = 100_000;
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Limit the requests of every client by a token bucket.
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    key_by_api_key: bool,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &HttpRateLimitConfig, key_by_api_key: bool) -> Self {
+        Self {
+            requests_per_second: config.requests_per_second.max(1) as f64,
+            burst: config.burst.max(1) as f64,
+            key_by_api_key,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The client is identified by the api key in the `Authorization` header
+    /// if allowed, otherwise by the remote ip.
+    fn client_key(&self, authorization: Option<&str>, remote: Option<SocketAddr>) -> String {
+        let api_key = authorization
+            .filter(|_| self.key_by_api_key)
+            .and_then(|v| v.trim().strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|v| !v.is_empty());
+        match (api_key, remote) {
+            (Some(key), _) => format!("key:{key}"),
+            (None, Some(addr)) => format!("ip:{}", addr.ip()),
+            (None, None) => "unknown".to_string(),
+        }
+    }
+
+    fn acquire(&self, client: &str) -> Result<()> {
+        self.acquire_at(client, Instant::now())
+    }
+
+    fn acquire_at(&self, client: &str, now: Instant) -> Result<()> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_RATE_LIMITED_CLIENTS && !buckets.contains_key(client) {
+            self.evict_idle_clients(&mut buckets, now);
+        }
+
+        let bucket = buckets
+            .entry(client.to_string())
+            .or_insert_with(|| TokenBucket {
+                tokens: self.burst,
+                last_refill: now,
+            });
+        bucket.tokens = self.refilled_tokens(bucket, now);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let wait_secs = ((1.0 - bucket.tokens) / self.requests_per_second).ceil();
+        RateLimited {
+            client,
+            retry_after: Duration::from_secs(wait_secs.max(1.0) as u64),
+        }
+        .fail()
+    }
+
+    fn refilled_tokens(&self, bucket: &TokenBucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        (bucket.tokens + elapsed.as_secs_f64() * self.requests_per_second).min(self.burst)
+    }
+
+    /// Evict the clients whose buckets are full again, which are equivalent to
+    /// the new ones.
+    fn evict_idle_clients(&self, buckets: &mut HashMap<String, TokenBucket>, now: Instant) {
+        buckets.retain(|_, bucket| self.refilled_tokens(bucket, now) < self.burst);
+    }
+}
+
+/// Limit the rate of the requests of every client, and all requests are passed
+/// if `limiter` is None.
+pub fn rate_limit_filter(
+    limiter: Option<Arc<RateLimiter>>,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::ext::optional::<RemoteAddr>())
+        .and(header::optional::<String>(AUTHORIZATION.as_str()))
+        .and_then(
+            move |remote: Option<SocketAddr>,
+                  remote_ext: Option<RemoteAddr>,
+                  authorization: Option<String>| {
+                let remote = remote.or(remote_ext.map(|v| v.0));
+                let result = match &limiter {
+                    Some(limiter) => {
+                        limiter.acquire(&limiter.client_key(authorization.as_deref(), remote))
+                    }
+                    None => Ok(()),
+                };
+                async move { result.map_err(reject::custom) }
+            },
+        )
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::http::{header::RETRY_AFTER, StatusCode};
+
+    use super::*;
+    use crate::http::{handle_rejection, Error};
+
+    #[test]
+    fn test_rate_limiter_refill() {
+        let config = HttpRateLimitConfig {
+            requests_per_second: 2,
+            burst: 2,
+        };
+        let limiter = RateLimiter::new(&config, false);
+        let now = Instant::now();
+
+        limiter.acquire_at("a", now).unwrap();
+        limiter.acquire_at("a", now).unwrap();
+        let err = limiter.acquire_at("a", now).unwrap_err();
+        match err {
+            Error::RateLimited { retry_after, .. } => {
+                assert_eq!(Duration::from_secs(1), retry_after)
+            }
+            e => panic!("unexpected error:{e}"),
+        }
+        // Other clients are not affected.
+        limiter.acquire_at("b", now).unwrap();
+
+        // One token is refilled after 500ms.
+        let now = now + Duration::from_millis(500);
+        limiter.acquire_at("a", now).unwrap();
+        assert!(limiter.acquire_at("a", now).is_err());
+
+        // The bucket is never filled beyond the burst.
+        let now = now + Duration::from_secs(10);
+        limiter.acquire_at("a", now).unwrap();
+        limiter.acquire_at("a", now).unwrap();
+        assert!(limiter.acquire_at("a", now).is_err());
+    }
+
+    #[test]
+    fn test_rate_limiter_client_key() {
+        let config = HttpRateLimitConfig::default();
+        let remote = Some(SocketAddr::from(([127, 0, 0, 1], 1234)));
+
+        let limiter = RateLimiter::new(&config, true);
+        assert_eq!("key:key1", limiter.client_key(Some("Bearer key1"), remote));
+        assert_eq!("ip:127.0.0.1", limiter.client_key(None, remote));
+        assert_eq!("unknown", limiter.client_key(None, None));
+
+        // The api key is ignored if it is not authenticated.
+        let limiter = RateLimiter::new(&config, false);
+        assert_eq!(
+            "ip:127.0.0.1",
+            limiter.client_key(Some("Bearer key1"), remote)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_burst() {
+        let config = HttpRateLimitConfig {
+            requests_per_second: 1,
+            burst: 3,
+        };
+        let limiter = Some(Arc::new(RateLimiter::new(&config, false)));
+        let filter = rate_limit_filter(limiter)
+            .and(warp::path!("sql"))
+            .map(warp::reply)
+            .recover(handle_rejection);
+        let request = |ip: [u8; 4]| {
+            warp::test::request()
+                .path("/sql")
+                .remote_addr(SocketAddr::from((ip, 1234)))
+        };
+
+        let mut statuses = Vec::new();
+        for _ in 0..6 {
+            let resp = request([10, 0, 0, 1]).reply(&filter).await;
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                assert_eq!("1", resp.headers()[RETRY_AFTER]);
+            }
+            statuses.push(resp.status());
+        }
+        let num_limited = statuses
+            .iter()
+            .filter(|v| **v == StatusCode::TOO_MANY_REQUESTS)
+            .count();
+        assert_eq!(&[StatusCode::OK; 3], &statuses[..3]);
+        assert_eq!(3, num_limited);
+
+        // Requests from another client are not limited.
+        let resp = request([10, 0, 0, 2]).reply(&filter).await;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        // The budget of a client is shared by `/sql` and `/sql/arrow`, and every
+        // request consumes one token, the same as the routes of the service.
+        let limiter = Some(Arc::new(RateLimiter::new(&config, false)));
+        let filter = warp::path!("sql")
+            .and(rate_limit_filter(limiter.clone()))
+            .or(warp::path!("sql" / "arrow").and(rate_limit_filter(limiter)))
+            .map(|_| warp::reply())
+            .recover(handle_rejection);
+        let request = |path: &str, ip: [u8; 4]| {
+            warp::test::request()
+                .path(path)
+                .remote_addr(SocketAddr::from((ip, 1234)))
+        };
+        for _ in 0..3 {
+            let resp = request("/sql", [10, 0, 0, 1]).reply(&filter).await;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+        let resp = request("/sql/arrow", [10, 0, 0, 1]).reply(&filter).await;
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, resp.status());
+        for _ in 0..3 {
+            let resp = request("/sql/arrow", [10, 0, 0, 2]).reply(&filter).await;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+        let resp = request("/sql/arrow", [10, 0, 0, 2]).reply(&filter).await;
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, resp.status());
+
+        // No rate limit.
+        let filter = rate_limit_filter(None)
+            .and(warp::path!("sql"))
+            .map(warp::reply);
+        for _ in 0..6 {
+            let resp = request("/sql", [10, 0, 0, 1]).reply(&filter).await;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+    }
+}
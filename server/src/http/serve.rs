@@ -0,0 +1,351 @@
+// Copyright 2023 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Serving the http service over plaintext or tls connections
+
+use std::{
+    convert::Infallible,
+    fs::File,
+    io::BufReader,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use common_util::runtime::JoinHandle;
+use log::{error, info, warn};
+use snafu::{ensure, OptionExt, ResultExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::{oneshot::Receiver, watch},
+    task::JoinSet,
+};
+use tokio_rustls::{
+    rustls::{self, Certificate, PrivateKey},
+    TlsAcceptor,
+};
+use warp::{
+    http::{Request as HttpRequest, Response},
+    hyper::{
+        server::conn::Http,
+        service::{service_fn, Service as HyperService},
+        Body,
+    },
+};
+
+use crate::{
+    config::HttpTlsConfig,
+    http::{serve_with_request_id, BuildTlsConfig, InvalidTlsFile, ReadTlsFile, Result},
+};
+
+/// Load the tls config of the server from the certificate chain and private key
+/// files in the pem format.
+pub fn load_tls_config(config: &HttpTlsConfig) -> Result<rustls::ServerConfig> {
+    let read_pem = |path: &str| -> Result<Vec<rustls_pemfile::Item>> {
+        let file = File::open(path).context(ReadTlsFile { path })?;
+        rustls_pemfile::read_all(&mut BufReader::new(file)).context(ReadTlsFile { path })
+    };
+
+    let certs: Vec<_> = read_pem(&config.cert_path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::X509Certificate(v) => Some(Certificate(v)),
+            _ => None,
+        })
+        .collect();
+    ensure!(
+        !certs.is_empty(),
+        InvalidTlsFile {
+            path: &config.cert_path,
+            msg: "no certificate found",
+        }
+    );
+    let key = read_pem(&config.key_path)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(v)
+            | rustls_pemfile::Item::PKCS8Key(v)
+            | rustls_pemfile::Item::ECKey(v) => Some(PrivateKey(v)),
+            _ => None,
+        })
+        .with_context(|| InvalidTlsFile {
+            path: &config.key_path,
+            msg: "no private key found",
+        })?;
+
+    let mut tls_config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context(BuildTlsConfig)?;
+    tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(tls_config)
+}
+
+/// Serve the requests by `service` on the connections accepted by the
+/// `listener` until the `shutdown` message is received, and the connections
+/// are served over tls if the `acceptor` is set.
+///
+/// Once shutdown, the new connections are not accepted anymore, and it returns
+/// after the in-flight requests of the alive connections are done. The alive
+/// connections are forced to close if it is aborted.
+pub async fn serve<S>(
+    listener: std::net::TcpListener,
+    acceptor: Option<TlsAcceptor>,
+    service: S,
+    mut shutdown: Receiver<()>,
+) where
+    S: HyperService<HttpRequest<Body>, Response = Response<Body>, Error = Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    let listener = match TcpListener::from_std(listener) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("HTTP server fails to listen, err:{}", e);
+            return;
+        }
+    };
+
+    // The alive connections are notified to close once the value is sent.
+    let (drain_tx, drain_rx) = watch::channel(());
+    let mut connections = JoinSet::new();
+    loop {
+        let (stream, remote_addr) = tokio::select! {
+            res = listener.accept() => match res {
+                Ok(v) => v,
+                Err(e) => {
+                    // The error is likely to be caused by running out of the file
+                    // descriptors, so backoff a while.
+                    error!("HTTP server fails to accept connection, err:{}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            // Reap the closed connections.
+            Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+            _ = &mut shutdown => break,
+        };
+
+        let acceptor = acceptor.clone();
+        let service = service.clone();
+        let drain_rx = drain_rx.clone();
+        connections.spawn(async move {
+            let acceptor = match acceptor {
+                Some(v) => v,
+                None => return serve_connection(stream, remote_addr, service, drain_rx).await,
+            };
+            match acceptor.accept(stream).await {
+                Ok(stream) => serve_connection(stream, remote_addr, service, drain_rx).await,
+                Err(e) => {
+                    warn!("Failed to do tls handshake, remote_addr:{remote_addr}, err:{e}");
+                }
+            }
+        });
+    }
+
+    drop(listener);
+    info!(
+        "HTTP server stops accepting connections, alive_connections:{}",
+        connections.len()
+    );
+    let _ = drain_tx.send(());
+    while connections.join_next().await.is_some() {}
+}
+
+/// Serve the requests on the connection, and the connection is closed after
+/// the in-flight request once the `drain_rx` is notified.
+async fn serve_connection<I, S>(
+    io: I,
+    remote_addr: SocketAddr,
+    service: S,
+    mut drain_rx: watch::Receiver<()>,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: HyperService<HttpRequest<Body>, Response = Response<Body>, Error = Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    let service = service_fn(move |req| serve_with_request_id(service.clone(), remote_addr, req));
+    let conn = Http::new().serve_connection(io, service);
+    tokio::pin!(conn);
+    let res = tokio::select! {
+        res = &mut conn => res,
+        _ = drain_rx.changed() => {
+            conn.as_mut().graceful_shutdown();
+            conn.await
+        }
+    };
+    if let Err(e) = res {
+        warn!("Failed to serve connection, remote_addr:{remote_addr}, err:{e}");
+    }
+}
+
+/// Wait for the `server` to finish the in-flight requests after the shutdown,
+/// and abort it once the `drain_timeout` elapses.
+///
+/// Returns false if the server is aborted.
+pub async fn drain_server(mut server: JoinHandle<()>, drain_timeout: Option<Duration>) -> bool {
+    let begin = Instant::now();
+    let res = match drain_timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, &mut server).await {
+            Ok(res) => res,
+            Err(_) => {
+                warn!("HTTP server fails to drain in time and is forced to close, drain_timeout:{timeout:?}");
+                server.abort();
+                return false;
+            }
+        },
+        None => server.await,
+    };
+    if let Err(e) = res {
+        error!("HTTP server task fails, err:{}", e);
+    }
+
+    info!("HTTP server is drained, cost:{:?}", begin.elapsed());
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::oneshot;
+    use warp::Filter;
+
+    use super::*;
+    use crate::{consts, http::Error};
+
+    fn new_test_tls_config() -> HttpTlsConfig {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/tls");
+        HttpTlsConfig {
+            cert_path: dir.join("cert.pem").to_string_lossy().to_string(),
+            key_path: dir.join("key.pem").to_string_lossy().to_string(),
+        }
+    }
+
+    #[test]
+    fn test_load_tls_config() {
+        let tls_config = new_test_tls_config();
+        load_tls_config(&tls_config).unwrap();
+
+        let mut invalid_config = tls_config.clone();
+        invalid_config.key_path = format!("{}.not_exist", tls_config.key_path);
+        let err = load_tls_config(&invalid_config).unwrap_err();
+        assert!(matches!(err, Error::ReadTlsFile { .. }), "{err}");
+
+        let mut invalid_config = tls_config.clone();
+        invalid_config.key_path = tls_config.cert_path.clone();
+        let err = load_tls_config(&invalid_config).unwrap_err();
+        assert!(err.to_string().contains("no private key found"), "{err}");
+
+        let mut invalid_config = tls_config.clone();
+        invalid_config.cert_path = tls_config.key_path;
+        let err = load_tls_config(&invalid_config).unwrap_err();
+        assert!(err.to_string().contains("no certificate found"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_serve_tls() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_rustls::{
+            rustls::{ClientConfig, RootCertStore, ServerName},
+            TlsConnector,
+        };
+
+        let tls_config = new_test_tls_config();
+        let acceptor = TlsAcceptor::from(Arc::new(load_tls_config(&tls_config).unwrap()));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = warp::service(warp::path!("ping").map(|| "pong"));
+        let (tx, rx) = oneshot::channel();
+        let server = tokio::spawn(serve(listener, Some(acceptor), service, rx));
+
+        // The self-signed certificate is trusted by the client.
+        let mut root_store = RootCertStore::empty();
+        let cert_file = File::open(&tls_config.cert_path).unwrap();
+        for cert in rustls_pemfile::certs(&mut BufReader::new(cert_file)).unwrap() {
+            root_store.add(&Certificate(cert)).unwrap();
+        }
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let mut stream = connector.connect(server_name, stream).await.unwrap();
+        stream
+            .write_all(b"GET /ping HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut resp = Vec::new();
+        // The peer may close the connection without the close notify, which is
+        // harmless here.
+        let _ = stream.read_to_end(&mut resp).await;
+        let resp = String::from_utf8(resp).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+        assert!(resp.contains(consts::REQUEST_ID_HEADER), "{resp}");
+        assert!(resp.ends_with("pong"), "{resp}");
+
+        tx.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_drain_server() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let runtime = common_util::runtime::Builder::default().build().unwrap();
+        let slow = warp::path!("slow" / u64).and_then(|millis| async move {
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            Ok::<_, warp::Rejection>("done")
+        });
+        let service = warp::service(slow);
+
+        // Returns the response and the address of the server.
+        let serve_slow_request = |millis: u64, drain_timeout: Duration| {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.set_nonblocking(true).unwrap();
+            let addr = listener.local_addr().unwrap();
+            let (tx, rx) = oneshot::channel();
+            let server = runtime.spawn(serve(listener, None, service.clone(), rx));
+            let client = runtime.spawn(async move {
+                let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+                let req = format!("GET /slow/{millis} HTTP/1.1\r\nhost: localhost\r\n\r\n");
+                stream.write_all(req.as_bytes()).await.unwrap();
+                let mut resp = Vec::new();
+                let _ = stream.read_to_end(&mut resp).await;
+                String::from_utf8(resp).unwrap()
+            });
+
+            runtime.block_on(async {
+                // Shutdown while the request is in flight.
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                tx.send(()).unwrap();
+                let drained = drain_server(server, Some(drain_timeout)).await;
+                (drained, client.await.unwrap(), addr)
+            })
+        };
+
+        // The in-flight request is allowed to finish within the timeout.
+        let (drained, resp, addr) = serve_slow_request(500, Duration::from_secs(10));
+        assert!(drained);
+        assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+        assert!(resp.ends_with("done"), "{resp}");
+        // The new connections are refused after shutdown.
+        runtime.block_on(async {
+            assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+        });
+
+        // The connection is forced to close once the timeout elapses.
+        let (drained, resp, _) = serve_slow_request(60_000, Duration::from_millis(100));
+        assert!(!drained);
+        assert!(!resp.contains("done"), "{resp}");
+    }
+}
@@ -2,9 +2,15 @@
 
 //! Metrics util for server.
 
+use std::fmt::Write;
+
 use lazy_static::lazy_static;
 use log::warn;
-use prometheus::{exponential_buckets, register_histogram_vec, Encoder, HistogramVec, TextEncoder};
+use prometheus::{
+    exponential_buckets,
+    proto::{LabelPair, MetricFamily, MetricType},
+    register_histogram_vec, Encoder, HistogramVec, TextEncoder,
+};
 
 lazy_static! {
     pub static ref HTTP_HANDLER_DURATION_HISTOGRAM_VEC: HistogramVec = register_histogram_vec!(
@@ -17,15 +23,315 @@ lazy_static! {
     .unwrap();
 }
 
-/// Gather and dump prometheus to string.
-pub fn dump() -> String {
-    let mut buffer = vec![];
-    let encoder = TextEncoder::new();
-    let metric_families = prometheus::gather();
+/// Content type of the OpenMetrics text format.
+pub const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text";
+
+/// Units recognized from the suffixes of the metric names.
+const OPENMETRICS_UNITS: [&str; 2] = ["seconds", "bytes"];
+
+/// Format of the dumped metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MetricsFormat {
+    /// The legacy text exposition format of prometheus.
+    #[default]
+    Prometheus,
+    /// The OpenMetrics text format.
+    OpenMetrics,
+}
+
+impl MetricsFormat {
+    /// Decide the format by the `Accept` header, the OpenMetrics format is
+    /// chosen only if it is accepted explicitly.
+    pub fn from_accept(accept: Option<&str>) -> Self {
+        let accept_openmetrics = accept
+            .map(|accept| {
+                accept
+                    .split(',')
+                    .any(|v| v.split(';').next().unwrap().trim() == OPENMETRICS_CONTENT_TYPE)
+            })
+            .unwrap_or(false);
+
+        if accept_openmetrics {
+            MetricsFormat::OpenMetrics
+        } else {
+            MetricsFormat::Prometheus
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            MetricsFormat::Prometheus => prometheus::TEXT_FORMAT,
+            MetricsFormat::OpenMetrics => {
+                "application/openmetrics-text; version=1.0.0; charset=utf-8"
+            }
+        }
+    }
+}
+
+/// Gather and dump prometheus to string in the given format.
+pub fn dump(format: MetricsFormat) -> String {
+    encode(&prometheus::gather(), format)
+}
+
+fn encode(metric_families: &[MetricFamily], format: MetricsFormat) -> String {
+    match format {
+        MetricsFormat::Prometheus => {
+            let mut buffer = vec![];
+            let encoder = TextEncoder::new();
+            for mf in metric_families {
+                if let Err(e) = encoder.encode(std::slice::from_ref(mf), &mut buffer) {
+                    warn!("prometheus encoding error, err:{}", e);
+                }
+            }
+            String::from_utf8(buffer).unwrap()
+        }
+        MetricsFormat::OpenMetrics => encode_openmetrics(metric_families),
+    }
+}
+
+/// Encode the metric families in the [OpenMetrics text format][1].
+///
+/// [1]: https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md
+fn encode_openmetrics(metric_families: &[MetricFamily]) -> String {
+    let mut buffer = String::new();
     for mf in metric_families {
-        if let Err(e) = encoder.encode(&[mf], &mut buffer) {
-            warn!("prometheus encoding error, err:{}", e);
+        let metric_type = mf.get_field_type();
+        // The samples of the counter are suffixed by `_total` rather than the family.
+        let name = match metric_type {
+            MetricType::COUNTER => mf.get_name().trim_end_matches("_total"),
+            _ => mf.get_name(),
+        };
+        let type_name = match metric_type {
+            MetricType::COUNTER => "counter",
+            MetricType::GAUGE => "gauge",
+            MetricType::HISTOGRAM => "histogram",
+            MetricType::SUMMARY => "summary",
+            MetricType::UNTYPED => "unknown",
+        };
+
+        writeln!(buffer, "# TYPE {name} {type_name}").unwrap();
+        if let Some(unit) = OPENMETRICS_UNITS
+            .iter()
+            .find(|unit| name.ends_with(&format!("_{unit}")))
+        {
+            writeln!(buffer, "# UNIT {name} {unit}").unwrap();
+        }
+        writeln!(buffer, "# HELP {name} {}", escape(mf.get_help())).unwrap();
+
+        for metric in mf.get_metric() {
+            let labels = metric.get_label();
+            match metric_type {
+                MetricType::COUNTER => {
+                    write_sample(
+                        &mut buffer,
+                        &format!("{name}_total"),
+                        labels,
+                        None,
+                        metric.get_counter().get_value(),
+                    );
+                }
+                MetricType::GAUGE => {
+                    write_sample(
+                        &mut buffer,
+                        name,
+                        labels,
+                        None,
+                        metric.get_gauge().get_value(),
+                    );
+                }
+                MetricType::UNTYPED => {
+                    write_sample(
+                        &mut buffer,
+                        name,
+                        labels,
+                        None,
+                        metric.get_untyped().get_value(),
+                    );
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let bucket_name = format!("{name}_bucket");
+                    let mut has_inf_bucket = false;
+                    for bucket in histogram.get_bucket() {
+                        let upper_bound = bucket.get_upper_bound();
+                        has_inf_bucket |= upper_bound == f64::INFINITY;
+                        write_sample(
+                            &mut buffer,
+                            &bucket_name,
+                            labels,
+                            Some(("le", upper_bound)),
+                            bucket.get_cumulative_count() as f64,
+                        );
+                    }
+                    if !has_inf_bucket {
+                        write_sample(
+                            &mut buffer,
+                            &bucket_name,
+                            labels,
+                            Some(("le", f64::INFINITY)),
+                            histogram.get_sample_count() as f64,
+                        );
+                    }
+                    write_sample(
+                        &mut buffer,
+                        &format!("{name}_count"),
+                        labels,
+                        None,
+                        histogram.get_sample_count() as f64,
+                    );
+                    write_sample(
+                        &mut buffer,
+                        &format!("{name}_sum"),
+                        labels,
+                        None,
+                        histogram.get_sample_sum(),
+                    );
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        write_sample(
+                            &mut buffer,
+                            name,
+                            labels,
+                            Some(("quantile", quantile.get_quantile())),
+                            quantile.get_value(),
+                        );
+                    }
+                    write_sample(
+                        &mut buffer,
+                        &format!("{name}_count"),
+                        labels,
+                        None,
+                        summary.get_sample_count() as f64,
+                    );
+                    write_sample(
+                        &mut buffer,
+                        &format!("{name}_sum"),
+                        labels,
+                        None,
+                        summary.get_sample_sum(),
+                    );
+                }
+            }
         }
     }
-    String::from_utf8(buffer).unwrap()
+    buffer.push_str("# EOF\n");
+
+    buffer
+}
+
+fn write_sample(
+    buffer: &mut String,
+    name: &str,
+    labels: &[LabelPair],
+    extra_label: Option<(&str, f64)>,
+    value: f64,
+) {
+    buffer.push_str(name);
+
+    let mut label_strs: Vec<_> = labels
+        .iter()
+        .map(|label| format!("{}=\"{}\"", label.get_name(), escape(label.get_value())))
+        .collect();
+    if let Some((label_name, label_value)) = extra_label {
+        // The `le` and `quantile` labels are in the canonical form, e.g. `1.0`.
+        let label_value = if label_value.is_finite() {
+            format!("{label_value:?}")
+        } else {
+            format_float(label_value)
+        };
+        label_strs.push(format!("{label_name}=\"{label_value}\""));
+    }
+    if !label_strs.is_empty() {
+        write!(buffer, "{{{}}}", label_strs.join(",")).unwrap();
+    }
+
+    writeln!(buffer, " {}", format_float(value)).unwrap();
+}
+
+fn format_float(v: f64) -> String {
+    if v == f64::INFINITY {
+        "+Inf".to_string()
+    } else if v == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else if v.is_nan() {
+        "NaN".to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::{Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry};
+
+    use super::*;
+
+    #[test]
+    fn test_metrics_format_from_accept() {
+        assert_eq!(MetricsFormat::Prometheus, MetricsFormat::from_accept(None));
+        assert_eq!(
+            MetricsFormat::Prometheus,
+            MetricsFormat::from_accept(Some("text/plain"))
+        );
+        assert_eq!(
+            MetricsFormat::OpenMetrics,
+            MetricsFormat::from_accept(Some(
+                "application/openmetrics-text; version=1.0.0,text/plain;q=0.5"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_encode_openmetrics() {
+        let registry = Registry::new();
+        let counter = IntCounterVec::new(
+            Opts::new("test_requests_total", "Requests of test"),
+            &["type"],
+        )
+        .unwrap();
+        let gauge = IntGauge::new("test_connections", "Connections of test").unwrap();
+        let histogram = Histogram::with_opts(
+            HistogramOpts::new("test_duration_seconds", "Duration of test").buckets(vec![1.0]),
+        )
+        .unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        counter.with_label_values(&["write"]).inc_by(3);
+        gauge.set(2);
+        histogram.observe(0.5);
+        histogram.observe(1.5);
+
+        let output = encode(&registry.gather(), MetricsFormat::OpenMetrics);
+        let lines: Vec<_> = output.lines().collect();
+        for expect in [
+            "# TYPE test_connections gauge",
+            "test_connections 2",
+            "# TYPE test_duration_seconds histogram",
+            "# UNIT test_duration_seconds seconds",
+            "test_duration_seconds_bucket{le=\"1.0\"} 1",
+            "test_duration_seconds_bucket{le=\"+Inf\"} 2",
+            "test_duration_seconds_count 2",
+            "test_duration_seconds_sum 2",
+            "# TYPE test_requests counter",
+            "test_requests_total{type=\"write\"} 3",
+        ] {
+            assert!(lines.contains(&expect), "{expect} not found in:\n{output}");
+        }
+        assert_eq!(Some(&"# EOF"), lines.last());
+        assert!(output.ends_with("# EOF\n"));
+
+        // The legacy format is kept without the terminator.
+        let output = encode(&registry.gather(), MetricsFormat::Prometheus);
+        assert!(output.contains("# TYPE test_requests_total counter"));
+        assert!(!output.contains("# EOF"));
+    }
 }
@@ -2,7 +2,7 @@
 
 //! Server
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use analytic_engine::setup::OpenedWals;
 use catalog::manager::ManagerRef;
@@ -15,6 +15,7 @@ use partition_table_engine::PartitionTableEngine;
 use proxy::{
     instance::{Instance, InstanceRef},
     limiter::Limiter,
+    running_query::RunningQueries,
     schema_config_provider::SchemaConfigProviderRef,
     Proxy,
 };
@@ -107,12 +108,14 @@ pub struct Server<Q: QueryExecutor + 'static> {
     instance: InstanceRef<Q>,
     cluster: Option<ClusterRef>,
     local_tables_recoverer: Option<LocalTablesRecoverer>,
+    /// Max time to wait for the in-flight http requests when stopping.
+    http_drain_timeout: Option<Duration>,
 }
 
 impl<Q: QueryExecutor + 'static> Server<Q> {
     pub async fn stop(mut self) {
         self.rpc_services.shutdown().await;
-        self.http_service.stop();
+        self.http_service.stop(self.http_drain_timeout).await;
         self.mysql_service.shutdown();
 
         if let Some(cluster) = &self.cluster {
@@ -182,6 +185,7 @@ pub struct Builder<Q> {
     remote_engine_client_config: remote_engine_client::config::Config,
     node_addr: String,
     config_content: Option<String>,
+    config_json: Option<serde_json::Value>,
     engine_runtimes: Option<Arc<EngineRuntimes>>,
     log_runtime: Option<Arc<RuntimeLevel>>,
     catalog_manager: Option<ManagerRef>,
@@ -204,6 +208,7 @@ impl<Q: QueryExecutor + 'static> Builder<Q> {
             remote_engine_client_config: remote_engine_client::Config::default(),
             node_addr: "".to_string(),
             config_content: None,
+            config_json: None,
             engine_runtimes: None,
             log_runtime: None,
             catalog_manager: None,
@@ -230,6 +235,12 @@ impl<Q: QueryExecutor + 'static> Builder<Q> {
         self
     }
 
+    /// The server config in json, whose secrets should be redacted.
+    pub fn config_json(mut self, config_json: serde_json::Value) -> Self {
+        self.config_json = Some(config_json);
+        self
+    }
+
     pub fn engine_runtimes(mut self, engine_runtimes: Arc<EngineRuntimes>) -> Self {
         self.engine_runtimes = Some(engine_runtimes);
         self
@@ -314,6 +325,7 @@ impl<Q: QueryExecutor + 'static> Builder<Q> {
         let log_runtime = self.log_runtime.context(MissingLogRuntime)?;
         let engine_runtimes = self.engine_runtimes.context(MissingEngineRuntimes)?;
         let config_content = self.config_content.expect("Missing config content");
+        let config_json = self.config_json.expect("Missing config json");
 
         let remote_engine_ref = Arc::new(RemoteEngineImpl::new(
             self.remote_engine_client_config.clone(),
@@ -333,6 +345,7 @@ impl<Q: QueryExecutor + 'static> Builder<Q> {
                 limiter: self.limiter,
                 table_manipulator,
                 remote_engine_ref,
+                running_queries: Arc::new(RunningQueries::default()),
             };
             InstanceRef::new(instance)
         };
@@ -352,6 +365,11 @@ impl<Q: QueryExecutor + 'static> Builder<Q> {
             endpoint: http_endpoint,
             max_body_size: self.server_config.http_max_body_size.as_byte(),
             timeout: self.server_config.timeout.map(|v| v.0),
+            max_timeout: self.server_config.http_max_timeout.0,
+            max_profile_duration: self.server_config.http_max_profile_duration.0,
+            auth: self.server_config.http_auth.clone(),
+            rate_limit: self.server_config.http_rate_limit.clone(),
+            tls: self.server_config.http_tls.clone(),
         };
 
         let proxy = Arc::new(Proxy::new(
@@ -371,8 +389,10 @@ impl<Q: QueryExecutor + 'static> Builder<Q> {
             .engine_runtimes(engine_runtimes.clone())
             .log_runtime(log_runtime)
             .config_content(config_content)
+            .config_json(config_json)
             .proxy(proxy.clone())
             .opened_wals(opened_wals.clone())
+            .cluster(self.cluster.clone())
             .build()
             .context(HttpService {
                 msg: "build failed",
@@ -408,6 +428,7 @@ impl<Q: QueryExecutor + 'static> Builder<Q> {
             instance,
             cluster: self.cluster,
             local_tables_recoverer: self.local_tables_recoverer,
+            http_drain_timeout: self.server_config.http_drain_timeout.map(|v| v.0),
         };
         Ok(server)
     }
@@ -103,3 +103,45 @@ impl Default for RuntimeConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_config_with_secrets_redacted() {
+        let config_content = r#"
+[server]
+http_port = 5440
+
+[[server.http_auth.api_keys]]
+key = "api-key"
+allowed_schemas = ["public"]
+
+[analytic.storage.object_store]
+type = "Aliyun"
+key_id = "id"
+key_secret = "secret"
+endpoint = "oss.aliyuncs.com"
+bucket = "ceresdb"
+prefix = "test"
+"#;
+        let config: Config = toml::from_str(config_content).unwrap();
+        let config = serde_json::to_value(config).unwrap();
+        assert_eq!(5440, config["server"]["http_port"]);
+        // The defaults are included.
+        assert_eq!(
+            RuntimeConfig::default().read_thread_num,
+            config["runtime"]["read_thread_num"]
+        );
+        let api_key = &config["server"]["http_auth"]["api_keys"][0];
+        assert_eq!("***", api_key["key"]);
+        assert_eq!("public", api_key["allowed_schemas"][0]);
+        let object_store = &config["analytic"]["storage"]["object_store"];
+        assert_eq!("***", object_store["key_id"]);
+        assert_eq!("***", object_store["key_secret"]);
+        assert_eq!("oss.aliyuncs.com", object_store["endpoint"]);
+        let json = config.to_string();
+        assert!(!json.contains("\"secret\"") && !json.contains("api-key"));
+    }
+}
@@ -10,7 +10,10 @@ use analytic_engine::{
     WalStorageConfig,
 };
 use catalog::{manager::ManagerRef, schema::OpenOptions, table_operator::TableOperator};
-use catalog_impls::{table_based::TableBasedManager, volatile, CatalogManagerImpl};
+use catalog_impls::{
+    table_based::TableBasedManager, table_flusher::CatalogBasedTableFlusher,
+    table_stats::CatalogBasedTableStatsProvider, volatile, CatalogManagerImpl,
+};
 use cluster::{
     cluster_impl::ClusterImpl, config::ClusterConfig, shard_tables_cache::ShardTablesCache,
 };
@@ -122,10 +125,13 @@ async fn run_server_with_runtimes<T>(
     // Config limiter
     let limiter = Limiter::new(config.limiter.clone());
     let config_content = toml::to_string(&config).expect("Fail to serialize config");
+    // The secrets are redacted by the serialization of the config.
+    let config_json = serde_json::to_value(&config).expect("Fail to serialize config into json");
 
     let builder = Builder::new(config.server.clone())
         .node_addr(config.node.addr.clone())
         .config_content(config_content)
+        .config_json(config_json)
         .engine_runtimes(engine_runtimes.clone())
         .log_runtime(log_runtime.clone())
         .query_executor(query_executor)
@@ -212,23 +218,6 @@ async fn build_with_meta<Q: Executor + 'static, T: WalsOpener>(
             .expect("fail to build meta client");
 
     let shard_tables_cache = ShardTablesCache::default();
-    let cluster = {
-        let cluster_impl = ClusterImpl::try_new(
-            endpoint,
-            shard_tables_cache.clone(),
-            meta_client.clone(),
-            cluster_config.clone(),
-            runtimes.meta_runtime.clone(),
-        )
-        .await
-        .unwrap();
-        Arc::new(cluster_impl)
-    };
-    let router = Arc::new(ClusterBasedRouter::new(
-        cluster.clone(),
-        config.server.route_cache.clone(),
-    ));
-
     let opened_wals = wal_opener
         .open_wals(&config.analytic.wal, runtimes.clone())
         .await
@@ -241,13 +230,35 @@ async fn build_with_meta<Q: Executor + 'static, T: WalsOpener>(
     let engine_proxy = build_table_engine_proxy(engine_builder).await;
 
     let meta_based_manager_ref = Arc::new(volatile::ManagerImpl::new(
-        shard_tables_cache,
+        shard_tables_cache.clone(),
         meta_client.clone(),
     ));
 
     // Build catalog manager.
     let catalog_manager = Arc::new(CatalogManagerImpl::new(meta_based_manager_ref));
 
+    let table_stats_provider =
+        Arc::new(CatalogBasedTableStatsProvider::new(catalog_manager.clone()));
+    let table_flusher = Arc::new(CatalogBasedTableFlusher::new(catalog_manager.clone()));
+    let cluster = {
+        let cluster_impl = ClusterImpl::try_new(
+            endpoint,
+            shard_tables_cache,
+            meta_client.clone(),
+            cluster_config.clone(),
+            runtimes.meta_runtime.clone(),
+            Some(table_stats_provider),
+            Some(table_flusher),
+        )
+        .await
+        .unwrap();
+        Arc::new(cluster_impl)
+    };
+    let router = Arc::new(ClusterBasedRouter::new(
+        cluster.clone(),
+        config.server.route_cache.clone(),
+    ));
+
     let table_manipulator = Arc::new(meta_based::TableManipulatorImpl::new(meta_client));
 
     let schema_config_provider = Arc::new(ClusterBasedProvider::new(cluster.clone()));
@@ -338,7 +338,7 @@ impl SysCatalogTable {
 
         let row_group = request.into_row_group(self.table.schema())?;
 
-        let write_req = WriteRequest { row_group };
+        let write_req = WriteRequest::new(row_group);
         self.table.write(write_req).await.context(PersistCatalog)?;
 
         Ok(())
@@ -350,7 +350,7 @@ impl SysCatalogTable {
 
         let row_group = request.into_row_group(self.table.schema())?;
 
-        let write_req = WriteRequest { row_group };
+        let write_req = WriteRequest::new(row_group);
         self.table.write(write_req).await.context(PersistSchema)?;
 
         Ok(())
@@ -988,7 +988,7 @@ pub struct TableWriter {
 impl TableWriter {
     async fn write(&self) -> Result<()> {
         let row_group = self.convert_table_info_to_row_group()?;
-        let write_req = WriteRequest { row_group };
+        let write_req = WriteRequest::new(row_group);
         self.catalog_table
             .write(write_req)
             .await
@@ -290,10 +290,54 @@ impl fmt::Display for TableId {
     }
 }
 
+/// Mode of the write request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// The whole request fails if any row of it fails.
+    #[default]
+    AllOrNothing,
+    /// Skip the rows failed to be encoded and write the remaining ones.
+    BestEffort,
+}
+
+/// Durability required by the write request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// The written data may be lost if the machine crashes, and the engine is
+    /// allowed to persist it in batch for better throughput.
+    #[default]
+    Relaxed,
+    /// The written data must be persisted before the write returns.
+    Strict,
+}
+
 #[derive(Clone, Debug)]
 pub struct WriteRequest {
     /// rows to write
     pub row_group: RowGroup,
+    /// Mode of the write
+    pub mode: WriteMode,
+    /// Only validate the request without writing it if set
+    pub dry_run: bool,
+    /// Durability of the write
+    pub durability: Durability,
+    /// Id (usually an UUID) provided by the client to deduplicate the retried
+    /// write requests, and the retry of a partially written request resumes
+    /// after its written batches
+    pub request_id: Option<String>,
+}
+
+impl WriteRequest {
+    /// Create a write request in the default [WriteMode::AllOrNothing] mode.
+    pub fn new(row_group: RowGroup) -> Self {
+        Self {
+            row_group,
+            mode: WriteMode::default(),
+            dry_run: false,
+            durability: Durability::default(),
+            request_id: None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -561,6 +605,15 @@ pub struct TableStats {
     pub num_read: u64,
     /// Total flush request
     pub num_flush: u64,
+    /// Number of write requests waiting for the flush of the table
+    pub num_write_waiting_flush: u64,
+    /// Total time in milliseconds of the write requests waiting for the flush
+    /// of the table
+    pub write_flush_wait_ms: u64,
+    /// Approximate memory usage in bytes of the memtables
+    pub memtable_memory_usage: u64,
+    /// Sequence number of the last write to the table
+    pub last_sequence: u64,
 }
 
 /// A reference-counted pointer to Table
@@ -12,6 +12,7 @@ use common_types::{
 };
 use common_util::{error::BoxError, runtime::Runtime};
 pub use error::*;
+use serde::Serialize;
 use snafu::ResultExt;
 
 use crate::log_batch::{LogEntry, LogWriteBatch, PayloadDecoder};
@@ -153,17 +154,32 @@ impl WalLocation {
     }
 }
 
+/// Durability of the logs to write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteDurability {
+    /// The logs may be synced to the disk in batch after the write returns,
+    /// so the latest logs may be lost if the machine crashes.
+    #[default]
+    Relaxed,
+    /// The logs are synced to the disk before the write returns.
+    Sync,
+}
+
 #[derive(Debug, Clone)]
 pub struct WriteContext {
     /// Timeout to write wal and it only takes effect when writing to a Wal on a
     /// remote machine (writing to the local disk does not have timeout).
     pub timeout: Duration,
+    /// Durability of the logs and it only takes effect when writing to a Wal
+    /// on the local disk (the remote Wal has its own replication mechanism).
+    pub durability: WriteDurability,
 }
 
 impl Default for WriteContext {
     fn default() -> Self {
         Self {
             timeout: Duration::from_secs(1),
+            durability: WriteDurability::default(),
         }
     }
 }
@@ -277,6 +293,22 @@ pub trait AsyncLogIterator: Send + fmt::Debug {
     async fn next_log_entry(&mut self) -> Result<Option<LogEntry<&'_ [u8]>>>;
 }
 
+/// Structured statistics of the wal.
+///
+/// The fields unknown to the wal implementation are left as `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WalStats {
+    /// Total size of the logs on the disk in bytes.
+    pub total_size: Option<u64>,
+    /// Number of the segments (e.g. files) storing the logs.
+    pub num_segments: Option<u64>,
+    /// Minimum sequence number of the logs not deleted yet across all the
+    /// tables.
+    pub oldest_sequence: Option<SequenceNumber>,
+    /// Maximum sequence number of the logs across all the tables.
+    pub newest_sequence: Option<SequenceNumber>,
+}
+
 /// Management of multi-region Wals.
 ///
 /// Every region has its own increasing (and maybe hallow) sequence number
@@ -319,6 +351,19 @@ pub trait WalManager: Send + Sync + fmt::Debug + 'static {
     fn get_statistics(&self) -> Option<String> {
         None
     }
+
+    /// Get statistics in the structured form, returns None if the wal
+    /// doesn't support it.
+    fn get_structured_statistics(&self) -> Option<WalStats> {
+        None
+    }
+
+    /// Check whether the wal is still writable.
+    ///
+    /// The wal is assumed to be writable if the implementation can't tell.
+    async fn check_writable(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -21,7 +21,8 @@ use common_util::{error::BoxError, runtime::Runtime};
 use log::{debug, info, warn};
 use rocksdb::{
     rocksdb_options::ColumnFamilyDescriptor, ColumnFamilyOptions, DBCompactionStyle, DBIterator,
-    DBOptions, FifoCompactionOptions, ReadOptions, SeekKey, Statistics, Writable, WriteBatch, DB,
+    DBOptions, FifoCompactionOptions, ReadOptions, SeekKey, Statistics, Writable, WriteBatch,
+    WriteOptions, DB,
 };
 use snafu::ResultExt;
 use tokio::sync::Mutex;
@@ -31,10 +32,14 @@ use crate::{
     log_batch::{LogEntry, LogWriteBatch},
     manager::{
         error::*, BatchLogIteratorAdapter, ReadContext, ReadRequest, RegionId, ScanContext,
-        ScanRequest, SyncLogIterator, WalLocation, WalManager, WriteContext,
+        ScanRequest, SyncLogIterator, WalLocation, WalManager, WalStats, WriteContext,
+        WriteDurability,
     },
 };
 
+/// Number of the levels of the db, which is the default of the rocksdb.
+const NUM_LEVELS: usize = 7;
+
 /// Table unit in the Wal.
 struct TableUnit {
     /// id of the Region
@@ -209,9 +214,12 @@ impl TableUnit {
         };
 
         let db = self.db.clone();
+        let sync = ctx.durability == WriteDurability::Sync;
         self.runtime
             .spawn_blocking(move || {
-                db.write(&wb)
+                let mut write_opts = WriteOptions::new();
+                write_opts.set_sync(sync);
+                db.write_opt(&wb, &write_opts)
                     .map(|_| max_sequence_num)
                     .map_err(|e| e.into())
                     .context(Write)
@@ -522,6 +530,69 @@ impl RocksImpl {
         let table_units = self.table_units.read().unwrap();
         table_units.get(&location.table_id).cloned()
     }
+
+    /// Find the minimum sequence number of the logs across all the tables.
+    ///
+    /// Only the first log of every region and table is visited by seeking to
+    /// the start of the next table once a log is found.
+    fn find_oldest_sequence(&self) -> Result<Option<SequenceNumber>> {
+        let mut oldest_seq: Option<SequenceNumber> = None;
+        let mut start_boundary_key_buf = BytesMut::new();
+        let mut log_key = CommonLogKey::new(0, 0, MIN_SEQUENCE_NUMBER);
+        loop {
+            self.log_encoding
+                .encode_key(&mut start_boundary_key_buf, &log_key)
+                .box_err()
+                .context(Encoding)?;
+            let mut iter = self.db.iter();
+            let found = iter
+                .seek(SeekKey::Key(&start_boundary_key_buf))
+                .map_err(|e| e.into())
+                .context(Read)?;
+            if !found
+                || !self
+                    .log_encoding
+                    .is_log_key(iter.key())
+                    .box_err()
+                    .context(Decoding)?
+            {
+                break;
+            }
+
+            let found_log_key = self
+                .log_encoding
+                .decode_key(iter.key())
+                .box_err()
+                .context(Decoding)?;
+            oldest_seq = Some(oldest_seq.map_or(found_log_key.sequence_num, |seq| {
+                seq.min(found_log_key.sequence_num)
+            }));
+
+            log_key = if found_log_key.table_id < TableId::MAX {
+                CommonLogKey::new(
+                    found_log_key.region_id,
+                    found_log_key.table_id + 1,
+                    MIN_SEQUENCE_NUMBER,
+                )
+            } else if found_log_key.region_id < u64::MAX {
+                CommonLogKey::new(found_log_key.region_id + 1, 0, MIN_SEQUENCE_NUMBER)
+            } else {
+                break;
+            };
+        }
+
+        Ok(oldest_seq)
+    }
+
+    /// Sum the integer property of the db over all the levels.
+    fn sum_property_of_levels(&self, property_prefix: &str) -> Option<u64> {
+        (0..NUM_LEVELS)
+            .map(|level| {
+                self.db
+                    .get_property_int(&format!("{property_prefix}{level}"))
+            })
+            .sum()
+    }
 }
 
 /// Builder for `RocksImpl`.
@@ -895,6 +966,23 @@ impl WalManager for RocksImpl {
         ))
     }
 
+    async fn check_writable(&self) -> Result<()> {
+        // Nothing is written by an empty batch, but the background error is
+        // returned if the writes of the RocksDB are stopped.
+        let db = self.db.clone();
+        self.runtime
+            .spawn_blocking(move || {
+                let mut write_opts = WriteOptions::new();
+                write_opts.set_sync(true);
+                db.write_opt(&WriteBatch::default(), &write_opts)
+                    .map_err(|e| e.into())
+                    .context(Write)
+            })
+            .await
+            .box_err()
+            .context(Write)?
+    }
+
     fn get_statistics(&self) -> Option<String> {
         if let Some(stats) = &self.stats {
             stats.to_string()
@@ -902,6 +990,30 @@ impl WalManager for RocksImpl {
             None
         }
     }
+
+    fn get_structured_statistics(&self) -> Option<WalStats> {
+        let oldest_sequence = self.find_oldest_sequence().unwrap_or_else(|e| {
+            warn!(
+                "RocksImpl failed to find the oldest sequence, wal_path:{}, err:{}",
+                self.wal_path, e
+            );
+            None
+        });
+        let newest_sequence = {
+            let table_units = self.table_units.read().unwrap();
+            table_units
+                .values()
+                .filter_map(|table_unit| table_unit.sequence_num().ok())
+                .max()
+        };
+
+        Some(WalStats {
+            total_size: self.db.get_property_int("rocksdb.total-sst-files-size"),
+            num_segments: self.sum_property_of_levels("rocksdb.num-files-at-level"),
+            oldest_sequence,
+            newest_sequence,
+        })
+    }
 }
 
 impl fmt::Debug for RocksImpl {